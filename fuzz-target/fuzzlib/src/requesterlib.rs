@@ -86,8 +86,10 @@ pub fn req_create_info() -> (common::SpdmConfigInfo, common::SpdmProvisionInfo)
     let provision_info = common::SpdmProvisionInfo {
         my_cert_chain_data: None,
         my_cert_chain: None,
+        peer_root_cert_data: Default::default(),
+        peer_cert_chain_root_hash: Default::default(),
+        peer_root_crl_data: Default::default(),
         peer_cert_chain_data: Some(peer_cert_chain_data),
-        peer_cert_chain_root_hash: None,
         default_version: SpdmVersion::SpdmVersion12,
     };
 
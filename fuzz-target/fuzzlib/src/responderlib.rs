@@ -90,8 +90,10 @@ pub fn rsp_create_info() -> (common::SpdmConfigInfo, common::SpdmProvisionInfo)
     let provision_info = common::SpdmProvisionInfo {
         my_cert_chain_data: Some(my_cert_chain_data),
         my_cert_chain: None,
+        peer_root_cert_data: Default::default(),
+        peer_cert_chain_root_hash: Default::default(),
+        peer_root_crl_data: Default::default(),
         peer_cert_chain_data: None,
-        peer_cert_chain_root_hash: None,
         default_version: SpdmVersion::SpdmVersion12,
     };
 
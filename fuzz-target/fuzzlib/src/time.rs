@@ -2,10 +2,25 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+use conquer_once::spin::OnceCell;
 use spdmlib::time::SpdmTime;
+use std::time::Instant;
+
 pub static SPDM_TIME_IMPL: SpdmTime = SpdmTime {
     sleep_cb: |time: usize| {
         use std::{thread, time::Duration};
         thread::sleep(Duration::from_millis(time as u64));
     },
+    get_unix_time_cb: || {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|ds| ds.as_secs())
+    },
+    now_cb: || {
+        static START: OnceCell<Instant> = OnceCell::uninit();
+        let start = START.try_get_or_init(Instant::now).ok()?;
+        Some(start.elapsed().as_micros() as u64)
+    },
 };
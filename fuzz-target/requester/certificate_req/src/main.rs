@@ -38,12 +38,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            responder.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
-
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester =
             fake_device_io::FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -59,11 +53,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
         requester.common.negotiate_info.base_asym_sel =
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            requester.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let _ = requester.send_receive_spdm_certificate(None, 0).is_err();
     }
     {
@@ -87,11 +76,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            responder.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester =
             fake_device_io::FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -107,11 +91,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
         requester.common.negotiate_info.base_asym_sel =
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            requester.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let _ = requester.send_receive_spdm_certificate(None, 0).is_err();
     }
     {
@@ -134,11 +113,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            responder.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester =
             fake_device_io::FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -161,11 +135,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
         tmp.data_size += 1;
         requester.common.provision_info.peer_cert_chain_data = Some(tmp);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            requester.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let _ = requester.send_receive_spdm_certificate(None, 0).is_err();
     }
     {
@@ -189,11 +158,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            responder.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         // digest_rsp
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -217,11 +181,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
         requester.common.negotiate_info.base_asym_sel =
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            requester.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let _ = requester.send_receive_spdm_certificate(None, 0).is_err();
     }
     {
@@ -243,11 +202,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            responder.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester =
             fake_device_io::FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -262,11 +216,6 @@ fn fuzz_send_receive_spdm_certificate(fuzzdata: &[u8]) {
         requester.common.negotiate_info.base_asym_sel =
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            requester.common.runtime_info.digest_context_m1m2 =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-        }
         let _ = requester.send_receive_spdm_certificate(None, 0).is_err();
     }
 }
@@ -6,6 +6,7 @@ use fuzzlib::{
     spdmlib::common::session::{SpdmSession, SpdmSessionState},
     *,
 };
+use spdmlib::message::SpdmEndSessionRequestAttributes;
 use spdmlib::protocol::*;
 
 fn fuzz_send_receive_spdm_end_session(fuzzdata: &[u8]) {
@@ -64,7 +65,8 @@ fn fuzz_send_receive_spdm_end_session(fuzzdata: &[u8]) {
         );
         requester.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
 
-        let _ = requester.send_receive_spdm_end_session(4294901758);
+        let _ = requester
+            .send_receive_spdm_end_session(4294901758, SpdmEndSessionRequestAttributes::empty());
     }
 
     {
@@ -116,7 +118,8 @@ fn fuzz_send_receive_spdm_end_session(fuzzdata: &[u8]) {
         );
         requester.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
 
-        let _ = requester.send_receive_spdm_end_session(4294901758);
+        let _ = requester
+            .send_receive_spdm_end_session(4294901758, SpdmEndSessionRequestAttributes::empty());
     }
 }
 
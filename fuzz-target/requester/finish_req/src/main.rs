@@ -60,15 +60,14 @@ fn fuzz_send_receive_spdm_finish(fuzzdata: &[u8]) {
             SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
         );
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             responder.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            responder.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            responder.common.session[0].runtime_info.transcript_th =
+                responder.common.new_transcript().unwrap();
         }
         responder.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
 
@@ -113,15 +112,14 @@ fn fuzz_send_receive_spdm_finish(fuzzdata: &[u8]) {
             SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
         );
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             requester.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            requester.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            requester.common.session[0].runtime_info.transcript_th =
+                requester.common.new_transcript().unwrap();
         }
 
         requester.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -179,15 +177,14 @@ fn fuzz_send_receive_spdm_finish(fuzzdata: &[u8]) {
             SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
         );
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             responder.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            responder.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            responder.common.session[0].runtime_info.transcript_th =
+                responder.common.new_transcript().unwrap();
         }
 
         responder.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -231,15 +228,14 @@ fn fuzz_send_receive_spdm_finish(fuzzdata: &[u8]) {
             SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
         );
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             requester.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            requester.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            requester.common.session[0].runtime_info.transcript_th =
+                requester.common.new_transcript().unwrap();
         }
 
         requester.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -290,15 +286,14 @@ fn fuzz_send_receive_spdm_finish(fuzzdata: &[u8]) {
             SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
         );
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             responder.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            responder.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            responder.common.session[0].runtime_info.transcript_th =
+                responder.common.new_transcript().unwrap();
         }
 
         responder.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -342,15 +337,14 @@ fn fuzz_send_receive_spdm_finish(fuzzdata: &[u8]) {
             SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
         );
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             requester.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            requester.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            requester.common.session[0].runtime_info.transcript_th =
+                requester.common.new_transcript().unwrap();
         }
 
         requester.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -41,8 +41,6 @@ fn fuzz_send_receive_spdm_key_exchange(fuzzdata: &[u8]) {
 
         responder.common.reset_runtime_info();
 
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2.is_some();
         // responder.common.peer_info.peer_cert_chain.cert_chain = REQ_CERT_CHAIN_DATA;
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -64,8 +62,6 @@ fn fuzz_send_receive_spdm_key_exchange(fuzzdata: &[u8]) {
 
         requester.common.reset_runtime_info();
 
-        #[cfg(feature = "hashed-transcript-data")]
-        requester.common.runtime_info.digest_context_m1m2.is_some();
         // requester.common.peer_info.peer_cert_chain.cert_chain = REQ_CERT_CHAIN_DATA;
 
         let _ = requester.send_receive_spdm_key_exchange(
@@ -104,8 +100,6 @@ fn fuzz_send_receive_spdm_key_exchange(fuzzdata: &[u8]) {
 
         responder.common.reset_runtime_info();
 
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2.is_some();
         // responder.common.peer_info.peer_cert_chain.cert_chain = REQ_CERT_CHAIN_DATA;
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -127,8 +121,6 @@ fn fuzz_send_receive_spdm_key_exchange(fuzzdata: &[u8]) {
 
         requester.common.reset_runtime_info();
 
-        #[cfg(feature = "hashed-transcript-data")]
-        requester.common.runtime_info.digest_context_m1m2.is_some();
         // requester.common.peer_info.peer_cert_chain.cert_chain = REQ_CERT_CHAIN_DATA;
 
         let _ = requester.send_receive_spdm_key_exchange(
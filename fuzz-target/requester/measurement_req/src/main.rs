@@ -49,8 +49,6 @@ fn fuzz_send_receive_spdm_measurement(fuzzdata: &[u8]) {
         responder.common.negotiate_info.measurement_hash_sel =
             SpdmMeasurementHashAlgo::TPM_ALG_SHA_384;
         let message_m = &[0];
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2.is_some();
         responder.common.reset_runtime_info();
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -125,8 +123,6 @@ fn fuzz_send_receive_spdm_measurement(fuzzdata: &[u8]) {
         responder.common.negotiate_info.measurement_hash_sel =
             SpdmMeasurementHashAlgo::TPM_ALG_SHA_384;
         let message_m = &[0];
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2.is_some();
         responder.common.reset_runtime_info();
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -201,8 +197,6 @@ fn fuzz_send_receive_spdm_measurement(fuzzdata: &[u8]) {
         responder.common.negotiate_info.measurement_hash_sel =
             SpdmMeasurementHashAlgo::TPM_ALG_SHA_384;
         let message_m = &[0];
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2.is_some();
         responder.common.reset_runtime_info();
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -276,8 +270,6 @@ fn fuzz_send_receive_spdm_measurement(fuzzdata: &[u8]) {
         responder.common.negotiate_info.measurement_hash_sel =
             SpdmMeasurementHashAlgo::TPM_ALG_SHA_384;
         let message_m = &[0];
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2.is_some();
         responder.common.reset_runtime_info();
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -41,15 +41,14 @@ fn fuzz_send_receive_spdm_psk_finish(fuzzdata: &[u8]) {
         SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
     );
 
-    #[cfg(feature = "hashed-transcript-data")]
     {
         let mut dhe_secret = SpdmDheFinalKeyStruct::default();
         dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
         responder.common.session[0]
             .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
             .unwrap();
-        responder.common.session[0].runtime_info.digest_context_th =
-            spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+        responder.common.session[0].runtime_info.transcript_th =
+            responder.common.new_transcript().unwrap();
     }
 
     let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
@@ -79,15 +78,14 @@ fn fuzz_send_receive_spdm_psk_finish(fuzzdata: &[u8]) {
     );
     requester.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
 
-    #[cfg(feature = "hashed-transcript-data")]
     {
         let mut dhe_secret = SpdmDheFinalKeyStruct::default();
         dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
         requester.common.session[0]
             .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
             .unwrap();
-        requester.common.session[0].runtime_info.digest_context_th =
-            spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+        requester.common.session[0].runtime_info.transcript_th =
+            requester.common.new_transcript().unwrap();
     }
 
     let _ = requester.send_receive_spdm_psk_finish(4294901758);
@@ -28,12 +28,6 @@ fn fuzz_handle_spdm_certificate(data: &[u8]) {
 
     context.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
 
-    #[cfg(feature = "hashed-transcript-data")]
-    {
-        context.common.runtime_info.digest_context_m1m2 =
-            spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-    }
-
     context.handle_spdm_certificate(data, None);
 }
 fn main() {
@@ -52,15 +52,14 @@ fn fuzz_handle_spdm_finish(data: &[u8]) {
         context.common.negotiate_info.rsp_capabilities_sel =
             SpdmResponseCapabilityFlags::HANDSHAKE_IN_THE_CLEAR_CAP;
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
@@ -96,15 +95,14 @@ fn fuzz_handle_spdm_finish(data: &[u8]) {
         );
         context.common.provision_info.my_cert_chain_data = None;
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -140,15 +138,14 @@ fn fuzz_handle_spdm_finish(data: &[u8]) {
         context.common.negotiate_info.rsp_capabilities_sel =
             SpdmResponseCapabilityFlags::HANDSHAKE_IN_THE_CLEAR_CAP;
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
         context.handle_spdm_finish(4294901758, data);
@@ -185,15 +182,14 @@ fn fuzz_handle_spdm_finish(data: &[u8]) {
         context.common.negotiate_info.rsp_capabilities_sel =
             SpdmResponseCapabilityFlags::HANDSHAKE_IN_THE_CLEAR_CAP;
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
@@ -234,15 +230,14 @@ fn fuzz_handle_spdm_finish(data: &[u8]) {
 
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context
@@ -287,15 +282,14 @@ fn fuzz_handle_spdm_finish(data: &[u8]) {
         context.common.negotiate_info.rsp_capabilities_sel =
             SpdmResponseCapabilityFlags::HANDSHAKE_IN_THE_CLEAR_CAP;
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
@@ -32,12 +32,6 @@ fn fuzz_handle_spdm_measurement(data: &[u8]) {
     context.common.negotiate_info.measurement_specification_sel =
         SpdmMeasurementSpecification::DMTF;
 
-    #[cfg(feature = "hashed-transcript-data")]
-    {
-        context.common.runtime_info.digest_context_m1m2 =
-            spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
-    }
-
     context.handle_spdm_measurement(None, data);
 }
 fn main() {
@@ -48,15 +48,14 @@ fn fuzz_handle_spdm_psk_finish(data: &[u8]) {
         );
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.handle_spdm_psk_finish(4294901758, data);
@@ -91,15 +90,14 @@ fn fuzz_handle_spdm_psk_finish(data: &[u8]) {
             .append_message(&[1u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE]);
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.handle_spdm_psk_finish(4294901758, data);
@@ -128,15 +126,14 @@ fn fuzz_handle_spdm_psk_finish(data: &[u8]) {
         );
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
 
         context.handle_spdm_psk_finish(4294901758, data);
@@ -166,15 +163,14 @@ fn fuzz_handle_spdm_psk_finish(data: &[u8]) {
         );
         context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
 
-        #[cfg(feature = "hashed-transcript-data")]
         {
             let mut dhe_secret = SpdmDheFinalKeyStruct::default();
             dhe_secret.data_size = SpdmDheAlgo::SECP_384_R1.get_size();
             context.common.session[0]
                 .set_dhe_secret(SpdmVersion::SpdmVersion12, dhe_secret)
                 .unwrap();
-            context.common.session[0].runtime_info.digest_context_th =
-                spdmlib::crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384);
+            context.common.session[0].runtime_info.transcript_th =
+                context.common.new_transcript().unwrap();
         }
         context.handle_spdm_psk_finish(4294901758, data);
     }
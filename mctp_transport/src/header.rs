@@ -150,6 +150,49 @@ impl SpdmTransportEncap for MctpTransportEncap {
         Ok((payload_size, is_app_mesaage))
     }
 
+    fn encap_app_message(
+        &mut self,
+        app_message: &[u8],
+        app_buffer: &mut [u8],
+        app_type: u8,
+    ) -> SpdmResult<usize> {
+        let payload_len = app_message.len();
+        let mut writer = Writer::init(&mut *app_buffer);
+        let mctp_header = MctpMessageHeader {
+            r#type: MctpMessageType::Unknown(app_type),
+        };
+        mctp_header.encode(&mut writer);
+        let header_size = writer.used();
+        if app_buffer.len() < header_size + payload_len {
+            return spdm_result_err!(EINVAL);
+        }
+        app_buffer[header_size..(header_size + payload_len)].copy_from_slice(app_message);
+        Ok(header_size + payload_len)
+    }
+
+    fn decap_app_message(
+        &mut self,
+        app_buffer: &[u8],
+        app_message: &mut [u8],
+    ) -> SpdmResult<(usize, Option<u8>)> {
+        let mut reader = Reader::init(app_buffer);
+        let app_type = match MctpMessageHeader::read(&mut reader) {
+            Some(mctp_header) => match mctp_header.r#type {
+                MctpMessageType::MctpMessageTypeSpdm => None,
+                MctpMessageType::MctpMessageTypeSecuredMctp => return spdm_result_err!(EINVAL),
+                r#type => Some(r#type.get_u8()),
+            },
+            None => return spdm_result_err!(EIO),
+        };
+        let header_size = reader.used();
+        let payload_size = app_buffer.len() - header_size;
+        if app_message.len() < payload_size {
+            return spdm_result_err!(EINVAL);
+        }
+        app_message[..payload_size].copy_from_slice(&app_buffer[header_size..]);
+        Ok((payload_size, app_type))
+    }
+
     fn get_sequence_number_count(&mut self) -> u8 {
         2
     }
@@ -283,6 +326,36 @@ mod tests {
         assert!(status);
     }
     #[test]
+    fn test_case0_encap_decap_app_message() {
+        let mut mctp_transport_encap = MctpTransportEncap {};
+        let mut app_buffer = [0u8; 100];
+        let app_message = [0xAAu8; 10];
+
+        let used = mctp_transport_encap
+            .encap_app_message(&app_message, &mut app_buffer, 0x04)
+            .unwrap();
+        assert_eq!(used, 11);
+        assert_eq!(app_buffer[0], 0x04);
+
+        let mut decoded = [0u8; 100];
+        let (decoded_size, app_type) = mctp_transport_encap
+            .decap_app_message(&app_buffer[..used], &mut decoded)
+            .unwrap();
+        assert_eq!(app_type, Some(0x04));
+        assert_eq!(&decoded[..decoded_size], &app_message[..]);
+
+        app_buffer[0] = 0x05;
+        let (_, app_type) = mctp_transport_encap
+            .decap_app_message(&app_buffer[..used], &mut decoded)
+            .unwrap();
+        assert_eq!(app_type, None);
+
+        app_buffer[0] = 0x06;
+        assert!(mctp_transport_encap
+            .decap_app_message(&app_buffer[..used], &mut decoded)
+            .is_err());
+    }
+    #[test]
     fn test_case0_get_sequence_number_count() {
         let mut mctp_transport_encap = MctpTransportEncap {};
         assert_eq!(mctp_transport_encap.get_sequence_number_count(), 2);
@@ -51,6 +51,7 @@ struct SpdmAlgoConfig {
 struct SpdmCertConfig {
     max_cert_portion_len: usize,
     max_cert_chain_data_size: usize,
+    max_root_cert_support: usize,
 }
 
 #[derive(Debug, PartialEq, Deserialize)]
@@ -96,6 +97,10 @@ pub const MAX_SPDM_CERT_PORTION_LEN: usize = {cert_portion_len}; // 0x200;
 /// This is used in SpdmCertChainData without SpdmCertChainHeader.
 pub const MAX_SPDM_CERT_CHAIN_DATA_SIZE: usize = {cert_chain_data_sz}; // 0x1000;
 
+/// This is used in SpdmProvisionInfo, the number of trusted root certificates
+/// and root certificate hashes a requester can be provisioned with.
+pub const MAX_ROOT_CERT_SUPPORT: usize = {root_cert_support};
+
 /// This is used in SpdmOpaqueStruct <- SpdmChallengeAuthResponsePayload / SpdmMeasurementsResponsePayload
 /// It should be smaller than 1024
 pub const MAX_SPDM_OPAQUE_SIZE: usize = {opaque_sz};
@@ -167,6 +172,7 @@ fn main() {
         ext_algo_struct_cnt = spdm_config.algo_config.max_ext_algo_struct_count,
         cert_portion_len = spdm_config.cert_config.max_cert_portion_len,
         cert_chain_data_sz = spdm_config.cert_config.max_cert_chain_data_size,
+        root_cert_support = spdm_config.cert_config.max_root_cert_support,
         opaque_sz = spdm_config.max_opaque_size,
         meas_val_len = spdm_config.measurement_config.max_measurement_val_len,
         psk_ctx_sz = spdm_config.psk_config.max_psk_context_size,
@@ -0,0 +1,527 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+//! Handoff of an established session, with the connection state it needs, to
+//! another `SpdmContext`, e.g. from the firmware to the OS driver.
+//!
+//! The blob is `SPDM_HANDOFF_MAGIC`, `SPDM_HANDOFF_VERSION`, the base hash
+//! algorithm, the state, and an HMAC of all of it keyed with the handoff key
+//! that both sides share. The session, which holds the secrets, is encrypted
+//! in the state with AES-256-GCM under a key derived from the handoff key.
+//! The rest of the state, the negotiated algorithms, VCA transcript and peer
+//! cert chains, is in the clear.
+
+use super::session::SpdmSession;
+use super::*;
+use codec::{Codec, Reader};
+use zeroize::Zeroize;
+
+pub const SPDM_HANDOFF_MAGIC: [u8; 8] = *b"SPDMHOFF";
+pub const SPDM_HANDOFF_VERSION: u16 = 1;
+
+const SPDM_HANDOFF_AEAD_ALGO: SpdmAeadAlgo = SpdmAeadAlgo::AES_256_GCM;
+const SPDM_HANDOFF_ENCRYPTION_KEY_LABEL: &[u8] = b"spdm handoff encryption key";
+/// Largest session that `SpdmSession::write_handoff` writes.
+const MAX_SPDM_HANDOFF_SESSION_SIZE: usize = 0x400;
+
+/// Encode `value`, an error if it does not fit.
+pub(crate) fn write_handoff_value<T: Codec>(writer: &mut Writer, value: &T) -> SpdmResult {
+    let used = writer.used();
+    value.encode(writer);
+    if writer.used() == used {
+        return spdm_result_err!(ENOMEM);
+    }
+    Ok(())
+}
+
+/// Encode `bytes` after their u32 length.
+pub(crate) fn write_handoff_bytes(writer: &mut Writer, bytes: &[u8]) -> SpdmResult {
+    write_handoff_value(writer, &(bytes.len() as u32))?;
+    writer.extend_from_slice(bytes).ok_or(spdm_err!(ENOMEM))?;
+    Ok(())
+}
+
+/// Read what `write_handoff_bytes` wrote into `bytes`, which they must fit.
+pub(crate) fn read_handoff_bytes(r: &mut Reader, bytes: &mut [u8]) -> Option<u16> {
+    let size = u32::read(r)? as usize;
+    if size > bytes.len() {
+        return None;
+    }
+    bytes[..size].copy_from_slice(r.take(size)?);
+    Some(size as u16)
+}
+
+fn write_negotiate_info(writer: &mut Writer, negotiate_info: &SpdmNegotiateInfo) -> SpdmResult {
+    write_handoff_value(writer, &negotiate_info.spdm_version_sel)?;
+    write_handoff_value(writer, &negotiate_info.req_capabilities_sel)?;
+    write_handoff_value(writer, &negotiate_info.rsp_capabilities_sel)?;
+    write_handoff_value(writer, &negotiate_info.req_ct_exponent_sel)?;
+    write_handoff_value(writer, &negotiate_info.rsp_ct_exponent_sel)?;
+    write_handoff_value(writer, &negotiate_info.measurement_specification_sel)?;
+    write_handoff_value(writer, &negotiate_info.measurement_hash_sel)?;
+    write_handoff_value(writer, &negotiate_info.base_hash_sel)?;
+    write_handoff_value(writer, &negotiate_info.base_asym_sel)?;
+    write_handoff_value(writer, &negotiate_info.dhe_sel)?;
+    write_handoff_value(writer, &negotiate_info.aead_sel)?;
+    write_handoff_value(writer, &negotiate_info.req_asym_sel)?;
+    write_handoff_value(writer, &negotiate_info.key_schedule_sel)?;
+    write_handoff_value(writer, &negotiate_info.opaque_data_support)?;
+    write_handoff_value(writer, &(negotiate_info.termination_policy_set as u8))?;
+    write_handoff_value(writer, &negotiate_info.req_data_transfer_size_sel)?;
+    write_handoff_value(writer, &negotiate_info.req_max_spdm_msg_size_sel)?;
+    write_handoff_value(writer, &negotiate_info.rsp_data_transfer_size_sel)?;
+    write_handoff_value(writer, &negotiate_info.rsp_max_spdm_msg_size_sel)
+}
+
+fn read_negotiate_info(r: &mut Reader) -> Option<SpdmNegotiateInfo> {
+    Some(SpdmNegotiateInfo {
+        spdm_version_sel: SpdmVersion::read(r)?,
+        req_capabilities_sel: SpdmRequestCapabilityFlags::read(r)?,
+        rsp_capabilities_sel: SpdmResponseCapabilityFlags::read(r)?,
+        req_ct_exponent_sel: u8::read(r)?,
+        rsp_ct_exponent_sel: u8::read(r)?,
+        measurement_specification_sel: SpdmMeasurementSpecification::read(r)?,
+        measurement_hash_sel: SpdmMeasurementHashAlgo::read(r)?,
+        base_hash_sel: SpdmBaseHashAlgo::read(r)?,
+        base_asym_sel: SpdmBaseAsymAlgo::read(r)?,
+        dhe_sel: SpdmDheAlgo::read(r)?,
+        aead_sel: SpdmAeadAlgo::read(r)?,
+        req_asym_sel: SpdmReqAsymAlgo::read(r)?,
+        key_schedule_sel: SpdmKeyScheduleAlgo::read(r)?,
+        opaque_data_support: SpdmOpaqueSupport::read(r)?,
+        termination_policy_set: u8::read(r)? != 0,
+        req_data_transfer_size_sel: u32::read(r)?,
+        req_max_spdm_msg_size_sel: u32::read(r)?,
+        rsp_data_transfer_size_sel: u32::read(r)?,
+        rsp_max_spdm_msg_size_sel: u32::read(r)?,
+    })
+}
+
+fn write_connection_state(
+    writer: &mut Writer,
+    connection_state: SpdmConnectionState,
+) -> SpdmResult {
+    let value: u8 = match connection_state {
+        SpdmConnectionState::NotStarted => 0,
+        SpdmConnectionState::AfterVersion => 1,
+        SpdmConnectionState::AfterCapabilities => 2,
+        SpdmConnectionState::Negotiated => 3,
+    };
+    write_handoff_value(writer, &value)
+}
+
+fn read_connection_state(r: &mut Reader) -> Option<SpdmConnectionState> {
+    match u8::read(r)? {
+        0 => Some(SpdmConnectionState::NotStarted),
+        1 => Some(SpdmConnectionState::AfterVersion),
+        2 => Some(SpdmConnectionState::AfterCapabilities),
+        3 => Some(SpdmConnectionState::Negotiated),
+        _ => None,
+    }
+}
+
+fn write_peer_cert_chains(writer: &mut Writer, peer_info: &SpdmPeerInfo) -> SpdmResult {
+    for peer_cert_chain in peer_info.peer_cert_chain.iter() {
+        match peer_cert_chain {
+            Some(peer_cert_chain) => {
+                write_handoff_value(writer, &1u8)?;
+                write_handoff_bytes(writer, peer_cert_chain.root_hash.as_ref())?;
+                write_handoff_bytes(writer, peer_cert_chain.cert_chain.as_ref())?;
+            }
+            None => write_handoff_value(writer, &0u8)?,
+        }
+    }
+    Ok(())
+}
+
+fn read_peer_cert_chains(r: &mut Reader, peer_info: &mut SpdmPeerInfo) -> Option<()> {
+    for peer_cert_chain in peer_info.peer_cert_chain.iter_mut() {
+        *peer_cert_chain = match u8::read(r)? {
+            0 => None,
+            1 => {
+                let mut cert_chain = SpdmCertChain::default();
+                cert_chain.root_hash.data_size =
+                    read_handoff_bytes(r, &mut cert_chain.root_hash.data[..])?;
+                cert_chain.cert_chain.data_size =
+                    read_handoff_bytes(r, &mut cert_chain.cert_chain.data)?;
+                Some(cert_chain)
+            }
+            _ => return None,
+        };
+    }
+    Some(())
+}
+
+/// The key the session is encrypted with, the HMAC of a label keyed with the
+/// handoff key, as the handoff key need not have the size of the hash.
+fn handoff_encryption_key(
+    base_hash_sel: SpdmBaseHashAlgo,
+    handoff_key: &[u8],
+) -> SpdmResult<SpdmDigestStruct> {
+    let mut key = crypto::hmac::hmac(
+        base_hash_sel,
+        handoff_key,
+        SPDM_HANDOFF_ENCRYPTION_KEY_LABEL,
+    )
+    .ok_or(spdm_err!(EFAULT))?;
+    let key_size = SPDM_HANDOFF_AEAD_ALGO.get_key_size();
+    if key.data_size < key_size {
+        return spdm_result_err!(EFAULT);
+    }
+    key.data_size = key_size;
+    Ok(key)
+}
+
+/// The session, encrypted: the IV, the cipher text after its u32 length and
+/// the tag.
+fn write_session(
+    writer: &mut Writer,
+    base_hash_sel: SpdmBaseHashAlgo,
+    handoff_key: &[u8],
+    session: &SpdmSession,
+) -> SpdmResult {
+    let mut plain_text = [0u8; MAX_SPDM_HANDOFF_SESSION_SIZE];
+    let mut cipher_text = [0u8; MAX_SPDM_HANDOFF_SESSION_SIZE];
+    let mut iv = [0u8; AEAD_AES_256_GCM_IV_SIZE];
+    let mut tag = [0u8; AEAD_AES_256_GCM_TAG_SIZE];
+    let result = (|| -> SpdmResult<usize> {
+        let mut session_writer = Writer::init(&mut plain_text);
+        session.write_handoff(&mut session_writer)?;
+        let size = session_writer.used();
+        let key = handoff_encryption_key(base_hash_sel, handoff_key)?;
+        crypto::rand::get_random(&mut iv)?;
+        crypto::aead::encrypt(
+            SPDM_HANDOFF_AEAD_ALGO,
+            key.as_ref(),
+            &iv,
+            &[],
+            &plain_text[..size],
+            &mut tag,
+            &mut cipher_text[..size],
+        )?;
+        Ok(size)
+    })();
+    plain_text.zeroize();
+    let size = result?;
+
+    writer.extend_from_slice(&iv).ok_or(spdm_err!(ENOMEM))?;
+    write_handoff_bytes(writer, &cipher_text[..size])?;
+    writer.extend_from_slice(&tag).ok_or(spdm_err!(ENOMEM))?;
+    Ok(())
+}
+
+/// Decrypt and read what `write_session` wrote.
+fn read_session(
+    r: &mut Reader,
+    base_hash_sel: SpdmBaseHashAlgo,
+    handoff_key: &[u8],
+) -> SpdmResult<SpdmSession> {
+    let mut iv = [0u8; AEAD_AES_256_GCM_IV_SIZE];
+    iv.copy_from_slice(r.take(AEAD_AES_256_GCM_IV_SIZE).ok_or(spdm_err!(EINVAL))?);
+    let mut cipher_text = [0u8; MAX_SPDM_HANDOFF_SESSION_SIZE];
+    let size = read_handoff_bytes(r, &mut cipher_text).ok_or(spdm_err!(EINVAL))? as usize;
+    let mut tag = [0u8; AEAD_AES_256_GCM_TAG_SIZE];
+    tag.copy_from_slice(r.take(AEAD_AES_256_GCM_TAG_SIZE).ok_or(spdm_err!(EINVAL))?);
+
+    let mut plain_text = [0u8; MAX_SPDM_HANDOFF_SESSION_SIZE];
+    let result = (|| -> SpdmResult<SpdmSession> {
+        let key = handoff_encryption_key(base_hash_sel, handoff_key)?;
+        crypto::aead::decrypt(
+            SPDM_HANDOFF_AEAD_ALGO,
+            key.as_ref(),
+            &iv,
+            &[],
+            &cipher_text[..size],
+            &tag,
+            &mut plain_text[..size],
+        )?;
+        let mut session_reader = Reader::init(&plain_text[..size]);
+        match SpdmSession::read_handoff(&mut session_reader) {
+            Some(session) if !session_reader.any_left() => Ok(session),
+            _ => spdm_result_err!(EINVAL),
+        }
+    })();
+    plain_text.zeroize();
+    result
+}
+
+impl<'a> SpdmContext<'a> {
+    /// Hand the established session `session_id` off: write it to `buf` with
+    /// the negotiated state, VCA transcript and peer cert chains, to be given
+    /// to `import_session` of another context.
+    ///
+    /// The session is torn down here, as its keys and sequence numbers must
+    /// only be used by one side.
+    ///
+    /// The session secrets in `buf` are encrypted with a key derived from
+    /// `handoff_key`, which must only be known to the two contexts. The rest
+    /// is readable by anyone who sees `buf`.
+    pub fn export_session(
+        &mut self,
+        session_id: u32,
+        handoff_key: &[u8],
+        buf: &mut [u8],
+    ) -> SpdmResult<usize> {
+        let base_hash_sel = self.negotiate_info.base_hash_sel;
+        let session = self
+            .get_immutable_session_via_id(session_id)
+            .ok_or(spdm_err!(EINVAL))?;
+        if session.get_session_state() != SpdmSessionState::SpdmSessionEstablished {
+            error!("!!! export session : not established !!!\n");
+            return spdm_result_err!(EINVAL);
+        }
+
+        let mut writer = Writer::init(buf);
+        writer
+            .extend_from_slice(&SPDM_HANDOFF_MAGIC)
+            .ok_or(spdm_err!(ENOMEM))?;
+        write_handoff_value(&mut writer, &SPDM_HANDOFF_VERSION)?;
+        write_handoff_value(&mut writer, &base_hash_sel)?;
+        write_negotiate_info(&mut writer, &self.negotiate_info)?;
+        write_connection_state(&mut writer, self.runtime_info.connection_state)?;
+        write_handoff_bytes(&mut writer, self.runtime_info.message_a.as_ref())?;
+        write_peer_cert_chains(&mut writer, &self.peer_info)?;
+        write_session(&mut writer, base_hash_sel, handoff_key, session)?;
+
+        let hmac = crypto::hmac::hmac(base_hash_sel, handoff_key, writer.used_slice())
+            .ok_or(spdm_err!(EFAULT))?;
+        writer
+            .extend_from_slice(hmac.as_ref())
+            .ok_or(spdm_err!(ENOMEM))?;
+        let used = writer.used();
+
+        self.get_session_via_id(session_id)
+            .ok_or(spdm_err!(EINVAL))?
+            .teardown(session_id)?;
+        Ok(used)
+    }
+
+    /// Take over the session in `blob`, written by `export_session` with the
+    /// same `handoff_key`, replacing the negotiated state of this context.
+    ///
+    /// The context must not have a session in use, as they would not belong
+    /// to the imported connection.
+    pub fn import_session(&mut self, handoff_key: &[u8], blob: &[u8]) -> SpdmResult<u32> {
+        if self
+            .session
+            .iter()
+            .any(|session| session.get_session_id() != 0)
+        {
+            error!("!!! import session : session in use !!!\n");
+            return spdm_result_err!(EINVAL);
+        }
+
+        let mut r = Reader::init(blob);
+        if r.take(SPDM_HANDOFF_MAGIC.len()) != Some(&SPDM_HANDOFF_MAGIC[..])
+            || u16::read(&mut r) != Some(SPDM_HANDOFF_VERSION)
+        {
+            error!("!!! import session : unknown blob !!!\n");
+            return spdm_result_err!(EINVAL);
+        }
+        let base_hash_sel = SpdmBaseHashAlgo::read(&mut r).ok_or(spdm_err!(EINVAL))?;
+        let hash_size = base_hash_sel.get_size() as usize;
+        if hash_size == 0 || blob.len() < r.used() + hash_size {
+            return spdm_result_err!(EINVAL);
+        }
+        let (data, hmac) = blob.split_at(blob.len() - hash_size);
+        let mut hmac_struct = SpdmDigestStruct::default();
+        hmac_struct.data_size = hash_size as u16;
+        hmac_struct.data[..hash_size].copy_from_slice(hmac);
+        crypto::hmac::hmac_verify(base_hash_sel, handoff_key, data, &hmac_struct).map_err(
+            |_| {
+                error!("!!! import session : hmac verify fail !!!\n");
+                spdm_err!(ESEC)
+            },
+        )?;
+
+        let mut r = Reader::init(&data[r.used()..]);
+        let negotiate_info = read_negotiate_info(&mut r).ok_or(spdm_err!(EINVAL))?;
+        let connection_state = read_connection_state(&mut r).ok_or(spdm_err!(EINVAL))?;
+        let mut message_a = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let message_a_size =
+            read_handoff_bytes(&mut r, &mut message_a).ok_or(spdm_err!(EINVAL))? as usize;
+        let mut peer_info = SpdmPeerInfo::default();
+        read_peer_cert_chains(&mut r, &mut peer_info).ok_or(spdm_err!(EINVAL))?;
+        let session = read_session(&mut r, base_hash_sel, handoff_key)?;
+        if r.any_left() || negotiate_info.base_hash_sel != base_hash_sel {
+            return spdm_result_err!(EINVAL);
+        }
+
+        let session_id = session.get_session_id();
+        if session_id == 0 {
+            return spdm_result_err!(EINVAL);
+        }
+        *self.get_next_avaiable_session().ok_or(spdm_err!(ENOMEM))? = session;
+
+        self.negotiate_info = negotiate_info;
+        self.reset_runtime_info();
+        self.runtime_info.connection_state = connection_state;
+        self.runtime_info
+            .message_a
+            .append_message(&message_a[..message_a_size])
+            .ok_or(spdm_err!(EINVAL))?;
+        self.peer_info = peer_info;
+        info!("imported session {:08x}\n", session_id);
+        Ok(session_id)
+    }
+}
+
+#[cfg(all(test,))]
+mod tests {
+    use super::*;
+    use crate::error::SpdmErrorKind;
+    use crate::message::*;
+    use crate::requester::RequesterContext;
+    use crate::testlib::*;
+
+    #[test]
+    fn test_case0_session_handoff() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let handoff_key = [0x5au8; 32];
+            let mut blob = [0u8; 0x4000];
+            let (used, session_id, message_a_size) = {
+                let (req_config_info, req_provision_info) = create_info();
+                let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+                let mut requester = new_connected_requester(
+                    device_io,
+                    pcidoe_transport_encap,
+                    req_config_info,
+                    req_provision_info,
+                );
+                let session_id = requester
+                    .start_session(
+                        false,
+                        0,
+                        SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                    )
+                    .unwrap();
+                assert!(requester.send_receive_spdm_heartbeat(session_id).is_ok());
+
+                // only one side may use the session
+                let used = requester
+                    .common
+                    .export_session(session_id, &handoff_key, &mut blob)
+                    .unwrap();
+                assert!(requester.common.get_session_via_id(session_id).is_none());
+                assert!(requester
+                    .common
+                    .export_session(session_id, &handoff_key, &mut blob)
+                    .is_err());
+                (
+                    used,
+                    session_id,
+                    requester.common.runtime_info.message_a.as_ref().len(),
+                )
+            };
+
+            // a blob not written with the handoff key is rejected
+            {
+                let (req_config_info, req_provision_info) = create_info();
+                let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+                let mut tampered = blob;
+                tampered[used / 2] ^= 1;
+                let result = RequesterContext::import_session(
+                    device_io,
+                    pcidoe_transport_encap,
+                    req_config_info,
+                    req_provision_info,
+                    &handoff_key,
+                    &tampered[..used],
+                );
+                assert_eq!(result.err().map(|e| e.kind), Some(SpdmErrorKind::Crypto));
+            }
+
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let (mut requester, imported_session_id) = RequesterContext::import_session(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+                &handoff_key,
+                &blob[..used],
+            )
+            .unwrap();
+            assert_eq!(imported_session_id, session_id);
+            assert_eq!(
+                requester.common.runtime_info.message_a.as_ref().len(),
+                message_a_size
+            );
+            assert!(requester.common.peer_info.peer_cert_chain[0].is_some());
+
+            // the session carries on, key update included
+            assert!(requester.send_receive_spdm_heartbeat(session_id).is_ok());
+            assert!(requester
+                .send_receive_spdm_key_update(session_id, SpdmKeyUpdateOperation::SpdmUpdateAllKeys)
+                .is_ok());
+            assert!(requester.send_receive_spdm_heartbeat(session_id).is_ok());
+            assert!(requester
+                .end_session(
+                    session_id,
+                    SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE
+                )
+                .is_ok());
+        });
+    }
+
+    #[test]
+    fn test_case1_import_session_with_session_in_use() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = new_connected_requester(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+            let mut session_ids = [0u32; 2];
+            for session_id in session_ids.iter_mut() {
+                *session_id = requester
+                    .start_session(
+                        false,
+                        0,
+                        SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                    )
+                    .unwrap();
+            }
+
+            let handoff_key = [0x5au8; 32];
+            let mut blob = [0u8; 0x4000];
+            let used = requester
+                .common
+                .export_session(session_ids[0], &handoff_key, &mut blob)
+                .unwrap();
+
+            // the other session would not belong to the imported connection
+            let err = requester
+                .common
+                .import_session(&handoff_key, &blob[..used])
+                .unwrap_err();
+            assert_eq!(err.kind, SpdmErrorKind::Local);
+            assert!(requester
+                .send_receive_spdm_heartbeat(session_ids[1])
+                .is_ok());
+
+            assert!(requester
+                .end_session(
+                    session_ids[1],
+                    SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE
+                )
+                .is_ok());
+            assert_eq!(
+                requester
+                    .common
+                    .import_session(&handoff_key, &blob[..used])
+                    .unwrap(),
+                session_ids[0]
+            );
+            assert!(requester
+                .send_receive_spdm_heartbeat(session_ids[0])
+                .is_ok());
+        });
+    }
+}
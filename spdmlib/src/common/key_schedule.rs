@@ -6,6 +6,7 @@ use crate::config::MAX_SPDM_MESSAGE_BUFFER_SIZE;
 use crate::crypto;
 use crate::protocol::*;
 use codec::{Codec, Writer};
+use zeroize::Zeroize;
 extern crate alloc;
 use alloc::boxed::Box;
 
@@ -21,6 +22,7 @@ const BIN_STR6_LABEL: &[u8] = b"iv";
 const BIN_STR7_LABEL: &[u8] = b"finished";
 const BIN_STR8_LABEL: &[u8] = b"exp master";
 const BIN_STR9_LABEL: &[u8] = b"traffic upd";
+const BIN_STR_EXPORTER_LABEL: &[u8] = b"exporter";
 const SPDM_VERSION_VALUE: &[u8; 8] = b"spdm .  ";
 const SPDM_VERSION_VALUE_MAJOR_INDEX: usize = 4;
 const SPDM_VERSION_VALUE_MINOR_INDEX: usize = 6;
@@ -224,6 +226,75 @@ impl SpdmKeySchedule {
         crypto::hkdf::hkdf_expand(hash_algo, key, bin_str8, hash_algo.get_size())
     }
 
+    /// Keying material for `label` and `context`, as the TLS 1.3 exporter:
+    /// a secret for `label` from `key`, the export master secret, then
+    /// `keying_material.len()` bytes of it for `context`, at most 255 hashes.
+    pub fn derive_exported_keying_material(
+        &self,
+        spdm_version: SpdmVersion,
+        hash_algo: SpdmBaseHashAlgo,
+        key: &[u8],
+        label: &[u8],
+        context: &[u8],
+        keying_material: &mut [u8],
+    ) -> Option<()> {
+        let hash_size = hash_algo.get_size() as usize;
+        if hash_size == 0 || keying_material.is_empty() || keying_material.len() > 255 * hash_size {
+            return None;
+        }
+
+        let buffer = &mut [0; MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let empty_hash = crypto::hash::hash_all(hash_algo, &[])?;
+        let bin_str = self.binconcat(
+            hash_algo.get_size(),
+            spdm_version,
+            label,
+            Some(empty_hash.as_ref()),
+            buffer,
+        )?;
+        let secret = crypto::hkdf::hkdf_expand(hash_algo, key, bin_str, hash_algo.get_size())?;
+
+        // HKDF-Expand one block at a time, the crypto callback gives one hash
+        // at most: block i is the HMAC of block i - 1, the info and i. Block
+        // i - 1 is kept right before the info in `buffer`, the counter after.
+        let context_hash = crypto::hash::hash_all(hash_algo, context)?;
+        let info_size = self
+            .binconcat(
+                keying_material.len() as u16,
+                spdm_version,
+                BIN_STR_EXPORTER_LABEL,
+                Some(context_hash.as_ref()),
+                &mut buffer[SPDM_MAX_HASH_SIZE..MAX_SPDM_MESSAGE_BUFFER_SIZE - 1],
+            )?
+            .len();
+        let info_end = SPDM_MAX_HASH_SIZE + info_size;
+        let mut result = Some(());
+        for (i, chunk) in keying_material.chunks_mut(hash_size).enumerate() {
+            let start = if i == 0 {
+                SPDM_MAX_HASH_SIZE
+            } else {
+                SPDM_MAX_HASH_SIZE - hash_size
+            };
+            buffer[info_end] = i as u8 + 1;
+            let block = match crypto::hmac::hmac(
+                hash_algo,
+                secret.as_ref(),
+                &buffer[start..info_end + 1],
+            ) {
+                Some(block) => block,
+                None => {
+                    result = None;
+                    break;
+                }
+            };
+            chunk.copy_from_slice(&block.as_ref()[..chunk.len()]);
+            buffer[SPDM_MAX_HASH_SIZE - hash_size..SPDM_MAX_HASH_SIZE]
+                .copy_from_slice(block.as_ref());
+        }
+        buffer[..SPDM_MAX_HASH_SIZE].zeroize();
+        result
+    }
+
     pub fn derive_update_secret(
         &self,
         spdm_version: SpdmVersion,
@@ -2,22 +2,28 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+pub mod handoff;
 pub mod key_schedule;
 pub mod opaque;
 pub mod session;
 pub mod spdm_codec;
+pub mod transcript;
 
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::crypto::x509v3::SpdmCertInfo;
 use crate::{crypto, protocol::*};
 
 pub use opaque::*;
 pub use spdm_codec::SpdmCodec;
+pub use transcript::{SpdmTranscript, SpdmTranscriptMode};
 
 use crate::config;
 use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use codec::Writer;
 use session::*;
 
-#[cfg(feature = "hashed-transcript-data")]
 pub use crate::crypto::HashCtx;
 
 #[cfg(feature = "downcast")]
@@ -30,6 +36,9 @@ use core::any::Any;
 /// https://www.dmtf.org/sites/default/files/standards/documents/DSP0274_1.1.0.pdf
 pub const ST1: usize = 1_000_000;
 
+/// first half session ID handed out, counting down
+pub const INITIAL_HALF_SESSION_ID: u16 = 0xFFFE;
+
 /// used as parameter to be slot_id when use_psk is true
 pub const INVALID_SLOT: u8 = 0xFF;
 
@@ -75,6 +84,27 @@ pub trait SpdmTransportEncap {
     fn decap_app(&mut self, app_buffer: &[u8], spdm_buffer: &mut [u8])
         -> SpdmResult<(usize, bool)>;
 
+    /// Encap an application message of the transport message type `app_type`.
+    fn encap_app_message(
+        &mut self,
+        app_message: &[u8],
+        app_buffer: &mut [u8],
+        _app_type: u8,
+    ) -> SpdmResult<usize> {
+        self.encap_app(app_message, app_buffer, true)
+    }
+
+    /// Decap an SPDM message (None) or an application message with its
+    /// transport message type.
+    fn decap_app_message(
+        &mut self,
+        app_buffer: &[u8],
+        app_message: &mut [u8],
+    ) -> SpdmResult<(usize, Option<u8>)> {
+        let (used, is_app_message) = self.decap_app(app_buffer, app_message)?;
+        Ok((used, if is_app_message { Some(0) } else { None }))
+    }
+
     // for session
     fn get_sequence_number_count(&mut self) -> u8;
     fn get_max_random_count(&mut self) -> u16;
@@ -152,6 +182,25 @@ impl<'a> SpdmContext<'a> {
         }
     }
 
+    /// Clear the negotiated state as END_SESSION asks, false if it is kept
+    /// because another session still relies on it.
+    pub fn reset_negotiated_state(&mut self) -> bool {
+        if self
+            .session
+            .iter()
+            .any(|session| session.get_session_id() != 0)
+        {
+            info!("keep the negotiated state for the other sessions\n");
+            return false;
+        }
+        info!("clear the negotiated state\n");
+        self.reset_negotiate_info();
+        self.reset_peer_info();
+        self.runtime_info.message_a.reset_message();
+        self.runtime_info.connection_state = SpdmConnectionState::NotStarted;
+        true
+    }
+
     pub fn get_immutable_session_via_id(&self, session_id: u32) -> Option<&SpdmSession> {
         self.session
             .iter()
@@ -164,133 +213,195 @@ impl<'a> SpdmContext<'a> {
             .find(|session| session.get_session_id() == session_id)
     }
 
+    /// Fill `keying_material` for `label` and `context` from the established
+    /// session `session_id`, see `SpdmSession::export_keying_material`.
+    pub fn export_keying_material(
+        &self,
+        session_id: u32,
+        label: &[u8],
+        context: &[u8],
+        keying_material: &mut [u8],
+    ) -> SpdmResult {
+        self.get_immutable_session_via_id(session_id)
+            .ok_or(spdm_err!(EINVAL))?
+            .export_keying_material(
+                self.negotiate_info.spdm_version_sel,
+                label,
+                context,
+                keying_material,
+            )
+    }
+
     pub fn get_next_avaiable_session(&mut self) -> Option<&mut SpdmSession> {
         self.get_session_via_id(0)
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn calc_req_transcript_data(
+    /// A half session ID for this side, `is_requester` selects the upper half,
+    /// that no session uses. None when all sessions are in use.
+    pub fn get_next_half_session_id(&self, is_requester: bool) -> Option<u16> {
+        self.get_immutable_session_via_id(0)?;
+        let in_use = |half_session_id: u16| {
+            self.session.iter().any(|session| {
+                let session_id = session.get_session_id();
+                let half = if is_requester {
+                    (session_id >> 16) as u16
+                } else {
+                    session_id as u16
+                };
+                session_id != 0 && half == half_session_id
+            })
+        };
+        // one of MAX_SPDM_SESSION_COUNT + 1 IDs is free
+        (0..=config::MAX_SPDM_SESSION_COUNT as u16)
+            .map(|i| INITIAL_HALF_SESSION_ID - i)
+            .find(|half_session_id| !in_use(*half_session_id))
+    }
+
+    /// Empty transcript in the configured mode for the negotiated hash.
+    pub fn new_transcript(&self) -> SpdmResult<SpdmTranscript> {
+        SpdmTranscript::new(
+            self.config_info.transcript_mode,
+            self.negotiate_info.base_hash_sel,
+        )
+    }
+
+    /// Session transcript seeded with message_a and, unless PSK is used,
+    /// the hash of the peer cert chain in `slot_id`.
+    pub fn create_req_transcript_th(
         &self,
         slot_id: u8,
         use_psk: bool,
-        message_k: &ManagedBuffer,
-        message_f: Option<&ManagedBuffer>,
-    ) -> SpdmResult<ManagedBuffer> {
-        let mut message = ManagedBuffer::default();
-        message
-            .append_message(self.runtime_info.message_a.as_ref())
-            .ok_or(spdm_err!(ENOMEM))?;
+    ) -> SpdmResult<SpdmTranscript> {
+        let mut transcript = self.new_transcript()?;
+        transcript.append(self.runtime_info.message_a.as_ref())?;
         debug!("message_a - {:02x?}", self.runtime_info.message_a.as_ref());
-
         if !use_psk {
-            if self.peer_info.peer_cert_chain[slot_id as usize].is_none() {
-                error!("peer_cert_chain is not populated!\n");
-                return spdm_result_err!(EINVAL);
-            }
-
-            let cert_chain_data = &self.peer_info.peer_cert_chain[slot_id as usize]
-                .as_ref()
-                .unwrap()
-                .cert_chain
-                .data[..(self.peer_info.peer_cert_chain[slot_id as usize]
-                .as_ref()
-                .unwrap()
-                .cert_chain
-                .data_size as usize)];
-            let cert_chain_hash =
-                crypto::hash::hash_all(self.negotiate_info.base_hash_sel, cert_chain_data)
-                    .ok_or_else(|| spdm_err!(EFAULT))?;
-            message
-                .append_message(cert_chain_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            debug!("cert_chain_data - {:02x?}", cert_chain_data);
+            let cert_chain_hash = self
+                .get_certchain_hash_req(slot_id, use_psk)
+                .ok_or_else(|| spdm_err!(EINVAL))?;
+            transcript.append(cert_chain_hash.as_ref())?;
         }
-        message
-            .append_message(message_k.as_ref())
-            .ok_or_else(|| spdm_err!(ENOMEM))?;
-        debug!("message_k - {:02x?}", message_k.as_ref());
-        if message_f.is_some() {
-            message
-                .append_message(message_f.unwrap().as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            debug!("message_f - {:02x?}", message_f.unwrap().as_ref());
+        Ok(transcript)
+    }
+
+    /// Session transcript seeded with message_a and, unless PSK is used,
+    /// the hash of our own cert chain.
+    pub fn create_rsp_transcript_th(&self, use_psk: bool) -> SpdmResult<SpdmTranscript> {
+        let mut transcript = self.new_transcript()?;
+        transcript.append(self.runtime_info.message_a.as_ref())?;
+        debug!("message_a - {:02x?}", self.runtime_info.message_a.as_ref());
+        if !use_psk {
+            let cert_chain_hash = self
+                .get_certchain_hash_rsp(use_psk)
+                .ok_or_else(|| spdm_err!(EINVAL))?;
+            transcript.append(cert_chain_hash.as_ref())?;
         }
+        Ok(transcript)
+    }
 
-        Ok(message)
+    /// Append to the M1/M2 transcript, starting it with message_a first
+    /// if this is the first message after algorithm negotiation.
+    pub fn append_message_m1m2(&mut self, message: &[u8]) -> SpdmResult {
+        if !self.runtime_info.transcript_m1m2.is_initialized() {
+            let mut transcript = self.new_transcript()?;
+            transcript.append(self.runtime_info.message_a.as_ref())?;
+            self.runtime_info.transcript_m1m2 = transcript;
+        }
+        self.runtime_info.transcript_m1m2.append(message)
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn calc_rsp_transcript_data(
+    /// L1/L2 transcript of `session_id`, or the one kept outside of any session.
+    pub fn get_transcript_l1l2(
         &mut self,
-        use_psk: bool,
-        message_k: &ManagedBuffer,
-        message_f: Option<&ManagedBuffer>,
-    ) -> SpdmResult<ManagedBuffer> {
-        let mut message = ManagedBuffer::default();
-        message
-            .append_message(self.runtime_info.message_a.as_ref())
-            .ok_or(spdm_err!(ENOMEM))?;
-        debug!("message_a - {:02x?}", self.runtime_info.message_a.as_ref());
-        if !use_psk {
-            if self.provision_info.my_cert_chain.is_none() {
-                error!("my_cert_chain is not populated!\n");
-                return spdm_result_err!(EINVAL);
+        session_id: Option<u32>,
+    ) -> SpdmResult<&mut SpdmTranscript> {
+        match session_id {
+            None => Ok(&mut self.runtime_info.transcript_l1l2),
+            Some(session_id) => {
+                let session = self
+                    .get_session_via_id(session_id)
+                    .ok_or_else(|| spdm_err!(EINVAL))?;
+                Ok(&mut session.runtime_info.transcript_l1l2)
             }
+        }
+    }
 
-            let my_cert_chain_data = self.provision_info.my_cert_chain.as_ref().unwrap();
-            let cert_chain_data = my_cert_chain_data.as_ref();
-            let cert_chain_hash =
-                crypto::hash::hash_all(self.negotiate_info.base_hash_sel, cert_chain_data)
-                    .ok_or_else(|| spdm_err!(EFAULT))?;
+    /// Append to the L1/L2 transcript, starting it first (with message_a
+    /// for SPDM 1.2) if this is the first measurement message.
+    pub fn append_message_l1l2(&mut self, session_id: Option<u32>, message: &[u8]) -> SpdmResult {
+        if !self.get_transcript_l1l2(session_id)?.is_initialized() {
+            let mut transcript = self.new_transcript()?;
+            if self.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
+                transcript.append(self.runtime_info.message_a.as_ref())?;
+            }
+            *self.get_transcript_l1l2(session_id)? = transcript;
+        }
+        self.get_transcript_l1l2(session_id)?.append(message)
+    }
 
-            message
-                .append_message(cert_chain_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            debug!("cert_chain_data - {:02x?}", cert_chain_data);
+    /// Parsed view of every certificate of the peer cert chain in `slot_id`, root first.
+    pub fn get_peer_cert_chain_info(&self, slot_id: u8) -> SpdmResult<Vec<SpdmCertInfo<'_>>> {
+        let peer_cert_chain = match self.peer_info.peer_cert_chain.get(slot_id as usize) {
+            Some(Some(peer_cert_chain)) => &peer_cert_chain.cert_chain,
+            _ => return spdm_result_err!(EINVAL),
+        };
+        // skip the SpdmCertChainHeader: length, reserved and root hash
+        let base_hash_size = self.negotiate_info.base_hash_sel.get_size() as usize;
+        if peer_cert_chain.data_size as usize <= 4 + base_hash_size {
+            return spdm_result_err!(EINVAL);
         }
-        message
-            .append_message(message_k.as_ref())
-            .ok_or_else(|| spdm_err!(ENOMEM))?;
-        debug!("message_k - {:02x?}", message_k.as_ref());
-        if message_f.is_some() {
-            message
-                .append_message(message_f.unwrap().as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            debug!("message_f - {:02x?}", message_f.unwrap().as_ref());
+        let mut certs =
+            &peer_cert_chain.data[(4 + base_hash_size)..(peer_cert_chain.data_size as usize)];
+
+        let mut cert_chain_info = Vec::new();
+        while !certs.is_empty() {
+            let (cert_begin, cert_end) =
+                crypto::cert_operation::get_cert_from_cert_chain(certs, 0)?;
+            cert_chain_info.push(crypto::cert_operation::get_cert_info(
+                &certs[cert_begin..cert_end],
+            )?);
+            certs = &certs[cert_end..];
         }
-
-        Ok(message)
+        Ok(cert_chain_info)
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn calc_req_transcript_hash(
-        &self,
-        slot_id: u8,
-        use_psk: bool,
-        message_k: &ManagedBuffer,
-        message_f: Option<&ManagedBuffer>,
-    ) -> SpdmResult<SpdmDigestStruct> {
-        let message = self.calc_req_transcript_data(slot_id, use_psk, message_k, message_f)?;
-
-        let transcript_hash =
-            crypto::hash::hash_all(self.negotiate_info.base_hash_sel, message.as_ref())
-                .ok_or_else(|| spdm_err!(EFAULT))?;
-        Ok(transcript_hash)
+    pub fn reset_transcript_l1l2(&mut self, session_id: Option<u32>) -> SpdmResult {
+        self.get_transcript_l1l2(session_id)?.reset();
+        Ok(())
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn calc_rsp_transcript_hash(
-        &mut self,
-        use_psk: bool,
-        message_k: &ManagedBuffer,
-        message_f: Option<&ManagedBuffer>,
-    ) -> SpdmResult<SpdmDigestStruct> {
-        let message = self.calc_rsp_transcript_data(use_psk, message_k, message_f)?;
+    /// Data covered by a signature over `transcript`. SPDM 1.2 signs the
+    /// signing context and the transcript hash; earlier versions sign the
+    /// transcript itself, which is left empty when only its hash is kept.
+    pub fn get_signing_data(
+        &self,
+        transcript: &SpdmTranscript,
+        zeropad: &[u8],
+        sign_context: &[u8],
+    ) -> SpdmResult<ManagedBuffer> {
+        let message_hash = transcript.hash()?;
+        debug!("message_hash - {:02x?}", message_hash.as_ref());
 
-        let transcript_hash =
-            crypto::hash::hash_all(self.negotiate_info.base_hash_sel, message.as_ref())
-                .ok_or_else(|| spdm_err!(EFAULT))?;
-        Ok(transcript_hash)
+        let mut message = ManagedBuffer::default();
+        if self.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
+            message
+                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
+                .ok_or_else(|| spdm_err!(ENOMEM))?;
+            message
+                .append_message(zeropad)
+                .ok_or_else(|| spdm_err!(ENOMEM))?;
+            message
+                .append_message(sign_context)
+                .ok_or_else(|| spdm_err!(ENOMEM))?;
+            message
+                .append_message(message_hash.as_ref())
+                .ok_or_else(|| spdm_err!(ENOMEM))?;
+        } else if let Some(data) = transcript.data() {
+            message
+                .append_message(data)
+                .ok_or_else(|| spdm_err!(ENOMEM))?;
+        }
+        Ok(message)
     }
 
     pub fn get_certchain_hash_rsp(&self, use_psk: bool) -> Option<SpdmDigestStruct> {
@@ -362,14 +473,48 @@ impl<'a> SpdmContext<'a> {
         let used = self
             .transport_encap
             .encap_app(send_buffer, &mut app_buffer, is_app_message)?;
+        self.encode_secured_app_buffer(
+            session_id,
+            &app_buffer[..used],
+            transport_buffer,
+            is_requester,
+        )
+    }
+
+    pub fn encode_secured_app_message(
+        &mut self,
+        session_id: u32,
+        app_message: &[u8],
+        transport_buffer: &mut [u8],
+        is_requester: bool,
+        app_type: u8,
+    ) -> SpdmResult<usize> {
+        let mut app_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let used =
+            self.transport_encap
+                .encap_app_message(app_message, &mut app_buffer, app_type)?;
+        self.encode_secured_app_buffer(
+            session_id,
+            &app_buffer[..used],
+            transport_buffer,
+            is_requester,
+        )
+    }
 
+    fn encode_secured_app_buffer(
+        &mut self,
+        session_id: u32,
+        app_buffer: &[u8],
+        transport_buffer: &mut [u8],
+        is_requester: bool,
+    ) -> SpdmResult<usize> {
         let spdm_session = self
             .get_session_via_id(session_id)
             .ok_or(spdm_err!(EINVAL))?;
 
         let mut encoded_send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let encode_size = spdm_session.encode_spdm_secured_message(
-            &app_buffer[0..used],
+            app_buffer,
             &mut encoded_send_buffer,
             is_requester,
         )?;
@@ -400,6 +545,19 @@ impl<'a> SpdmContext<'a> {
         transport_buffer: &[u8],
         receive_buffer: &mut [u8],
     ) -> SpdmResult<usize> {
+        let (used, _) =
+            self.decode_secured_app_message(session_id, transport_buffer, receive_buffer)?;
+        Ok(used)
+    }
+
+    /// Decode a secured message, also returning the transport message type
+    /// of an application message, None for an SPDM message.
+    pub fn decode_secured_app_message(
+        &mut self,
+        session_id: u32,
+        transport_buffer: &[u8],
+        receive_buffer: &mut [u8],
+    ) -> SpdmResult<(usize, Option<u8>)> {
         let mut encoded_receive_buffer = [0u8; config::DATA_TRANSFER_SIZE];
         let (used, secured_message) = self
             .transport_encap
@@ -420,11 +578,8 @@ impl<'a> SpdmContext<'a> {
             false,
         )?;
 
-        let used = self
-            .transport_encap
-            .decap_app(&app_buffer[0..decode_size], receive_buffer)?;
-
-        Ok(used.0)
+        self.transport_encap
+            .decap_app_message(&app_buffer[0..decode_size], receive_buffer)
     }
 }
 
@@ -449,10 +604,25 @@ pub struct SpdmConfigInfo {
     pub data_transfer_size: u32,
     pub max_spdm_msg_size: u32,
     pub heartbeat_period: u8,    // used by responder only
-    pub secure_spdm_version: u8, // used by responder only
+    pub secure_spdm_version: u8, // highest secured message version, 0 for any
+    pub transcript_mode: SpdmTranscriptMode,
+    pub algo_priority: SpdmAlgoPriority,
+    pub key_update_policy: SpdmKeyUpdatePolicy,
 }
 
-#[derive(Debug, Default)]
+/// Preferred algorithms, most preferred first, used by the responder to pick from
+/// what the requester offers. An empty list keeps the built-in order; entries not in
+/// the matching `SpdmConfigInfo` bitmask, or not offered by the peer, are skipped.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpdmAlgoPriority {
+    pub base_hash_algo: &'static [SpdmBaseHashAlgo],
+    pub base_asym_algo: &'static [SpdmBaseAsymAlgo],
+    pub dhe_algo: &'static [SpdmDheAlgo],
+    pub aead_algo: &'static [SpdmAeadAlgo],
+    pub req_asym_algo: &'static [SpdmReqAsymAlgo],
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct SpdmNegotiateInfo {
     pub spdm_version_sel: SpdmVersion,
     pub req_capabilities_sel: SpdmRequestCapabilityFlags,
@@ -513,27 +683,24 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-#[cfg(not(feature = "hashed-transcript-data"))]
-pub struct SpdmRuntimeInfo {
-    pub need_measurement_summary_hash: bool,
-    pub need_measurement_signature: bool,
-    pub message_a: ManagedBuffer,
-    pub message_b: ManagedBuffer,
-    pub message_c: ManagedBuffer,
-    pub message_m: ManagedBuffer,
-    pub content_changed: SpdmMeasurementContentChanged, // used by responder, set when content changed and spdm version is 1.2.
-                                                        // used by requester, consume when measurement response report content changed.
+/// Where the connection is in the SPDM message flow, reset by GET_VERSION.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum SpdmConnectionState {
+    #[default]
+    NotStarted,
+    AfterVersion,
+    AfterCapabilities,
+    Negotiated,
 }
 
-#[derive(Clone, Default)]
-#[cfg(feature = "hashed-transcript-data")]
+#[derive(Debug, Clone, Default)]
 pub struct SpdmRuntimeInfo {
+    pub connection_state: SpdmConnectionState,
     pub need_measurement_summary_hash: bool,
     pub need_measurement_signature: bool,
     pub message_a: ManagedBuffer,
-    pub digest_context_m1m2: Option<HashCtx>, // for M1/M2
-    pub digest_context_l1l2: Option<HashCtx>, // for out of session get measurement/measurement
+    pub transcript_m1m2: SpdmTranscript, // for M1/M2
+    pub transcript_l1l2: SpdmTranscript, // for out of session get measurement/measurement
     pub content_changed: SpdmMeasurementContentChanged, // used by responder, set when content changed and spdm version is 1.2.
                                                         // used by requester, consume when measurement response report content changed.
 }
@@ -542,14 +709,90 @@ pub struct SpdmRuntimeInfo {
 pub struct SpdmProvisionInfo {
     pub my_cert_chain_data: Option<SpdmCertChainData>,
     pub my_cert_chain: Option<SpdmCertChainData>, // use SpdmCertChainData instead of SpdmCertChain for easy command sending.
-    // TBD: union peer. But it is still option.
+    // Trust anchors: a peer cert chain is accepted if its root certificate is one of
+    // peer_root_cert_data, or hashes to one of peer_cert_chain_root_hash.
+    pub peer_root_cert_data: [Option<SpdmCertChainData>; config::MAX_ROOT_CERT_SUPPORT],
+    pub peer_cert_chain_root_hash: [Option<SpdmDigestStruct>; config::MAX_ROOT_CERT_SUPPORT],
+    // DER CRLs of the trusted PKIs, e.g. peer_root_crl_data[i] for peer_root_cert_data[i].
+    // A CRL applies to a peer cert chain when it is issued by one of the chain's CAs.
+    pub peer_root_crl_data: [Option<SpdmCertChainData>; config::MAX_ROOT_CERT_SUPPORT],
+    // Optional pinning: if set, the peer cert chain must also match it byte for byte.
     pub peer_cert_chain_data: Option<SpdmCertChainData>,
-    pub peer_cert_chain_root_hash: Option<SpdmDigestStruct>,
     pub default_version: SpdmVersion,
 }
 
 #[derive(Default)]
 pub struct SpdmPeerInfo {
     pub peer_cert_chain: [Option<SpdmCertChain>; 8],
-    // pub peer_cert_chain_data: SpdmCertChain,
+    pub peer_digests: [Option<SpdmDigestStruct>; 8], // from the last DIGESTS response
+}
+
+#[cfg(all(test,))]
+mod tests {
+    use super::*;
+    use crate::message::*;
+    use crate::testlib::*;
+
+    #[test]
+    fn test_case0_session_limit() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = new_connected_requester(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+
+            let mut session_ids = [0u32; config::MAX_SPDM_SESSION_COUNT];
+            for (i, session_id) in session_ids.iter_mut().enumerate() {
+                *session_id = requester
+                    .start_session(
+                        i % 2 == 0,
+                        0,
+                        SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                    )
+                    .unwrap();
+                let half_session_id = INITIAL_HALF_SESSION_ID as u32 - i as u32;
+                assert_eq!(*session_id, (half_session_id << 16) + half_session_id);
+            }
+
+            // the requester forgets a session the responder still holds
+            let session_id = session_ids[0];
+            let _ = requester
+                .common
+                .get_session_via_id(session_id)
+                .unwrap()
+                .teardown(session_id);
+            let err = requester
+                .start_session(
+                    true,
+                    0,
+                    SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                )
+                .unwrap_err();
+            assert_eq!(
+                err.peer_error().map(|payload| payload.error_code),
+                Some(SpdmErrorCode::SpdmErrorSessionLimitExceeded)
+            );
+
+            // END_SESSION gives the slot back
+            assert!(requester
+                .end_session(
+                    session_ids[1],
+                    SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE
+                )
+                .is_ok());
+            let session_id = requester
+                .start_session(
+                    false,
+                    0,
+                    SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                )
+                .unwrap();
+            assert_eq!(session_id & 0xFFFF, session_ids[1] & 0xFFFF);
+        });
+    }
 }
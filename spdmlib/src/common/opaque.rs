@@ -30,8 +30,6 @@ pub const DMTF_SUPPORTED_SECURE_SPDM_VERSION_LIST: [SecuredMessageVersion;
     SecuredMessageVersion::from_secure_spdm_version(DMTF_SECURE_SPDM_VERSION_10),
     SecuredMessageVersion::from_secure_spdm_version(DMTF_SECURE_SPDM_VERSION_11),
 ];
-pub const DMTF_SECURE_SPDM_VERSION_SELECTION: SecuredMessageVersion =
-    SecuredMessageVersion::from_secure_spdm_version(DMTF_SECURE_SPDM_VERSION_10);
 
 pub const REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT0: [u8; 20] = [
     0x46,
@@ -162,6 +160,33 @@ pub struct SecuredMessageVersionList {
     pub versions_list: [SecuredMessageVersion; MAX_SECURE_SPDM_VERSION_COUNT],
 }
 
+impl SecuredMessageVersionList {
+    /// The highest version of the list also in DMTF_SUPPORTED_SECURE_SPDM_VERSION_LIST,
+    /// no higher than `max_version` unless it is 0.
+    pub fn select_secure_spdm_version(&self, max_version: u8) -> Option<SecuredMessageVersion> {
+        self.versions_list
+            .iter()
+            .take(self.version_count as usize)
+            .map(|version| version.get_secure_spdm_version())
+            .filter(|version| is_supported_secure_spdm_version_up_to(*version, max_version))
+            .max()
+            .map(SecuredMessageVersion::from_secure_spdm_version)
+    }
+}
+
+pub fn is_supported_secure_spdm_version(secure_spdm_version: u8) -> bool {
+    DMTF_SUPPORTED_SECURE_SPDM_VERSION_LIST
+        .iter()
+        .any(|version| version.get_secure_spdm_version() == secure_spdm_version)
+}
+
+/// Whether `secure_spdm_version` is supported and no higher than `max_version`,
+/// any supported version if it is 0.
+pub fn is_supported_secure_spdm_version_up_to(secure_spdm_version: u8, max_version: u8) -> bool {
+    is_supported_secure_spdm_version(secure_spdm_version)
+        && (max_version == 0 || secure_spdm_version <= max_version)
+}
+
 impl SpdmCodec for SecuredMessageVersionList {
     fn spdm_encode(&self, context: &mut SpdmContext, bytes: &mut Writer) {
         self.version_count.encode(bytes);
@@ -493,6 +518,83 @@ impl SpdmOpaqueStruct {
         })
     }
 
+    /// The opaque data of a requester offering the supported versions no higher
+    /// than `max_version`, all of them if it is 0.
+    pub fn req_set_dmtf_supported_secure_spdm_version_list(
+        context: &SpdmContext,
+        max_version: u8,
+    ) -> SpdmOpaqueStruct {
+        let version_list: &[u8] = if context
+            .negotiate_info
+            .opaque_data_support
+            .contains(SpdmOpaqueSupport::OPAQUE_DATA_FMT1)
+        {
+            &REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT1
+        } else {
+            &REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT0
+        };
+        let data_size = version_list.len();
+        let mut opaque = SpdmOpaqueStruct {
+            data_size: data_size as u16,
+            ..Default::default()
+        };
+        opaque.data[..data_size].copy_from_slice(version_list);
+
+        // the element ends with the version count, the versions and its padding,
+        // which makes up for the versions left out
+        let versions_offset = data_size - 1 - 2 * MAX_SECURE_SPDM_VERSION_COUNT;
+        let mut version_count = 0;
+        for version in DMTF_SUPPORTED_SECURE_SPDM_VERSION_LIST
+            .iter()
+            .filter(|version| {
+                is_supported_secure_spdm_version_up_to(
+                    version.get_secure_spdm_version(),
+                    max_version,
+                )
+            })
+        {
+            let offset = versions_offset + 2 * version_count;
+            opaque.data[offset] = (version.update_version_number << 4) + version.alpha;
+            opaque.data[offset + 1] = (version.major_version << 4) + version.minor_version;
+            version_count += 1;
+        }
+        for d in opaque.data[versions_offset + 2 * version_count..data_size].iter_mut() {
+            *d = PADDING;
+        }
+        opaque.data[versions_offset - 1] = version_count as u8;
+        // SM data version, SM data ID and version count before the versions
+        let element_len = (3 + 2 * version_count) as u16;
+        opaque.data[versions_offset - 5..versions_offset - 3]
+            .copy_from_slice(&element_len.to_le_bytes());
+        opaque
+    }
+
+    /// The opaque data of a responder selecting `version`.
+    pub fn rsp_set_dmtf_secure_spdm_version_selection(
+        context: &SpdmContext,
+        version: SecuredMessageVersion,
+    ) -> SpdmOpaqueStruct {
+        let selection: &[u8] = if context
+            .negotiate_info
+            .opaque_data_support
+            .contains(SpdmOpaqueSupport::OPAQUE_DATA_FMT1)
+        {
+            &RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT1
+        } else {
+            &RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT0
+        };
+        let data_size = selection.len();
+        let mut opaque = SpdmOpaqueStruct {
+            data_size: data_size as u16,
+            ..Default::default()
+        };
+        opaque.data[..data_size].copy_from_slice(selection);
+        // the selected version ends the element
+        opaque.data[data_size - 2] = (version.update_version_number << 4) + version.alpha;
+        opaque.data[data_size - 1] = (version.major_version << 4) + version.minor_version;
+        opaque
+    }
+
     pub fn req_get_dmtf_secure_spdm_version_selection(
         &self,
         context: &mut SpdmContext,
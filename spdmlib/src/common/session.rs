@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+use super::handoff::{read_handoff_bytes, write_handoff_bytes, write_handoff_value};
 use super::key_schedule::SpdmKeySchedule;
 use crate::config;
 use crate::crypto;
@@ -48,6 +49,76 @@ pub struct SpdmSessionSecretParam {
     pub encryption_key: SpdmAeadKeyStruct,
     pub salt: SpdmAeadIvStruct,
     pub sequence_number: u64,
+    /// Bytes of secured message payload protected with the key.
+    pub byte_count: u64,
+    /// Monotonic time in us the key was derived, None if not known.
+    pub key_time: Option<u64>,
+    /// Sequence numbers received before the highest one, bit i for
+    /// `sequence_number - 2 - i`, when they are explicit (Secured Messages 1.1).
+    pub replay_window: u64,
+}
+
+impl SpdmSessionSecretParam {
+    /// The full sequence number of a received message that carries the low
+    /// `sequence_number_count` bytes of it, an error if it was already received,
+    /// is too old for the replay window or is the last one, which never follows.
+    fn expand_received_sequence_number(
+        &self,
+        low: u64,
+        sequence_number_count: u8,
+    ) -> SpdmResult<u64> {
+        let expected = self.sequence_number;
+        let sequence_number = if sequence_number_count >= 8 {
+            low
+        } else {
+            // the closest number to the expected one with these low bytes
+            let modulus = 1u64 << (8 * sequence_number_count as u32);
+            let candidate = (expected & !(modulus - 1)) | low;
+            if candidate > expected && candidate - expected > modulus / 2 {
+                candidate.checked_sub(modulus).unwrap_or(candidate)
+            } else if candidate < expected && expected - candidate > modulus / 2 {
+                candidate.checked_add(modulus).unwrap_or(candidate)
+            } else {
+                candidate
+            }
+        };
+        if sequence_number == u64::MAX {
+            error!("!!! sequence number exhausted !!!\n");
+            return spdm_result_err!(ESEC);
+        }
+        if sequence_number < expected {
+            let age = expected - 1 - sequence_number;
+            if age == 0 || age > 64 || self.replay_window & (1 << (age - 1)) != 0 {
+                error!("!!! replayed sequence number {:x} !!!\n", sequence_number);
+                return spdm_result_err!(ESEC);
+            }
+        }
+        Ok(sequence_number)
+    }
+
+    /// Record `sequence_number` as received.
+    fn mark_sequence_number_received(&mut self, sequence_number: u64) -> SpdmResult {
+        if sequence_number >= self.sequence_number {
+            let next = sequence_number
+                .checked_add(1)
+                .ok_or(spdm_err!(ESEC, "sequence number exhausted"))?;
+            let shift = sequence_number - self.sequence_number + 1;
+            // the previous highest number, if any, joins the window
+            let previous = if self.sequence_number != 0 { 1 } else { 0 };
+            self.replay_window = if shift > 64 {
+                0
+            } else {
+                ((self.replay_window << 1) | previous)
+                    .checked_shl(shift as u32 - 1)
+                    .unwrap_or(0)
+            };
+            self.sequence_number = next;
+        } else {
+            let age = self.sequence_number - 1 - sequence_number;
+            self.replay_window |= 1 << (age - 1);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Default, Zeroize, ZeroizeOnDrop)]
@@ -69,6 +140,23 @@ pub struct SpdmSessionAppliationSecret {
     pub response_direction: SpdmSessionSecretParam,
 }
 
+/// Thresholds at which the application keys of a session are updated, 0 disables one.
+///
+/// The requester sends KEY_UPDATE before the next secured message once a threshold
+/// is reached. A responder cannot send KEY_UPDATE, there a request other than
+/// KEY_UPDATE on keys past the message or byte threshold ends the session. The
+/// responder does not check `max_key_age`, the keys of the requester need not
+/// have been derived at the same time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpdmKeyUpdatePolicy {
+    /// Secured messages in one direction.
+    pub max_messages: u64,
+    /// Bytes of secured message payload in one direction.
+    pub max_bytes: u64,
+    /// Seconds since the keys were derived, needs `time::now`.
+    pub max_key_age: u64,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SpdmSessionTransportParam {
     pub sequence_number_count: u8,
@@ -76,18 +164,9 @@ pub struct SpdmSessionTransportParam {
 }
 
 #[derive(Debug, Clone, Default)]
-#[cfg(not(feature = "hashed-transcript-data"))]
-pub struct SpdmSessionRuntimeInfo {
-    pub message_k: ManagedBuffer,
-    pub message_f: ManagedBuffer,
-    pub message_m: ManagedBuffer,
-}
-
-#[derive(Clone, Default)]
-#[cfg(feature = "hashed-transcript-data")]
 pub struct SpdmSessionRuntimeInfo {
-    pub digest_context_th: Option<HashCtx>,
-    pub digest_context_l1l2: Option<HashCtx>,
+    pub transcript_th: SpdmTranscript,
+    pub transcript_l1l2: SpdmTranscript,
 }
 
 #[derive(Clone)]
@@ -105,6 +184,8 @@ pub struct SpdmSession {
     key_schedule: SpdmKeySchedule,
     pub heartbeat_period: u8, // valid only when HEARTBEAT cap set
     pub secure_spdm_version_sel: u8,
+    /// Monotonic time in us of the last secured message sent or received.
+    pub last_message_time: Option<u64>,
 }
 
 impl Default for SpdmSession {
@@ -129,6 +210,7 @@ impl SpdmSession {
             key_schedule: SpdmKeySchedule::new(),
             heartbeat_period: 0,
             secure_spdm_version_sel: config::SECURE_SPDM_VERSION,
+            last_message_time: None,
         }
     }
 
@@ -153,15 +235,17 @@ impl SpdmSession {
         self.use_psk = false;
         self.session_state = SpdmSessionState::default();
         self.crypto_param = SpdmSessionCryptoParam::default();
-        self.master_secret = SpdmSessionMasterSecret::default();
-        self.handshake_secret = SpdmSessionHandshakeSecret::default();
-        self.application_secret = SpdmSessionAppliationSecret::default();
-        self.application_secret_backup = SpdmSessionAppliationSecret::default();
+        // wipe the secrets in place, not only in the dropped values
+        self.master_secret.zeroize();
+        self.handshake_secret.zeroize();
+        self.application_secret.zeroize();
+        self.application_secret_backup.zeroize();
         self.transport_param = SpdmSessionTransportParam::default();
         self.runtime_info = SpdmSessionRuntimeInfo::default();
         self.key_schedule = SpdmKeySchedule::default();
         self.heartbeat_period = 0;
         self.secure_spdm_version_sel = config::SECURE_SPDM_VERSION;
+        self.last_message_time = None;
     }
 
     pub fn get_session_id(&self) -> u32 {
@@ -258,6 +342,10 @@ impl SpdmSession {
         self.session_state = session_state;
     }
 
+    pub fn get_session_state(&self) -> SpdmSessionState {
+        self.session_state
+    }
+
     pub fn generate_handshake_secret(
         &mut self,
         spdm_version: SpdmVersion,
@@ -478,6 +566,9 @@ impl SpdmSession {
             "!!! response_direction.salt !!!: {:02x?}\n",
             self.application_secret.response_direction.salt.as_ref()
         );
+        let key_time = crate::time::now();
+        self.application_secret.request_direction.key_time = key_time;
+        self.application_secret.response_direction.key_time = key_time;
 
         Ok(())
     }
@@ -540,6 +631,9 @@ impl SpdmSession {
                 self.application_secret.request_direction.salt.as_ref()
             );
             self.application_secret.request_direction.sequence_number = 0;
+            self.application_secret.request_direction.replay_window = 0;
+            self.application_secret.request_direction.byte_count = 0;
+            self.application_secret.request_direction.key_time = crate::time::now();
         }
 
         if update_responder {
@@ -587,6 +681,9 @@ impl SpdmSession {
                 self.application_secret.response_direction.salt.as_ref()
             );
             self.application_secret.response_direction.sequence_number = 0;
+            self.application_secret.response_direction.replay_window = 0;
+            self.application_secret.response_direction.byte_count = 0;
+            self.application_secret.response_direction.key_time = crate::time::now();
         }
         Ok(())
     }
@@ -626,22 +723,71 @@ impl SpdmSession {
         Ok(())
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn generate_hmac_with_response_finished_key(
-        &mut self,
-        message: &[u8],
-    ) -> SpdmResult<SpdmDigestStruct> {
-        crypto::hmac::hmac(
-            self.crypto_param.base_hash_algo,
-            self.handshake_secret.response_finished_key.as_ref(),
-            crypto::hash::hash_all(self.crypto_param.base_hash_algo, message)
-                .ok_or(spdm_err!(EINVAL))?
-                .as_ref(),
-        )
-        .ok_or(spdm_err!(ESEC))
+    /// Whether the application keys reached a threshold of `policy`, in either
+    /// direction. `now` is the monotonic time in us, None skips the key age.
+    pub fn key_update_due(&self, policy: &SpdmKeyUpdatePolicy, now: Option<u64>) -> bool {
+        if self.session_state != SpdmSessionState::SpdmSessionEstablished {
+            return false;
+        }
+        [
+            &self.application_secret.request_direction,
+            &self.application_secret.response_direction,
+        ]
+        .iter()
+        .any(|direction| {
+            (policy.max_messages != 0 && direction.sequence_number >= policy.max_messages)
+                || (policy.max_bytes != 0 && direction.byte_count >= policy.max_bytes)
+                || match (now, direction.key_time) {
+                    (Some(now), Some(key_time)) if policy.max_key_age != 0 => {
+                        now.saturating_sub(key_time) >= policy.max_key_age.saturating_mul(1_000_000)
+                    }
+                    _ => false,
+                }
+        })
+    }
+
+    /// Time in us since the last secured message, None while heartbeat is off
+    /// for the session or before the first message.
+    pub fn heartbeat_idle_time(&self, now: u64) -> Option<u64> {
+        if self.session_state != SpdmSessionState::SpdmSessionEstablished
+            || self.heartbeat_period == 0
+        {
+            return None;
+        }
+        self.last_message_time
+            .map(|last_message_time| now.saturating_sub(last_message_time))
+    }
+
+    /// Fill `keying_material` for `label` and `context`, derived from the
+    /// export master secret, independent of the keys of the session and of
+    /// their updates. It is at most 255 times the size of the negotiated hash.
+    pub fn export_keying_material(
+        &self,
+        spdm_version: SpdmVersion,
+        label: &[u8],
+        context: &[u8],
+        keying_material: &mut [u8],
+    ) -> SpdmResult {
+        if self.session_state != SpdmSessionState::SpdmSessionEstablished {
+            return spdm_result_err!(EINVAL);
+        }
+        let hash_algo = self.crypto_param.base_hash_algo;
+        if keying_material.is_empty() || keying_material.len() > 255 * hash_algo.get_size() as usize
+        {
+            return spdm_result_err!(EINVAL);
+        }
+        self.key_schedule
+            .derive_exported_keying_material(
+                spdm_version,
+                hash_algo,
+                self.handshake_secret.export_master_secret.as_ref(),
+                label,
+                context,
+                keying_material,
+            )
+            .ok_or(spdm_err!(ESEC))
     }
 
-    #[cfg(feature = "hashed-transcript-data")]
     pub fn generate_hmac_with_response_finished_key(
         &mut self,
         message_hash: &[u8],
@@ -654,22 +800,6 @@ impl SpdmSession {
         .ok_or(spdm_err!(ESEC))
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn generate_hmac_with_request_finished_key(
-        &mut self,
-        message: &[u8],
-    ) -> SpdmResult<SpdmDigestStruct> {
-        crypto::hmac::hmac(
-            self.crypto_param.base_hash_algo,
-            self.handshake_secret.request_finished_key.as_ref(),
-            crypto::hash::hash_all(self.crypto_param.base_hash_algo, message)
-                .ok_or(spdm_err!(EINVAL))?
-                .as_ref(),
-        )
-        .ok_or(spdm_err!(ESEC))
-    }
-
-    #[cfg(feature = "hashed-transcript-data")]
     pub fn generate_hmac_with_request_finished_key(
         &mut self,
         message_hash: &[u8],
@@ -682,23 +812,6 @@ impl SpdmSession {
         .ok_or(spdm_err!(ESEC))
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn verify_hmac_with_response_finished_key(
-        &self,
-        message: &[u8],
-        hmac: &SpdmDigestStruct,
-    ) -> SpdmResult {
-        crypto::hmac::hmac_verify(
-            self.crypto_param.base_hash_algo,
-            self.handshake_secret.response_finished_key.as_ref(),
-            crypto::hash::hash_all(self.crypto_param.base_hash_algo, message)
-                .ok_or(spdm_err!(EINVAL))?
-                .as_ref(),
-            hmac,
-        )
-    }
-
-    #[cfg(feature = "hashed-transcript-data")]
     pub fn verify_hmac_with_response_finished_key(
         &self,
         message_hash: &[u8],
@@ -712,23 +825,6 @@ impl SpdmSession {
         )
     }
 
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn verify_hmac_with_request_finished_key(
-        &self,
-        message: &[u8],
-        hmac: &SpdmDigestStruct,
-    ) -> SpdmResult {
-        crypto::hmac::hmac_verify(
-            self.crypto_param.base_hash_algo,
-            self.handshake_secret.request_finished_key.as_ref(),
-            crypto::hash::hash_all(self.crypto_param.base_hash_algo, message)
-                .ok_or(spdm_err!(EINVAL))?
-                .as_ref(),
-            hmac,
-        )
-    }
-
-    #[cfg(feature = "hashed-transcript-data")]
     pub fn verify_hmac_with_request_finished_key(
         &self,
         message_hash: &[u8],
@@ -752,6 +848,9 @@ impl SpdmSession {
                     .clone(),
                 salt: self.application_secret.request_direction.salt.clone(),
                 sequence_number: self.application_secret.request_direction.sequence_number,
+                byte_count: self.application_secret.request_direction.byte_count,
+                key_time: self.application_secret.request_direction.key_time,
+                replay_window: self.application_secret.request_direction.replay_window,
             },
             SpdmSessionSecretParam {
                 encryption_key: self
@@ -761,17 +860,116 @@ impl SpdmSession {
                     .clone(),
                 salt: self.application_secret.response_direction.salt.clone(),
                 sequence_number: self.application_secret.response_direction.sequence_number,
+                byte_count: self.application_secret.response_direction.byte_count,
+                key_time: self.application_secret.response_direction.key_time,
+                replay_window: self.application_secret.response_direction.replay_window,
             },
         )
     }
 
+    /// Write what an established session needs to carry on in another context:
+    /// the export master secret and the application secrets, not the ones of
+    /// the handshake or of a key update in progress.
+    ///
+    /// The secrets are written in the clear, `SpdmContext::export_session`
+    /// encrypts what this writes. The key age is written, not the key time,
+    /// as the clock of the other context need not match.
+    pub(crate) fn write_handoff(&self, writer: &mut Writer) -> SpdmResult {
+        write_handoff_value(writer, &self.session_id)?;
+        write_handoff_value(writer, &(self.use_psk as u8))?;
+        write_handoff_value(writer, &self.session_state)?;
+        write_handoff_value(writer, &self.crypto_param.base_hash_algo)?;
+        write_handoff_value(writer, &self.crypto_param.dhe_algo)?;
+        write_handoff_value(writer, &self.crypto_param.aead_algo)?;
+        write_handoff_value(writer, &self.crypto_param.key_schedule_algo)?;
+        write_handoff_value(writer, &self.transport_param.sequence_number_count)?;
+        write_handoff_value(writer, &self.transport_param.max_random_count)?;
+        write_handoff_value(writer, &self.heartbeat_period)?;
+        write_handoff_value(writer, &self.secure_spdm_version_sel)?;
+        write_handoff_bytes(writer, self.handshake_secret.export_master_secret.as_ref())?;
+        write_handoff_bytes(writer, self.application_secret.request_data_secret.as_ref())?;
+        write_handoff_bytes(
+            writer,
+            self.application_secret.response_data_secret.as_ref(),
+        )?;
+        for secret_param in [
+            &self.application_secret.request_direction,
+            &self.application_secret.response_direction,
+        ] {
+            write_handoff_bytes(
+                writer,
+                &secret_param.encryption_key.data[..secret_param.encryption_key.data_size as usize],
+            )?;
+            write_handoff_bytes(
+                writer,
+                &secret_param.salt.data[..secret_param.salt.data_size as usize],
+            )?;
+            write_handoff_value(writer, &secret_param.sequence_number)?;
+            write_handoff_value(writer, &secret_param.byte_count)?;
+            let key_age = match (crate::time::now(), secret_param.key_time) {
+                (Some(now), Some(key_time)) => now.saturating_sub(key_time),
+                _ => 0,
+            };
+            write_handoff_value(writer, &key_age)?;
+            write_handoff_value(writer, &secret_param.replay_window)?;
+        }
+        Ok(())
+    }
+
+    /// Read what `write_handoff` wrote, with the key time and the time of the
+    /// last message on the clocks of this context.
+    pub(crate) fn read_handoff(r: &mut Reader) -> Option<SpdmSession> {
+        let mut session = SpdmSession::new();
+        session.session_id = u32::read(r)?;
+        session.use_psk = u8::read(r)? != 0;
+        session.session_state = SpdmSessionState::read(r)?;
+        if session.session_state != SpdmSessionState::SpdmSessionEstablished {
+            return None;
+        }
+        session.crypto_param = SpdmSessionCryptoParam {
+            base_hash_algo: SpdmBaseHashAlgo::read(r)?,
+            dhe_algo: SpdmDheAlgo::read(r)?,
+            aead_algo: SpdmAeadAlgo::read(r)?,
+            key_schedule_algo: SpdmKeyScheduleAlgo::read(r)?,
+        };
+        session.transport_param = SpdmSessionTransportParam {
+            sequence_number_count: u8::read(r)?,
+            max_random_count: u16::read(r)?,
+        };
+        session.heartbeat_period = u8::read(r)?;
+        session.secure_spdm_version_sel = u8::read(r)?;
+        for digest in [
+            &mut session.handshake_secret.export_master_secret,
+            &mut session.application_secret.request_data_secret,
+            &mut session.application_secret.response_data_secret,
+        ] {
+            digest.data_size = read_handoff_bytes(r, &mut digest.data[..])?;
+        }
+        for secret_param in [
+            &mut session.application_secret.request_direction,
+            &mut session.application_secret.response_direction,
+        ] {
+            secret_param.encryption_key.data_size =
+                read_handoff_bytes(r, &mut secret_param.encryption_key.data[..])?;
+            secret_param.salt.data_size = read_handoff_bytes(r, &mut secret_param.salt.data[..])?;
+            secret_param.sequence_number = u64::read(r)?;
+            secret_param.byte_count = u64::read(r)?;
+            let key_age = u64::read(r)?;
+            secret_param.key_time = crate::time::now().map(|now| now.saturating_sub(key_age));
+            secret_param.replay_window = u64::read(r)?;
+        }
+        session.last_message_time = crate::time::now();
+        Some(session)
+    }
+
     pub fn encode_spdm_secured_message(
         &mut self,
         app_buffer: &[u8],
         secured_buffer: &mut [u8],
         is_requester: bool,
     ) -> SpdmResult<usize> {
-        match self.session_state {
+        self.check_sequence_number(is_requester)?;
+        let r = match self.session_state {
             SpdmSessionState::SpdmSessionNotStarted => spdm_result_err!(EINVAL),
             SpdmSessionState::SpdmSessionHandshaking => {
                 if is_requester {
@@ -800,6 +998,7 @@ impl SpdmSession {
                         &self.application_secret.request_direction,
                     );
                     self.application_secret.request_direction.sequence_number += 1;
+                    self.application_secret.request_direction.byte_count += app_buffer.len() as u64;
                     r
                 } else {
                     let r = self.encode_msg(
@@ -808,11 +1007,17 @@ impl SpdmSession {
                         &self.application_secret.response_direction,
                     );
                     self.application_secret.response_direction.sequence_number += 1;
+                    self.application_secret.response_direction.byte_count +=
+                        app_buffer.len() as u64;
                     r
                 }
             }
             _ => panic!("unknown session state"),
+        };
+        if r.is_ok() {
+            self.last_message_time = crate::time::now();
         }
+        r
     }
 
     pub fn decode_spdm_secured_message(
@@ -821,50 +1026,92 @@ impl SpdmSession {
         app_buffer: &mut [u8],
         is_requester: bool,
     ) -> SpdmResult<usize> {
-        match self.session_state {
-            SpdmSessionState::SpdmSessionNotStarted => spdm_result_err!(EINVAL),
-            SpdmSessionState::SpdmSessionHandshaking => {
-                if is_requester {
-                    let r = self.decode_msg(
-                        secured_buffer,
-                        app_buffer,
-                        &self.handshake_secret.request_direction,
-                    );
-                    self.handshake_secret.request_direction.sequence_number += 1;
-                    r
+        self.check_sequence_number(is_requester)?;
+        let secret_param = match (self.session_state, is_requester) {
+            (SpdmSessionState::SpdmSessionNotStarted, _) => return spdm_result_err!(EINVAL),
+            (SpdmSessionState::SpdmSessionHandshaking, true) => {
+                &self.handshake_secret.request_direction
+            }
+            (SpdmSessionState::SpdmSessionHandshaking, false) => {
+                &self.handshake_secret.response_direction
+            }
+            (SpdmSessionState::SpdmSessionEstablished, true) => {
+                &self.application_secret.request_direction
+            }
+            (SpdmSessionState::SpdmSessionEstablished, false) => {
+                &self.application_secret.response_direction
+            }
+            _ => return spdm_result_err!(ENOMEM),
+        };
+        let r = self.decode_msg(secured_buffer, app_buffer, secret_param);
+
+        let explicit_sequence_number = self.explicit_sequence_number();
+        let is_established = self.session_state == SpdmSessionState::SpdmSessionEstablished;
+        let secret_param = match (self.session_state, is_requester) {
+            (SpdmSessionState::SpdmSessionHandshaking, true) => {
+                &mut self.handshake_secret.request_direction
+            }
+            (SpdmSessionState::SpdmSessionHandshaking, false) => {
+                &mut self.handshake_secret.response_direction
+            }
+            (_, true) => &mut self.application_secret.request_direction,
+            (_, false) => &mut self.application_secret.response_direction,
+        };
+        match r {
+            Ok((used, sequence_number)) => {
+                if explicit_sequence_number {
+                    secret_param.mark_sequence_number_received(sequence_number)?;
                 } else {
-                    let r = self.decode_msg(
-                        secured_buffer,
-                        app_buffer,
-                        &self.handshake_secret.response_direction,
-                    );
-                    self.handshake_secret.response_direction.sequence_number += 1;
-                    r
+                    secret_param.sequence_number += 1;
+                }
+                if is_established {
+                    secret_param.byte_count += used as u64;
                 }
+                self.last_message_time = crate::time::now();
+                Ok(used)
             }
-            SpdmSessionState::SpdmSessionEstablished => {
-                if is_requester {
-                    let r = self.decode_msg(
-                        secured_buffer,
-                        app_buffer,
-                        &self.application_secret.request_direction,
-                    );
-                    self.application_secret.request_direction.sequence_number += 1;
-                    r
-                } else {
-                    let r = self.decode_msg(
-                        secured_buffer,
-                        app_buffer,
-                        &self.application_secret.response_direction,
-                    );
-                    self.application_secret.response_direction.sequence_number += 1;
-                    r
+            Err(e) => {
+                // a message that fails does not move an explicit sequence number
+                if !explicit_sequence_number {
+                    secret_param.sequence_number += 1;
                 }
+                Err(e)
             }
-            _ => spdm_result_err!(ENOMEM),
         }
     }
 
+    /// Whether received messages carry their sequence number, which may then
+    /// arrive out of order within the replay window (Secured Messages 1.1).
+    fn explicit_sequence_number(&self) -> bool {
+        self.secure_spdm_version_sel >= super::opaque::DMTF_SECURE_SPDM_VERSION_11
+            && self.transport_param.sequence_number_count != 0
+    }
+
+    /// Sequence numbers never wrap, a session that used up one direction is ended.
+    fn check_sequence_number(&mut self, is_requester: bool) -> SpdmResult {
+        let sequence_number = match (self.session_state, is_requester) {
+            (SpdmSessionState::SpdmSessionHandshaking, true) => {
+                self.handshake_secret.request_direction.sequence_number
+            }
+            (SpdmSessionState::SpdmSessionHandshaking, false) => {
+                self.handshake_secret.response_direction.sequence_number
+            }
+            (SpdmSessionState::SpdmSessionEstablished, true) => {
+                self.application_secret.request_direction.sequence_number
+            }
+            (SpdmSessionState::SpdmSessionEstablished, false) => {
+                self.application_secret.response_direction.sequence_number
+            }
+            _ => return Ok(()),
+        };
+        if sequence_number == u64::MAX {
+            error!("!!! sequence number exhausted, end session !!!\n");
+            self.set_default();
+            return Err(spdm_err!(ESEC, "sequence number exhausted"));
+        }
+        Ok(())
+    }
+
     fn encode_msg(
         &self,
         app_buffer: &[u8],
@@ -875,8 +1122,30 @@ impl SpdmSession {
         let aead_algo = self.crypto_param.aead_algo;
         let transport_param = &self.transport_param;
 
-        let cipher_text_size = app_buffer.len() + 2;
+        let mut plain_text_buf =
+            [0; config::MAX_SPDM_MESSAGE_BUFFER_SIZE + core::mem::size_of::<u16>()]; // app length + app buffer
         let tag_size = aead_algo.get_tag_size() as usize;
+        let aad_size = 6 + transport_param.sequence_number_count as usize;
+
+        // Secured Messages 1.1 hides the message size with random data, as much
+        // as the transport allows and fits
+        let mut random_count = 0;
+        if self.secure_spdm_version_sel >= super::opaque::DMTF_SECURE_SPDM_VERSION_11
+            && transport_param.max_random_count != 0
+        {
+            let mut random = [0u8; 2];
+            crypto::rand::get_random(&mut random)?;
+            random_count =
+                (u16::from_le_bytes(random) % (transport_param.max_random_count + 1)) as usize;
+            random_count = random_count
+                .min(plain_text_buf.len().saturating_sub(app_buffer.len() + 2))
+                .min(
+                    secured_buffer
+                        .len()
+                        .saturating_sub(aad_size + app_buffer.len() + 2 + tag_size),
+                );
+        }
+        let cipher_text_size = app_buffer.len() + 2 + random_count;
 
         let mut aad_buffer = [0u8; 6 + 8];
         let mut writer = Writer::init(&mut aad_buffer);
@@ -891,16 +1160,18 @@ impl SpdmSession {
             }
         }
         length.encode(&mut writer);
-        let aad_size = writer.used();
-        assert_eq!(aad_size, 6 + transport_param.sequence_number_count as usize);
+        assert_eq!(aad_size, writer.used());
 
-        let mut plain_text_buf =
-            [0; config::MAX_SPDM_MESSAGE_BUFFER_SIZE + core::mem::size_of::<u16>()]; // app length + app buffer
         let mut writer = Writer::init(&mut plain_text_buf);
         app_length.encode(&mut writer);
         let head_size = writer.used();
         assert_eq!(head_size, 2);
         plain_text_buf[head_size..(head_size + app_buffer.len())].copy_from_slice(app_buffer);
+        if random_count != 0 {
+            crypto::rand::get_random(
+                &mut plain_text_buf[(head_size + app_buffer.len())..cipher_text_size],
+            )?;
+        }
 
         let mut tag_buffer = [0u8; 16];
 
@@ -941,7 +1212,7 @@ impl SpdmSession {
         secured_buffer: &[u8],
         app_buffer: &mut [u8],
         secret_param: &SpdmSessionSecretParam,
-    ) -> SpdmResult<usize> {
+    ) -> SpdmResult<(usize, u64)> {
         let session_id = self.session_id;
         let aead_algo = self.crypto_param.aead_algo;
         let transport_param = &self.transport_param;
@@ -953,15 +1224,22 @@ impl SpdmSession {
             error!("session_id mismatch!\n");
             return spdm_result_err!(EINVAL);
         }
+        let mut sequence_number = secret_param.sequence_number;
         if transport_param.sequence_number_count != 0 {
-            let sequence_number = secret_param.sequence_number;
+            let mut low = 0u64;
             for i in 0..transport_param.sequence_number_count {
                 let s = u8::read(&mut reader).ok_or(spdm_err!(EIO))?;
-                if s != ((sequence_number >> (8 * i)) & 0xFF) as u8 {
+                if self.explicit_sequence_number() {
+                    low |= (s as u64) << (8 * i as u32);
+                } else if s != ((sequence_number >> (8 * i)) & 0xFF) as u8 {
                     info!("sequence_num mismatch!\n");
                     return spdm_result_err!(EINVAL);
                 }
             }
+            if self.explicit_sequence_number() {
+                sequence_number = secret_param
+                    .expand_received_sequence_number(low, transport_param.sequence_number_count)?;
+            }
         }
         let length = u16::read(&mut reader).ok_or(spdm_err!(EIO))?;
         let aad_size = reader.used();
@@ -983,7 +1261,6 @@ impl SpdmSession {
         let mut plain_text_buf = [0; config::DATA_TRANSFER_SIZE];
 
         let mut salt = secret_param.salt.data.clone();
-        let sequence_number = secret_param.sequence_number;
         salt[0] ^= (sequence_number & 0xFF) as u8;
         salt[1] ^= ((sequence_number >> 8) & 0xFF) as u8;
         salt[2] ^= ((sequence_number >> 16) & 0xFF) as u8;
@@ -1011,13 +1288,14 @@ impl SpdmSession {
         }
 
         app_buffer[..app_length].copy_from_slice(&plain_text_buf[2..(app_length + 2)]);
-        Ok(app_length)
+        Ok((app_length, sequence_number))
     }
 }
 
 #[cfg(all(test,))]
 mod tests_session {
     use super::*;
+    use crate::testlib::*;
 
     #[test]
     fn test_case0_activate_data_secret_update() {
@@ -1063,6 +1341,7 @@ mod tests_session {
                 data: Box::new([10u8; SPDM_MAX_AEAD_IV_SIZE]),
             },
             sequence_number: 100u64,
+            ..Default::default()
         };
         session.transport_param.sequence_number_count = 1;
 
@@ -1113,6 +1392,173 @@ mod tests_session {
         assert!(status);
     }
     #[test]
+    fn test_case0_key_update_due() {
+        let mut session = SpdmSession::default();
+        let policy = SpdmKeyUpdatePolicy {
+            max_messages: 10,
+            max_bytes: 100,
+            max_key_age: 60,
+        };
+        session.application_secret.request_direction.key_time = Some(1_000_000);
+        session.application_secret.response_direction.key_time = Some(1_000_000);
+        assert!(!session.key_update_due(&policy, Some(100_000_000)));
+
+        session.set_session_state(SpdmSessionState::SpdmSessionEstablished);
+        assert!(!session.key_update_due(&policy, Some(60_999_999)));
+        assert!(session.key_update_due(&policy, Some(61_000_000)));
+        assert!(!session.key_update_due(&policy, None));
+
+        session.set_response_direction_sequence_number(10);
+        assert!(session.key_update_due(&policy, None));
+        assert!(!session.key_update_due(&SpdmKeyUpdatePolicy::default(), Some(100_000_000)));
+        session.set_response_direction_sequence_number(0);
+
+        session.application_secret.request_direction.byte_count = 100;
+        assert!(session.key_update_due(&policy, None));
+    }
+    #[test]
+    fn test_case0_sequence_number_exhausted() {
+        let mut session = SpdmSession::default();
+        let session_id = 4294901758u32;
+        let send_buffer = [100u8; 16];
+        let mut encoded_send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+
+        session.setup(session_id).unwrap();
+        session.set_session_state(SpdmSessionState::SpdmSessionEstablished);
+        session.set_request_direction_sequence_number(u64::MAX);
+        let err = session
+            .encode_spdm_secured_message(&send_buffer, &mut encoded_send_buffer, true)
+            .unwrap_err();
+        assert_eq!(err.num, crate::error::SpdmErrorNum::ESEC);
+        assert_eq!(session.get_session_id(), 0);
+    }
+    fn new_handshaking_session(secure_spdm_version_sel: u8) -> SpdmSession {
+        let mut session = SpdmSession::default();
+        session.setup(4294901758u32).unwrap();
+        session.set_crypto_param(
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmDheAlgo::SECP_384_R1,
+            SpdmAeadAlgo::AES_256_GCM,
+            SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+        );
+        session.set_session_state(SpdmSessionState::SpdmSessionHandshaking);
+        session.handshake_secret.request_direction.encryption_key = SpdmAeadKeyStruct {
+            data_size: 32,
+            data: Box::new([10u8; SPDM_MAX_AEAD_KEY_SIZE]),
+        };
+        session.handshake_secret.request_direction.salt = SpdmAeadIvStruct {
+            data_size: 12,
+            data: Box::new([10u8; SPDM_MAX_AEAD_IV_SIZE]),
+        };
+        session.set_transport_param(2, 0);
+        session.secure_spdm_version_sel = secure_spdm_version_sel;
+        session
+    }
+    #[test]
+    fn test_case0_replay_window() {
+        let mut sender = new_handshaking_session(0x11);
+        let mut receiver = new_handshaking_session(0x11);
+        let send_buffer = [100u8; 16];
+        let mut messages = [[0u8; 128]; 3];
+        let mut sizes = [0usize; 3];
+        for (message, size) in messages.iter_mut().zip(sizes.iter_mut()) {
+            *size = sender
+                .encode_spdm_secured_message(&send_buffer, message, true)
+                .unwrap();
+        }
+
+        let mut decode = |index: usize| {
+            let mut app_buffer = [0u8; 128];
+            receiver
+                .decode_spdm_secured_message(
+                    &messages[index][..sizes[index]],
+                    &mut app_buffer,
+                    true,
+                )
+                .map(|used| assert_eq!(&app_buffer[..used], &send_buffer[..]))
+                .is_ok()
+        };
+        // out of order within the window, but only once
+        assert!(decode(2));
+        assert!(decode(0));
+        assert!(!decode(0));
+        assert!(decode(1));
+        assert!(!decode(2));
+        assert_eq!(
+            receiver.handshake_secret.request_direction.sequence_number,
+            3
+        );
+
+        // a forged message does not move the window
+        let mut forged = messages[2];
+        forged[4] = 3;
+        forged[10] ^= 0xFF;
+        let mut app_buffer = [0u8; 128];
+        assert!(receiver
+            .decode_spdm_secured_message(&forged[..sizes[2]], &mut app_buffer, true)
+            .is_err());
+        assert_eq!(
+            receiver.handshake_secret.request_direction.sequence_number,
+            3
+        );
+    }
+    #[test]
+    fn test_case0_replay_window_too_old() {
+        let mut param = SpdmSessionSecretParam::default();
+        param.mark_sequence_number_received(0).unwrap();
+        assert!(param.expand_received_sequence_number(0, 2).is_err());
+        param.mark_sequence_number_received(65).unwrap();
+        // 1..=64 are in the window, 0 is received already
+        assert!(param.expand_received_sequence_number(1, 2).is_ok());
+        assert!(param.expand_received_sequence_number(0, 2).is_err());
+        param.mark_sequence_number_received(66).unwrap();
+        assert!(param.expand_received_sequence_number(1, 2).is_err());
+        assert!(param.expand_received_sequence_number(2, 2).is_ok());
+
+        // the high bytes come from the expected number
+        let mut param = SpdmSessionSecretParam::default();
+        param.sequence_number = 0x1_FFFE;
+        assert_eq!(
+            param.expand_received_sequence_number(0x0001, 2).unwrap(),
+            0x2_0001
+        );
+        param.mark_sequence_number_received(0x2_0001).unwrap();
+        assert_eq!(
+            param.expand_received_sequence_number(0xFFFF, 2).unwrap(),
+            0x1_FFFF
+        );
+
+        // the last sequence number has no next one
+        let mut param = SpdmSessionSecretParam::default();
+        assert!(param.expand_received_sequence_number(u64::MAX, 8).is_err());
+        assert!(param.mark_sequence_number_received(u64::MAX).is_err());
+        assert_eq!(param.sequence_number, 0);
+    }
+    #[test]
+    fn test_case0_secured_message_10_in_order() {
+        let mut sender = new_handshaking_session(0x10);
+        let mut receiver = new_handshaking_session(0x10);
+        let send_buffer = [100u8; 16];
+        let mut message0 = [0u8; 128];
+        let mut message1 = [0u8; 128];
+        let size0 = sender
+            .encode_spdm_secured_message(&send_buffer, &mut message0, true)
+            .unwrap();
+        let size1 = sender
+            .encode_spdm_secured_message(&send_buffer, &mut message1, true)
+            .unwrap();
+        assert_eq!(size0, 4 + 2 + 2 + 2 + 16 + 16);
+
+        // the expected number moves on even when a message fails
+        let mut app_buffer = [0u8; 128];
+        assert!(receiver
+            .decode_spdm_secured_message(&message1[..size1], &mut app_buffer, true)
+            .is_err());
+        assert!(receiver
+            .decode_spdm_secured_message(&message0[..size0], &mut app_buffer, true)
+            .is_err());
+    }
+    #[test]
     #[should_panic]
     fn test_case0_setup() {
         let mut session = SpdmSession::default();
@@ -1121,6 +1567,43 @@ mod tests_session {
         let _ = session.setup(session_id).is_err();
     }
     #[test]
+    fn test_case1_teardown() {
+        let session_id = 4294901758u32;
+        let mut session = new_handshaking_session(0x11);
+        session.master_secret.master_secret = SpdmDigestStruct {
+            data_size: 48,
+            data: Box::new([10u8; SPDM_MAX_HASH_SIZE]),
+        };
+        session.application_secret.response_direction.encryption_key = SpdmAeadKeyStruct {
+            data_size: 32,
+            data: Box::new([10u8; SPDM_MAX_AEAD_KEY_SIZE]),
+        };
+        assert!(session.teardown(session_id).is_ok());
+
+        assert_eq!(session.get_session_id(), 0);
+        assert_eq!(session.master_secret.master_secret.data_size, 0);
+        assert!(session
+            .master_secret
+            .master_secret
+            .data
+            .iter()
+            .all(|b| *b == 0));
+        let request_direction = &session.handshake_secret.request_direction;
+        assert_eq!(request_direction.encryption_key.data_size, 0);
+        assert!(request_direction
+            .encryption_key
+            .data
+            .iter()
+            .all(|b| *b == 0));
+        assert!(request_direction.salt.data.iter().all(|b| *b == 0));
+        let response_direction = &session.application_secret.response_direction;
+        assert!(response_direction
+            .encryption_key
+            .data
+            .iter()
+            .all(|b| *b == 0));
+    }
+    #[test]
     #[should_panic]
     fn test_case0_teardown() {
         let mut session = SpdmSession::default();
@@ -1128,4 +1611,125 @@ mod tests_session {
         let session_id = 4294901758u32;
         let _ = session.teardown(session_id).is_err();
     }
+
+    #[test]
+    fn test_case0_key_update_policy() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        let (mut req_config_info, req_provision_info) = create_info();
+        let key_update_policy = SpdmKeyUpdatePolicy {
+            max_messages: 3,
+            ..Default::default()
+        };
+        rsp_config_info.key_update_policy = key_update_policy;
+        req_config_info.key_update_policy = key_update_policy;
+
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = new_connected_requester(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+            let session_id = requester
+                .start_session(
+                    true,
+                    0,
+                    SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                )
+                .unwrap();
+
+            // the keys are updated transparently, and never used past the policy
+            for _ in 0..10 {
+                assert!(requester.send_receive_spdm_heartbeat(session_id).is_ok());
+                let session = requester.common.get_session_via_id(session_id).unwrap();
+                assert!(session.get_request_direction_sequence_number() <= 3);
+                assert!(session.get_response_direction_sequence_number() <= 3);
+            }
+
+            // a requester that does not update the keys loses the session
+            requester.common.config_info.key_update_policy = SpdmKeyUpdatePolicy::default();
+            let mut heartbeats = 0;
+            while requester.send_receive_spdm_heartbeat(session_id).is_ok() {
+                heartbeats += 1;
+                assert!(heartbeats <= 3);
+            }
+        });
+    }
+
+    #[test]
+    fn test_case0_export_keying_material() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = new_connected_requester(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+            let session_id = requester
+                .start_session(
+                    false,
+                    0,
+                    SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                )
+                .unwrap();
+            let hash_size = requester.common.negotiate_info.base_hash_sel.get_size() as usize;
+
+            // one block and several blocks of the hash
+            let mut short_keying_material = [0u8; 32];
+            assert!(requester
+                .common
+                .export_keying_material(
+                    session_id,
+                    b"ide key",
+                    &[0x01, 0x02],
+                    &mut short_keying_material
+                )
+                .is_ok());
+            let mut long_keying_material = [0u8; 4 * 48 + 8];
+            assert!(requester
+                .common
+                .export_keying_material(
+                    session_id,
+                    b"ide key",
+                    &[0x01, 0x02],
+                    &mut long_keying_material
+                )
+                .is_ok());
+            assert_ne!(short_keying_material, [0u8; 32]);
+            assert_ne!(short_keying_material[..], long_keying_material[..32]);
+
+            let mut too_long_keying_material = [0u8; 255 * 64 + 1];
+            for len in [0, 255 * hash_size + 1] {
+                assert!(requester
+                    .common
+                    .export_keying_material(
+                        session_id,
+                        b"ide key",
+                        &[0x01, 0x02],
+                        &mut too_long_keying_material[..len]
+                    )
+                    .is_err());
+            }
+
+            // the responder exports the same keying material
+            drop(requester);
+            let responder = &device_io.responder;
+            let mut keying_material = [0u8; 32];
+            assert!(responder
+                .common
+                .export_keying_material(session_id, b"ide key", &[0x01, 0x02], &mut keying_material)
+                .is_ok());
+            assert_eq!(keying_material, short_keying_material);
+            let mut keying_material = [0u8; 4 * 48 + 8];
+            assert!(responder
+                .common
+                .export_keying_material(session_id, b"ide key", &[0x01, 0x02], &mut keying_material)
+                .is_ok());
+            assert_eq!(keying_material[..], long_keying_material[..]);
+        });
+    }
 }
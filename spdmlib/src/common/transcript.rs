@@ -0,0 +1,222 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+extern crate alloc;
+use alloc::boxed::Box;
+
+use super::ManagedBuffer;
+use crate::crypto::{self, HashCtx};
+use crate::error::{spdm_err, spdm_result_err, SpdmResult};
+use crate::protocol::{SpdmBaseHashAlgo, SpdmDigestStruct};
+
+/// How a transcript keeps the messages appended to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpdmTranscriptMode {
+    /// Keep every message, so the full transcript is available for diagnostics.
+    Buffered,
+    /// Keep only a running hash of the messages.
+    Hashed,
+}
+
+impl Default for SpdmTranscriptMode {
+    // The hashed-transcript-data feature only selects the default mode,
+    // both modes are always available at runtime.
+    fn default() -> Self {
+        if cfg!(feature = "hashed-transcript-data") {
+            SpdmTranscriptMode::Hashed
+        } else {
+            SpdmTranscriptMode::Buffered
+        }
+    }
+}
+
+#[derive(Clone)]
+enum SpdmTranscriptState {
+    Uninitialized,
+    Buffered(Box<ManagedBuffer>),
+    Hashed(HashCtx),
+}
+
+/// A transcript (VCA/M1M2/L1L2/TH) kept either as a buffer or as a running hash.
+#[derive(Clone)]
+pub struct SpdmTranscript {
+    base_hash_algo: SpdmBaseHashAlgo,
+    state: SpdmTranscriptState,
+}
+
+impl Default for SpdmTranscript {
+    fn default() -> Self {
+        SpdmTranscript {
+            base_hash_algo: SpdmBaseHashAlgo::default(),
+            state: SpdmTranscriptState::Uninitialized,
+        }
+    }
+}
+
+impl core::fmt::Debug for SpdmTranscript {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.state {
+            SpdmTranscriptState::Uninitialized => write!(f, "SpdmTranscript(Uninitialized)"),
+            SpdmTranscriptState::Buffered(buffer) => {
+                write!(f, "SpdmTranscript(Buffered {:02x?})", (**buffer).as_ref())
+            }
+            SpdmTranscriptState::Hashed(_) => write!(f, "SpdmTranscript(Hashed)"),
+        }
+    }
+}
+
+impl SpdmTranscript {
+    pub fn new(mode: SpdmTranscriptMode, base_hash_algo: SpdmBaseHashAlgo) -> SpdmResult<Self> {
+        let mut transcript = SpdmTranscript::default();
+        transcript.init(mode, base_hash_algo)?;
+        Ok(transcript)
+    }
+
+    /// Start an empty transcript, dropping whatever was recorded before.
+    pub fn init(
+        &mut self,
+        mode: SpdmTranscriptMode,
+        base_hash_algo: SpdmBaseHashAlgo,
+    ) -> SpdmResult {
+        self.base_hash_algo = base_hash_algo;
+        self.state = match mode {
+            SpdmTranscriptMode::Buffered => SpdmTranscriptState::Buffered(Box::default()),
+            SpdmTranscriptMode::Hashed => SpdmTranscriptState::Hashed(
+                crypto::hash::hash_ctx_init(base_hash_algo).ok_or_else(|| spdm_err!(EFAULT))?,
+            ),
+        };
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.state = SpdmTranscriptState::Uninitialized;
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        !matches!(self.state, SpdmTranscriptState::Uninitialized)
+    }
+
+    pub fn mode(&self) -> Option<SpdmTranscriptMode> {
+        match self.state {
+            SpdmTranscriptState::Uninitialized => None,
+            SpdmTranscriptState::Buffered(_) => Some(SpdmTranscriptMode::Buffered),
+            SpdmTranscriptState::Hashed(_) => Some(SpdmTranscriptMode::Hashed),
+        }
+    }
+
+    pub fn append(&mut self, message: &[u8]) -> SpdmResult {
+        match &mut self.state {
+            SpdmTranscriptState::Uninitialized => spdm_result_err!(EINVAL),
+            SpdmTranscriptState::Buffered(buffer) => buffer
+                .append_message(message)
+                .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(())),
+            SpdmTranscriptState::Hashed(ctx) => crypto::hash::hash_ctx_update(ctx, message),
+        }
+    }
+
+    /// Hash of everything appended so far; the transcript can still be extended afterwards.
+    pub fn hash(&self) -> SpdmResult<SpdmDigestStruct> {
+        match &self.state {
+            SpdmTranscriptState::Uninitialized => spdm_result_err!(EINVAL),
+            SpdmTranscriptState::Buffered(buffer) => {
+                crypto::hash::hash_all(self.base_hash_algo, (**buffer).as_ref())
+                    .ok_or_else(|| spdm_err!(EFAULT))
+            }
+            SpdmTranscriptState::Hashed(ctx) => {
+                crypto::hash::hash_ctx_finalize(ctx.clone()).ok_or_else(|| spdm_err!(EFAULT))
+            }
+        }
+    }
+
+    /// The raw transcript, only available in buffered mode.
+    pub fn data(&self) -> Option<&[u8]> {
+        match &self.state {
+            SpdmTranscriptState::Buffered(buffer) => Some((**buffer).as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test,))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case0_transcript_buffered() {
+        let mut transcript = SpdmTranscript::new(
+            SpdmTranscriptMode::Buffered,
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+        )
+        .unwrap();
+        for message in [&[1u8, 2, 3][..], &[4u8; 100][..], &[][..]] {
+            transcript.append(message).unwrap();
+        }
+        assert_eq!(transcript.mode(), Some(SpdmTranscriptMode::Buffered));
+        assert_eq!(transcript.data().unwrap().len(), 103);
+        assert_eq!(&transcript.data().unwrap()[..4], &[1u8, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_case1_transcript_uninitialized() {
+        let mut transcript = SpdmTranscript::default();
+        assert!(!transcript.is_initialized());
+        assert!(transcript.append(&[0u8]).is_err());
+        assert!(transcript.hash().is_err());
+
+        transcript
+            .init(
+                SpdmTranscriptMode::Buffered,
+                SpdmBaseHashAlgo::TPM_ALG_SHA_256,
+            )
+            .unwrap();
+        assert!(transcript.is_initialized());
+        transcript.reset();
+        assert!(!transcript.is_initialized());
+        assert!(transcript.mode().is_none());
+    }
+
+    #[test]
+    fn test_case2_transcript_buffer_overflow() {
+        let mut transcript = SpdmTranscript::new(
+            SpdmTranscriptMode::Buffered,
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256,
+        )
+        .unwrap();
+        let message = [0u8; crate::config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        assert!(transcript.append(&message).is_ok());
+        assert!(transcript.append(&[0u8]).is_err());
+    }
+
+    #[test]
+    fn test_case3_transcript_modes_hash_alike() {
+        // the backend is used directly, hashing through crypto::hash here
+        // would set the default before the register tests run
+        let hash = &crypto::spdm_ring::hash_impl::DEFAULT;
+        for base_hash_algo in [
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256,
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmBaseHashAlgo::TPM_ALG_SHA_512,
+        ] {
+            let mut buffered =
+                SpdmTranscript::new(SpdmTranscriptMode::Buffered, base_hash_algo).unwrap();
+            let mut hashed = SpdmTranscript {
+                base_hash_algo,
+                state: SpdmTranscriptState::Hashed(
+                    (hash.hash_ctx_init_cb)(base_hash_algo).unwrap(),
+                ),
+            };
+            for message in [&[][..], &[1u8, 2, 3][..], &[4u8; 200][..], &[][..]] {
+                buffered.append(message).unwrap();
+                hashed.append(message).unwrap();
+                // the hash of the buffered mode, and hashing does not end the transcript
+                assert_eq!(
+                    hashed.hash().unwrap().as_ref(),
+                    (hash.hash_all_cb)(base_hash_algo, buffered.data().unwrap())
+                        .unwrap()
+                        .as_ref()
+                );
+            }
+        }
+    }
+}
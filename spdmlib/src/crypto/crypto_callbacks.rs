@@ -2,28 +2,40 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+use crate::crypto::x509v3::SpdmCertInfo;
 use crate::error::SpdmResult;
 
 extern crate alloc;
 use alloc::boxed::Box;
 
-#[cfg(feature = "hashed-transcript-data")]
-use super::spdm_ring::hash_impl::HashCtx;
-
 use crate::protocol::{
     SpdmAeadAlgo, SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmDheAlgo, SpdmDheExchangeStruct,
-    SpdmDheFinalKeyStruct, SpdmDigestStruct, SpdmSignatureStruct,
+    SpdmDheFinalKeyStruct, SpdmDigestStruct, SpdmSignatureStruct, SpdmVersion,
 };
 
 #[derive(Clone)]
 pub struct SpdmHash {
     pub hash_all_cb: fn(base_hash_algo: SpdmBaseHashAlgo, data: &[u8]) -> Option<SpdmDigestStruct>,
-    #[cfg(feature = "hashed-transcript-data")]
-    pub hash_ctx_init_cb: fn(base_hash_algo: SpdmBaseHashAlgo) -> Option<HashCtx>,
-    #[cfg(feature = "hashed-transcript-data")]
-    pub hash_ctx_update_cb: fn(ctx: &mut HashCtx, data: &[u8]),
-    #[cfg(feature = "hashed-transcript-data")]
-    pub hash_ctx_finalize_cb: fn(ctx: HashCtx) -> Option<SpdmDigestStruct>,
+    pub hash_ctx_init_cb: fn(base_hash_algo: SpdmBaseHashAlgo) -> Option<Box<dyn SpdmHashCtx>>,
+}
+
+/// Streaming hash state owned by the crypto backend.
+///
+/// The library only drives the context through this trait, so any backend
+/// (software, mbedtls, HSM) can keep a running transcript hash.
+pub trait SpdmHashCtx {
+    fn update(&mut self, data: &[u8]) -> SpdmResult;
+
+    fn finalize(self: Box<Self>) -> Option<SpdmDigestStruct>;
+
+    /// Duplicate the current state, used to fork a transcript.
+    fn box_clone(&self) -> Box<dyn SpdmHashCtx>;
+}
+
+impl Clone for Box<dyn SpdmHashCtx> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
 }
 
 #[derive(Clone)]
@@ -66,13 +78,26 @@ pub struct SpdmAead {
     pub decrypt_cb: DecryptCb,
 }
 
+type SignCb = fn(
+    slot_id: u8,
+    spdm_version: SpdmVersion,
+    base_hash_algo: SpdmBaseHashAlgo,
+    base_asym_algo: SpdmBaseAsymAlgo,
+    sign_context: &[u8],
+    data: &[u8],
+) -> Option<SpdmSignatureStruct>;
+
+/// Responder signer.
+///
+/// `slot_id` selects the key (per-slot keys, keys held in a secure element).
+/// `sign_context` is the opcode-specific SPDM signing context, e.g.
+/// `SPDM_CHALLENGE_AUTH_SIGN_CONTEXT`, given for every version so the signer
+/// knows which operation it signs. `data` is the full message the signature
+/// covers: for SPDM 1.2 the combined signing prefix, context and transcript
+/// hash, for earlier versions the transcript itself.
 #[derive(Clone)]
 pub struct SpdmAsymSign {
-    pub sign_cb: fn(
-        base_hash_algo: SpdmBaseHashAlgo,
-        base_asym_algo: SpdmBaseAsymAlgo,
-        data: &[u8],
-    ) -> Option<SpdmSignatureStruct>,
+    pub sign_cb: SignCb,
 }
 
 #[derive(Clone)]
@@ -103,6 +128,9 @@ pub struct SpdmCertOperation {
     pub get_cert_from_cert_chain_cb: GetCertFromCertChainCb,
 
     pub verify_cert_chain_cb: fn(cert_chain: &[u8]) -> SpdmResult,
+
+    /// Parse one DER certificate, e.g. with `crypto::x509v3::get_cert_info`.
+    pub get_cert_info_cb: fn(cert: &[u8]) -> SpdmResult<SpdmCertInfo<'_>>,
 }
 
 type GenerateKeyPairCb =
@@ -2,20 +2,16 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::crypto::SpdmHash;
-use crate::protocol::{SpdmBaseHashAlgo, SpdmDigestStruct};
+extern crate alloc;
+use alloc::boxed::Box;
 
-#[cfg(feature = "hashed-transcript-data")]
-pub type HashCtx = ring::digest::Context;
+use crate::crypto::{SpdmHash, SpdmHashCtx};
+use crate::error::SpdmResult;
+use crate::protocol::{SpdmBaseHashAlgo, SpdmDigestStruct};
 
 pub static DEFAULT: SpdmHash = SpdmHash {
     hash_all_cb: hash_all,
-    #[cfg(feature = "hashed-transcript-data")]
     hash_ctx_init_cb: hash_ctx_init,
-    #[cfg(feature = "hashed-transcript-data")]
-    hash_ctx_update_cb: hash_ctx_update,
-    #[cfg(feature = "hashed-transcript-data")]
-    hash_ctx_finalize_cb: hash_ctx_finalize,
 };
 
 fn hash_all(base_hash_algo: SpdmBaseHashAlgo, data: &[u8]) -> Option<SpdmDigestStruct> {
@@ -29,26 +25,35 @@ fn hash_all(base_hash_algo: SpdmBaseHashAlgo, data: &[u8]) -> Option<SpdmDigestS
     Some(SpdmDigestStruct::from(digest_value.as_ref()))
 }
 
-#[cfg(feature = "hashed-transcript-data")]
-fn hash_ctx_init(base_hash_algo: SpdmBaseHashAlgo) -> Option<HashCtx> {
+fn hash_ctx_init(base_hash_algo: SpdmBaseHashAlgo) -> Option<Box<dyn SpdmHashCtx>> {
     let algorithm = match base_hash_algo {
         SpdmBaseHashAlgo::TPM_ALG_SHA_256 => &ring::digest::SHA256,
         SpdmBaseHashAlgo::TPM_ALG_SHA_384 => &ring::digest::SHA384,
         SpdmBaseHashAlgo::TPM_ALG_SHA_512 => &ring::digest::SHA512,
         _ => return None,
     };
-    Some(HashCtx::new(algorithm))
+    Some(Box::new(SpdmHashCtxRing(ring::digest::Context::new(
+        algorithm,
+    ))))
 }
 
-#[cfg(feature = "hashed-transcript-data")]
-fn hash_ctx_update(ctx: &mut HashCtx, data: &[u8]) {
-    ctx.update(data)
-}
+#[derive(Clone)]
+struct SpdmHashCtxRing(ring::digest::Context);
 
-#[cfg(feature = "hashed-transcript-data")]
-fn hash_ctx_finalize(ctx: HashCtx) -> Option<SpdmDigestStruct> {
-    let digest_value = ctx.finish();
-    Some(SpdmDigestStruct::from(digest_value.as_ref()))
+impl SpdmHashCtx for SpdmHashCtxRing {
+    fn update(&mut self, data: &[u8]) -> SpdmResult {
+        self.0.update(data);
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Option<SpdmDigestStruct> {
+        let digest_value = self.0.finish();
+        Some(SpdmDigestStruct::from(digest_value.as_ref()))
+    }
+
+    fn box_clone(&self) -> Box<dyn SpdmHashCtx> {
+        Box::new(self.clone())
+    }
 }
 
 #[cfg(all(test,))]
@@ -96,4 +101,22 @@ mod tests {
         assert_eq!(&hellobuddy.as_ref(), &multi_part_hellobuddy.as_ref());
         assert_eq!(&hellobuddy.as_ref(), &multi_part_hellobuddy_twice.as_ref());
     }
+    #[test]
+    fn test_case0_hash_ctx() {
+        let helloworld = hash_all(SpdmBaseHashAlgo::TPM_ALG_SHA_384, b"hello, world").unwrap();
+        let hellobuddy = hash_all(SpdmBaseHashAlgo::TPM_ALG_SHA_384, b"hello, buddy").unwrap();
+        let mut ctx = hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384).unwrap();
+        assert!(ctx.update(b"hello").is_ok());
+        assert!(ctx.update(b", ").is_ok());
+        let mut ctx_d = ctx.clone();
+        assert!(ctx_d.update(b"buddy").is_ok());
+        assert!(ctx.update(b"world").is_ok());
+        assert_eq!(ctx.finalize().unwrap().as_ref(), helloworld.as_ref());
+        assert_eq!(ctx_d.finalize().unwrap().as_ref(), hellobuddy.as_ref());
+    }
+    #[test]
+    fn test_case1_hash_ctx() {
+        let ctx = hash_ctx_init(SpdmBaseHashAlgo::empty());
+        assert!(ctx.is_none());
+    }
 }
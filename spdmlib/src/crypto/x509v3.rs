@@ -0,0 +1,1102 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+//! SPDM 1.2 certificate model checks.
+//!
+//! The crypto backend only validates the certificate path. The rules below
+//! come from the SPDM 1.2 certificate requirements and are independent of the
+//! backend, so they are checked here on the raw DER.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::crypto;
+use crate::error::{spdm_err, SpdmResult};
+use crate::protocol::{SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmSignatureStruct};
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_VERSION: u8 = 0xA0;
+const TAG_EXTENSIONS: u8 = 0xA3;
+
+// 2.5.4.3
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+// 2.5.29.x
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x0F];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1D, 0x13];
+const OID_EXT_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x25];
+
+// 1.3.6.1.4.1.412.274.x (id-DMTF)
+const OID_DMTF_HARDWARE_IDENTITY: &[u8] =
+    &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x02];
+const OID_DMTF_EKU_RESPONDER_AUTH: &[u8] =
+    &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x03];
+const OID_DMTF_EKU_REQUESTER_AUTH: &[u8] =
+    &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x04];
+const OID_DMTF_SPDM_EXTENSION: &[u8] =
+    &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x06];
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+const OID_CURVE_P256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+const OID_CURVE_P384: &[u8] = &[0x2B, 0x81, 0x04, 0x00, 0x22];
+const OID_CURVE_P521: &[u8] = &[0x2B, 0x81, 0x04, 0x00, 0x23];
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+const OID_RSASSA_PSS: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0A];
+
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+const OID_ECDSA_WITH_SHA384: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x03];
+const OID_SHA256_WITH_RSA_ENCRYPTION: &[u8] =
+    &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0B];
+const OID_SHA384_WITH_RSA_ENCRYPTION: &[u8] =
+    &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0C];
+const OID_SHA512_WITH_RSA_ENCRYPTION: &[u8] =
+    &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0D];
+
+/// Certificate model in use by the peer, selected by ALIAS_CERT_CAP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpdmCertModel {
+    /// The leaf is the device certificate and carries the hardware identity.
+    DeviceCert,
+    /// The leaf is an alias certificate issued by the (CA) device certificate.
+    AliasCert,
+}
+
+/// The certificate rule a chain failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpdmCertRule {
+    /// The certificate is not well formed DER.
+    Encoding,
+    /// The extended key usage forbids the SPDM role of the certificate.
+    ExtendedKeyUsage,
+    /// The hardware identity OID is missing on a device cert or present on an alias cert.
+    HardwareIdentity,
+    /// The leaf is a CA, or an alias chain intermediate is not.
+    BasicConstraints,
+    /// The key usage does not allow digitalSignature.
+    KeyUsage,
+    /// The leaf public key does not match the negotiated asymmetric algorithm.
+    PublicKeyAlgorithm,
+    /// The CRL signature is unsupported or does not verify with its issuer.
+    CrlSignature,
+    /// The CRL is not yet valid or has expired.
+    CrlValidity,
+    /// A certificate of the chain is listed in the CRL.
+    Revoked,
+}
+
+impl SpdmCertRule {
+    pub fn as_str(&self) -> &'static str {
+        use SpdmCertRule::*;
+        match *self {
+            Encoding => "certificate encoding",
+            ExtendedKeyUsage => "certificate extended key usage",
+            HardwareIdentity => "certificate hardware identity",
+            BasicConstraints => "certificate basic constraints",
+            KeyUsage => "certificate key usage",
+            PublicKeyAlgorithm => "certificate public key algorithm",
+            CrlSignature => "CRL signature",
+            CrlValidity => "CRL validity period",
+            Revoked => "certificate revoked",
+        }
+    }
+}
+
+/// A DER encoded X.501 Name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpdmCertName<'a> {
+    pub der: &'a [u8],
+}
+
+impl<'a> SpdmCertName<'a> {
+    /// The raw value of the first attribute with the given OID (DER contents).
+    pub fn attribute(&self, oid: &[u8]) -> Option<&'a [u8]> {
+        let mut rdns = DerReader::new(DerReader::new(self.der).read(TAG_SEQUENCE).ok()?);
+        while !rdns.is_empty() {
+            let mut rdn = DerReader::new(rdns.read(TAG_SET).ok()?);
+            while !rdn.is_empty() {
+                let mut attribute = DerReader::new(rdn.read(TAG_SEQUENCE).ok()?);
+                let attribute_oid = attribute.read(TAG_OID).ok()?;
+                let (_, value) = attribute.read_any().ok()?;
+                if attribute_oid == oid {
+                    return Some(value);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn common_name(&self) -> Option<&'a [u8]> {
+        self.attribute(OID_COMMON_NAME)
+    }
+}
+
+/// An entry of the SPDM certificate extension (id-DMTF-spdm-extension).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpdmCertOidInfo<'a> {
+    /// DER contents of the OID.
+    pub id: &'a [u8],
+    pub value: Option<&'a [u8]>,
+}
+
+/// A parsed view of one certificate, borrowing from its DER.
+#[derive(Debug, Clone)]
+pub struct SpdmCertInfo<'a> {
+    /// X.509 version, 1 to 3.
+    pub version: u8,
+    /// DER contents of the serial number INTEGER.
+    pub serial_number: &'a [u8],
+    pub issuer: SpdmCertName<'a>,
+    pub subject: SpdmCertName<'a>,
+    /// Validity period, in seconds since the UNIX epoch.
+    pub not_before: u64,
+    pub not_after: u64,
+    /// The asymmetric algorithms the public key can be used with.
+    pub public_key_algorithms: SpdmBaseAsymAlgo,
+    pub is_ca: bool,
+    /// SPDM extended key usages.
+    pub spdm_responder_auth: bool,
+    pub spdm_requester_auth: bool,
+    /// Entries of the SPDM certificate extension, empty if it is absent.
+    pub spdm_extension: Vec<SpdmCertOidInfo<'a>>,
+    /// The SPDM extension carries the hardware identity OID.
+    pub hardware_identity: bool,
+}
+
+/// Parse one DER certificate.
+pub fn get_cert_info(cert: &[u8]) -> SpdmResult<SpdmCertInfo<'_>> {
+    parse_cert_info(cert).map_err(|rule| spdm_err!(EINVAL, rule.as_str()))
+}
+
+fn parse_cert_info(cert_der: &[u8]) -> Result<SpdmCertInfo<'_>, SpdmCertRule> {
+    let cert = Certificate::parse(cert_der)?;
+
+    let mut validity = DerReader::new(cert.validity);
+    let (tag, not_before) = validity.read_any()?;
+    let not_before = parse_time(tag, not_before)?;
+    let (tag, not_after) = validity.read_any()?;
+    let not_after = parse_time(tag, not_after)?;
+
+    let (mut spdm_responder_auth, mut spdm_requester_auth) = (false, false);
+    if let Some(value) = cert.extension(OID_EXT_KEY_USAGE)? {
+        let mut purposes = DerReader::new(DerReader::new(value).read(TAG_SEQUENCE)?);
+        while !purposes.is_empty() {
+            let purpose = purposes.read(TAG_OID)?;
+            spdm_responder_auth |= purpose == OID_DMTF_EKU_RESPONDER_AUTH;
+            spdm_requester_auth |= purpose == OID_DMTF_EKU_REQUESTER_AUTH;
+        }
+    }
+
+    let spdm_extension = cert.spdm_extension()?;
+    let hardware_identity = spdm_extension
+        .iter()
+        .any(|entry| entry.id == OID_DMTF_HARDWARE_IDENTITY);
+
+    Ok(SpdmCertInfo {
+        version: cert.version,
+        serial_number: cert.serial_number,
+        issuer: SpdmCertName { der: cert.issuer },
+        subject: SpdmCertName { der: cert.subject },
+        not_before,
+        not_after,
+        public_key_algorithms: cert.public_key_algorithms()?,
+        is_ca: cert.is_ca()?,
+        spdm_responder_auth,
+        spdm_requester_auth,
+        spdm_extension,
+        hardware_identity,
+    })
+}
+
+/// Check a DER certificate chain (root first, leaf last) against the SPDM 1.2
+/// certificate model.
+///
+/// `is_requester_cert` selects the SPDM EKU expected on the leaf, and
+/// `base_asym_algo` is the negotiated asymmetric algorithm the leaf key must use.
+pub fn check_cert_chain(
+    cert_chain: &[u8],
+    cert_model: SpdmCertModel,
+    is_requester_cert: bool,
+    base_asym_algo: SpdmBaseAsymAlgo,
+) -> Result<(), SpdmCertRule> {
+    let mut cert_count = 0usize;
+    let mut leaf = None;
+    for cert in CertChainIter::new(cert_chain) {
+        let (_, cert) = cert?;
+        if let Some(previous) = leaf.replace(cert) {
+            // In the alias model the device cert and everything above the
+            // alias leaf issue certificates, so they must be CAs.
+            if cert_model == SpdmCertModel::AliasCert && cert_count > 1 && !previous.is_ca()? {
+                return Err(SpdmCertRule::BasicConstraints);
+            }
+        }
+        cert_count += 1;
+    }
+    let leaf = leaf.ok_or(SpdmCertRule::Encoding)?;
+    if cert_model == SpdmCertModel::AliasCert && cert_count < 2 {
+        return Err(SpdmCertRule::BasicConstraints);
+    }
+
+    if leaf.is_ca()? {
+        return Err(SpdmCertRule::BasicConstraints);
+    }
+    check_key_usage(&leaf)?;
+    check_extended_key_usage(&leaf, is_requester_cert)?;
+    check_hardware_identity(&leaf, cert_model)?;
+    check_public_key_algorithm(&leaf, base_asym_algo)
+}
+
+fn check_key_usage(leaf: &Certificate) -> Result<(), SpdmCertRule> {
+    if let Some(value) = leaf.extension(OID_KEY_USAGE)? {
+        let bits = DerReader::new(value).read(TAG_BIT_STRING)?;
+        // bits[0] is the count of unused bits, digitalSignature is bit 0.
+        if bits.len() < 2 || bits[1] & 0x80 == 0 {
+            return Err(SpdmCertRule::KeyUsage);
+        }
+    }
+    Ok(())
+}
+
+fn check_extended_key_usage(
+    leaf: &Certificate,
+    is_requester_cert: bool,
+) -> Result<(), SpdmCertRule> {
+    let value = match leaf.extension(OID_EXT_KEY_USAGE)? {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let mut purposes = DerReader::new(DerReader::new(value).read(TAG_SEQUENCE)?);
+    let mut responder_auth = false;
+    let mut requester_auth = false;
+    while !purposes.is_empty() {
+        let purpose = purposes.read(TAG_OID)?;
+        responder_auth |= purpose == OID_DMTF_EKU_RESPONDER_AUTH;
+        requester_auth |= purpose == OID_DMTF_EKU_REQUESTER_AUTH;
+    }
+    // Non-SPDM purposes are allowed, but an SPDM purpose must name this role.
+    let allowed = if is_requester_cert {
+        requester_auth || !responder_auth
+    } else {
+        responder_auth || !requester_auth
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(SpdmCertRule::ExtendedKeyUsage)
+    }
+}
+
+fn check_hardware_identity(
+    leaf: &Certificate,
+    cert_model: SpdmCertModel,
+) -> Result<(), SpdmCertRule> {
+    let has_hardware_identity = leaf
+        .spdm_extension()?
+        .iter()
+        .any(|entry| entry.id == OID_DMTF_HARDWARE_IDENTITY);
+    match (cert_model, has_hardware_identity) {
+        (SpdmCertModel::DeviceCert, false) | (SpdmCertModel::AliasCert, true) => {
+            Err(SpdmCertRule::HardwareIdentity)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_public_key_algorithm(
+    leaf: &Certificate,
+    base_asym_algo: SpdmBaseAsymAlgo,
+) -> Result<(), SpdmCertRule> {
+    if !base_asym_algo.is_empty()
+        && base_asym_algo.is_no_more_than_one_selected()
+        && leaf.public_key_algorithms()?.contains(base_asym_algo)
+    {
+        Ok(())
+    } else {
+        Err(SpdmCertRule::PublicKeyAlgorithm)
+    }
+}
+
+/// Check a DER certificate chain (root first, leaf last) against a DER CRL.
+///
+/// The CRL only applies when it is issued by one of the chain's certificates.
+/// It must then be signed by that certificate and current at `now` (seconds
+/// since the UNIX epoch), and must not list any certificate of the chain.
+pub fn check_cert_chain_revocation(
+    cert_chain: &[u8],
+    crl: &[u8],
+    now: u64,
+) -> Result<(), SpdmCertRule> {
+    let crl = Crl::parse(crl)?;
+    let mut issuer = None;
+    for cert in CertChainIter::new(cert_chain) {
+        let (cert_der, cert) = cert?;
+        if cert.subject == crl.issuer {
+            issuer = Some((cert_der, cert));
+        }
+    }
+    let (issuer_der, issuer) = match issuer {
+        Some(issuer) => issuer,
+        None => return Ok(()),
+    };
+    crl.verify_signature(issuer_der, &issuer)?;
+    if crl.this_update > now || matches!(crl.next_update, Some(next) if next <= now) {
+        return Err(SpdmCertRule::CrlValidity);
+    }
+
+    for cert in CertChainIter::new(cert_chain) {
+        let (_, cert) = cert?;
+        if cert.issuer == crl.issuer && crl.is_revoked(cert.serial_number)? {
+            return Err(SpdmCertRule::Revoked);
+        }
+    }
+    Ok(())
+}
+
+/// Iterates over the (DER, parsed) certificates of a chain.
+struct CertChainIter<'a> {
+    reader: DerReader<'a>,
+}
+
+impl<'a> CertChainIter<'a> {
+    fn new(cert_chain: &'a [u8]) -> Self {
+        CertChainIter {
+            reader: DerReader::new(cert_chain),
+        }
+    }
+}
+
+impl<'a> Iterator for CertChainIter<'a> {
+    type Item = Result<(&'a [u8], Certificate<'a>), SpdmCertRule>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.is_empty() {
+            return None;
+        }
+        let cert = self
+            .reader
+            .read_raw(TAG_SEQUENCE)
+            .and_then(|cert_der| Ok((cert_der, Certificate::parse(cert_der)?)));
+        if cert.is_err() {
+            // stop after the first malformed certificate
+            self.reader = DerReader::new(&[]);
+        }
+        Some(cert)
+    }
+}
+
+/// Size in bytes of the modulus of an RSAPublicKey bit string.
+fn rsa_modulus_size(public_key: &[u8]) -> Result<usize, SpdmCertRule> {
+    // skip the unused bits count
+    let key = public_key.get(1..).ok_or(SpdmCertRule::Encoding)?;
+    let mut rsa_key = DerReader::new(DerReader::new(key).read(TAG_SEQUENCE)?);
+    let modulus = rsa_key.read(TAG_INTEGER)?;
+    let leading_zeros = modulus.iter().take_while(|b| **b == 0).count();
+    Ok(modulus.len() - leading_zeros)
+}
+
+/// Strip the sign padding of a DER INTEGER.
+fn unsigned_integer(integer: &[u8]) -> &[u8] {
+    let leading_zeros = integer.iter().take_while(|b| **b == 0).count();
+    &integer[leading_zeros..]
+}
+
+/// Seconds since the UNIX epoch of a UTCTime or GeneralizedTime "...Z" value.
+fn parse_time(tag: u8, time: &[u8]) -> Result<u64, SpdmCertRule> {
+    let digits = match (tag, time.split_last()) {
+        (TAG_UTC_TIME, Some((b'Z', digits))) if digits.len() == 12 => digits,
+        (TAG_GENERALIZED_TIME, Some((b'Z', digits))) if digits.len() == 14 => digits,
+        _ => return Err(SpdmCertRule::Encoding),
+    };
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return Err(SpdmCertRule::Encoding);
+    }
+    let number = |digits: &[u8]| {
+        digits
+            .iter()
+            .fold(0u64, |value, digit| value * 10 + (digit - b'0') as u64)
+    };
+    let (year, rest) = if tag == TAG_UTC_TIME {
+        // RFC 5280: YY >= 50 is 19YY, otherwise 20YY
+        let year = number(&digits[..2]);
+        (
+            if year >= 50 { 1900 + year } else { 2000 + year },
+            &digits[2..],
+        )
+    } else {
+        (number(&digits[..4]), &digits[4..])
+    };
+    let (month, day) = (number(&rest[0..2]), number(&rest[2..4]));
+    let (hour, minute, second) = (
+        number(&rest[4..6]),
+        number(&rest[6..8]),
+        number(&rest[8..10]),
+    );
+    if year < 1970
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(SpdmCertRule::Encoding);
+    }
+
+    // days from 1970-01-01 to the start of the month, civil calendar
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era_days = 365 * y + y / 4 - y / 100 + y / 400 + (153 * m + 2) / 5;
+    let days = era_days + day - 1 - 719_468;
+    Ok(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// The parts of a CRL the revocation check looks at.
+struct Crl<'a> {
+    tbs_cert_list: &'a [u8],
+    signature_algorithm: &'a [u8],
+    signature: &'a [u8],
+    issuer: &'a [u8],
+    this_update: u64,
+    next_update: Option<u64>,
+    revoked_certificates: &'a [u8],
+}
+
+impl<'a> Crl<'a> {
+    fn parse(crl: &'a [u8]) -> Result<Self, SpdmCertRule> {
+        let mut crl = DerReader::new(DerReader::new(crl).read(TAG_SEQUENCE)?);
+        let tbs_cert_list = crl.read_raw(TAG_SEQUENCE)?;
+        let signature_algorithm = DerReader::new(crl.read(TAG_SEQUENCE)?).read(TAG_OID)?;
+        let signature = crl.read(TAG_BIT_STRING)?;
+
+        let mut tbs = DerReader::new(DerReader::new(tbs_cert_list).read(TAG_SEQUENCE)?);
+        if tbs.peek_tag() == Some(TAG_INTEGER) {
+            tbs.read(TAG_INTEGER)?; // version
+        }
+        tbs.read(TAG_SEQUENCE)?; // signature
+        let issuer = tbs.read_raw(TAG_SEQUENCE)?;
+        let (tag, time) = tbs.read_any()?;
+        let this_update = parse_time(tag, time)?;
+        let mut next_update = None;
+        if let Some(tag @ (TAG_UTC_TIME | TAG_GENERALIZED_TIME)) = tbs.peek_tag() {
+            next_update = Some(parse_time(tag, tbs.read(tag)?)?);
+        }
+        let mut revoked_certificates: &[u8] = &[];
+        if tbs.peek_tag() == Some(TAG_SEQUENCE) {
+            revoked_certificates = tbs.read(TAG_SEQUENCE)?;
+        }
+        Ok(Crl {
+            tbs_cert_list,
+            signature_algorithm,
+            signature,
+            issuer,
+            this_update,
+            next_update,
+            revoked_certificates,
+        })
+    }
+
+    fn is_revoked(&self, serial_number: &[u8]) -> Result<bool, SpdmCertRule> {
+        let mut revoked_certificates = DerReader::new(self.revoked_certificates);
+        while !revoked_certificates.is_empty() {
+            let mut entry = DerReader::new(revoked_certificates.read(TAG_SEQUENCE)?);
+            if entry.read(TAG_INTEGER)? == serial_number {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Verify the CRL signature with the registered asym_verify backend.
+    fn verify_signature(
+        &self,
+        issuer_der: &[u8],
+        issuer: &Certificate,
+    ) -> Result<(), SpdmCertRule> {
+        let (base_hash_algo, is_ecdsa) = match self.signature_algorithm {
+            OID_ECDSA_WITH_SHA256 => (SpdmBaseHashAlgo::TPM_ALG_SHA_256, true),
+            OID_ECDSA_WITH_SHA384 => (SpdmBaseHashAlgo::TPM_ALG_SHA_384, true),
+            OID_SHA256_WITH_RSA_ENCRYPTION => (SpdmBaseHashAlgo::TPM_ALG_SHA_256, false),
+            OID_SHA384_WITH_RSA_ENCRYPTION => (SpdmBaseHashAlgo::TPM_ALG_SHA_384, false),
+            OID_SHA512_WITH_RSA_ENCRYPTION => (SpdmBaseHashAlgo::TPM_ALG_SHA_512, false),
+            _ => return Err(SpdmCertRule::CrlSignature),
+        };
+        let key_algorithms = issuer.public_key_algorithms()?;
+        let base_asym_algo = if is_ecdsa {
+            key_algorithms
+                & (SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256
+                    | SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384)
+        } else {
+            key_algorithms
+                & (SpdmBaseAsymAlgo::TPM_ALG_RSASSA_2048
+                    | SpdmBaseAsymAlgo::TPM_ALG_RSASSA_3072
+                    | SpdmBaseAsymAlgo::TPM_ALG_RSASSA_4096)
+        };
+        if base_asym_algo.is_empty() {
+            return Err(SpdmCertRule::CrlSignature);
+        }
+
+        // skip the unused bits count
+        let signature_value = self.signature.get(1..).ok_or(SpdmCertRule::Encoding)?;
+        let mut signature = SpdmSignatureStruct {
+            data_size: base_asym_algo.get_size(),
+            ..Default::default()
+        };
+        if is_ecdsa {
+            // ECDSA-Sig-Value ::= SEQUENCE { r INTEGER, s INTEGER }, to r || s
+            let mut ecdsa_sig = DerReader::new(DerReader::new(signature_value).read(TAG_SEQUENCE)?);
+            let half = signature.data_size as usize / 2;
+            for (i, integer) in [ecdsa_sig.read(TAG_INTEGER)?, ecdsa_sig.read(TAG_INTEGER)?]
+                .iter()
+                .enumerate()
+            {
+                let integer = unsigned_integer(integer);
+                if integer.len() > half {
+                    return Err(SpdmCertRule::CrlSignature);
+                }
+                signature.data[(i + 1) * half - integer.len()..(i + 1) * half]
+                    .copy_from_slice(integer);
+            }
+        } else {
+            if signature_value.len() != signature.data_size as usize {
+                return Err(SpdmCertRule::CrlSignature);
+            }
+            signature.data[..signature_value.len()].copy_from_slice(signature_value);
+        }
+
+        crypto::asym_verify::verify(
+            base_hash_algo,
+            base_asym_algo,
+            issuer_der,
+            self.tbs_cert_list,
+            &signature,
+        )
+        .map_err(|_| SpdmCertRule::CrlSignature)
+    }
+}
+
+/// The parts of a certificate the SPDM rules look at.
+struct Certificate<'a> {
+    version: u8,
+    serial_number: &'a [u8],
+    issuer: &'a [u8],
+    validity: &'a [u8],
+    subject: &'a [u8],
+    subject_public_key_info: &'a [u8],
+    extensions: Option<&'a [u8]>,
+}
+
+impl<'a> Certificate<'a> {
+    fn parse(cert: &'a [u8]) -> Result<Self, SpdmCertRule> {
+        let mut cert = DerReader::new(DerReader::new(cert).read(TAG_SEQUENCE)?);
+        let mut tbs = DerReader::new(cert.read(TAG_SEQUENCE)?);
+        // version is v1 (0) when absent
+        let mut version = 1;
+        if tbs.peek_tag() == Some(TAG_VERSION) {
+            let value = DerReader::new(tbs.read(TAG_VERSION)?).read(TAG_INTEGER)?;
+            version = match value {
+                [v @ 0..=2] => v + 1,
+                _ => return Err(SpdmCertRule::Encoding),
+            };
+        }
+        let serial_number = tbs.read(TAG_INTEGER)?;
+        tbs.read(TAG_SEQUENCE)?; // signature
+        let issuer = tbs.read_raw(TAG_SEQUENCE)?;
+        let validity = tbs.read(TAG_SEQUENCE)?;
+        let subject = tbs.read_raw(TAG_SEQUENCE)?;
+        let subject_public_key_info = tbs.read(TAG_SEQUENCE)?;
+        let mut extensions = None;
+        while !tbs.is_empty() {
+            // skip issuerUniqueID and subjectUniqueID
+            let (tag, value) = tbs.read_any()?;
+            if tag == TAG_EXTENSIONS {
+                extensions = Some(DerReader::new(value).read(TAG_SEQUENCE)?);
+            }
+        }
+        Ok(Certificate {
+            version,
+            serial_number,
+            issuer,
+            validity,
+            subject,
+            subject_public_key_info,
+            extensions,
+        })
+    }
+
+    /// The extnValue of the extension with the given OID, if present.
+    fn extension(&self, oid: &[u8]) -> Result<Option<&'a [u8]>, SpdmCertRule> {
+        let mut extensions = DerReader::new(self.extensions.unwrap_or(&[]));
+        while !extensions.is_empty() {
+            let mut extension = DerReader::new(extensions.read(TAG_SEQUENCE)?);
+            let extension_oid = extension.read(TAG_OID)?;
+            if extension.peek_tag() == Some(TAG_BOOLEAN) {
+                extension.read(TAG_BOOLEAN)?; // critical
+            }
+            let value = extension.read(TAG_OCTET_STRING)?;
+            if extension_oid == oid {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The entries of the SPDM extension, empty if it is absent.
+    fn spdm_extension(&self) -> Result<Vec<SpdmCertOidInfo<'a>>, SpdmCertRule> {
+        let mut spdm_extension = Vec::new();
+        if let Some(value) = self.extension(OID_DMTF_SPDM_EXTENSION)? {
+            // SEQUENCE OF SEQUENCE { id OID, value OCTET STRING OPTIONAL }
+            let mut entries = DerReader::new(DerReader::new(value).read(TAG_SEQUENCE)?);
+            while !entries.is_empty() {
+                let mut entry = DerReader::new(entries.read(TAG_SEQUENCE)?);
+                let id = entry.read(TAG_OID)?;
+                let value = if entry.is_empty() {
+                    None
+                } else {
+                    Some(entry.read(TAG_OCTET_STRING)?)
+                };
+                spdm_extension.push(SpdmCertOidInfo { id, value });
+            }
+        }
+        Ok(spdm_extension)
+    }
+
+    /// The asymmetric algorithms the subject public key can be used with.
+    fn public_key_algorithms(&self) -> Result<SpdmBaseAsymAlgo, SpdmCertRule> {
+        let mut spki = DerReader::new(self.subject_public_key_info);
+        let mut algorithm = DerReader::new(spki.read(TAG_SEQUENCE)?);
+        let algorithm_oid = algorithm.read(TAG_OID)?;
+        let public_key = spki.read(TAG_BIT_STRING)?;
+
+        if algorithm_oid == OID_EC_PUBLIC_KEY {
+            return Ok(match algorithm.read(TAG_OID)? {
+                OID_CURVE_P256 => SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256,
+                OID_CURVE_P384 => SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+                OID_CURVE_P521 => SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P521,
+                _ => SpdmBaseAsymAlgo::empty(),
+            });
+        }
+        if algorithm_oid != OID_RSA_ENCRYPTION && algorithm_oid != OID_RSASSA_PSS {
+            return Ok(SpdmBaseAsymAlgo::empty());
+        }
+        let (rsassa, rsapss) = match rsa_modulus_size(public_key)? {
+            256 => (
+                SpdmBaseAsymAlgo::TPM_ALG_RSASSA_2048,
+                SpdmBaseAsymAlgo::TPM_ALG_RSAPSS_2048,
+            ),
+            384 => (
+                SpdmBaseAsymAlgo::TPM_ALG_RSASSA_3072,
+                SpdmBaseAsymAlgo::TPM_ALG_RSAPSS_3072,
+            ),
+            512 => (
+                SpdmBaseAsymAlgo::TPM_ALG_RSASSA_4096,
+                SpdmBaseAsymAlgo::TPM_ALG_RSAPSS_4096,
+            ),
+            _ => return Ok(SpdmBaseAsymAlgo::empty()),
+        };
+        // an RSASSA-PSS key is restricted to PSS signatures
+        if algorithm_oid == OID_RSASSA_PSS {
+            Ok(rsapss)
+        } else {
+            Ok(rsassa | rsapss)
+        }
+    }
+
+    fn is_ca(&self) -> Result<bool, SpdmCertRule> {
+        let value = match self.extension(OID_BASIC_CONSTRAINTS)? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        let mut constraints = DerReader::new(DerReader::new(value).read(TAG_SEQUENCE)?);
+        if constraints.peek_tag() == Some(TAG_BOOLEAN) {
+            let ca = constraints.read(TAG_BOOLEAN)?;
+            return Ok(ca.iter().any(|v| *v != 0));
+        }
+        Ok(false)
+    }
+}
+
+/// Minimal DER TLV reader, enough for the certificate fields above.
+struct DerReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> DerReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        DerReader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Read one TLV and return (tag, header length, value length).
+    fn read_header(&self) -> Result<(u8, usize, usize), SpdmCertRule> {
+        let tag = *self.data.first().ok_or(SpdmCertRule::Encoding)?;
+        let first = *self.data.get(1).ok_or(SpdmCertRule::Encoding)?;
+        if first & 0x80 == 0 {
+            return Ok((tag, 2, first as usize));
+        }
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 {
+            return Err(SpdmCertRule::Encoding);
+        }
+        let bytes = self.data.get(2..2 + count).ok_or(SpdmCertRule::Encoding)?;
+        let len = bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+        Ok((tag, 2 + count, len))
+    }
+
+    fn read_tlv(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), SpdmCertRule> {
+        let (tag, header_len, value_len) = self.read_header()?;
+        let end = header_len
+            .checked_add(value_len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(SpdmCertRule::Encoding)?;
+        let raw = &self.data[..end];
+        let value = &self.data[header_len..end];
+        self.data = &self.data[end..];
+        Ok((tag, raw, value))
+    }
+
+    fn read_any(&mut self) -> Result<(u8, &'a [u8]), SpdmCertRule> {
+        let (tag, _, value) = self.read_tlv()?;
+        Ok((tag, value))
+    }
+
+    fn read(&mut self, expected_tag: u8) -> Result<&'a [u8], SpdmCertRule> {
+        match self.read_tlv()? {
+            (tag, _, value) if tag == expected_tag => Ok(value),
+            _ => Err(SpdmCertRule::Encoding),
+        }
+    }
+
+    /// Like `read`, but keeps the tag and length.
+    fn read_raw(&mut self, expected_tag: u8) -> Result<&'a [u8], SpdmCertRule> {
+        match self.read_tlv()? {
+            (tag, raw, _) if tag == expected_tag => Ok(raw),
+            _ => Err(SpdmCertRule::Encoding),
+        }
+    }
+}
+
+#[cfg(all(test,))]
+mod tests {
+    extern crate alloc;
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    const CHAIN: &[&[u8]] = &[
+        include_bytes!("../../../test_key/EcP384/ca.cert.der"),
+        include_bytes!("../../../test_key/EcP384/inter.cert.der"),
+        include_bytes!("../../../test_key/EcP384/end_responder.cert.der"),
+    ];
+
+    fn cert_chain(certs: &[&[u8]]) -> Vec<u8> {
+        certs.iter().flat_map(|c| c.iter().copied()).collect()
+    }
+
+    #[test]
+    fn test_case0_check_cert_chain() {
+        let chain = cert_chain(CHAIN);
+        assert_eq!(
+            check_cert_chain(
+                &chain,
+                SpdmCertModel::DeviceCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+            ),
+            Ok(())
+        );
+        // the leaf carries the hardware identity, so it is no alias cert
+        assert_eq!(
+            check_cert_chain(
+                &chain,
+                SpdmCertModel::AliasCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+            ),
+            Err(SpdmCertRule::HardwareIdentity)
+        );
+    }
+
+    #[test]
+    fn test_case1_check_cert_chain_algorithm() {
+        let chain = cert_chain(CHAIN);
+        for base_asym_algo in [
+            SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256,
+            SpdmBaseAsymAlgo::TPM_ALG_RSASSA_3072,
+            SpdmBaseAsymAlgo::empty(),
+        ] {
+            assert_eq!(
+                check_cert_chain(&chain, SpdmCertModel::DeviceCert, false, base_asym_algo),
+                Err(SpdmCertRule::PublicKeyAlgorithm)
+            );
+        }
+
+        let chain = cert_chain(&[
+            include_bytes!("../../../test_key/Rsa3072/ca.cert.der"),
+            include_bytes!("../../../test_key/Rsa3072/inter.cert.der"),
+            include_bytes!("../../../test_key/Rsa3072/end_responder.cert.der"),
+        ]);
+        assert!(check_cert_chain(
+            &chain,
+            SpdmCertModel::DeviceCert,
+            false,
+            SpdmBaseAsymAlgo::TPM_ALG_RSAPSS_3072
+        )
+        .is_ok());
+        assert_eq!(
+            check_cert_chain(
+                &chain,
+                SpdmCertModel::DeviceCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_RSASSA_2048
+            ),
+            Err(SpdmCertRule::PublicKeyAlgorithm)
+        );
+    }
+
+    #[test]
+    fn test_case2_check_cert_chain_basic_constraints() {
+        // the leaf of a CA-only chain is a CA
+        let chain = cert_chain(&CHAIN[..2]);
+        assert_eq!(
+            check_cert_chain(
+                &chain,
+                SpdmCertModel::DeviceCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+            ),
+            Err(SpdmCertRule::BasicConstraints)
+        );
+
+        // an alias chain needs a CA device cert above the leaf
+        let chain = cert_chain(&[CHAIN[0], CHAIN[2], CHAIN[2]]);
+        assert_eq!(
+            check_cert_chain(
+                &chain,
+                SpdmCertModel::AliasCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+            ),
+            Err(SpdmCertRule::BasicConstraints)
+        );
+        assert!(check_cert_chain(
+            &chain,
+            SpdmCertModel::DeviceCert,
+            false,
+            SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_case3_check_cert_chain_encoding() {
+        let mut chain = cert_chain(CHAIN);
+        chain.truncate(chain.len() - 1);
+        assert_eq!(
+            check_cert_chain(
+                &chain,
+                SpdmCertModel::DeviceCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+            ),
+            Err(SpdmCertRule::Encoding)
+        );
+        assert_eq!(
+            check_cert_chain(
+                &[],
+                SpdmCertModel::DeviceCert,
+                false,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+            ),
+            Err(SpdmCertRule::Encoding)
+        );
+    }
+
+    #[test]
+    fn test_case4_check_extended_key_usage() {
+        // a leaf restricted to SPDM requester authentication
+        let ext_key_usage = [
+            0x30, 0x0C, 0x06, 0x0A, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x04,
+        ];
+        let mut extension = vec![0x30, 0x15, 0x06, 0x03, 0x55, 0x1D, 0x25, 0x04, 0x0E];
+        extension.extend_from_slice(&ext_key_usage);
+        let leaf = Certificate {
+            version: 3,
+            serial_number: &[],
+            issuer: &[],
+            validity: &[],
+            subject: &[],
+            subject_public_key_info: &[],
+            extensions: Some(&extension),
+        };
+        assert_eq!(
+            check_extended_key_usage(&leaf, false),
+            Err(SpdmCertRule::ExtendedKeyUsage)
+        );
+        assert_eq!(check_extended_key_usage(&leaf, true), Ok(()));
+    }
+
+    #[test]
+    fn test_case5_parse_time() {
+        assert_eq!(parse_time(TAG_UTC_TIME, b"700101000000Z"), Ok(0));
+        assert_eq!(parse_time(TAG_UTC_TIME, b"000229120000Z"), Ok(951_825_600));
+        assert_eq!(
+            parse_time(TAG_GENERALIZED_TIME, b"20380119031408Z"),
+            Ok(2_147_483_648)
+        );
+        assert!(parse_time(TAG_UTC_TIME, b"700101000000").is_err());
+        assert!(parse_time(TAG_GENERALIZED_TIME, b"700101000000Z").is_err());
+        assert!(parse_time(TAG_UTC_TIME, b"701301000000Z").is_err());
+        assert!(parse_time(TAG_UTC_TIME, b"700101240000Z").is_err());
+        assert!(parse_time(TAG_UTC_TIME, b"700101006000Z").is_err());
+        assert!(parse_time(TAG_UTC_TIME, b"700101000060Z").is_err());
+        assert_eq!(parse_time(TAG_UTC_TIME, b"700101235959Z"), Ok(86_399));
+    }
+
+    #[test]
+    fn test_case6_check_cert_chain_revocation() {
+        let chain = cert_chain(CHAIN);
+        // both CRLs are issued by the intermediate, valid from 2026-10-18 to 2036-10-15
+        let empty_crl = &include_bytes!("../../../test_key/EcP384/inter_empty.crl.der")[..];
+        let revoked_crl =
+            &include_bytes!("../../../test_key/EcP384/inter_revoked_responder.crl.der")[..];
+        let now = 1_800_000_000;
+
+        assert_eq!(check_cert_chain_revocation(&chain, empty_crl, now), Ok(()));
+        assert_eq!(
+            check_cert_chain_revocation(&chain, revoked_crl, now),
+            Err(SpdmCertRule::Revoked)
+        );
+        assert_eq!(
+            check_cert_chain_revocation(&chain, revoked_crl, 1_700_000_000),
+            Err(SpdmCertRule::CrlValidity)
+        );
+        assert_eq!(
+            check_cert_chain_revocation(&chain, revoked_crl, 2_200_000_000),
+            Err(SpdmCertRule::CrlValidity)
+        );
+
+        // a CRL from outside the chain does not apply
+        assert_eq!(
+            check_cert_chain_revocation(&cert_chain(&CHAIN[..1]), revoked_crl, now),
+            Ok(())
+        );
+
+        let mut tampered_crl = revoked_crl.to_vec();
+        let last = tampered_crl.len() - 1;
+        tampered_crl[last] ^= 0x01;
+        assert_eq!(
+            check_cert_chain_revocation(&chain, &tampered_crl, now),
+            Err(SpdmCertRule::CrlSignature)
+        );
+    }
+
+    #[test]
+    fn test_case7_get_cert_info() {
+        let info = get_cert_info(CHAIN[2]).unwrap();
+        assert_eq!(info.version, 3);
+        assert_eq!(info.serial_number, &[0x03]);
+        assert_eq!(
+            info.subject.common_name(),
+            Some(&b"intel test ECP256 responder cert"[..])
+        );
+        assert_eq!(
+            info.issuer.common_name(),
+            Some(&b"intel test ECP256 intermediate cert"[..])
+        );
+        assert_eq!(info.not_before, 1_641_362_914);
+        assert_eq!(info.not_after, 1_956_722_914);
+        assert_eq!(
+            info.public_key_algorithms,
+            SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+        );
+        assert!(!info.is_ca);
+        assert!(!info.spdm_responder_auth && !info.spdm_requester_auth);
+        assert_eq!(
+            info.spdm_extension,
+            vec![SpdmCertOidInfo {
+                id: OID_DMTF_HARDWARE_IDENTITY,
+                value: None
+            }]
+        );
+        assert!(info.hardware_identity);
+
+        let info = get_cert_info(CHAIN[1]).unwrap();
+        assert!(info.is_ca);
+        assert_eq!(info.subject, get_cert_info(CHAIN[2]).unwrap().issuer);
+
+        assert!(get_cert_info(&CHAIN[2][..100]).is_err());
+    }
+
+    fn spdm_extension(entries: &[u8]) -> Vec<u8> {
+        let mut value = vec![0x30, entries.len() as u8];
+        value.extend_from_slice(entries);
+        let mut extension = vec![0x30, (14 + value.len()) as u8, 0x06, 0x0A];
+        extension.extend_from_slice(OID_DMTF_SPDM_EXTENSION);
+        extension.extend_from_slice(&[0x04, value.len() as u8]);
+        extension.extend_from_slice(&value);
+        extension
+    }
+
+    #[test]
+    fn test_case8_check_hardware_identity() {
+        let hardware_identity = [
+            0x06, 0x0A, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x02,
+        ];
+        let mut entry = vec![0x30, 0x0C];
+        entry.extend_from_slice(&hardware_identity);
+        // the OID in the value of another entry is no hardware identity
+        let mut other_entry = vec![
+            0x30, 0x1A, 0x06, 0x0A, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12, 0x01,
+            0x04, 0x0C,
+        ];
+        other_entry.extend_from_slice(&hardware_identity);
+
+        for (extensions, has_hardware_identity) in [
+            (None, false),
+            (Some(spdm_extension(&other_entry)), false),
+            (Some(spdm_extension(&entry)), true),
+        ] {
+            let leaf = Certificate {
+                version: 3,
+                serial_number: &[],
+                issuer: &[],
+                validity: &[],
+                subject: &[],
+                subject_public_key_info: &[],
+                extensions: extensions.as_deref(),
+            };
+            let expected = |valid: bool| {
+                if valid {
+                    Ok(())
+                } else {
+                    Err(SpdmCertRule::HardwareIdentity)
+                }
+            };
+            assert_eq!(
+                check_hardware_identity(&leaf, SpdmCertModel::DeviceCert),
+                expected(has_hardware_identity)
+            );
+            assert_eq!(
+                check_hardware_identity(&leaf, SpdmCertModel::AliasCert),
+                expected(!has_hardware_identity)
+            );
+        }
+    }
+}
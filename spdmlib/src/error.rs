@@ -4,6 +4,8 @@
 
 use core::fmt::{Debug, Formatter, Result};
 
+use crate::message::SpdmErrorResponsePayload;
+
 /// POSIX errno + custom errno(bigger than 0xFFFF)
 /// https://github.com/torvalds/linux/blob/master/include/uapi/asm-generic/errno.h
 /// https://github.com/torvalds/linux/blob/master/include/uapi/asm-generic/errno-base.h
@@ -24,12 +26,32 @@ pub enum SpdmErrorNum {
     EINVAL = 22,
     ERANGE = 34,
     ENOSYS = 38,
-    ESEC = 0xFFFF + 1, //Security violation was observed
-    EDEV = 0xFFFF + 2, //Device error
+    ESEC = 0xFFFF + 1,       //Security violation was observed
+    EDEV = 0xFFFF + 2,       //Device error
+    EPOLICY = 0xFFFF + 3,    //Negotiated state rejected by the local policy
+    ETRANSPORT = 0xFFFF + 4, //Device IO failed to send or receive a message
+}
+
+/// Where an error comes from, so callers can tell a local mistake from a
+/// failing link, a failed verification or an ERROR response of the peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpdmErrorKind {
+    /// Invalid argument, state or resource on this side, including a peer
+    /// message that is malformed (EIO) or not expected in this state.
+    Local,
+    /// The device IO failed to send or receive a message (ETRANSPORT).
+    Transport,
+    /// A crypto operation or a signature, MAC or certificate check failed.
+    Crypto,
+    /// The negotiated state was rejected by the local policy.
+    Policy,
+    /// The peer answered with an SPDM ERROR message.
+    Peer(SpdmErrorResponsePayload),
 }
 
 pub struct SpdmError {
     pub num: SpdmErrorNum,
+    pub kind: SpdmErrorKind,
     pub loc_file: &'static str,
     pub loc_line: u32,
     pub loc_col: u32,
@@ -57,6 +79,19 @@ impl SpdmErrorNum {
             ENOSYS => "Function not implemented",
             ESEC => "Security violation",
             EDEV => "Device error",
+            EPOLICY => "Policy violation",
+            ETRANSPORT => "Transport error",
+        }
+    }
+
+    /// The kind of an error that is not a peer ERROR response.
+    pub fn kind(&self) -> SpdmErrorKind {
+        use SpdmErrorNum::*;
+        match *self {
+            ETRANSPORT => SpdmErrorKind::Transport,
+            ESEC => SpdmErrorKind::Crypto,
+            EPOLICY => SpdmErrorKind::Policy,
+            _ => SpdmErrorKind::Local,
         }
     }
 }
@@ -70,6 +105,7 @@ impl SpdmError {
         msg: &'static str,
     ) -> Self {
         Self {
+            kind: num.kind(),
             num,
             loc_file,
             loc_line,
@@ -78,9 +114,23 @@ impl SpdmError {
         }
     }
 
+    /// Replace the kind derived from the error number.
+    pub fn with_kind(mut self, kind: SpdmErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     pub fn code(&self) -> i32 {
         -(self.num.clone() as u32 as i32)
     }
+
+    /// The ERROR response of the peer, if this error is one.
+    pub fn peer_error(&self) -> Option<&SpdmErrorResponsePayload> {
+        match &self.kind {
+            SpdmErrorKind::Peer(payload) => Some(payload),
+            _ => None,
+        }
+    }
 }
 
 impl Debug for SpdmError {
@@ -94,6 +144,9 @@ impl Debug for SpdmError {
             self.num.as_str(),
             self.msg
         )?;
+        if let SpdmErrorKind::Peer(payload) = &self.kind {
+            write!(f, " ({:?}, {:#x})", payload.error_code, payload.error_data)?;
+        }
         Ok(())
     }
 }
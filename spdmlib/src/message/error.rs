@@ -153,7 +153,7 @@ impl Default for SpdmErrorResponseExtData {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SpdmErrorResponsePayload {
     pub error_code: SpdmErrorCode,
     pub error_data: u8,
@@ -139,8 +139,8 @@ impl Codec for SpdmMessageGeneralPayload {
 
 impl SpdmCodec for SpdmMessageGeneralPayload {
     fn spdm_encode(&self, _context: &mut SpdmContext, bytes: &mut Writer) {
-        0u8.encode(bytes); // param1
-        0u8.encode(bytes); // param2
+        self.param1.encode(bytes); // param1
+        self.param2.encode(bytes); // param2
     }
 
     fn spdm_read(_context: &mut SpdmContext, r: &mut Reader) -> Option<SpdmMessageGeneralPayload> {
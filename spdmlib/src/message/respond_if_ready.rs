@@ -47,6 +47,14 @@ impl SpdmCodec for SpdmRespondIfReadyRespondPayload {
     }
 }
 
+/// What answers a request once the responder is ready: an SPDM response, or
+/// for an application message an application message of the same type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpdmExpectedResponse {
+    Spdm(super::SpdmRequestResponseCode),
+    App(u8),
+}
+
 #[derive(Debug, Clone)]
 pub struct ReceivedMessage {
     pub receive_buffer: [u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE],
@@ -548,6 +548,34 @@ impl Codec for SpdmReqAsymAlgo {
     }
 }
 
+macro_rules! impl_prioritize_with {
+    ($($algo:ident),+) => {
+        $(
+            impl $algo {
+                /// Like `prioritize`, but the first entry of `priority` supported by both sides wins.
+                pub fn prioritize_with(&mut self, peer: $algo, priority: &[$algo]) {
+                    *self &= peer;
+                    for v in priority.iter() {
+                        if v.bits().count_ones() == 1 && self.contains(*v) {
+                            *self = *v;
+                            return;
+                        }
+                    }
+                    self.prioritize(peer);
+                }
+            }
+        )+
+    };
+}
+
+impl_prioritize_with!(
+    SpdmBaseAsymAlgo,
+    SpdmBaseHashAlgo,
+    SpdmDheAlgo,
+    SpdmAeadAlgo,
+    SpdmReqAsymAlgo
+);
+
 bitflags! {
     #[derive(Default)]
     pub struct SpdmKeyScheduleAlgo: u16 {
@@ -1620,4 +1648,39 @@ mod tests {
         let mut reader = Reader::init(u8_slice);
         SpdmUnknownAlgo::read(&mut reader);
     }
+    #[test]
+    fn test_case0_prioritize_with() {
+        let supported = SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256
+            | SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+
+        let mut sel = supported;
+        sel.prioritize_with(supported, &[]);
+        assert_eq!(sel, SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384);
+
+        let mut sel = supported;
+        sel.prioritize_with(
+            supported,
+            &[
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+            ],
+        );
+        assert_eq!(sel, SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256);
+
+        // entries not supported by both sides, or not a single algorithm, are skipped
+        let mut sel = SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+        sel.prioritize_with(
+            supported,
+            &[
+                supported,
+                SpdmBaseAsymAlgo::TPM_ALG_RSASSA_3072,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256,
+            ],
+        );
+        assert_eq!(sel, SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384);
+
+        let mut sel = SpdmDheAlgo::SECP_256_R1 | SpdmDheAlgo::FFDHE_3072;
+        sel.prioritize_with(SpdmDheAlgo::SECP_384_R1, &[SpdmDheAlgo::SECP_384_R1]);
+        assert!(sel.is_empty());
+    }
 }
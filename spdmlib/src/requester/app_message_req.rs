@@ -0,0 +1,509 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use crate::common::SpdmCodec;
+use crate::error::{spdm_result_err, SpdmResult};
+use crate::message::*;
+use crate::requester::*;
+
+impl<'a> RequesterContext<'a> {
+    /// Send the application message `request` of the transport message type
+    /// `app_type` (the MCTP message type for MCTP) in the session, and receive
+    /// its response into `response`.
+    ///
+    /// Application messages of other types received meanwhile are dropped and
+    /// a KEY_UPDATE of the responder is acknowledged. An ERROR ends the exchange
+    /// with a peer error, unless the responder was not ready and answers later.
+    pub fn send_receive_app_message(
+        &mut self,
+        session_id: u32,
+        app_type: u8,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> SpdmResult<usize> {
+        info!("send app message {:02x}\n", app_type);
+        self.update_key_if_due(session_id)?;
+        self.send_secured_app_request(session_id, request, app_type)?;
+        let request_code = self.last_request.request_code();
+
+        let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        loop {
+            let (used, received_app_type) =
+                self.receive_with_retry(Some(session_id), &mut receive_buffer)?;
+            let used = match received_app_type {
+                Some(received_app_type) if received_app_type == app_type => used,
+                Some(received_app_type) => {
+                    info!("drop app message {:02x}\n", received_app_type);
+                    continue;
+                }
+                None => {
+                    let spdm_message = &receive_buffer[..used];
+                    match self.handle_spdm_message_in_app_exchange(
+                        session_id,
+                        app_type,
+                        request_code,
+                        spdm_message,
+                    )? {
+                        Some(received_message) => {
+                            receive_buffer = received_message.receive_buffer;
+                            received_message.used
+                        }
+                        None => continue,
+                    }
+                }
+            };
+            if response.len() < used {
+                return spdm_result_err!(EINVAL);
+            }
+            response[..used].copy_from_slice(&receive_buffer[..used]);
+            return Ok(used);
+        }
+    }
+
+    /// Handle an SPDM message received instead of the application message
+    /// response, the response if the message was an ERROR the responder
+    /// answered later.
+    fn handle_spdm_message_in_app_exchange(
+        &mut self,
+        session_id: u32,
+        app_type: u8,
+        request_code: SpdmRequestResponseCode,
+        bytes: &[u8],
+    ) -> SpdmResult<Option<ReceivedMessage>> {
+        let mut reader = Reader::init(bytes);
+        let message_header = match SpdmMessageHeader::read(&mut reader) {
+            Some(message_header)
+                if message_header.version == self.common.negotiate_info.spdm_version_sel =>
+            {
+                message_header
+            }
+            _ => return spdm_result_err!(EFAULT),
+        };
+        match message_header.request_response_code {
+            SpdmRequestResponseCode::SpdmResponseError => self
+                .spdm_handle_error_response(
+                    Some(session_id),
+                    bytes,
+                    request_code,
+                    SpdmExpectedResponse::App(app_type),
+                )
+                .map(Some),
+            SpdmRequestResponseCode::SpdmRequestKeyUpdate => {
+                self.handle_spdm_key_update_in_app_exchange(session_id, &mut reader)?;
+                Ok(None)
+            }
+            _ => {
+                error!("!!! unexpected spdm message in app exchange !!!\n");
+                spdm_result_err!(EFAULT)
+            }
+        }
+    }
+
+    /// Update the keys of the responder as its KEY_UPDATE asks, and send the
+    /// KEY_UPDATE_ACK in the session.
+    fn handle_spdm_key_update_in_app_exchange(
+        &mut self,
+        session_id: u32,
+        reader: &mut Reader,
+    ) -> SpdmResult {
+        let key_update_req = match SpdmKeyUpdateRequestPayload::spdm_read(&mut self.common, reader)
+        {
+            Some(key_update_req) => key_update_req,
+            None => {
+                error!("!!! key_update req : fail !!!\n");
+                return spdm_result_err!(EFAULT);
+            }
+        };
+        debug!("!!! key_update req : {:02x?}\n", key_update_req);
+
+        let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
+        let session = match self.common.get_session_via_id(session_id) {
+            Some(session) => session,
+            None => return spdm_result_err!(EFAULT),
+        };
+        match key_update_req.key_update_operation {
+            SpdmKeyUpdateOperation::SpdmUpdateSingleKey => {
+                session.create_data_secret_update(spdm_version_sel, false, true)?;
+            }
+            SpdmKeyUpdateOperation::SpdmUpdateAllKeys => {
+                session.create_data_secret_update(spdm_version_sel, true, true)?;
+                session.activate_data_secret_update(spdm_version_sel, true, true, true)?;
+            }
+            SpdmKeyUpdateOperation::SpdmVerifyNewKey => {
+                session.activate_data_secret_update(spdm_version_sel, false, true, true)?;
+            }
+            _ => return spdm_result_err!(EFAULT),
+        }
+
+        let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let mut writer = Writer::init(&mut send_buffer);
+        let response = SpdmMessage {
+            header: SpdmMessageHeader {
+                version: spdm_version_sel,
+                request_response_code: SpdmRequestResponseCode::SpdmResponseKeyUpdateAck,
+            },
+            payload: SpdmMessagePayload::SpdmKeyUpdateResponse(SpdmKeyUpdateResponsePayload {
+                key_update_operation: key_update_req.key_update_operation,
+                tag: key_update_req.tag,
+            }),
+        };
+        response.spdm_encode(&mut self.common, &mut writer);
+
+        // a response, the application message stays the request to retry
+        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+        let used = self.common.encode_secured_message(
+            session_id,
+            writer.used_slice(),
+            &mut transport_buffer,
+            true,
+            false,
+        )?;
+        self.common.device_io.send(&transport_buffer[..used])
+    }
+}
+
+#[cfg(all(test,))]
+mod tests_requester {
+    use super::*;
+    use crate::common::session::{SpdmSession, SpdmSessionState};
+    use crate::common::{SpdmDeviceIo, SpdmTransportEncap};
+    use crate::protocol::*;
+    use crate::responder::SpdmAppMessageHandler;
+    use crate::testlib::*;
+    use crate::{crypto, responder};
+
+    const SESSION_ID: u32 = 0xFFFEFFFE;
+    const APP_TYPE: u8 = 0x01;
+
+    /// PCI DOE, with an MCTP like message type byte before secured messages.
+    struct AppTransportEncap {}
+
+    impl SpdmTransportEncap for AppTransportEncap {
+        fn encap(
+            &mut self,
+            spdm_buffer: &[u8],
+            transport_buffer: &mut [u8],
+            secured_message: bool,
+        ) -> SpdmResult<usize> {
+            PciDoeTransportEncap {}.encap(spdm_buffer, transport_buffer, secured_message)
+        }
+
+        fn decap(
+            &mut self,
+            transport_buffer: &[u8],
+            spdm_buffer: &mut [u8],
+        ) -> SpdmResult<(usize, bool)> {
+            PciDoeTransportEncap {}.decap(transport_buffer, spdm_buffer)
+        }
+
+        fn encap_app(
+            &mut self,
+            spdm_buffer: &[u8],
+            app_buffer: &mut [u8],
+            is_app_message: bool,
+        ) -> SpdmResult<usize> {
+            let app_type = if is_app_message { APP_TYPE } else { 0x05 };
+            self.encap_app_message(spdm_buffer, app_buffer, app_type)
+        }
+
+        fn decap_app(
+            &mut self,
+            app_buffer: &[u8],
+            spdm_buffer: &mut [u8],
+        ) -> SpdmResult<(usize, bool)> {
+            let (used, app_type) = self.decap_app_message(app_buffer, spdm_buffer)?;
+            Ok((used, app_type.is_some()))
+        }
+
+        fn encap_app_message(
+            &mut self,
+            app_message: &[u8],
+            app_buffer: &mut [u8],
+            app_type: u8,
+        ) -> SpdmResult<usize> {
+            app_buffer[0] = app_type;
+            app_buffer[1..app_message.len() + 1].copy_from_slice(app_message);
+            Ok(app_message.len() + 1)
+        }
+
+        fn decap_app_message(
+            &mut self,
+            app_buffer: &[u8],
+            app_message: &mut [u8],
+        ) -> SpdmResult<(usize, Option<u8>)> {
+            let app_type = match app_buffer.first() {
+                Some(0x05) => None,
+                Some(app_type) => Some(*app_type),
+                None => return spdm_result_err!(EIO),
+            };
+            app_message[..app_buffer.len() - 1].copy_from_slice(&app_buffer[1..]);
+            Ok((app_buffer.len() - 1, app_type))
+        }
+
+        fn get_sequence_number_count(&mut self) -> u8 {
+            0
+        }
+
+        fn get_max_random_count(&mut self) -> u16 {
+            0
+        }
+    }
+
+    struct EchoAppMessageHandler {}
+
+    impl SpdmAppMessageHandler for EchoAppMessageHandler {
+        fn dispatch_secured_app_message(
+            &mut self,
+            _session_id: u32,
+            _app_type: u8,
+            request: &[u8],
+            response: &mut [u8],
+        ) -> SpdmResult<usize> {
+            response[..request.len()].copy_from_slice(request);
+            Ok(request.len())
+        }
+    }
+
+    fn new_session() -> SpdmSession {
+        let mut session = SpdmSession::new();
+        session.setup(SESSION_ID).unwrap();
+        session.set_crypto_param(
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmDheAlgo::SECP_384_R1,
+            SpdmAeadAlgo::AES_256_GCM,
+            SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+        );
+        session
+            .set_dhe_secret(
+                SpdmVersion::SpdmVersion11,
+                SpdmDheFinalKeyStruct {
+                    data_size: 48,
+                    data: Box::new([0; SPDM_MAX_DHE_KEY_SIZE]),
+                },
+            )
+            .unwrap();
+        let th = SpdmDigestStruct {
+            data_size: 48,
+            data: Box::new([0; SPDM_MAX_HASH_SIZE]),
+        };
+        session
+            .generate_handshake_secret(SpdmVersion::SpdmVersion11, &th)
+            .unwrap();
+        session
+            .generate_data_secret(SpdmVersion::SpdmVersion11, &th)
+            .unwrap();
+        session.set_session_state(SpdmSessionState::SpdmSessionEstablished);
+        session
+    }
+
+    #[test]
+    fn test_case0_send_receive_app_message() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        let (req_config_info, req_provision_info) = create_info();
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let transport_encap = &mut AppTransportEncap {};
+        let mut app_message_handler = EchoAppMessageHandler {};
+
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+        responder.set_app_message_handler(&mut app_message_handler);
+        responder.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        responder.common.session = gen_array_clone(SpdmSession::new(), 4);
+        responder.common.session[0] = new_session();
+
+        let transport_encap2 = &mut AppTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        requester.common.session = gen_array_clone(SpdmSession::new(), 4);
+        requester.common.session[0] = new_session();
+
+        let request = [0x80u8, 0x00, 0x02];
+        let mut response = [0u8; 16];
+        let used = requester
+            .send_receive_app_message(SESSION_ID, APP_TYPE, &request, &mut response)
+            .unwrap();
+        assert_eq!(&response[..used], &request[..]);
+
+        // the response does not fit
+        let mut response = [0u8; 2];
+        assert!(requester
+            .send_receive_app_message(SESSION_ID, APP_TYPE, &request, &mut response)
+            .is_err());
+    }
+
+    /// A responder that sends `interleaved` before it answers the app message.
+    struct InterleavingDeviceIo {
+        session: SpdmSession,
+        interleaved: &'static [u8],
+        received: usize,
+        sent: usize,
+        last_sent: Vec<u8>,
+    }
+
+    impl InterleavingDeviceIo {
+        fn encode(&mut self, app_buffer: &[u8], read_buffer: &mut [u8]) -> usize {
+            let mut encoded = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            let used = self
+                .session
+                .encode_spdm_secured_message(app_buffer, &mut encoded, false)
+                .unwrap();
+            AppTransportEncap {}
+                .encap(&encoded[..used], read_buffer, true)
+                .unwrap()
+        }
+    }
+
+    impl SpdmDeviceIo for InterleavingDeviceIo {
+        fn receive(&mut self, read_buffer: &mut [u8], _timeout: usize) -> Result<usize, usize> {
+            let mut app_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            let used = if self.received == 0 {
+                AppTransportEncap {}
+                    .encap_app(self.interleaved, &mut app_buffer, false)
+                    .unwrap()
+            } else {
+                AppTransportEncap {}
+                    .encap_app_message(&[0xAA], &mut app_buffer, APP_TYPE)
+                    .unwrap()
+            };
+            let used = self.encode(&app_buffer[..used], read_buffer);
+            // KEY_UPDATE UpdateAllKeys, the following messages use the new keys
+            if self.received == 0 && self.interleaved[1..3] == [0xE9, 0x02] {
+                self.session
+                    .create_data_secret_update(SpdmVersion::SpdmVersion11, true, true)
+                    .unwrap();
+                self.session
+                    .activate_data_secret_update(SpdmVersion::SpdmVersion11, true, true, true)
+                    .unwrap();
+            }
+            self.received += 1;
+            Ok(used)
+        }
+
+        fn send(&mut self, buffer: &[u8]) -> SpdmResult {
+            let mut encoded = [0u8; config::DATA_TRANSFER_SIZE];
+            let (used, _) = AppTransportEncap {}.decap(buffer, &mut encoded)?;
+            let mut app_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            let used = self.session.decode_spdm_secured_message(
+                &encoded[..used],
+                &mut app_buffer,
+                true,
+            )?;
+            self.last_sent = app_buffer[..used].to_vec();
+            self.sent += 1;
+            Ok(())
+        }
+
+        fn flush_all(&mut self) -> SpdmResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_case1_send_receive_app_message_key_update() {
+        let (config_info, provision_info) = create_info();
+        let transport_encap = &mut AppTransportEncap {};
+        let mut device_io = InterleavingDeviceIo {
+            session: new_session(),
+            // KEY_UPDATE UpdateAllKeys, tag 1
+            interleaved: &[0x11, 0xE9, 0x02, 0x01],
+            received: 0,
+            sent: 0,
+            last_sent: Vec::new(),
+        };
+
+        let mut requester =
+            RequesterContext::new(&mut device_io, transport_encap, config_info, provision_info);
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        requester.common.session = gen_array_clone(SpdmSession::new(), 4);
+        requester.common.session[0] = new_session();
+
+        // the KEY_UPDATE is acknowledged with the new keys before the response
+        let mut response = [0u8; 16];
+        let used = requester
+            .send_receive_app_message(SESSION_ID, APP_TYPE, &[0x80, 0x00, 0x02], &mut response)
+            .unwrap();
+        assert_eq!(&response[..used], &[0xAA]);
+        drop(requester);
+        assert_eq!(device_io.sent, 2);
+        assert_eq!(device_io.received, 2);
+        // the ACK, with the secured message type byte of the transport
+        assert_eq!(device_io.last_sent, [0x05, 0x11, 0x69, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_case2_send_receive_app_message_error() {
+        let (config_info, provision_info) = create_info();
+        let transport_encap = &mut AppTransportEncap {};
+        let mut device_io = InterleavingDeviceIo {
+            session: new_session(),
+            // ERROR UnexpectedRequest
+            interleaved: &[0x11, 0x7F, 0x04, 0x00],
+            received: 0,
+            sent: 0,
+            last_sent: Vec::new(),
+        };
+
+        let mut requester =
+            RequesterContext::new(&mut device_io, transport_encap, config_info, provision_info);
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        requester.common.session = gen_array_clone(SpdmSession::new(), 4);
+        requester.common.session[0] = new_session();
+
+        let mut response = [0u8; 16];
+        let err = requester
+            .send_receive_app_message(SESSION_ID, APP_TYPE, &[0x80, 0x00, 0x02], &mut response)
+            .unwrap_err();
+        assert_eq!(
+            err.peer_error().map(|payload| payload.error_code),
+            Some(SpdmErrorCode::SpdmErrorUnexpectedRequest)
+        );
+        assert_eq!(device_io.received, 1);
+    }
+
+    #[test]
+    fn test_case3_send_receive_app_message_not_ready() {
+        let (config_info, provision_info) = create_info();
+        let transport_encap = &mut AppTransportEncap {};
+        let mut device_io = InterleavingDeviceIo {
+            session: new_session(),
+            // ERROR ResponseNotReady, RDT 2^4 us, token 7, RDTM 2
+            interleaved: &[0x11, 0x7F, 0x42, 0x00, 0x04, 0x00, 0x07, 0x02],
+            received: 0,
+            sent: 0,
+            last_sent: Vec::new(),
+        };
+
+        crate::time::register(TIME_IMPL.clone());
+        let mut requester =
+            RequesterContext::new(&mut device_io, transport_encap, config_info, provision_info);
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        requester.common.session = gen_array_clone(SpdmSession::new(), 4);
+        requester.common.session[0] = new_session();
+
+        // the response to RESPOND_IF_READY is the application message
+        let mut response = [0u8; 16];
+        let used = requester
+            .send_receive_app_message(SESSION_ID, APP_TYPE, &[0x80, 0x00, 0x02], &mut response)
+            .unwrap();
+        assert_eq!(&response[..used], &[0xAA]);
+        drop(requester);
+        assert_eq!(device_io.sent, 2);
+        assert_eq!(device_io.last_sent, [0x05, 0x11, 0xFF, 0x00, 0x07]);
+    }
+}
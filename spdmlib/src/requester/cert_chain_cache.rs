@@ -0,0 +1,41 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use crate::common::{ManagedBuffer, SpdmNegotiateInfo};
+use crate::protocol::{SpdmCertChainData, SpdmDigestStruct};
+
+/// Negotiated state (VCA) of a responder that reported CACHE_CAP.
+#[derive(Debug, Clone)]
+pub struct SpdmCachedNegotiation {
+    pub negotiate_info: SpdmNegotiateInfo,
+    pub message_a: ManagedBuffer,
+}
+
+/// Storage kept by the integrator across connections, so that a requester can
+/// skip GET_CERTIFICATE, and VCA for CACHE_CAP responders, when reconnecting.
+///
+/// `device_id` is the identity given to `RequesterContext::set_cert_chain_cache`.
+pub trait SpdmCertChainCache {
+    /// The cert chain (as returned by GET_CERTIFICATE) of `slot_id` whose digest is `digest`.
+    fn get_cert_chain(
+        &mut self,
+        device_id: &[u8],
+        slot_id: u8,
+        digest: &SpdmDigestStruct,
+    ) -> Option<SpdmCertChainData>;
+
+    fn put_cert_chain(
+        &mut self,
+        device_id: &[u8],
+        slot_id: u8,
+        digest: &SpdmDigestStruct,
+        cert_chain: &SpdmCertChainData,
+    );
+
+    fn get_negotiation(&mut self, device_id: &[u8]) -> Option<SpdmCachedNegotiation>;
+
+    fn put_negotiation(&mut self, device_id: &[u8], negotiation: &SpdmCachedNegotiation);
+
+    fn remove_negotiation(&mut self, device_id: &[u8]);
+}
@@ -22,7 +22,7 @@ impl<'a> RequesterContext<'a> {
 
         // Receive
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_message(&mut receive_buffer, true)?;
+        let used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_challenge_response(
             0, // NULL
             slot_id,
@@ -97,36 +97,9 @@ impl<'a> RequesterContext<'a> {
                                 self.common.negotiate_info.base_asym_sel.get_size() as usize;
                             let temp_used = used - base_asym_size;
 
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                let message_c = &mut self.common.runtime_info.message_c;
-                                message_c
-                                    .append_message(send_buffer)
-                                    .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                message_c
-                                    .append_message(&receive_buffer[..temp_used])
-                                    .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                            }
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    send_buffer,
-                                );
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    &receive_buffer[..temp_used],
-                                );
-                            }
+                            self.common.append_message_m1m2(send_buffer)?;
+                            self.common
+                                .append_message_m1m2(&receive_buffer[..temp_used])?;
 
                             if self
                                 .verify_challenge_auth_signature(slot_id, &challenge_auth.signature)
@@ -163,7 +136,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -178,47 +151,6 @@ impl<'a> RequesterContext<'a> {
         slot_id: u8,
         signature: &SpdmSignatureStruct,
     ) -> SpdmResult {
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let mut message = ManagedBuffer::default();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            message
-                .append_message(self.common.runtime_info.message_a.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(self.common.runtime_info.message_b.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(self.common.runtime_info.message_c.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
-
-        // we dont need create message hash for verify
-        // we just print message hash for debug purpose
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let message_hash =
-            crypto::hash::hash_all(self.common.negotiate_info.base_hash_sel, message.as_ref())
-                .ok_or_else(|| spdm_err!(EFAULT))?;
-        #[cfg(feature = "hashed-transcript-data")]
-        let message_hash;
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            let digest = crypto::hash::hash_ctx_finalize(
-                self.common
-                    .runtime_info
-                    .digest_context_m1m2
-                    .as_mut()
-                    .cloned()
-                    .unwrap(),
-            );
-            if let Some(digest) = digest {
-                message_hash = digest;
-            } else {
-                return spdm_result_err!(ESEC);
-            }
-        }
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
         if self.common.peer_info.peer_cert_chain[slot_id as usize].is_none() {
             error!("peer_cert_chain is not populated!\n");
             return spdm_result_err!(EINVAL);
@@ -235,24 +167,11 @@ impl<'a> RequesterContext<'a> {
                 .cert_chain
                 .data_size as usize)];
 
-        #[cfg(feature = "hashed-transcript-data")]
-        let mut message = ManagedBuffer::default();
-
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_4)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_CHALLENGE_AUTH_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
+        let message = self.common.get_signing_data(
+            &self.common.runtime_info.transcript_m1m2,
+            &SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_4,
+            &SPDM_CHALLENGE_AUTH_SIGN_CONTEXT,
+        )?;
 
         crypto::asym_verify::verify(
             self.common.negotiate_info.base_hash_sel,
@@ -301,9 +220,7 @@ mod tests_requester {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.runtime_info.need_measurement_summary_hash = true;
 
-        responder.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(responder.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -334,9 +251,6 @@ mod tests_requester {
             .unwrap()
             .cert_chain = REQ_CERT_CHAIN_DATA;
         requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
-        requester.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(requester.common.negotiate_info.base_hash_sel).unwrap(),
-        );
 
         let status = requester
             .send_receive_spdm_challenge(
@@ -2,14 +2,25 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::common::ST1;
-use crate::common::{self, SpdmDeviceIo, SpdmTransportEncap};
+use crate::common::{self, SpdmCodec, SpdmDeviceIo, SpdmTransportEncap};
 use crate::config;
-use crate::error::{spdm_err, spdm_result_err, SpdmResult};
+use crate::error::{spdm_err, spdm_result_err, SpdmErrorKind, SpdmResult};
+use crate::message::*;
 use crate::protocol::*;
+use crate::requester::retry::SpdmLastRequest;
+use crate::requester::{
+    SpdmCachedNegotiation, SpdmCertChainCache, SpdmRequesterPolicy, SpdmRetryPolicy,
+};
+use crate::time::SpdmDeadline;
+use codec::{Codec, Reader};
 
 pub struct RequesterContext<'a> {
     pub common: common::SpdmContext<'a>,
+    pub cert_chain_cache: Option<&'a mut dyn SpdmCertChainCache>,
+    pub device_id: &'a [u8],
+    pub policy: SpdmRequesterPolicy,
+    pub retry_policy: SpdmRetryPolicy,
+    pub(crate) last_request: SpdmLastRequest,
 }
 
 impl<'a> RequesterContext<'a> {
@@ -26,13 +37,116 @@ impl<'a> RequesterContext<'a> {
                 config_info,
                 provision_info,
             ),
+            cert_chain_cache: None,
+            device_id: &[],
+            policy: SpdmRequesterPolicy::default(),
+            retry_policy: SpdmRetryPolicy::default(),
+            last_request: SpdmLastRequest::default(),
         }
     }
 
+    /// A requester on `device_io` and `transport_encap` that carries on with the
+    /// session in `blob`, see `SpdmContext::export_session`, and its session ID.
+    pub fn import_session(
+        device_io: &'a mut dyn SpdmDeviceIo,
+        transport_encap: &'a mut dyn SpdmTransportEncap,
+        config_info: common::SpdmConfigInfo,
+        provision_info: common::SpdmProvisionInfo,
+        handoff_key: &[u8],
+        blob: &[u8],
+    ) -> SpdmResult<(Self, u32)> {
+        let mut context = Self::new(device_io, transport_encap, config_info, provision_info);
+        let session_id = context.common.import_session(handoff_key, blob)?;
+        Ok((context, session_id))
+    }
+
+    /// Use `cert_chain_cache` to keep the cert chains, and the negotiated state
+    /// of a CACHE_CAP responder, of the device identified by `device_id`.
+    pub fn set_cert_chain_cache(
+        &mut self,
+        cert_chain_cache: &'a mut dyn SpdmCertChainCache,
+        device_id: &'a [u8],
+    ) {
+        self.cert_chain_cache = Some(cert_chain_cache);
+        self.device_id = device_id;
+    }
+
+    /// Reject responders whose negotiated state does not satisfy `policy`.
+    pub fn set_policy(&mut self, policy: SpdmRequesterPolicy) {
+        self.policy = policy;
+    }
+
+    /// Wait for and retry requests as `retry_policy` says.
+    pub fn set_retry_policy(&mut self, retry_policy: SpdmRetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
     pub fn init_connection(&mut self) -> SpdmResult {
+        if self.restore_cached_negotiation() {
+            info!("reuse the cached negotiated state\n");
+            return self.check_policy();
+        }
+
         self.send_receive_spdm_version()?;
         self.send_receive_spdm_capability()?;
-        self.send_receive_spdm_algorithm()
+        self.send_receive_spdm_algorithm()?;
+        self.check_policy()?;
+
+        if self
+            .common
+            .negotiate_info
+            .rsp_capabilities_sel
+            .contains(SpdmResponseCapabilityFlags::CACHE_CAP)
+        {
+            if let Some(cache) = self.cert_chain_cache.as_mut() {
+                cache.put_negotiation(
+                    self.device_id,
+                    &SpdmCachedNegotiation {
+                        negotiate_info: self.common.negotiate_info.clone(),
+                        message_a: self.common.runtime_info.message_a.clone(),
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the cached negotiated state, e.g. when the responder lost it,
+    /// so that the next `init_connection` runs VCA again.
+    pub fn invalidate_cached_negotiation(&mut self) {
+        if let Some(cache) = self.cert_chain_cache.as_mut() {
+            cache.remove_negotiation(self.device_id);
+        }
+    }
+
+    fn check_policy(&self) -> SpdmResult {
+        self.policy.check(&self.common.negotiate_info).map_err(|e| {
+            error!("!!! policy violation : {}\n", e.msg);
+            e
+        })
+    }
+
+    fn restore_cached_negotiation(&mut self) -> bool {
+        let negotiation = match self.cert_chain_cache.as_mut() {
+            Some(cache) => cache.get_negotiation(self.device_id),
+            None => None,
+        };
+        match negotiation {
+            Some(negotiation)
+                if negotiation
+                    .negotiate_info
+                    .rsp_capabilities_sel
+                    .contains(SpdmResponseCapabilityFlags::CACHE_CAP) =>
+            {
+                // clear cache data, as GET_VERSION does
+                self.common.reset_runtime_info();
+                self.common.negotiate_info = negotiation.negotiate_info;
+                self.common.runtime_info.message_a = negotiation.message_a;
+                self.common.runtime_info.connection_state = common::SpdmConnectionState::Negotiated;
+                true
+            }
+            _ => false,
+        }
     }
 
     pub fn start_session(
@@ -42,41 +156,40 @@ impl<'a> RequesterContext<'a> {
         measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
     ) -> SpdmResult<u32> {
         if !use_psk {
-            let result =
-                self.send_receive_spdm_key_exchange(slot_id, measurement_summary_hash_type);
-            if let Ok(session_id) = result {
-                let result = self.send_receive_spdm_finish(slot_id, session_id);
-                if result.is_ok() {
-                    Ok(session_id)
-                } else {
-                    spdm_result_err!(EIO)
-                }
-            } else {
-                spdm_result_err!(EIO)
-            }
+            let session_id =
+                self.send_receive_spdm_key_exchange(slot_id, measurement_summary_hash_type)?;
+            self.send_receive_spdm_finish(slot_id, session_id)?;
+            Ok(session_id)
         } else {
-            let result = self.send_receive_spdm_psk_exchange(measurement_summary_hash_type);
-            if let Ok(session_id) = result {
-                let result = self.send_receive_spdm_psk_finish(session_id);
-                if result.is_ok() {
-                    Ok(session_id)
-                } else {
-                    spdm_result_err!(EIO)
-                }
-            } else {
-                spdm_result_err!(EIO)
-            }
+            let session_id = self.send_receive_spdm_psk_exchange(measurement_summary_hash_type)?;
+            self.send_receive_spdm_psk_finish(session_id)?;
+            Ok(session_id)
         }
     }
 
-    pub fn end_session(&mut self, session_id: u32) -> SpdmResult {
-        self.send_receive_spdm_end_session(session_id)
+    /// End the session, with `PRESERVE_NEGOTIATED_STATE` to keep the
+    /// negotiated state for the next one.
+    pub fn end_session(
+        &mut self,
+        session_id: u32,
+        end_session_request_attributes: SpdmEndSessionRequestAttributes,
+    ) -> SpdmResult {
+        self.send_receive_spdm_end_session(session_id, end_session_request_attributes)
     }
 
     pub fn send_message(&mut self, send_buffer: &[u8]) -> SpdmResult {
-        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
-        let used = self.common.encap(send_buffer, &mut transport_buffer)?;
-        self.common.device_io.send(&transport_buffer[..used])
+        let last_request = &mut self.last_request;
+        if send_buffer.len() > last_request.spdm_buffer.len() {
+            return spdm_result_err!(EINVAL);
+        }
+        last_request.spdm_buffer[..send_buffer.len()].copy_from_slice(send_buffer);
+        last_request.spdm_used = send_buffer.len();
+        last_request.session = None;
+        last_request.app_type = None;
+        last_request.transport_used = self
+            .common
+            .encap(send_buffer, &mut last_request.transport_buffer)?;
+        self.resend_message(false)
     }
 
     pub fn send_secured_message(
@@ -85,64 +198,221 @@ impl<'a> RequesterContext<'a> {
         send_buffer: &[u8],
         is_app_message: bool,
     ) -> SpdmResult {
-        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
-        let used = self.common.encode_secured_message(
-            session_id,
-            send_buffer,
-            &mut transport_buffer,
-            true,
-            is_app_message,
-        )?;
-        self.common.device_io.send(&transport_buffer[..used])
+        self.update_key_if_due(session_id)?;
+        self.send_secured_request(session_id, send_buffer, is_app_message)
     }
 
-    pub fn receive_message(
+    pub(crate) fn send_secured_request(
         &mut self,
-        receive_buffer: &mut [u8],
-        crypto_request: bool,
-    ) -> SpdmResult<usize> {
-        info!("receive_message!\n");
+        session_id: u32,
+        send_buffer: &[u8],
+        is_app_message: bool,
+    ) -> SpdmResult {
+        let last_request = &mut self.last_request;
+        if send_buffer.len() > last_request.spdm_buffer.len() {
+            return spdm_result_err!(EINVAL);
+        }
+        last_request.spdm_buffer[..send_buffer.len()].copy_from_slice(send_buffer);
+        last_request.spdm_used = send_buffer.len();
+        last_request.session = Some((session_id, is_app_message));
+        last_request.app_type = None;
+        self.resend_message(true)
+    }
 
-        let timeout: usize = if crypto_request {
-            2 << self.common.negotiate_info.rsp_ct_exponent_sel
-        } else {
-            ST1
-        };
+    pub(crate) fn send_secured_app_request(
+        &mut self,
+        session_id: u32,
+        app_message: &[u8],
+        app_type: u8,
+    ) -> SpdmResult {
+        let last_request = &mut self.last_request;
+        if app_message.len() > last_request.spdm_buffer.len() {
+            return spdm_result_err!(EINVAL);
+        }
+        last_request.spdm_buffer[..app_message.len()].copy_from_slice(app_message);
+        last_request.spdm_used = app_message.len();
+        last_request.session = Some((session_id, true));
+        last_request.app_type = Some(app_type);
+        self.resend_message(true)
+    }
 
-        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
-        let used = self
+    /// Update all keys of the session when they reached the key update policy.
+    pub(crate) fn update_key_if_due(&mut self, session_id: u32) -> SpdmResult {
+        let policy = self.common.config_info.key_update_policy;
+        let now = crate::time::now();
+        match self.common.get_session_via_id(session_id) {
+            Some(session) if session.key_update_due(&policy, now) => {}
+            _ => return Ok(()),
+        }
+        if !self
             .common
+            .negotiate_info
+            .req_capabilities_sel
+            .contains(SpdmRequestCapabilityFlags::KEY_UPD_CAP)
+            || !self
+                .common
+                .negotiate_info
+                .rsp_capabilities_sel
+                .contains(SpdmResponseCapabilityFlags::KEY_UPD_CAP)
+        {
+            info!("key update due, but KEY_UPD_CAP is not negotiated\n");
+            return Ok(());
+        }
+        info!("key update due, update all keys\n");
+        self.send_receive_spdm_key_update(session_id, SpdmKeyUpdateOperation::SpdmUpdateAllKeys)
+    }
+
+    /// Send the last request again, `encode` encodes a secured request again
+    /// with the next sequence number.
+    fn resend_message(&mut self, encode: bool) -> SpdmResult {
+        let last_request = &mut self.last_request;
+        if let (true, Some((session_id, is_app_message))) = (encode, last_request.session) {
+            last_request.transport_used = match last_request.app_type {
+                Some(app_type) => self.common.encode_secured_app_message(
+                    session_id,
+                    &last_request.spdm_buffer[..last_request.spdm_used],
+                    &mut last_request.transport_buffer,
+                    true,
+                    app_type,
+                )?,
+                None => self.common.encode_secured_message(
+                    session_id,
+                    &last_request.spdm_buffer[..last_request.spdm_used],
+                    &mut last_request.transport_buffer,
+                    true,
+                    is_app_message,
+                )?,
+            };
+        }
+        self.common
             .device_io
-            .receive(&mut transport_buffer, timeout)
-            .map_err(|_| spdm_err!(EIO))?;
+            .send(&last_request.transport_buffer[..last_request.transport_used])
+    }
 
-        self.common.decap(&transport_buffer[..used], receive_buffer)
+    pub fn receive_message(&mut self, receive_buffer: &mut [u8]) -> SpdmResult<usize> {
+        info!("receive_message!\n");
+
+        let (used, _) = self.receive_with_retry(None, receive_buffer)?;
+
+        // a responder that lost the negotiated state answers with VERSION_MISMATCH,
+        // in whatever version it is at, and the connection must restart from GET_VERSION
+        let mut reader = Reader::init(&receive_buffer[..used]);
+        if let Some(message_header) = SpdmMessageHeader::read(&mut reader) {
+            if message_header.request_response_code == SpdmRequestResponseCode::SpdmResponseError {
+                if let Some(payload) =
+                    SpdmErrorResponsePayload::spdm_read(&mut self.common, &mut reader)
+                {
+                    if payload.error_code == SpdmErrorCode::SpdmErrorVersionMismatch {
+                        error!("!!! version mismatch !!!\n");
+                        self.common.reset_context();
+                        self.invalidate_cached_negotiation();
+                        return Err(spdm_err!(EDEV, "version mismatch")
+                            .with_kind(SpdmErrorKind::Peer(payload)));
+                    }
+                }
+            }
+        }
+        Ok(used)
     }
 
     pub fn receive_secured_message(
         &mut self,
         session_id: u32,
         receive_buffer: &mut [u8],
-        crypto_request: bool,
     ) -> SpdmResult<usize> {
         info!("receive_secured_message!\n");
 
-        let timeout: usize = if crypto_request {
-            2 << self.common.negotiate_info.rsp_ct_exponent_sel
-        } else {
-            ST1
-        };
+        let (used, _) = self.receive_with_retry(Some(session_id), receive_buffer)?;
+        Ok(used)
+    }
 
-        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+    /// Receive the response to the last request, sending the request again
+    /// on Busy or timeout as the retry policy allows. Also returns the
+    /// transport message type of a secured application message.
+    pub(crate) fn receive_with_retry(
+        &mut self,
+        session_id: Option<u32>,
+        receive_buffer: &mut [u8],
+    ) -> SpdmResult<(usize, Option<u8>)> {
+        let timeout = self.retry_policy.timeout(
+            self.last_request.timeout_class(),
+            self.common.negotiate_info.rsp_ct_exponent_sel,
+        );
 
-        let used = self
-            .common
-            .device_io
-            .receive(&mut transport_buffer, timeout)
-            .map_err(|_| spdm_err!(EIO))?;
+        // a retry is only made if its delay ends before the retry time is up
+        let deadline = match self.retry_policy.max_retry_time {
+            0 => None,
+            max_retry_time => Some(SpdmDeadline::after(max_retry_time)),
+        };
+        let in_time = |delay: usize| match deadline.and_then(|deadline| deadline.remaining()) {
+            Some(remaining) => remaining > delay,
+            None => true,
+        };
 
-        self.common
-            .decode_secured_message(session_id, &transport_buffer[..used], receive_buffer)
+        let mut retry = 0;
+        loop {
+            let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+            let used = match self
+                .common
+                .device_io
+                .receive(&mut transport_buffer, timeout)
+            {
+                Ok(used) => used,
+                Err(_) => {
+                    if self.retry_policy.retransmit_on_timeout
+                        && retry < self.retry_policy.max_retries
+                        && in_time(0)
+                    {
+                        info!("no response, retransmit the request\n");
+                        retry += 1;
+                        self.resend_message(false)?;
+                        continue;
+                    }
+                    return Err(spdm_err!(ETRANSPORT, "no response from the device"));
+                }
+            };
+
+            let (used, app_type) = match session_id {
+                Some(session_id) => self.common.decode_secured_app_message(
+                    session_id,
+                    &transport_buffer[..used],
+                    receive_buffer,
+                )?,
+                None => (
+                    self.common
+                        .decap(&transport_buffer[..used], receive_buffer)?,
+                    None,
+                ),
+            };
+
+            let delay = self.retry_policy.retry_delay(retry);
+            if app_type.is_none()
+                && is_busy_response(&receive_buffer[..used])
+                && retry < self.retry_policy.max_retries
+                && in_time(delay)
+            {
+                info!("responder busy, retry the request\n");
+                crate::time::sleep(delay);
+                retry += 1;
+                self.resend_message(true)?;
+                continue;
+            }
+            return Ok((used, app_type));
+        }
+    }
+}
+
+fn is_busy_response(response: &[u8]) -> bool {
+    let mut reader = Reader::init(response);
+    match (
+        SpdmMessageHeader::read(&mut reader),
+        SpdmMessageGeneralPayload::read(&mut reader),
+    ) {
+        (Some(message_header), Some(payload)) => {
+            message_header.request_response_code == SpdmRequestResponseCode::SpdmResponseError
+                && payload.param1 == SpdmErrorCode::SpdmErrorBusy.get_u8()
+        }
+        _ => false,
     }
 }
 
@@ -150,11 +420,10 @@ impl<'a> RequesterContext<'a> {
 mod tests_requester {
     use super::*;
     use crate::common::session::SpdmSession;
-    use crate::common::*;
-    use crate::message::*;
     use crate::testlib::*;
     use crate::{crypto, responder};
     use codec::Writer;
+    use core::cell::Cell;
 
     #[test]
     fn test_case0_start_session() {
@@ -233,6 +502,7 @@ mod tests_requester {
             rsp_provision_info,
         );
 
+        responder.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
         responder.common.negotiate_info.base_hash_sel =
             crate::protocol::SpdmBaseHashAlgo::TPM_ALG_SHA_384;
         let rsp_session_id = 0xffu16;
@@ -295,8 +565,263 @@ mod tests_requester {
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
 
         let status = requester
-            .receive_secured_message(session_id, &mut receive_buffer, false)
+            .receive_secured_message(session_id, &mut receive_buffer)
             .is_ok();
         assert!(status);
     }
+
+    #[derive(Default)]
+    struct TestCertChainCache {
+        negotiation: Option<SpdmCachedNegotiation>,
+        cert_chain: Option<(SpdmDigestStruct, SpdmCertChainData)>,
+    }
+
+    impl SpdmCertChainCache for TestCertChainCache {
+        fn get_cert_chain(
+            &mut self,
+            device_id: &[u8],
+            slot_id: u8,
+            digest: &SpdmDigestStruct,
+        ) -> Option<SpdmCertChainData> {
+            assert_eq!(device_id, b"device0");
+            assert_eq!(slot_id, 0);
+            match &self.cert_chain {
+                Some((cached_digest, cert_chain)) if cached_digest.as_ref() == digest.as_ref() => {
+                    Some(cert_chain.clone())
+                }
+                _ => None,
+            }
+        }
+
+        fn put_cert_chain(
+            &mut self,
+            device_id: &[u8],
+            slot_id: u8,
+            digest: &SpdmDigestStruct,
+            cert_chain: &SpdmCertChainData,
+        ) {
+            assert_eq!(device_id, b"device0");
+            assert_eq!(slot_id, 0);
+            self.cert_chain = Some((digest.clone(), cert_chain.clone()));
+        }
+
+        fn get_negotiation(&mut self, _device_id: &[u8]) -> Option<SpdmCachedNegotiation> {
+            self.negotiation.clone()
+        }
+
+        fn put_negotiation(&mut self, _device_id: &[u8], negotiation: &SpdmCachedNegotiation) {
+            self.negotiation = Some(negotiation.clone());
+        }
+
+        fn remove_negotiation(&mut self, _device_id: &[u8]) {
+            self.negotiation = None;
+        }
+    }
+
+    #[test]
+    fn test_case1_cert_chain_cache() {
+        let mut cache = TestCertChainCache::default();
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        rsp_config_info.rsp_capabilities |= SpdmResponseCapabilityFlags::CACHE_CAP;
+        let peer_digests = with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = RequesterContext::new(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+            requester.set_cert_chain_cache(&mut cache, b"device0");
+
+            assert!(requester.init_connection().is_ok());
+            assert!(requester.send_receive_spdm_digest(None).is_ok());
+            assert!(requester.send_receive_spdm_certificate(None, 0).is_ok());
+            requester.common.peer_info.peer_digests.clone()
+        });
+        assert!(cache.negotiation.is_some());
+        assert!(cache.cert_chain.is_some());
+
+        // reconnect without any responder: both VCA and GET_CERTIFICATE come from the cache
+        let (req_config_info, req_provision_info) = create_info();
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_requester = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap,
+            req_config_info,
+            req_provision_info,
+        );
+        requester.set_cert_chain_cache(&mut cache, b"device0");
+
+        assert!(requester.init_connection().is_ok());
+        assert_eq!(
+            requester.common.negotiate_info.base_hash_sel,
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384
+        );
+        requester.common.peer_info.peer_digests = peer_digests;
+        assert!(requester.send_receive_spdm_certificate(None, 0).is_ok());
+        assert!(requester.common.peer_info.peer_cert_chain[0].is_some());
+
+        // a digest mismatch needs GET_CERTIFICATE, which has no responder here
+        requester.common.peer_info.peer_cert_chain[0] = None;
+        requester.common.peer_info.peer_digests[0]
+            .as_mut()
+            .unwrap()
+            .data[0] ^= 0xFF;
+        assert!(requester.send_receive_spdm_certificate(None, 0).is_err());
+
+        requester.invalidate_cached_negotiation();
+        assert!(requester.init_connection().is_err());
+    }
+
+    #[test]
+    fn test_case2_policy() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = RequesterContext::new(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+
+            requester.set_policy(SpdmRequesterPolicy {
+                min_hash_size: SHA384_DIGEST_SIZE as u16,
+                allowed_base_asym_algo: SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+                required_rsp_capabilities: SpdmResponseCapabilityFlags::CERT_CAP,
+                ..Default::default()
+            });
+            assert!(requester.init_connection().is_ok());
+
+            requester.set_policy(SpdmRequesterPolicy {
+                allowed_base_asym_algo: SpdmBaseAsymAlgo::TPM_ALG_RSASSA_3072
+                    | SpdmBaseAsymAlgo::TPM_ALG_RSAPSS_3072,
+                ..Default::default()
+            });
+            let err = requester.init_connection().unwrap_err();
+            assert_eq!(err.num, crate::error::SpdmErrorNum::EPOLICY);
+        });
+    }
+
+    /// Loses the first `lost` requests and answers the next `busy` ones with Busy.
+    struct LossyDeviceIo<'a> {
+        device_io: &'a mut dyn SpdmDeviceIo,
+        lost: &'a Cell<usize>,
+        busy: &'a Cell<usize>,
+        sent: &'a Cell<usize>,
+        response: Option<[u8; 4]>,
+    }
+
+    impl SpdmDeviceIo for LossyDeviceIo<'_> {
+        fn receive(&mut self, read_buffer: &mut [u8], timeout: usize) -> Result<usize, usize> {
+            if self.lost.get() != 0 {
+                self.lost.set(self.lost.get() - 1);
+                return Err(0);
+            }
+            match self.response.take() {
+                Some(response) => PciDoeTransportEncap {}
+                    .encap(&response, read_buffer, false)
+                    .map_err(|_| 0),
+                None => self.device_io.receive(read_buffer, timeout),
+            }
+        }
+
+        fn send(&mut self, buffer: &[u8]) -> SpdmResult {
+            self.sent.set(self.sent.get() + 1);
+            if self.lost.get() != 0 {
+                return Ok(());
+            }
+            if self.busy.get() != 0 {
+                self.busy.set(self.busy.get() - 1);
+                // answer in the version of the request, after the PCI DOE header
+                self.response = Some([
+                    buffer[8],
+                    SpdmRequestResponseCode::SpdmResponseError.get_u8(),
+                    SpdmErrorCode::SpdmErrorBusy.get_u8(),
+                    0,
+                ]);
+                return Ok(());
+            }
+            self.device_io.send(buffer)
+        }
+
+        fn flush_all(&mut self) -> SpdmResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_case3_retry_policy() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+
+        let lost = Cell::new(1);
+        let busy = Cell::new(1);
+        let sent = Cell::new(0);
+        crate::time::register(TIME_IMPL.clone());
+
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut device_io_requester = LossyDeviceIo {
+                device_io,
+                lost: &lost,
+                busy: &busy,
+                sent: &sent,
+                response: None,
+            };
+            let mut requester = RequesterContext::new(
+                &mut device_io_requester,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+
+            requester.set_retry_policy(SpdmRetryPolicy {
+                max_retries: 2,
+                retransmit_on_timeout: true,
+                ..Default::default()
+            });
+            // GET_VERSION is lost, then answered with Busy
+            assert!(requester.init_connection().is_ok());
+            assert_eq!(sent.get(), 5);
+
+            busy.set(3);
+            let err = requester.send_receive_spdm_digest(None).unwrap_err();
+            assert_eq!(
+                err.peer_error().map(|payload| payload.error_code),
+                Some(SpdmErrorCode::SpdmErrorBusy)
+            );
+            assert_eq!(sent.get(), 8);
+
+            busy.set(0);
+            lost.set(3);
+            assert_eq!(
+                requester.send_receive_spdm_digest(None).unwrap_err().kind,
+                crate::error::SpdmErrorKind::Transport
+            );
+            lost.set(0);
+
+            // no retry whose delay ends past the retry time
+            requester.set_retry_policy(SpdmRetryPolicy {
+                max_retries: 5,
+                retry_delay: 100,
+                max_retry_time: 250,
+                ..Default::default()
+            });
+            busy.set(5);
+            let sent_before = sent.get();
+            assert!(requester.send_receive_spdm_digest(None).is_err());
+            assert_eq!(sent.get(), sent_before + 2);
+            busy.set(0);
+
+            requester.set_retry_policy(SpdmRetryPolicy::default());
+            busy.set(1);
+            assert!(requester.send_receive_spdm_digest(None).is_err());
+            assert!(requester.send_receive_spdm_digest(None).is_ok());
+        });
+    }
 }
@@ -4,21 +4,46 @@
 
 use crate::error::{spdm_result_err, SpdmResult};
 use crate::message::*;
+use crate::protocol::SpdmResponseCapabilityFlags;
 use crate::requester::*;
 
 impl<'a> RequesterContext<'a> {
-    pub fn send_receive_spdm_end_session(&mut self, session_id: u32) -> SpdmResult {
+    /// End the session. Unless `PRESERVE_NEGOTIATED_STATE` is set, a CACHE_CAP
+    /// responder clears the negotiated state once no other session is left,
+    /// and so does the requester, the next request then has to be GET_VERSION.
+    pub fn send_receive_spdm_end_session(
+        &mut self,
+        session_id: u32,
+        end_session_request_attributes: SpdmEndSessionRequestAttributes,
+    ) -> SpdmResult {
         info!("send spdm end_session\n");
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.encode_spdm_end_session(&mut send_buffer);
+        let used = self.encode_spdm_end_session(&mut send_buffer, end_session_request_attributes);
         self.send_secured_message(session_id, &send_buffer[..used], false)?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_secured_message(session_id, &mut receive_buffer, false)?;
-        self.handle_spdm_end_session_response(session_id, &receive_buffer[..used])
+        let used = self.receive_secured_message(session_id, &mut receive_buffer)?;
+        self.handle_spdm_end_session_response(session_id, &receive_buffer[..used])?;
+
+        if self
+            .common
+            .negotiate_info
+            .rsp_capabilities_sel
+            .contains(SpdmResponseCapabilityFlags::CACHE_CAP)
+            && !end_session_request_attributes
+                .contains(SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE)
+            && self.common.reset_negotiated_state()
+        {
+            self.invalidate_cached_negotiation();
+        }
+        Ok(())
     }
 
-    pub fn encode_spdm_end_session(&mut self, buf: &mut [u8]) -> usize {
+    pub fn encode_spdm_end_session(
+        &mut self,
+        buf: &mut [u8],
+        end_session_request_attributes: SpdmEndSessionRequestAttributes,
+    ) -> usize {
         let mut writer = Writer::init(buf);
 
         let request = SpdmMessage {
@@ -27,7 +52,7 @@ impl<'a> RequesterContext<'a> {
                 request_response_code: SpdmRequestResponseCode::SpdmRequestEndSession,
             },
             payload: SpdmMessagePayload::SpdmEndSessionRequest(SpdmEndSessionRequestPayload {
-                end_session_request_attributes: SpdmEndSessionRequestAttributes::empty(),
+                end_session_request_attributes,
             }),
         };
         request.spdm_encode(&mut self.common, &mut writer);
@@ -82,7 +107,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -97,6 +122,7 @@ impl<'a> RequesterContext<'a> {
 mod tests_requester {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmConnectionState;
     use crate::testlib::*;
     use crate::{crypto, responder};
 
@@ -156,7 +182,143 @@ mod tests_requester {
         requester.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
 
-        let status = requester.end_session(session_id).is_ok();
+        let status = requester
+            .end_session(
+                session_id,
+                SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE,
+            )
+            .is_ok();
         assert!(status);
     }
+
+    /// Negotiated with a CACHE_CAP responder, with the established sessions
+    /// 0xFFFF_FFFF and 0xFFFE_FFFE.
+    fn establish_sessions(context: &mut SpdmContext) {
+        context.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        context.negotiate_info.rsp_capabilities_sel |= SpdmResponseCapabilityFlags::CACHE_CAP;
+        context.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+        context.runtime_info.message_a.append_message(&[0x10]);
+        context.session = gen_array_clone(SpdmSession::new(), 4);
+        for (i, session_id) in [0xFFFF_FFFFu32, 0xFFFE_FFFE].iter().enumerate() {
+            context.session[i].setup(*session_id).unwrap();
+            context.session[i].set_crypto_param(
+                SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+                SpdmDheAlgo::SECP_384_R1,
+                SpdmAeadAlgo::AES_256_GCM,
+                SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+            );
+            context.session[i].set_session_state(
+                crate::common::session::SpdmSessionState::SpdmSessionEstablished,
+            );
+        }
+    }
+
+    #[test]
+    fn test_case1_send_receive_spdm_end_session() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        rsp_config_info.rsp_capabilities |= SpdmResponseCapabilityFlags::CACHE_CAP;
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            establish_sessions(&mut device_io.responder.common);
+
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = RequesterContext::new(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+            establish_sessions(&mut requester.common);
+
+            // the negotiated state survives the first session
+            assert!(requester
+                .end_session(
+                    0xFFFF_FFFF,
+                    SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE
+                )
+                .is_ok());
+            assert!(requester.common.get_session_via_id(0xFFFF_FFFF).is_none());
+            assert_eq!(
+                requester.common.runtime_info.connection_state,
+                SpdmConnectionState::Negotiated
+            );
+            assert_eq!(requester.common.runtime_info.message_a.as_ref(), &[0x10]);
+            assert_eq!(
+                requester.common.negotiate_info.base_hash_sel,
+                SpdmBaseHashAlgo::TPM_ALG_SHA_384
+            );
+
+            // and is cleared with the second
+            assert!(requester
+                .send_receive_spdm_end_session(
+                    0xFFFE_FFFE,
+                    SpdmEndSessionRequestAttributes::empty()
+                )
+                .is_ok());
+            assert_eq!(
+                requester.common.runtime_info.connection_state,
+                SpdmConnectionState::NotStarted
+            );
+            assert!(requester.common.runtime_info.message_a.as_ref().is_empty());
+            assert!(requester
+                .common
+                .negotiate_info
+                .rsp_capabilities_sel
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_case2_end_session_keeps_state_for_other_sessions() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        rsp_config_info.rsp_capabilities |= SpdmResponseCapabilityFlags::CACHE_CAP;
+        with_fake_responder(rsp_config_info, rsp_provision_info, |device_io| {
+            establish_sessions(&mut device_io.responder.common);
+
+            let (req_config_info, req_provision_info) = create_info();
+            let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+            let mut requester = RequesterContext::new(
+                device_io,
+                pcidoe_transport_encap,
+                req_config_info,
+                req_provision_info,
+            );
+            establish_sessions(&mut requester.common);
+
+            // ending one session without PRESERVE_NEGOTIATED_STATE keeps the
+            // negotiated state while the other session is still in use
+            assert!(requester
+                .end_session(0xFFFF_FFFF, SpdmEndSessionRequestAttributes::empty())
+                .is_ok());
+            assert!(requester.common.get_session_via_id(0xFFFF_FFFF).is_none());
+            assert!(requester.common.get_session_via_id(0xFFFE_FFFE).is_some());
+            assert_eq!(
+                requester.common.runtime_info.connection_state,
+                SpdmConnectionState::Negotiated
+            );
+            assert_eq!(requester.common.runtime_info.message_a.as_ref(), &[0x10]);
+            assert!(requester.send_receive_spdm_heartbeat(0xFFFE_FFFE).is_ok());
+
+            // ending the last one clears it
+            assert!(requester
+                .end_session(0xFFFE_FFFE, SpdmEndSessionRequestAttributes::empty())
+                .is_ok());
+            assert_eq!(
+                requester.common.runtime_info.connection_state,
+                SpdmConnectionState::NotStarted
+            );
+            assert!(requester.common.runtime_info.message_a.as_ref().is_empty());
+
+            drop(requester);
+            let responder = &device_io.responder;
+            assert!(responder
+                .common
+                .get_immutable_session_via_id(0xFFFE_FFFE)
+                .is_none());
+            assert_eq!(
+                responder.common.runtime_info.connection_state,
+                SpdmConnectionState::NotStarted
+            );
+        });
+    }
 }
@@ -2,8 +2,6 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use crate::message::*;
 use crate::protocol::*;
@@ -11,23 +9,20 @@ use crate::requester::*;
 extern crate alloc;
 use alloc::boxed::Box;
 
-use crate::common::ManagedBuffer;
-
 impl<'a> RequesterContext<'a> {
     pub fn send_receive_spdm_finish(&mut self, slot_id: u8, session_id: u32) -> SpdmResult {
         info!("send spdm finish\n");
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let (send_used, base_hash_size, message_f) =
+        let (send_used, base_hash_size) =
             self.encode_spdm_finish(session_id, slot_id, &mut send_buffer)?;
         self.send_secured_message(session_id, &send_buffer[..send_used], false)?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let receive_used = self.receive_secured_message(session_id, &mut receive_buffer, false)?;
+        let receive_used = self.receive_secured_message(session_id, &mut receive_buffer)?;
         self.handle_spdm_finish_response(
             session_id,
             slot_id,
             base_hash_size,
-            message_f,
             &receive_buffer[..receive_used],
         )
     }
@@ -37,7 +32,7 @@ impl<'a> RequesterContext<'a> {
         session_id: u32,
         slot_id: u8,
         buf: &mut [u8],
-    ) -> SpdmResult<(usize, usize, ManagedBuffer)> {
+    ) -> SpdmResult<(usize, usize)> {
         let mut writer = Writer::init(buf);
 
         let request = SpdmMessage {
@@ -62,71 +57,22 @@ impl<'a> RequesterContext<'a> {
         let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
         let temp_used = send_used - base_hash_size;
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            let mut message_f = ManagedBuffer::default();
-            message_f
-                .append_message(&buf[..temp_used])
-                .ok_or(spdm_err!(ENOMEM))?;
-
-            let session = if let Some(s) = self.common.get_immutable_session_via_id(session_id) {
-                s
-            } else {
-                return spdm_result_err!(EFAULT);
-            };
-            let message_k = &session.runtime_info.message_k;
-
-            let transcript_data = self.common.calc_req_transcript_data(
-                slot_id,
-                false,
-                message_k,
-                Some(&message_f),
-            )?;
-            let session = if let Some(s) = self.common.get_session_via_id(session_id) {
-                s
-            } else {
-                return spdm_result_err!(EFAULT);
-            };
-
-            let hmac = session.generate_hmac_with_request_finished_key(transcript_data.as_ref())?;
-            message_f
-                .append_message(hmac.as_ref())
-                .ok_or(spdm_err!(ENOMEM))?;
-
-            // patch the message before send
-            buf[(send_used - base_hash_size)..send_used].copy_from_slice(hmac.as_ref());
-            Ok((send_used, base_hash_size, message_f))
-        }
-
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            let session = if let Some(s) = self.common.get_session_via_id(session_id) {
-                s
-            } else {
-                return spdm_result_err!(EFAULT);
-            };
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                &buf[..temp_used],
-            );
-            let message_hash = crypto::hash::hash_ctx_finalize(
-                session
-                    .runtime_info
-                    .digest_context_th
-                    .as_mut()
-                    .cloned()
-                    .unwrap(),
-            );
-            let hmac =
-                session.generate_hmac_with_request_finished_key(message_hash.unwrap().as_ref())?;
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                hmac.as_ref(),
-            );
-            // patch the message before send
-            buf[(send_used - base_hash_size)..send_used].copy_from_slice(hmac.as_ref());
-            Ok((send_used, base_hash_size, ManagedBuffer::default()))
-        }
+        let session = if let Some(s) = self.common.get_session_via_id(session_id) {
+            s
+        } else {
+            return spdm_result_err!(EFAULT);
+        };
+        session
+            .runtime_info
+            .transcript_th
+            .append(&buf[..temp_used])?;
+        let message_hash = session.runtime_info.transcript_th.hash()?;
+        let hmac = session.generate_hmac_with_request_finished_key(message_hash.as_ref())?;
+        session.runtime_info.transcript_th.append(hmac.as_ref())?;
+
+        // patch the message before send
+        buf[(send_used - base_hash_size)..send_used].copy_from_slice(hmac.as_ref());
+        Ok((send_used, base_hash_size))
     }
 
     pub fn handle_spdm_finish_response(
@@ -134,8 +80,6 @@ impl<'a> RequesterContext<'a> {
         session_id: u32,
         slot_id: u8,
         base_hash_size: usize,
-        #[cfg(not(feature = "hashed-transcript-data"))] mut message_f: ManagedBuffer,
-        #[cfg(feature = "hashed-transcript-data")] message_f: ManagedBuffer, // never use message_f for hashed-transcript-data, use session.runtime_info.message_f
         receive_buffer: &[u8],
     ) -> SpdmResult {
         let in_clear_text = self
@@ -151,6 +95,11 @@ impl<'a> RequesterContext<'a> {
 
         let mut reader = Reader::init(receive_buffer);
         match SpdmMessageHeader::read(&mut reader) {
+            Some(message_header)
+                if message_header.version != self.common.negotiate_info.spdm_version_sel =>
+            {
+                spdm_result_err!(EFAULT)
+            }
             Some(message_header) => match message_header.request_response_code {
                 SpdmRequestResponseCode::SpdmResponseFinishRsp => {
                     let finish_rsp =
@@ -159,61 +108,24 @@ impl<'a> RequesterContext<'a> {
                     if let Some(finish_rsp) = finish_rsp {
                         debug!("!!! finish rsp : {:02x?}\n", finish_rsp);
 
-                        if in_clear_text {
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let session = if let Some(s) =
-                                self.common.get_immutable_session_via_id(session_id)
-                            {
-                                s
-                            } else {
-                                return spdm_result_err!(EFAULT);
-                            };
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let message_k = &session.runtime_info.message_k;
+                        let session = if let Some(s) = self.common.get_session_via_id(session_id) {
+                            s
+                        } else {
+                            return spdm_result_err!(EFAULT);
+                        };
 
+                        if in_clear_text {
                             // verify HMAC with finished_key
                             let temp_used = receive_used - base_hash_size;
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            message_f
-                                .append_message(&receive_buffer[..temp_used])
-                                .ok_or(spdm_err!(ENOMEM))?;
-
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let transcript_data = self.common.calc_req_transcript_data(
-                                slot_id,
-                                false,
-                                message_k,
-                                Some(&message_f),
-                            )?;
-
-                            let session =
-                                if let Some(s) = self.common.get_session_via_id(session_id) {
-                                    s
-                                } else {
-                                    return spdm_result_err!(EFAULT);
-                                };
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            crypto::hash::hash_ctx_update(
-                                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                                &receive_buffer[..temp_used],
-                            );
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let ctx_cloned = session
+                            session
                                 .runtime_info
-                                .digest_context_th
-                                .as_mut()
-                                .cloned()
-                                .unwrap();
+                                .transcript_th
+                                .append(&receive_buffer[..temp_used])?;
+
+                            let message_hash = session.runtime_info.transcript_th.hash()?;
                             if session
                                 .verify_hmac_with_response_finished_key(
-                                    #[cfg(not(feature = "hashed-transcript-data"))]
-                                    transcript_data.as_ref(),
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    crypto::hash::hash_ctx_finalize(ctx_cloned)
-                                        .unwrap()
-                                        .as_ref(),
+                                    message_hash.as_ref(),
                                     &finish_rsp.verify_data,
                                 )
                                 .is_err()
@@ -224,76 +136,19 @@ impl<'a> RequesterContext<'a> {
                             } else {
                                 info!("verify_hmac_with_response_finished_key pass");
                             }
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            message_f
-                                .append_message(finish_rsp.verify_data.as_ref())
-                                .ok_or(spdm_err!(ENOMEM))?;
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                session.runtime_info.message_f = message_f.clone();
-                            }
-                            #[cfg(feature = "hashed-transcript-data")]
-                            crypto::hash::hash_ctx_update(
-                                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                                finish_rsp.verify_data.as_ref(),
-                            );
+                            session
+                                .runtime_info
+                                .transcript_th
+                                .append(finish_rsp.verify_data.as_ref())?;
                         } else {
-                            let session =
-                                if let Some(s) = self.common.get_session_via_id(session_id) {
-                                    s
-                                } else {
-                                    return spdm_result_err!(EFAULT);
-                                };
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            message_f
-                                .append_message(&receive_buffer[..receive_used])
-                                .ok_or(spdm_err!(ENOMEM))?;
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                session.runtime_info.message_f = message_f.clone();
-                            }
-                            #[cfg(feature = "hashed-transcript-data")]
-                            crypto::hash::hash_ctx_update(
-                                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                                &receive_buffer[..receive_used],
-                            );
+                            session
+                                .runtime_info
+                                .transcript_th
+                                .append(&receive_buffer[..receive_used])?;
                         }
 
-                        #[cfg(not(feature = "hashed-transcript-data"))]
-                        let session =
-                            if let Some(s) = self.common.get_immutable_session_via_id(session_id) {
-                                s
-                            } else {
-                                return spdm_result_err!(EFAULT);
-                            };
-                        #[cfg(not(feature = "hashed-transcript-data"))]
-                        let message_k = &session.runtime_info.message_k;
                         // generate the data secret
-                        #[cfg(not(feature = "hashed-transcript-data"))]
-                        let th2 = self.common.calc_req_transcript_hash(
-                            slot_id,
-                            false,
-                            message_k,
-                            Some(&message_f),
-                        )?;
-
-                        #[cfg(feature = "hashed-transcript-data")]
-                        let session = if let Some(s) = self.common.get_session_via_id(session_id) {
-                            s
-                        } else {
-                            return spdm_result_err!(EFAULT);
-                        };
-
-                        #[cfg(feature = "hashed-transcript-data")]
-                        let th2 = crypto::hash::hash_ctx_finalize(
-                            session
-                                .runtime_info
-                                .digest_context_th
-                                .as_mut()
-                                .cloned()
-                                .unwrap(),
-                        )
-                        .unwrap();
+                        let th2 = session.runtime_info.transcript_th.hash()?;
                         debug!("!!! th2 : {:02x?}\n", th2.as_ref());
                         let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
                         let session = if let Some(s) = self.common.get_session_via_id(session_id) {
@@ -332,11 +187,10 @@ impl<'a> RequesterContext<'a> {
                                 session_id,
                                 slot_id,
                                 base_hash_size,
-                                message_f,
                                 &receive_buffer[..used],
                             )
                         }
-                        _ => spdm_result_err!(EINVAL),
+                        Err(e) => Err(e),
                     }
                 }
                 _ => spdm_result_err!(EINVAL),
@@ -350,6 +204,7 @@ impl<'a> RequesterContext<'a> {
 mod tests_requester {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmConnectionState;
     use crate::testlib::*;
     use crate::{crypto, responder};
 
@@ -399,9 +254,8 @@ mod tests_requester {
         responder.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionHandshaking);
 
-        responder.common.session[0].runtime_info.digest_context_th = Some(
-            crypto::hash::hash_ctx_init(responder.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+        responder.common.session[0].runtime_info.transcript_th =
+            responder.common.new_transcript().unwrap();
 
         let dhe_secret = SpdmDheFinalKeyStruct {
             data_size: 48,
@@ -423,6 +277,8 @@ mod tests_requester {
             },
         );
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -463,9 +319,8 @@ mod tests_requester {
         );
         requester.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionHandshaking);
-        requester.common.session[0].runtime_info.digest_context_th = Some(
-            crypto::hash::hash_ctx_init(requester.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+        requester.common.session[0].runtime_info.transcript_th =
+            requester.common.new_transcript().unwrap();
 
         let dhe_secret = SpdmDheFinalKeyStruct {
             data_size: 48,
@@ -14,7 +14,7 @@ impl<'a> RequesterContext<'a> {
         self.send_message(&send_buffer[..send_used])?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_message(&mut receive_buffer, false)?;
+        let used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_capability_response(0, &send_buffer[..send_used], &receive_buffer[..used])
     }
 
@@ -86,7 +86,10 @@ impl<'a> RequesterContext<'a> {
                                 .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
                             message_a
                                 .append_message(&receive_buffer[..used])
-                                .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))
+                                .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
+                            self.common.runtime_info.connection_state =
+                                SpdmConnectionState::AfterCapabilities;
+                            Ok(())
                         } else {
                             error!("!!! capabilities : fail !!!\n");
                             spdm_result_err!(EFAULT)
@@ -109,7 +112,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -144,6 +147,8 @@ mod tests_requester {
             rsp_provision_info,
         );
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::AfterVersion;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -155,6 +160,7 @@ mod tests_requester {
         );
 
         requester.common.reset_runtime_info();
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
         requester.common.negotiate_info.measurement_hash_sel =
             SpdmMeasurementHashAlgo::TPM_ALG_SHA_384;
         requester.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
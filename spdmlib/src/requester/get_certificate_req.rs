@@ -2,8 +2,8 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::crypto;
-use crate::error::{spdm_result_err, SpdmResult};
+use crate::crypto::{self, x509v3::SpdmCertModel};
+use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use crate::message::*;
 use crate::protocol::*;
 use crate::requester::*;
@@ -23,7 +23,7 @@ impl<'a> RequesterContext<'a> {
         self.send_message(&send_buffer[..send_used])?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_message(&mut receive_buffer, false)?;
+        let used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_certificate_partial_response(
             session_id,
             slot_id,
@@ -117,36 +117,8 @@ impl<'a> RequesterContext<'a> {
                                 .cert_chain
                                 .data_size = offset + certificate.portion_length;
 
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                let message_b = &mut self.common.runtime_info.message_b;
-                                message_b
-                                    .append_message(send_buffer)
-                                    .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                message_b
-                                    .append_message(&receive_buffer[..used])
-                                    .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                            }
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    send_buffer,
-                                );
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    &receive_buffer[..used],
-                                );
-                            }
+                            self.common.append_message_m1m2(send_buffer)?;
+                            self.common.append_message_m1m2(&receive_buffer[..used])?;
 
                             Ok((certificate.portion_length, certificate.remainder_length))
                         } else {
@@ -173,7 +145,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -188,6 +160,14 @@ impl<'a> RequesterContext<'a> {
         session_id: Option<u32>,
         slot_id: u8,
     ) -> SpdmResult {
+        if self.load_cached_cert_chain(slot_id) {
+            if self.verify_spdm_certificate_chain(slot_id).is_ok() {
+                info!("reuse the cached cert_chain of slot {}\n", slot_id);
+                return Ok(());
+            }
+            self.common.peer_info.peer_cert_chain[slot_id as usize] = None;
+        }
+
         let mut offset = 0u16;
         let mut length = config::MAX_SPDM_CERT_PORTION_LEN as u16;
         while length != 0 {
@@ -201,109 +181,180 @@ impl<'a> RequesterContext<'a> {
                         length = config::MAX_SPDM_CERT_PORTION_LEN as u16;
                     }
                 }
-                Err(_) => return spdm_result_err!(EIO),
+                Err(e) => return Err(e),
             }
         }
-        self.verify_spdm_certificate_chain(slot_id)
+        self.verify_spdm_certificate_chain(slot_id)?;
+        self.store_cached_cert_chain(slot_id);
+        Ok(())
     }
 
-    pub fn verify_spdm_certificate_chain(&mut self, slot_id: u8) -> SpdmResult {
-        // verify
-        if let Some(peer_cert_chain_data) = &self.common.provision_info.peer_cert_chain_data {
-            //
-            // TBD: Verify cert chain
-            //
-            if self.common.peer_info.peer_cert_chain[slot_id as usize].is_none() {
-                error!("peer_cert_chain is not populated!\n");
-                return spdm_result_err!(EIO);
-            }
-            if self.common.peer_info.peer_cert_chain[slot_id as usize]
-                .as_ref()
-                .unwrap()
-                .cert_chain
-                .data_size
-                <= (4 + self.common.negotiate_info.base_hash_sel.get_size())
-            {
-                return spdm_result_err!(EIO);
+    /// Load the cert chain of `slot_id` from the cache if it matches the digest
+    /// from the last GET_DIGESTS.
+    fn load_cached_cert_chain(&mut self, slot_id: u8) -> bool {
+        let digest = match &self.common.peer_info.peer_digests[slot_id as usize] {
+            Some(digest) => digest,
+            None => return false,
+        };
+        let cert_chain = match self.cert_chain_cache.as_mut() {
+            Some(cache) => cache.get_cert_chain(self.device_id, slot_id, digest),
+            None => None,
+        };
+        let cert_chain = match cert_chain {
+            Some(cert_chain) => cert_chain,
+            None => return false,
+        };
+        match crypto::hash::hash_all(
+            self.common.negotiate_info.base_hash_sel,
+            cert_chain.as_ref(),
+        ) {
+            Some(cert_chain_hash) if cert_chain_hash.as_ref() == digest.as_ref() => {
+                self.common.peer_info.peer_cert_chain[slot_id as usize] = Some(SpdmCertChain {
+                    cert_chain,
+                    ..Default::default()
+                });
+                true
             }
+            _ => false,
+        }
+    }
 
-            let data_size = self.common.peer_info.peer_cert_chain[slot_id as usize]
-                .as_ref()
-                .unwrap()
-                .cert_chain
-                .data_size
-                - 4
-                - self.common.negotiate_info.base_hash_sel.get_size();
-            let mut data = [0u8; config::MAX_SPDM_CERT_CHAIN_DATA_SIZE];
-            data[0..(data_size as usize)].copy_from_slice(
-                &self.common.peer_info.peer_cert_chain[slot_id as usize]
-                    .as_ref()
-                    .unwrap()
-                    .cert_chain
-                    .data[(4usize
-                    + self.common.negotiate_info.base_hash_sel.get_size() as usize)
-                    ..(self.common.peer_info.peer_cert_chain[slot_id as usize]
-                        .as_ref()
-                        .unwrap()
-                        .cert_chain
-                        .data_size as usize)],
-            );
-            let runtime_peer_cert_chain_data = SpdmCertChainData { data_size, data };
-
-            let (root_cert_begin, root_cert_end) =
-                crypto::cert_operation::get_cert_from_cert_chain(
-                    &runtime_peer_cert_chain_data.data
-                        [..(runtime_peer_cert_chain_data.data_size as usize)],
-                    0,
-                )?;
-            let root_cert = &runtime_peer_cert_chain_data.data[root_cert_begin..root_cert_end];
-            let root_hash = if let Some(rh) =
-                crypto::hash::hash_all(self.common.negotiate_info.base_hash_sel, root_cert)
-            {
-                rh
-            } else {
-                return spdm_result_err!(ESEC);
-            };
-            if root_hash.data[..(root_hash.data_size as usize)]
-                != self.common.peer_info.peer_cert_chain[slot_id as usize]
-                    .as_ref()
-                    .unwrap()
-                    .cert_chain
-                    .data[4usize
-                    ..(4usize + self.common.negotiate_info.base_hash_sel.get_size() as usize)]
-            {
-                error!("root_hash - fail!\n");
-                return spdm_result_err!(EINVAL);
+    fn store_cached_cert_chain(&mut self, slot_id: u8) {
+        let cache = match self.cert_chain_cache.as_mut() {
+            Some(cache) => cache,
+            None => return,
+        };
+        let cert_chain = match &self.common.peer_info.peer_cert_chain[slot_id as usize] {
+            Some(peer_cert_chain) => &peer_cert_chain.cert_chain,
+            None => return,
+        };
+        let digest = match crypto::hash::hash_all(
+            self.common.negotiate_info.base_hash_sel,
+            cert_chain.as_ref(),
+        ) {
+            Some(digest) => digest,
+            None => return,
+        };
+        // do not cache a chain that does not match the digest the responder reported
+        if let Some(peer_digest) = &self.common.peer_info.peer_digests[slot_id as usize] {
+            if peer_digest.as_ref() != digest.as_ref() {
+                return;
             }
+        }
+        cache.put_cert_chain(self.device_id, slot_id, &digest, cert_chain);
+    }
+
+    pub fn verify_spdm_certificate_chain(&mut self, slot_id: u8) -> SpdmResult {
+        let peer_cert_chain = if let Some(peer_cert_chain) =
+            &self.common.peer_info.peer_cert_chain[slot_id as usize]
+        {
+            &peer_cert_chain.cert_chain
+        } else {
+            error!("peer_cert_chain is not populated!\n");
+            return spdm_result_err!(EIO);
+        };
+        let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
+        if peer_cert_chain.data_size as usize <= 4 + base_hash_size {
+            return spdm_result_err!(EIO);
+        }
+
+        // skip the SpdmCertChainHeader: length, reserved and root hash
+        let cert_chain_data =
+            &peer_cert_chain.data[(4 + base_hash_size)..(peer_cert_chain.data_size as usize)];
+        let (root_cert_begin, root_cert_end) =
+            crypto::cert_operation::get_cert_from_cert_chain(cert_chain_data, 0)?;
+        let root_cert = &cert_chain_data[root_cert_begin..root_cert_end];
+        let root_hash = if let Some(rh) =
+            crypto::hash::hash_all(self.common.negotiate_info.base_hash_sel, root_cert)
+        {
+            rh
+        } else {
+            return spdm_result_err!(ESEC);
+        };
+        if root_hash.as_ref() != &peer_cert_chain.data[4..(4 + base_hash_size)] {
+            error!("root_hash - fail!\n");
+            return spdm_result_err!(EINVAL);
+        }
+
+        let provision_info = &self.common.provision_info;
+        let trusted_by_root_cert = provision_info
+            .peer_root_cert_data
+            .iter()
+            .flatten()
+            .any(|trusted_root| trusted_root.as_ref() == root_cert);
+        let trusted_by_root_hash = provision_info
+            .peer_cert_chain_root_hash
+            .iter()
+            .flatten()
+            .any(|trusted_hash| trusted_hash.as_ref() == root_hash.as_ref());
 
-            if runtime_peer_cert_chain_data.data_size != peer_cert_chain_data.data_size {
-                error!("cert_chain size - fail!\n");
+        if let Some(pinned_cert_chain_data) = &provision_info.peer_cert_chain_data {
+            if pinned_cert_chain_data.as_ref() != cert_chain_data {
+                error!("cert_chain data - fail!\n");
                 debug!(
                     "provision cert_chain data size - {:?}\n",
-                    peer_cert_chain_data.data_size
+                    pinned_cert_chain_data.data_size
                 );
                 debug!(
                     "runtime cert_chain data size - {:?}\n",
-                    runtime_peer_cert_chain_data.data_size
+                    cert_chain_data.len()
                 );
                 return spdm_result_err!(EINVAL);
             }
-            if runtime_peer_cert_chain_data.data != peer_cert_chain_data.data {
-                error!("cert_chain data - fail!\n");
-                return spdm_result_err!(EINVAL);
-            }
+        } else if !trusted_by_root_cert && !trusted_by_root_hash {
+            error!("cert_chain root is not trusted!\n");
+            return spdm_result_err!(ESEC);
+        }
 
-            if crypto::cert_operation::verify_cert_chain(
-                &runtime_peer_cert_chain_data.data
-                    [..(runtime_peer_cert_chain_data.data_size as usize)],
-            )
-            .is_err()
-            {
-                error!("cert_chain verification - fail! - TBD later\n");
-                return spdm_result_err!(EFAULT);
+        if crypto::cert_operation::verify_cert_chain(cert_chain_data).is_err() {
+            error!("cert_chain verification - fail! - TBD later\n");
+            return spdm_result_err!(EFAULT);
+        }
+
+        let cert_model = if self
+            .common
+            .negotiate_info
+            .rsp_capabilities_sel
+            .contains(SpdmResponseCapabilityFlags::ALIAS_CERT_CAP)
+        {
+            SpdmCertModel::AliasCert
+        } else {
+            SpdmCertModel::DeviceCert
+        };
+        if let Err(rule) = crypto::x509v3::check_cert_chain(
+            cert_chain_data,
+            cert_model,
+            false,
+            self.common.negotiate_info.base_asym_sel,
+        ) {
+            error!("cert_chain {} - fail!\n", rule.as_str());
+            return Err(spdm_err!(ESEC, rule.as_str()));
+        }
+
+        let mut crls = self
+            .common
+            .provision_info
+            .peer_root_crl_data
+            .iter()
+            .flatten()
+            .peekable();
+        if crls.peek().is_some() {
+            let now = if let Some(now) = crate::time::get_unix_time() {
+                now
+            } else {
+                error!("no time source to check the CRL validity!\n");
+                return spdm_result_err!(EDEV);
+            };
+            for crl in crls {
+                if let Err(rule) =
+                    crypto::x509v3::check_cert_chain_revocation(cert_chain_data, crl.as_ref(), now)
+                {
+                    error!("cert_chain {} - fail!\n", rule.as_str());
+                    return Err(spdm_err!(ESEC, rule.as_str()));
+                }
             }
-            info!("cert_chain verification - pass!\n");
         }
+        info!("cert_chain verification - pass!\n");
         Ok(())
     }
 }
@@ -338,9 +389,8 @@ mod tests_requester {
         responder.common.negotiate_info.base_asym_sel =
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.provision_info.my_cert_chain = Some(REQ_CERT_CHAIN_DATA);
-        responder.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(responder.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -355,11 +405,112 @@ mod tests_requester {
         requester.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
         requester.common.negotiate_info.base_asym_sel =
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
-        requester.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(requester.common.negotiate_info.base_hash_sel).unwrap(),
-        );
 
         let status = requester.send_receive_spdm_certificate(None, 0).is_ok();
         assert!(status);
+
+        let cert_chain_info = requester.common.get_peer_cert_chain_info(0).unwrap();
+        assert_eq!(cert_chain_info.len(), 3);
+        assert!(cert_chain_info[0].is_ca);
+        assert!(!cert_chain_info[2].is_ca);
+        assert!(requester.common.get_peer_cert_chain_info(1).is_err());
+    }
+
+    #[test]
+    fn test_case1_verify_spdm_certificate_chain() {
+        let (req_config_info, mut req_provision_info) = create_info();
+        let cert_chain_data = req_provision_info.peer_cert_chain_data.take().unwrap();
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_requester = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap,
+            req_config_info,
+            req_provision_info,
+        );
+        requester.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        requester.common.negotiate_info.base_asym_sel =
+            SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+
+        let (root_cert_begin, root_cert_end) =
+            crypto::cert_operation::get_cert_from_cert_chain(cert_chain_data.as_ref(), 0).unwrap();
+        let mut root_cert = SpdmCertChainData {
+            data_size: (root_cert_end - root_cert_begin) as u16,
+            ..Default::default()
+        };
+        root_cert.data[..(root_cert.data_size as usize)]
+            .copy_from_slice(&cert_chain_data.as_ref()[root_cert_begin..root_cert_end]);
+        let root_hash =
+            crypto::hash::hash_all(SpdmBaseHashAlgo::TPM_ALG_SHA_384, root_cert.as_ref()).unwrap();
+
+        let mut peer_cert_chain = SpdmCertChain::default();
+        let data_size = 4 + root_hash.data_size + cert_chain_data.data_size;
+        peer_cert_chain.cert_chain.data_size = data_size;
+        peer_cert_chain.cert_chain.data[0] = (data_size & 0xFF) as u8;
+        peer_cert_chain.cert_chain.data[1] = (data_size >> 8) as u8;
+        peer_cert_chain.cert_chain.data[4..(4 + root_hash.data_size as usize)]
+            .copy_from_slice(root_hash.as_ref());
+        peer_cert_chain.cert_chain.data[(4 + root_hash.data_size as usize)..(data_size as usize)]
+            .copy_from_slice(cert_chain_data.as_ref());
+        requester.common.peer_info.peer_cert_chain[0] = Some(peer_cert_chain);
+
+        // no trust anchor and no pinned chain
+        assert!(requester.verify_spdm_certificate_chain(0).is_err());
+
+        requester.common.provision_info.peer_cert_chain_root_hash[0] = Some(root_hash.clone());
+        assert!(requester.verify_spdm_certificate_chain(0).is_ok());
+
+        let mut other_hash = root_hash;
+        other_hash.data[0] ^= 0xFF;
+        requester.common.provision_info.peer_cert_chain_root_hash[0] = Some(other_hash);
+        assert!(requester.verify_spdm_certificate_chain(0).is_err());
+
+        requester.common.provision_info.peer_root_cert_data[1] = Some(root_cert);
+        assert!(requester.verify_spdm_certificate_chain(0).is_ok());
+
+        // a pinned chain must match exactly
+        let mut pinned_cert_chain_data = cert_chain_data.clone();
+        pinned_cert_chain_data.data[pinned_cert_chain_data.data_size as usize - 1] ^= 0xFF;
+        requester.common.provision_info.peer_cert_chain_data = Some(pinned_cert_chain_data);
+        assert!(requester.verify_spdm_certificate_chain(0).is_err());
+
+        requester.common.provision_info.peer_cert_chain_data = Some(cert_chain_data);
+        assert!(requester.verify_spdm_certificate_chain(0).is_ok());
+
+        // the leaf key must match the negotiated asymmetric algorithm
+        requester.common.negotiate_info.base_asym_sel =
+            SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256;
+        assert!(requester.verify_spdm_certificate_chain(0).is_err());
+        requester.common.negotiate_info.base_asym_sel =
+            SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+
+        // a provisioned CRL from the intermediate revokes the responder cert,
+        // both CRLs are valid from 2026-10-18 to 2036-10-15
+        crate::time::register(TIME_IMPL.clone());
+        set_unix_time(1_800_000_000);
+        for (crl, revoked) in [
+            (
+                &include_bytes!("../../../test_key/EcP384/inter_empty.crl.der")[..],
+                false,
+            ),
+            (
+                &include_bytes!("../../../test_key/EcP384/inter_revoked_responder.crl.der")[..],
+                true,
+            ),
+        ] {
+            let mut crl_data = SpdmCertChainData {
+                data_size: crl.len() as u16,
+                ..Default::default()
+            };
+            crl_data.data[..crl.len()].copy_from_slice(crl);
+            requester.common.provision_info.peer_root_crl_data[0] = Some(crl_data);
+            assert_eq!(requester.verify_spdm_certificate_chain(0).is_err(), revoked);
+        }
+
+        // an expired CRL is rejected
+        set_unix_time(2_200_000_000);
+        assert!(requester.verify_spdm_certificate_chain(0).is_err());
     }
 }
@@ -2,8 +2,6 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::error::{spdm_result_err, SpdmResult};
 use crate::message::*;
 use crate::requester::*;
@@ -21,9 +19,9 @@ impl<'a> RequesterContext<'a> {
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let used = if session_id.is_none() {
-            self.receive_message(&mut receive_buffer, false)?
+            self.receive_message(&mut receive_buffer)?
         } else {
-            self.receive_secured_message(session_id.unwrap(), &mut receive_buffer, false)?
+            self.receive_secured_message(session_id.unwrap(), &mut receive_buffer)?
         };
 
         self.handle_spdm_digest_response(0, &send_buffer[..send_used], &receive_buffer[..used])
@@ -62,36 +60,20 @@ impl<'a> RequesterContext<'a> {
                         if let Some(digests) = digests {
                             debug!("!!! digests : {:02x?}\n", digests);
 
-                            #[cfg(not(feature = "hashed-transcript-data"))]
+                            // digests are listed in slot order, one per bit of slot_mask
+                            let mut slot_digests = digests.digests.iter();
+                            for (slot_id, peer_digest) in
+                                self.common.peer_info.peer_digests.iter_mut().enumerate()
                             {
-                                let message_b = &mut self.common.runtime_info.message_b;
-                                message_b
-                                    .append_message(send_buffer)
-                                    .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                message_b
-                                    .append_message(&receive_buffer[..used])
-                                    .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
+                                *peer_digest = if digests.slot_mask & (1 << slot_id) != 0 {
+                                    slot_digests.next().cloned()
+                                } else {
+                                    None
+                                };
                             }
 
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    send_buffer,
-                                );
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    &receive_buffer[..used],
-                                );
-                            }
+                            self.common.append_message_m1m2(send_buffer)?;
+                            self.common.append_message_m1m2(&receive_buffer[..used])?;
 
                             Ok(())
                         } else {
@@ -116,7 +98,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -155,9 +137,8 @@ mod tests_requester {
             data: [0u8; config::MAX_SPDM_CERT_CHAIN_DATA_SIZE],
         });
         responder.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
-        responder.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(responder.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
 
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
@@ -169,9 +150,6 @@ mod tests_requester {
             req_provision_info,
         );
         requester.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
-        requester.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(requester.common.negotiate_info.base_hash_sel).unwrap(),
-        );
 
         let status = requester.send_receive_spdm_digest(None).is_ok();
         assert!(status);
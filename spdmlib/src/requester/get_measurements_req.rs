@@ -37,10 +37,8 @@ impl<'a> RequesterContext<'a> {
         // Receive
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let used = match session_id {
-            Some(session_id) => {
-                self.receive_secured_message(session_id, &mut receive_buffer, true)?
-            }
-            None => self.receive_message(&mut receive_buffer, true)?,
+            Some(session_id) => self.receive_secured_message(session_id, &mut receive_buffer)?,
+            None => self.receive_message(&mut receive_buffer)?,
         };
 
         self.handle_spdm_measurement_record_response(
@@ -84,31 +82,7 @@ impl<'a> RequesterContext<'a> {
     }
 
     pub fn reset_l1l2(&mut self, session_id: Option<u32>) -> SpdmResult {
-        match session_id {
-            Some(session_id) => {
-                let session = if let Some(s) = self.common.get_session_via_id(session_id) {
-                    s
-                } else {
-                    log::error!("can't find session via session id!");
-                    return spdm_result_err!(EFAULT);
-                };
-                #[cfg(not(feature = "hashed-transcript-data"))]
-                session.runtime_info.message_m.reset_message();
-                #[cfg(feature = "hashed-transcript-data")]
-                {
-                    session.runtime_info.digest_context_l1l2 = None;
-                }
-            }
-            None => {
-                #[cfg(not(feature = "hashed-transcript-data"))]
-                self.common.runtime_info.message_m.reset_message();
-                #[cfg(feature = "hashed-transcript-data")]
-                {
-                    self.common.runtime_info.digest_context_l1l2 = None;
-                }
-            }
-        };
-        Ok(())
+        self.common.reset_transcript_l1l2(session_id)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -144,11 +118,7 @@ impl<'a> RequesterContext<'a> {
                         if let Some(measurements) = measurements {
                             debug!("!!! measurements : {:02x?}\n", measurements);
 
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let base_hash_sel = self.common.negotiate_info.base_hash_sel;
                             let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let message_a = self.common.runtime_info.message_a.clone();
 
                             if spdm_version_sel == SpdmVersion::SpdmVersion12 {
                                 self.common.runtime_info.content_changed =
@@ -159,117 +129,9 @@ impl<'a> RequesterContext<'a> {
                                 self.common.negotiate_info.base_asym_sel.get_size() as usize;
                             let temp_used = used - base_asym_size;
 
-                            match session_id {
-                                Some(session_id) => {
-                                    let session = if let Some(s) =
-                                        self.common.get_session_via_id(session_id)
-                                    {
-                                        s
-                                    } else {
-                                        return spdm_result_err!(EFAULT);
-                                    };
-
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    if session.runtime_info.digest_context_l1l2.is_none() {
-                                        session.runtime_info.digest_context_l1l2 =
-                                            crypto::hash::hash_ctx_init(base_hash_sel);
-                                        if spdm_version_sel == SpdmVersion::SpdmVersion12 {
-                                            crypto::hash::hash_ctx_update(
-                                                session
-                                                    .runtime_info
-                                                    .digest_context_l1l2
-                                                    .as_mut()
-                                                    .unwrap(),
-                                                message_a.as_ref(),
-                                            );
-                                        }
-                                    }
-
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    {
-                                        crypto::hash::hash_ctx_update(
-                                            session
-                                                .runtime_info
-                                                .digest_context_l1l2
-                                                .as_mut()
-                                                .unwrap(),
-                                            send_buffer,
-                                        );
-                                        crypto::hash::hash_ctx_update(
-                                            session
-                                                .runtime_info
-                                                .digest_context_l1l2
-                                                .as_mut()
-                                                .unwrap(),
-                                            &receive_buffer[..temp_used],
-                                        );
-                                    }
-
-                                    #[cfg(not(feature = "hashed-transcript-data"))]
-                                    {
-                                        session
-                                            .runtime_info
-                                            .message_m
-                                            .append_message(send_buffer)
-                                            .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                        session
-                                            .runtime_info
-                                            .message_m
-                                            .append_message(&receive_buffer[..temp_used])
-                                            .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                    }
-                                }
-                                None => {
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    if self.common.runtime_info.digest_context_l1l2.is_none() {
-                                        self.common.runtime_info.digest_context_l1l2 =
-                                            crypto::hash::hash_ctx_init(base_hash_sel);
-                                        if spdm_version_sel == SpdmVersion::SpdmVersion12 {
-                                            crypto::hash::hash_ctx_update(
-                                                self.common
-                                                    .runtime_info
-                                                    .digest_context_l1l2
-                                                    .as_mut()
-                                                    .unwrap(),
-                                                message_a.as_ref(),
-                                            );
-                                        }
-                                    }
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    {
-                                        crypto::hash::hash_ctx_update(
-                                            self.common
-                                                .runtime_info
-                                                .digest_context_l1l2
-                                                .as_mut()
-                                                .unwrap(),
-                                            send_buffer,
-                                        );
-                                        crypto::hash::hash_ctx_update(
-                                            self.common
-                                                .runtime_info
-                                                .digest_context_l1l2
-                                                .as_mut()
-                                                .unwrap(),
-                                            &receive_buffer[..temp_used],
-                                        );
-                                    }
-
-                                    #[cfg(not(feature = "hashed-transcript-data"))]
-                                    {
-                                        self.common
-                                            .runtime_info
-                                            .message_m
-                                            .append_message(send_buffer)
-                                            .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                        self.common
-                                            .runtime_info
-                                            .message_m
-                                            .append_message(&receive_buffer[..temp_used])
-                                            .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-                                    }
-                                }
-                            }
+                            self.common.append_message_l1l2(session_id, send_buffer)?;
+                            self.common
+                                .append_message_l1l2(session_id, &receive_buffer[..temp_used])?;
 
                             // verify signature
                             if measurement_attributes
@@ -331,7 +193,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -351,57 +213,22 @@ impl<'a> RequesterContext<'a> {
         //      number of blocks got measured.
         spdm_measurement_record_structure: &mut SpdmMeasurementRecordStructure, // out
     ) -> SpdmResult {
-        if let Ok(total_number) = self.send_receive_spdm_measurement_record(
+        *out_total_number = self.send_receive_spdm_measurement_record(
             session_id,
             spdm_measuremente_attributes,
             measurement_operation,
             spdm_measurement_record_structure,
             slot_id,
-        ) {
-            *out_total_number = total_number;
-            Ok(())
-        } else {
-            spdm_result_err!(EFAULT)
-        }
+        )?;
+        Ok(())
     }
 
-    #[cfg(feature = "hashed-transcript-data")]
     pub fn verify_measurement_signature(
         &mut self,
         slot_id: u8,
         session_id: Option<u32>,
         signature: &SpdmSignatureStruct,
     ) -> SpdmResult {
-        let message_hash = match session_id {
-            None => {
-                let ctx = self
-                    .common
-                    .runtime_info
-                    .digest_context_l1l2
-                    .as_mut()
-                    .cloned()
-                    .unwrap();
-                crypto::hash::hash_ctx_finalize(ctx)
-            }
-            Some(session_id) => {
-                let session = if let Some(s) = self.common.get_session_via_id(session_id) {
-                    s
-                } else {
-                    return spdm_result_err!(EINVAL);
-                };
-                let ctx = session
-                    .runtime_info
-                    .digest_context_l1l2
-                    .as_mut()
-                    .cloned()
-                    .unwrap();
-                crypto::hash::hash_ctx_finalize(ctx)
-            }
-        };
-        assert!(message_hash.is_some());
-        let message_hash = message_hash.unwrap();
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
         if self.common.peer_info.peer_cert_chain[slot_id as usize].is_none() {
             error!("peer_cert_chain is not populated!\n");
             return spdm_result_err!(EINVAL);
@@ -418,105 +245,21 @@ impl<'a> RequesterContext<'a> {
                 .cert_chain
                 .data_size as usize)];
 
-        let mut message = ManagedBuffer::default();
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_6)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_MEASUREMENTS_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
-
-        crypto::asym_verify::verify(
-            self.common.negotiate_info.base_hash_sel,
-            self.common.negotiate_info.base_asym_sel,
-            cert_chain_data,
-            message.as_ref(),
-            signature,
-        )
-    }
-
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn verify_measurement_signature(
-        &mut self,
-        slot_id: u8,
-        session_id: Option<u32>,
-        signature: &SpdmSignatureStruct,
-    ) -> SpdmResult {
-        let mut message = ManagedBuffer::default();
-
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            let message_a = self.common.runtime_info.message_a.clone();
-            message
-                .append_message(message_a.as_ref())
-                .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
-        }
-
-        match session_id {
-            None => {
-                message
-                    .append_message(self.common.runtime_info.message_m.as_ref())
-                    .ok_or_else(|| spdm_err!(ENOMEM))?;
-            }
+        let transcript = match session_id {
+            None => &self.common.runtime_info.transcript_l1l2,
             Some(session_id) => {
-                let session = if let Some(s) = self.common.get_session_via_id(session_id) {
-                    s
+                if let Some(s) = self.common.get_immutable_session_via_id(session_id) {
+                    &s.runtime_info.transcript_l1l2
                 } else {
                     return spdm_result_err!(EINVAL);
-                };
-                message
-                    .append_message(session.runtime_info.message_m.as_ref())
-                    .ok_or_else(|| spdm_err!(ENOMEM))?;
+                }
             }
-        }
-
-        // we dont need create message hash for verify
-        // we just print message hash for debug purpose
-        debug!("message_m - {:02x?}", message.as_ref());
-        let message_hash =
-            crypto::hash::hash_all(self.common.negotiate_info.base_hash_sel, message.as_ref())
-                .ok_or_else(|| spdm_err!(EFAULT))?;
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
-        if self.common.peer_info.peer_cert_chain[slot_id as usize].is_none() {
-            error!("peer_cert_chain is not populated!\n");
-            return spdm_result_err!(EINVAL);
-        }
-
-        let cert_chain_data = &self.common.peer_info.peer_cert_chain[slot_id as usize]
-            .as_ref()
-            .unwrap()
-            .cert_chain
-            .data[(4usize + self.common.negotiate_info.base_hash_sel.get_size() as usize)
-            ..(self.common.peer_info.peer_cert_chain[slot_id as usize]
-                .as_ref()
-                .unwrap()
-                .cert_chain
-                .data_size as usize)];
-
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_6)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_MEASUREMENTS_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
+        };
+        let message = self.common.get_signing_data(
+            transcript,
+            &SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_6,
+            &SPDM_MEASUREMENTS_SIGN_CONTEXT,
+        )?;
 
         crypto::asym_verify::verify(
             self.common.negotiate_info.base_hash_sel,
@@ -570,16 +313,15 @@ mod tests_requester {
             SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         responder.common.negotiate_info.measurement_hash_sel =
             SpdmMeasurementHashAlgo::TPM_ALG_SHA_384;
-        #[cfg(not(feature = "hashed-transcript-data"))]
         let message_m = &[0];
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        responder
+        assert!(responder
             .common
-            .runtime_info
-            .message_m
-            .append_message(message_m);
+            .append_message_l1l2(None, message_m)
+            .is_ok());
         responder.common.reset_runtime_info();
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::error::{spdm_result_err, SpdmResult};
+use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use crate::message::*;
 use crate::protocol::*;
 use crate::requester::*;
@@ -14,7 +14,7 @@ impl<'a> RequesterContext<'a> {
         self.send_message(&send_buffer[..send_used])?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_message(&mut receive_buffer, false)?;
+        let used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_version_response(0, &send_buffer[..send_used], &receive_buffer[..used])
     }
 
@@ -41,6 +41,9 @@ impl<'a> RequesterContext<'a> {
         match SpdmMessageHeader::read(&mut reader) {
             Some(message_header) => match message_header.request_response_code {
                 SpdmRequestResponseCode::SpdmResponseVersion => {
+                    if message_header.version != SpdmVersion::SpdmVersion10 {
+                        return spdm_result_err!(EFAULT);
+                    }
                     let version =
                         SpdmVersionResponsePayload::spdm_read(&mut self.common, &mut reader);
                     let used = reader.used();
@@ -55,11 +58,14 @@ impl<'a> RequesterContext<'a> {
                         versions
                             .sort_unstable_by(|a, b| b.version.get_u8().cmp(&a.version.get_u8()));
 
-                        self.common.negotiate_info.spdm_version_sel = SpdmVersion::Unknown(0);
-
+                        // the highest version supported by both sides
+                        let mut spdm_version_sel = SpdmVersion::Unknown(0);
                         for spdm_version_struct in
                             versions.iter().take(version_number_entry_count as usize)
                         {
+                            if matches!(spdm_version_struct.version, SpdmVersion::Unknown(_)) {
+                                continue;
+                            }
                             if spdm_version_struct.version
                                 == self.common.provision_info.default_version
                                 || self
@@ -68,13 +74,12 @@ impl<'a> RequesterContext<'a> {
                                     .spdm_version
                                     .contains(&spdm_version_struct.version)
                             {
-                                self.common.negotiate_info.spdm_version_sel =
-                                    spdm_version_struct.version;
+                                spdm_version_sel = spdm_version_struct.version;
                                 break;
                             }
                         }
 
-                        match self.common.negotiate_info.spdm_version_sel {
+                        match spdm_version_sel {
                             SpdmVersion::Unknown(_) => {
                                 debug!(
                                     "Version negotiation failed! with given version list: {:?}",
@@ -83,15 +88,23 @@ impl<'a> RequesterContext<'a> {
                                 return spdm_result_err!(EFAULT);
                             }
                             _ => {
-                                debug!(
-                                    "Version negotiated: {:?}",
-                                    self.common.negotiate_info.spdm_version_sel
-                                );
+                                debug!("Version negotiated: {:?}", spdm_version_sel);
                             }
                         }
+                        if spdm_version_sel.get_u8() < self.policy.min_spdm_version.get_u8() {
+                            error!(
+                                "!!! version {:?} is below the floor !!!\n",
+                                spdm_version_sel
+                            );
+                            return Err(spdm_err!(EPOLICY, "SPDM version"));
+                        }
 
-                        // clear cache data
-                        self.common.reset_runtime_info();
+                        // GET_VERSION restarts the connection: clear cache data, the
+                        // negotiated state, the peer info and the sessions
+                        self.common.reset_context();
+                        self.common.negotiate_info.spdm_version_sel = spdm_version_sel;
+                        self.common.runtime_info.connection_state =
+                            SpdmConnectionState::AfterVersion;
 
                         let message_a = &mut self.common.runtime_info.message_a;
                         message_a
@@ -122,7 +135,7 @@ impl<'a> RequesterContext<'a> {
                                 &receive_buffer[..used],
                             )
                         }
-                        _ => spdm_result_err!(EINVAL),
+                        Err(e) => Err(e),
                     }
                 }
                 _ => spdm_result_err!(EINVAL),
@@ -169,4 +182,112 @@ mod tests_requester {
         let status = requester.send_receive_spdm_version().is_ok();
         assert!(status);
     }
+
+    #[test]
+    fn test_case1_send_receive_spdm_version_negotiation() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        let (req_config_info, req_provision_info) = create_info();
+        rsp_config_info.spdm_version = [
+            SpdmVersion::SpdmVersion10,
+            SpdmVersion::SpdmVersion11,
+            SpdmVersion::SpdmVersion10,
+        ];
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            pcidoe_transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+
+        let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+
+        assert!(requester.init_connection().is_ok());
+        assert_eq!(
+            requester.common.negotiate_info.spdm_version_sel,
+            SpdmVersion::SpdmVersion11
+        );
+
+        // GET_VERSION again drops the negotiated state
+        assert!(requester.send_receive_spdm_version().is_ok());
+        assert_eq!(
+            requester.common.negotiate_info.spdm_version_sel,
+            SpdmVersion::SpdmVersion11
+        );
+        assert!(requester.common.negotiate_info.base_hash_sel.is_empty());
+        assert!(requester
+            .common
+            .negotiate_info
+            .rsp_capabilities_sel
+            .is_empty());
+
+        requester.policy.min_spdm_version = SpdmVersion::SpdmVersion12;
+        let err = requester.send_receive_spdm_version().unwrap_err();
+        assert_eq!(err.num, crate::error::SpdmErrorNum::EPOLICY);
+    }
+
+    #[test]
+    fn test_case2_version_mismatch() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        let (req_config_info, req_provision_info) = create_info();
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            pcidoe_transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+
+        let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+
+        assert!(requester.init_connection().is_ok());
+        assert_eq!(
+            requester.common.negotiate_info.spdm_version_sel,
+            SpdmVersion::SpdmVersion12
+        );
+
+        // the responder rejects a request in another version, and the
+        // requester has to restart the connection
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        let err = requester.send_receive_spdm_digest(None).unwrap_err();
+        assert_eq!(
+            err.peer_error().map(|payload| payload.error_code),
+            Some(SpdmErrorCode::SpdmErrorVersionMismatch)
+        );
+        assert_eq!(
+            requester.common.negotiate_info.spdm_version_sel,
+            SpdmVersion::Unknown(0)
+        );
+
+        assert!(requester.init_connection().is_ok());
+        assert!(requester.send_receive_spdm_digest(None).is_ok());
+    }
 }
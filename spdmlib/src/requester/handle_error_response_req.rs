@@ -5,71 +5,104 @@
 use codec::{Codec, Reader};
 
 use crate::common::session::SpdmSessionState;
-use crate::error::{spdm_result_err, SpdmResult};
+use crate::common::SpdmCodec;
+use crate::error::{spdm_err, spdm_result_err, SpdmErrorKind, SpdmResult};
 use crate::message::*;
+use crate::protocol::SpdmVersion;
 use crate::requester::RequesterContext;
-use crate::time::sleep;
+use crate::time::{sleep, SpdmDeadline};
+
+/// RDT in us, 2 to the power of the exponent.
+fn rdt(rdt_exponent: u8) -> usize {
+    1usize
+        .checked_shl(rdt_exponent as u32)
+        .unwrap_or(usize::MAX)
+}
 
 impl<'a> RequesterContext<'a> {
     fn spdm_handle_response_not_ready(
         &mut self,
-        _session_id: Option<u32>,
-        response: &[u8],
+        session_id: Option<u32>,
+        payload: SpdmErrorResponsePayload,
         original_request_code: SpdmRequestResponseCode,
-        expected_response_code: SpdmRequestResponseCode,
+        expected_response: SpdmExpectedResponse,
     ) -> SpdmResult<ReceivedMessage> {
-        if response.len()
-            != core::mem::size_of::<SpdmMessageHeader>()
-                + core::mem::size_of::<SpdmMessageGeneralPayload>()
-                + core::mem::size_of::<SpdmErrorResponseNotReadyExtData>()
-        {
-            spdm_result_err!(EDEV)
-        } else {
-            let extoff = core::mem::size_of::<SpdmMessageHeader>()
-                + core::mem::size_of::<SpdmMessageGeneralPayload>();
-            let mut extend_error_data_reader = Reader::init(&response[extoff..]);
-            let extend_error_data = if let Some(eed) =
-                SpdmErrorResponseNotReadyExtData::read(&mut extend_error_data_reader)
-            {
-                eed
-            } else {
-                return spdm_result_err!(EINVAL);
-            };
-
-            if extend_error_data.request_code != original_request_code.get_u8() {
-                return spdm_result_err!(EDEV);
+        // an application message has no request code to check
+        let is_original_request = |request_code: u8| {
+            matches!(expected_response, SpdmExpectedResponse::App(_))
+                || request_code == original_request_code.get_u8()
+        };
+        let mut extend_error_data = match payload.extended_data {
+            SpdmErrorResponseExtData::SpdmErrorExtDataNotReady(extend_error_data) => {
+                extend_error_data
             }
+            _ => return spdm_result_err!(EDEV),
+        };
+        if !is_original_request(extend_error_data.request_code) {
+            return spdm_result_err!(EDEV);
+        }
 
-            sleep(2 << extend_error_data.rdt_exponent);
+        // the responder has RDT * RDTM to get ready, asking every RDT
+        let deadline = SpdmDeadline::after(
+            rdt(extend_error_data.rdt_exponent).saturating_mul(extend_error_data.rdtm as usize),
+        );
+        for _ in 0..core::cmp::max(extend_error_data.rdtm, 1) {
+            sleep(rdt(extend_error_data.rdt_exponent));
 
-            self.spdm_requester_respond_if_ready(expected_response_code, extend_error_data)
+            let err = match self.spdm_requester_respond_if_ready(
+                session_id,
+                expected_response,
+                extend_error_data,
+            ) {
+                Err(err) => err,
+                received_message => return received_message,
+            };
+            match err.peer_error().map(|payload| &payload.extended_data) {
+                Some(SpdmErrorResponseExtData::SpdmErrorExtDataNotReady(ext_data))
+                    if is_original_request(ext_data.request_code) && !deadline.is_expired() =>
+                {
+                    info!("responder still not ready\n");
+                    extend_error_data = ext_data.clone();
+                }
+                _ => return Err(err),
+            }
         }
+        Err(spdm_err!(EIO, "peer not ready in time"))
     }
 
     fn spdm_handle_simple_error_response(
         &mut self,
         session_id: Option<u32>,
-        error_code: u8,
+        payload: SpdmErrorResponsePayload,
+        original_request_code: SpdmRequestResponseCode,
     ) -> SpdmResult<ReceivedMessage> {
         /* NOT_READY is treated as error here.
          * Use spdm_handle_error_response_main to handle NOT_READY message in long latency command.*/
-        if error_code == SpdmErrorCode::SpdmErrorResponseNotReady.get_u8() {
-            spdm_result_err!(EDEV)
-        } else if error_code == SpdmErrorCode::SpdmErrorBusy.get_u8() {
-            spdm_result_err!(EBUSY)
-        } else if error_code == SpdmErrorCode::SpdmErrorRequestResynch.get_u8() {
-            if let Some(sid) = session_id {
-                let session = if let Some(s) = self.common.get_session_via_id(sid) {
-                    s
-                } else {
-                    return spdm_result_err!(EFAULT);
-                };
-                session.set_session_state(SpdmSessionState::SpdmSessionNotStarted);
+        let err = match payload.error_code {
+            SpdmErrorCode::SpdmErrorBusy => spdm_err!(EBUSY, "peer busy"),
+            SpdmErrorCode::SpdmErrorRequestResynch => {
+                if let Some(sid) = session_id {
+                    let session = if let Some(s) = self.common.get_session_via_id(sid) {
+                        s
+                    } else {
+                        return spdm_result_err!(EFAULT);
+                    };
+                    session.set_session_state(SpdmSessionState::SpdmSessionNotStarted);
+                }
+                if self.retry_policy.reinit_on_resynch
+                    && original_request_code != SpdmRequestResponseCode::SpdmRequestGetVersion
+                {
+                    info!("resynch requested, run VCA again\n");
+                    self.invalidate_cached_negotiation();
+                    if let Err(e) = self.init_connection() {
+                        error!("!!! resynch : fail {:?} !!!\n", e);
+                    }
+                }
+                spdm_err!(EDEV, "peer requested resynch")
             }
-            spdm_result_err!(EDEV)
-        } else {
-            spdm_result_err!(EDEV)
-        }
+            _ => spdm_err!(EDEV, "peer error"),
+        };
+        Err(err.with_kind(SpdmErrorKind::Peer(payload)))
     }
 
     pub fn spdm_handle_error_response_main(
@@ -78,6 +111,23 @@ impl<'a> RequesterContext<'a> {
         response: &[u8],
         original_request_code: SpdmRequestResponseCode,
         expected_response_code: SpdmRequestResponseCode,
+    ) -> SpdmResult<ReceivedMessage> {
+        self.spdm_handle_error_response(
+            session_id,
+            response,
+            original_request_code,
+            SpdmExpectedResponse::Spdm(expected_response_code),
+        )
+    }
+
+    /// Handle the ERROR `response` to the request `original_request_code`,
+    /// returning the `expected_response` once the responder is ready.
+    pub(crate) fn spdm_handle_error_response(
+        &mut self,
+        session_id: Option<u32>,
+        response: &[u8],
+        original_request_code: SpdmRequestResponseCode,
+        expected_response: SpdmExpectedResponse,
     ) -> SpdmResult<ReceivedMessage> {
         let mut spdm_message_header_reader = Reader::init(response);
         let spdm_message_header =
@@ -88,7 +138,12 @@ impl<'a> RequesterContext<'a> {
             };
         let header_size = spdm_message_header_reader.used();
 
-        if spdm_message_header.version != self.common.negotiate_info.spdm_version_sel {
+        // errors before version negotiation are SPDM 1.0 messages
+        let spdm_version_sel = match self.common.negotiate_info.spdm_version_sel {
+            SpdmVersion::Unknown(_) => SpdmVersion::SpdmVersion10,
+            spdm_version_sel => spdm_version_sel,
+        };
+        if spdm_message_header.version != spdm_version_sel {
             return spdm_result_err!(EINVAL);
         }
 
@@ -97,34 +152,266 @@ impl<'a> RequesterContext<'a> {
         }
 
         let mut spdm_message_payload_reader = Reader::init(&response[header_size..]);
-        let spdm_message_general_payload =
-            if let Some(smgp) = SpdmMessageGeneralPayload::read(&mut spdm_message_payload_reader) {
-                smgp
-            } else {
-                return spdm_result_err!(EINVAL);
-            };
+        let payload = if let Some(payload) =
+            SpdmErrorResponsePayload::spdm_read(&mut self.common, &mut spdm_message_payload_reader)
+        {
+            payload
+        } else {
+            return spdm_result_err!(EINVAL);
+        };
 
-        if spdm_message_general_payload.param1 == SpdmErrorCode::SpdmErrorDecryptError.get_u8() {
-            if let Some(sid) = session_id {
-                let session = if let Some(s) = self.common.get_session_via_id(sid) {
-                    s
-                } else {
-                    return spdm_result_err!(EFAULT);
-                };
-                let _ = session.teardown(sid);
+        match payload.error_code {
+            SpdmErrorCode::SpdmErrorDecryptError => {
+                if let Some(sid) = session_id {
+                    let session = if let Some(s) = self.common.get_session_via_id(sid) {
+                        s
+                    } else {
+                        return spdm_result_err!(EFAULT);
+                    };
+                    let _ = session.teardown(sid);
+                }
+                Err(spdm_err!(ESEC, "peer failed to decrypt")
+                    .with_kind(SpdmErrorKind::Peer(payload)))
             }
-            spdm_result_err!(ESEC)
-        } else if spdm_message_general_payload.param1
-            == SpdmErrorCode::SpdmErrorResponseNotReady.get_u8()
-        {
-            self.spdm_handle_response_not_ready(
+            SpdmErrorCode::SpdmErrorResponseNotReady => self.spdm_handle_response_not_ready(
                 session_id,
-                response,
+                payload,
                 original_request_code,
-                expected_response_code,
+                expected_response,
+            ),
+            _ => self.spdm_handle_simple_error_response(session_id, payload, original_request_code),
+        }
+    }
+}
+
+#[cfg(all(test,))]
+mod tests_requester {
+    use super::*;
+    use crate::common::{SpdmDeviceIo, SpdmTransportEncap};
+    use crate::protocol::SpdmResponseCapabilityFlags;
+    use crate::testlib::*;
+    use crate::{crypto, responder};
+    use core::cell::Cell;
+
+    #[test]
+    fn test_case0_peer_error() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        let (req_config_info, req_provision_info) = create_info();
+        rsp_config_info.rsp_capabilities -= SpdmResponseCapabilityFlags::CERT_CAP;
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            pcidoe_transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+
+        let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+
+        assert!(requester.init_connection().is_ok());
+
+        let err = requester.send_receive_spdm_digest(None).unwrap_err();
+        assert_eq!(
+            err.kind,
+            SpdmErrorKind::Peer(SpdmErrorResponsePayload {
+                error_code: SpdmErrorCode::SpdmErrorUnsupportedRequest,
+                error_data: SpdmRequestResponseCode::SpdmRequestGetDigests.get_u8(),
+                extended_data: SpdmErrorResponseExtData::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_case1_handle_error_response() {
+        let (rsp_config_info, rsp_provision_info) = create_info();
+        let (req_config_info, req_provision_info) = create_info();
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            pcidoe_transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+
+        let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+
+        let err = requester
+            .spdm_handle_error_response_main(
+                None,
+                &[0x11, 0x7F, 0x03, 0x00],
+                SpdmRequestResponseCode::SpdmRequestGetDigests,
+                SpdmRequestResponseCode::SpdmResponseDigests,
             )
-        } else {
-            self.spdm_handle_simple_error_response(session_id, spdm_message_general_payload.param1)
+            .unwrap_err();
+        assert_eq!(err.num, crate::error::SpdmErrorNum::EBUSY);
+        assert_eq!(
+            err.peer_error().map(|payload| payload.error_code),
+            Some(SpdmErrorCode::SpdmErrorBusy)
+        );
+
+        let err = requester
+            .spdm_handle_error_response_main(
+                None,
+                &[0x11, 0x7F, 0xFF, 0x00, 0x01, 0x02, 0x03],
+                SpdmRequestResponseCode::SpdmRequestGetDigests,
+                SpdmRequestResponseCode::SpdmResponseDigests,
+            )
+            .unwrap_err();
+        let mut data = [0u8; 32];
+        data[..3].copy_from_slice(&[0x01, 0x02, 0x03]);
+        assert_eq!(
+            err.peer_error().map(|payload| &payload.extended_data),
+            Some(&SpdmErrorResponseExtData::SpdmErrorExtDataVendorDefined(
+                SpdmErrorResponseVendorExtData { data_size: 3, data }
+            ))
+        );
+
+        let err = requester
+            .spdm_handle_error_response_main(
+                None,
+                &[0x11, 0x7F],
+                SpdmRequestResponseCode::SpdmRequestGetDigests,
+                SpdmRequestResponseCode::SpdmResponseDigests,
+            )
+            .unwrap_err();
+        assert_eq!(err.kind, SpdmErrorKind::Local);
+    }
+
+    /// Answers with ResponseNotReady `not_ready` times, then with DIGESTS.
+    struct NotReadyDeviceIo<'a> {
+        not_ready: &'a Cell<usize>,
+        rdt_exponent: u8,
+        sent: &'a Cell<usize>,
+    }
+
+    impl SpdmDeviceIo for NotReadyDeviceIo<'_> {
+        fn receive(&mut self, read_buffer: &mut [u8], _timeout: usize) -> Result<usize, usize> {
+            let not_ready = [0x11, 0x7F, 0x42, 0x00, self.rdt_exponent, 0x81, 0x01, 0x04];
+            let response: &[u8] = if self.not_ready.get() != 0 {
+                self.not_ready.set(self.not_ready.get() - 1);
+                &not_ready
+            } else {
+                &[0x11, 0x01, 0x00, 0x00]
+            };
+            PciDoeTransportEncap {}
+                .encap(response, read_buffer, false)
+                .map_err(|_| 0)
+        }
+
+        fn send(&mut self, _buffer: &[u8]) -> SpdmResult {
+            self.sent.set(self.sent.get() + 1);
+            Ok(())
         }
+
+        fn flush_all(&mut self) -> SpdmResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_case2_response_not_ready() {
+        let (config_info, provision_info) = create_info();
+        let not_ready = Cell::new(2);
+        let sent = Cell::new(0);
+        let mut device_io = NotReadyDeviceIo {
+            not_ready: &not_ready,
+            rdt_exponent: 10,
+            sent: &sent,
+        };
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crate::time::register(TIME_IMPL.clone());
+
+        let mut requester = RequesterContext::new(
+            &mut device_io,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+
+        // RDT 1024us, RDTM 4
+        let not_ready_response = [0x11, 0x7F, 0x42, 0x00, 0x0A, 0x81, 0x01, 0x04];
+        let start = crate::time::now().unwrap();
+        let received_message = requester
+            .spdm_handle_error_response_main(
+                None,
+                &not_ready_response,
+                SpdmRequestResponseCode::SpdmRequestGetDigests,
+                SpdmRequestResponseCode::SpdmResponseDigests,
+            )
+            .unwrap();
+        assert_eq!(
+            &received_message.receive_buffer[..received_message.used],
+            &[0x11, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(sent.get(), 3);
+        assert_eq!(crate::time::now().unwrap() - start, 3 * 1024);
+    }
+
+    #[test]
+    fn test_case3_response_not_ready_timeout() {
+        let (config_info, provision_info) = create_info();
+        let not_ready = Cell::new(5);
+        let sent = Cell::new(0);
+        // the responder asks for a longer RDT than the first time
+        let mut device_io = NotReadyDeviceIo {
+            not_ready: &not_ready,
+            rdt_exponent: 12,
+            sent: &sent,
+        };
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crate::time::register(TIME_IMPL.clone());
+
+        let mut requester = RequesterContext::new(
+            &mut device_io,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+
+        let not_ready_response = [0x11, 0x7F, 0x42, 0x00, 0x0A, 0x81, 0x01, 0x04];
+        let err = requester
+            .spdm_handle_error_response_main(
+                None,
+                &not_ready_response,
+                SpdmRequestResponseCode::SpdmRequestGetDigests,
+                SpdmRequestResponseCode::SpdmResponseDigests,
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.peer_error().map(|payload| payload.error_code),
+            Some(SpdmErrorCode::SpdmErrorResponseNotReady)
+        );
+        assert_eq!(sent.get(), 2);
     }
 }
@@ -15,10 +15,47 @@ impl<'a> RequesterContext<'a> {
 
         // Receive
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_secured_message(session_id, &mut receive_buffer, false)?;
+        let used = self.receive_secured_message(session_id, &mut receive_buffer)?;
         self.handle_spdm_heartbeat_response(session_id, &receive_buffer[..used])
     }
 
+    /// Send HEARTBEAT in every session idle for its heartbeat period, the
+    /// responder ends a session idle for twice the period.
+    pub fn send_heartbeats_if_due(&mut self) -> SpdmResult {
+        let now = if let Some(now) = crate::time::now() {
+            now
+        } else {
+            return Ok(());
+        };
+        for i in 0..self.common.session.len() {
+            let session = &self.common.session[i];
+            let heartbeat_period = session.heartbeat_period as u64 * 1_000_000;
+            if let Some(idle_time) = session.heartbeat_idle_time(now) {
+                if idle_time >= heartbeat_period {
+                    let session_id = session.get_session_id();
+                    self.send_receive_spdm_heartbeat(session_id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Time in us until `send_heartbeats_if_due` sends the next HEARTBEAT,
+    /// None without a session that uses heartbeat.
+    pub fn next_heartbeat_in(&self) -> Option<u64> {
+        let now = crate::time::now()?;
+        self.common
+            .session
+            .iter()
+            .filter_map(|session| {
+                let heartbeat_period = session.heartbeat_period as u64 * 1_000_000;
+                session
+                    .heartbeat_idle_time(now)
+                    .map(|idle_time| heartbeat_period.saturating_sub(idle_time))
+            })
+            .min()
+    }
+
     pub fn encode_spdm_heartbeat(&mut self, buf: &mut [u8]) -> usize {
         let mut writer = Writer::init(buf);
         let request = SpdmMessage {
@@ -71,7 +108,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -86,6 +123,8 @@ impl<'a> RequesterContext<'a> {
 mod tests_requester {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmConnectionState;
+    use crate::protocol::SpdmMeasurementSummaryHashType;
     use crate::testlib::*;
     use crate::{crypto, responder};
 
@@ -121,6 +160,8 @@ mod tests_requester {
         responder.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionHandshaking);
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -148,4 +189,66 @@ mod tests_requester {
         let status = requester.send_receive_spdm_heartbeat(session_id).is_ok();
         assert!(status);
     }
+
+    #[test]
+    fn test_case1_send_heartbeats_if_due() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        let (req_config_info, req_provision_info) = create_info();
+        rsp_config_info.heartbeat_period = 1;
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+        crate::time::register(TIME_IMPL.clone());
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            pcidoe_transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+
+        let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+
+        assert!(requester.init_connection().is_ok());
+        assert_eq!(requester.next_heartbeat_in(), None);
+        let session_id = requester
+            .start_session(
+                true,
+                0,
+                SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+            )
+            .unwrap();
+        let next_heartbeat_in = requester.next_heartbeat_in().unwrap();
+        assert!(next_heartbeat_in > 0 && next_heartbeat_in <= 1_000_000);
+
+        let session = requester.common.get_session_via_id(session_id).unwrap();
+        assert_eq!(session.heartbeat_period, 1);
+        let sequence_number = session.get_request_direction_sequence_number();
+        assert!(requester.send_heartbeats_if_due().is_ok());
+        let session = requester.common.get_session_via_id(session_id).unwrap();
+        assert_eq!(
+            session.get_request_direction_sequence_number(),
+            sequence_number
+        );
+
+        session.last_message_time = Some(crate::time::now().unwrap() - 1_000_000);
+        assert_eq!(requester.next_heartbeat_in(), Some(0));
+        assert!(requester.send_heartbeats_if_due().is_ok());
+        let session = requester.common.get_session_via_id(session_id).unwrap();
+        assert_eq!(
+            session.get_request_direction_sequence_number(),
+            sequence_number + 1
+        );
+    }
 }
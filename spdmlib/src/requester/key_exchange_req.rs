@@ -5,7 +5,7 @@
 extern crate alloc;
 use alloc::boxed::Box;
 
-use crate::common::ManagedBuffer;
+use crate::common::SpdmTranscript;
 use crate::protocol::*;
 use crate::requester::*;
 
@@ -13,9 +13,7 @@ use crate::crypto;
 
 use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use crate::message::*;
-use crate::protocol::{SpdmMeasurementSummaryHashType, SpdmSignatureStruct, SpdmVersion};
-
-const INITIAL_SESSION_ID: u16 = 0xFFFE;
+use crate::protocol::{SpdmMeasurementSummaryHashType, SpdmSignatureStruct};
 
 impl<'a> RequesterContext<'a> {
     pub fn send_receive_spdm_key_exchange(
@@ -26,7 +24,7 @@ impl<'a> RequesterContext<'a> {
         info!("send spdm key exchange\n");
 
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let (key_exchange_context, send_used) = self.encode_spdm_key_exchange(
+        let (key_exchange_context, req_session_id, send_used) = self.encode_spdm_key_exchange(
             &mut send_buffer,
             slot_id,
             measurement_summary_hash_type,
@@ -35,9 +33,10 @@ impl<'a> RequesterContext<'a> {
 
         // Receive
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let receive_used = self.receive_message(&mut receive_buffer, false)?;
+        let receive_used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_key_exhcange_response(
             0,
+            req_session_id,
             slot_id,
             &send_buffer[..send_used],
             &receive_buffer[..receive_used],
@@ -51,10 +50,13 @@ impl<'a> RequesterContext<'a> {
         buf: &mut [u8],
         slot_id: u8,
         measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
-    ) -> SpdmResult<(Box<dyn crypto::SpdmDheKeyExchange>, usize)> {
+    ) -> SpdmResult<(Box<dyn crypto::SpdmDheKeyExchange>, u16, usize)> {
         let mut writer = Writer::init(buf);
 
-        let req_session_id = INITIAL_SESSION_ID;
+        let req_session_id = self
+            .common
+            .get_next_half_session_id(true)
+            .ok_or_else(|| spdm_err!(ENOMEM, "too many sessions"))?;
 
         let mut random = [0u8; SPDM_RANDOM_SIZE];
         crypto::rand::get_random(&mut random)?;
@@ -65,31 +67,10 @@ impl<'a> RequesterContext<'a> {
 
         debug!("!!! exchange data : {:02x?}\n", exchange);
 
-        let mut opaque;
-        if self
-            .common
-            .negotiate_info
-            .opaque_data_support
-            .contains(SpdmOpaqueSupport::OPAQUE_DATA_FMT1)
-        {
-            opaque = SpdmOpaqueStruct {
-                data_size: crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT1
-                    .len() as u16,
-                ..Default::default()
-            };
-            opaque.data[..(opaque.data_size as usize)].copy_from_slice(
-                crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT1.as_ref(),
-            );
-        } else {
-            opaque = SpdmOpaqueStruct {
-                data_size: crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT0
-                    .len() as u16,
-                ..Default::default()
-            };
-            opaque.data[..(opaque.data_size as usize)].copy_from_slice(
-                crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT0.as_ref(),
-            );
-        }
+        let opaque = SpdmOpaqueStruct::req_set_dmtf_supported_secure_spdm_version_list(
+            &self.common,
+            self.common.config_info.secure_spdm_version,
+        );
 
         let request = SpdmMessage {
             header: SpdmMessageHeader {
@@ -107,12 +88,14 @@ impl<'a> RequesterContext<'a> {
             }),
         };
         request.spdm_encode(&mut self.common, &mut writer);
-        Ok((key_exchange_context, writer.used()))
+        Ok((key_exchange_context, req_session_id, writer.used()))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_spdm_key_exhcange_response(
         &mut self,
         session_id: u32,
+        req_session_id: u16,
         slot_id: u8,
         send_buffer: &[u8],
         receive_buffer: &[u8],
@@ -162,57 +145,15 @@ impl<'a> RequesterContext<'a> {
                                 self.common.negotiate_info.base_hash_sel.get_size() as usize;
                             let temp_receive_used = receive_used - base_asym_size - base_hash_size;
 
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let cert_chain_hash;
-                            #[cfg(feature = "hashed-transcript-data")]
-                            if let Some(hash) = self.common.get_certchain_hash_req(slot_id, false) {
-                                cert_chain_hash = hash;
-                            } else {
-                                return spdm_result_err!(EFAULT);
-                            }
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let mut digest_context_th = crypto::hash::hash_ctx_init(
-                                self.common.negotiate_info.base_hash_sel,
-                            )
-                            .unwrap();
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    self.common.runtime_info.message_a.as_ref(),
-                                );
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    cert_chain_hash.as_ref(),
-                                );
-
-                                crypto::hash::hash_ctx_update(&mut digest_context_th, send_buffer);
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    &receive_buffer[..temp_receive_used],
-                                );
-                            }
-
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let mut message_k = ManagedBuffer::default();
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                message_k
-                                    .append_message(send_buffer)
-                                    .ok_or(spdm_err!(ENOMEM))?;
-                                message_k
-                                    .append_message(&receive_buffer[..temp_receive_used])
-                                    .ok_or(spdm_err!(ENOMEM))?;
-                            }
+                            let mut transcript_th =
+                                self.common.create_req_transcript_th(slot_id, false)?;
+                            transcript_th.append(send_buffer)?;
+                            transcript_th.append(&receive_buffer[..temp_receive_used])?;
 
                             if self
                                 .verify_key_exchange_rsp_signature(
                                     slot_id,
-                                    #[cfg(not(feature = "hashed-transcript-data"))]
-                                    &message_k,
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    digest_context_th.clone(),
+                                    &transcript_th,
                                     &key_exchange_rsp.signature,
                                 )
                                 .is_err()
@@ -223,25 +164,10 @@ impl<'a> RequesterContext<'a> {
                                 info!("verify_key_exchange_rsp_signature pass");
                             }
 
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            message_k
-                                .append_message(key_exchange_rsp.signature.as_ref())
-                                .ok_or(spdm_err!(ENOMEM))?;
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            crypto::hash::hash_ctx_update(
-                                &mut digest_context_th,
-                                key_exchange_rsp.signature.as_ref(),
-                            );
+                            transcript_th.append(key_exchange_rsp.signature.as_ref())?;
 
                             // create session - generate the handshake secret (including finished_key)
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let th1 = self
-                                .common
-                                .calc_req_transcript_hash(slot_id, false, &message_k, None)?;
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let th1 =
-                                crypto::hash::hash_ctx_finalize(digest_context_th.clone()).unwrap();
+                            let th1 = transcript_th.hash()?;
                             debug!("!!! th1 : {:02x?}\n", th1.as_ref());
                             let base_hash_algo = self.common.negotiate_info.base_hash_sel;
                             let dhe_algo = self.common.negotiate_info.dhe_sel;
@@ -259,16 +185,35 @@ impl<'a> RequesterContext<'a> {
                             {
                                 secured_message_version.get_secure_spdm_version()
                             } else {
-                                0
+                                crate::common::opaque::DMTF_SECURE_SPDM_VERSION_10
                             };
+                            // the requester offered no other versions
+                            if !crate::common::opaque::is_supported_secure_spdm_version_up_to(
+                                secure_spdm_version_sel,
+                                self.common.config_info.secure_spdm_version,
+                            ) {
+                                error!(
+                                    "!!! unsupported secured message version {:02x} !!!\n",
+                                    secure_spdm_version_sel
+                                );
+                                return spdm_result_err!(EFAULT);
+                            }
 
                             info!(
                                 "secure_spdm_version_sel set to {:02X?}",
                                 secure_spdm_version_sel
                             );
 
-                            let session_id = ((INITIAL_SESSION_ID as u32) << 16)
+                            let session_id = ((req_session_id as u32) << 16)
                                 + key_exchange_rsp.rsp_session_id as u32;
+                            if self
+                                .common
+                                .get_immutable_session_via_id(session_id)
+                                .is_some()
+                            {
+                                error!("!!! duplicate session ID : fail !!!\n");
+                                return spdm_result_err!(EINVAL);
+                            }
                             let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
                             let session = self
                                 .common
@@ -290,23 +235,14 @@ impl<'a> RequesterContext<'a> {
                             session.generate_handshake_secret(spdm_version_sel, &th1)?;
 
                             // verify HMAC with finished_key
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let transcript_data = self
-                                .common
-                                .calc_req_transcript_data(slot_id, false, &message_k, None)?;
-                            let mut session = self
+                            let session = self
                                 .common
                                 .get_session_via_id(session_id)
                                 .ok_or(spdm_err!(EINVAL))?;
 
                             if session
                                 .verify_hmac_with_response_finished_key(
-                                    #[cfg(not(feature = "hashed-transcript-data"))]
-                                    transcript_data.as_ref(),
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    crypto::hash::hash_ctx_finalize(digest_context_th.clone())
-                                        .unwrap()
-                                        .as_ref(),
+                                    th1.as_ref(),
                                     &key_exchange_rsp.verify_data,
                                 )
                                 .is_err()
@@ -317,23 +253,8 @@ impl<'a> RequesterContext<'a> {
                             } else {
                                 info!("verify_hmac_with_response_finished_key pass");
                             }
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                message_k
-                                    .append_message(key_exchange_rsp.verify_data.as_ref())
-                                    .ok_or(spdm_err!(ENOMEM))?;
-                                session.runtime_info.message_k = message_k;
-                            }
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    key_exchange_rsp.verify_data.as_ref(),
-                                );
-
-                                session.runtime_info.digest_context_th = Some(digest_context_th);
-                            }
+                            transcript_th.append(key_exchange_rsp.verify_data.as_ref())?;
+                            session.runtime_info.transcript_th = transcript_th;
 
                             session.set_session_state(
                                 crate::common::session::SpdmSessionState::SpdmSessionHandshaking,
@@ -361,6 +282,7 @@ impl<'a> RequesterContext<'a> {
                                 let used = rm.used;
                                 self.handle_spdm_key_exhcange_response(
                                     session_id,
+                                    req_session_id,
                                     slot_id,
                                     send_buffer,
                                     &receive_buffer[..used],
@@ -368,7 +290,7 @@ impl<'a> RequesterContext<'a> {
                                     key_exchange_context,
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -378,75 +300,12 @@ impl<'a> RequesterContext<'a> {
         }
     }
 
-    #[cfg(feature = "hashed-transcript-data")]
-    pub fn verify_key_exchange_rsp_signature(
-        &mut self,
-        slot_id: u8,
-        message_k: HashCtx,
-        signature: &SpdmSignatureStruct,
-    ) -> SpdmResult {
-        let message_hash = crypto::hash::hash_ctx_finalize(message_k).unwrap();
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
-        if self.common.peer_info.peer_cert_chain[slot_id as usize].is_none() {
-            error!("peer_cert_chain is not populated!\n");
-            return spdm_result_err!(EINVAL);
-        }
-
-        let cert_chain_data = &self.common.peer_info.peer_cert_chain[slot_id as usize]
-            .as_ref()
-            .unwrap()
-            .cert_chain
-            .data[(4usize + self.common.negotiate_info.base_hash_sel.get_size() as usize)
-            ..(self.common.peer_info.peer_cert_chain[slot_id as usize]
-                .as_ref()
-                .unwrap()
-                .cert_chain
-                .data_size as usize)];
-
-        let mut message = ManagedBuffer::default();
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_2)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
-
-        crypto::asym_verify::verify(
-            self.common.negotiate_info.base_hash_sel,
-            self.common.negotiate_info.base_asym_sel,
-            cert_chain_data,
-            message.as_ref(),
-            signature,
-        )
-    }
-
-    #[cfg(not(feature = "hashed-transcript-data"))]
     pub fn verify_key_exchange_rsp_signature(
         &mut self,
         slot_id: u8,
-        message_k: &ManagedBuffer,
+        transcript_th: &SpdmTranscript,
         signature: &SpdmSignatureStruct,
     ) -> SpdmResult {
-        let mut message = self
-            .common
-            .calc_req_transcript_data(slot_id, false, message_k, None)?;
-        // we dont need create message hash for verify
-        // we just print message hash for debug purpose
-        let message_hash =
-            crypto::hash::hash_all(self.common.negotiate_info.base_hash_sel, message.as_ref())
-                .ok_or_else(|| spdm_err!(EFAULT))?;
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
         if self.common.peer_info.peer_cert_chain[slot_id as usize].is_none() {
             error!("peer_cert_chain is not populated!\n");
             return spdm_result_err!(EINVAL);
@@ -463,21 +322,11 @@ impl<'a> RequesterContext<'a> {
                 .cert_chain
                 .data_size as usize)];
 
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_2)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
+        let message = self.common.get_signing_data(
+            transcript_th,
+            &SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_2,
+            &SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT,
+        )?;
 
         crypto::asym_verify::verify(
             self.common.negotiate_info.base_hash_sel,
@@ -527,28 +376,11 @@ mod tests_requester {
 
         responder.common.reset_runtime_info();
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        responder
-            .common
-            .runtime_info
-            .message_m
-            .append_message(message_m);
-        #[cfg(feature = "hashed-transcript-data")]
-        responder.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(responder.common.negotiate_info.base_hash_sel).unwrap(),
-        );
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            responder
-                .common
-                .runtime_info
-                .digest_context_m1m2
-                .as_mut()
-                .unwrap(),
-            message_m,
-        );
+        assert!(responder.common.append_message_m1m2(message_m).is_ok());
         responder.common.provision_info.my_cert_chain_data = Some(REQ_CERT_CHAIN_DATA);
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -567,27 +399,7 @@ mod tests_requester {
 
         requester.common.reset_runtime_info();
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        requester
-            .common
-            .runtime_info
-            .message_m
-            .append_message(message_m);
-        #[cfg(feature = "hashed-transcript-data")]
-        requester.common.runtime_info.digest_context_m1m2 = Some(
-            crypto::hash::hash_ctx_init(requester.common.negotiate_info.base_hash_sel).unwrap(),
-        );
-
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            requester
-                .common
-                .runtime_info
-                .digest_context_m1m2
-                .as_mut()
-                .unwrap(),
-            message_m,
-        );
+        assert!(requester.common.append_message_m1m2(message_m).is_ok());
         requester.common.peer_info.peer_cert_chain[0] = Some(SpdmCertChain::default());
         requester.common.peer_info.peer_cert_chain[0]
             .as_mut()
@@ -16,7 +16,7 @@ impl<'a> RequesterContext<'a> {
         info!("send spdm key_update\n");
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let used = self.encode_spdm_key_update_op(key_update_operation, tag, &mut send_buffer);
-        self.send_secured_message(session_id, &send_buffer[..used], false)?;
+        self.send_secured_request(session_id, &send_buffer[..used], false)?;
 
         // update key
         let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
@@ -30,7 +30,7 @@ impl<'a> RequesterContext<'a> {
         let update_responder = key_update_operation == SpdmKeyUpdateOperation::SpdmUpdateAllKeys;
         session.create_data_secret_update(spdm_version_sel, update_requester, update_responder)?;
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_secured_message(session_id, &mut receive_buffer, false)?;
+        let used = self.receive_secured_message(session_id, &mut receive_buffer)?;
 
         self.handle_spdm_key_update_op_response(
             session_id,
@@ -122,7 +122,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -155,6 +155,7 @@ impl<'a> RequesterContext<'a> {
 mod tests_requester {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmConnectionState;
     use crate::testlib::*;
     use crate::{crypto, responder};
     #[test]
@@ -207,6 +208,8 @@ mod tests_requester {
                 data: Box::new([0; SPDM_MAX_HASH_SIZE]),
             },
         );
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -2,8 +2,10 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+mod cert_chain_cache;
 mod context;
 
+mod app_message_req;
 mod challenge_req;
 mod end_session_req;
 mod finish_req;
@@ -17,12 +19,21 @@ mod heartbeat_req;
 mod key_exchange_req;
 pub mod key_update_req;
 mod negotiate_algorithms_req;
+mod policy;
 mod psk_exchange_req;
 mod psk_finish_req;
 mod respond_if_ready_req;
+mod retry;
+mod typestate;
 mod vendor_req;
 
+pub use cert_chain_cache::{SpdmCachedNegotiation, SpdmCertChainCache};
 pub use context::RequesterContext;
+pub use policy::SpdmRequesterPolicy;
+pub use retry::{SpdmRetryPolicy, SpdmTimeoutClass};
+pub use typestate::{
+    SpdmAuthenticated, SpdmConnected, SpdmNegotiated, SpdmSecuredSession, SpdmUnconnected,
+};
 
 use crate::common::*;
 use crate::config;
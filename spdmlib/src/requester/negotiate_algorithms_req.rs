@@ -2,8 +2,6 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::error::{spdm_result_err, SpdmResult};
 use crate::message::*;
 use crate::protocol::*;
@@ -16,7 +14,7 @@ impl<'a> RequesterContext<'a> {
         self.send_message(&send_buffer[..send_used])?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_message(&mut receive_buffer, false)?;
+        let used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_algorithm_response(0, &send_buffer[..send_used], &receive_buffer[..used])
     }
 
@@ -101,11 +99,21 @@ impl<'a> RequesterContext<'a> {
 
                             self.common.negotiate_info.measurement_hash_sel =
                                 algorithms.measurement_hash_algo;
-                            if algorithms.base_hash_sel.bits() == 0 {
+                            let config_info = &self.common.config_info;
+                            // exactly one of what we offered
+                            if algorithms.base_hash_sel.bits().count_ones() != 1
+                                || !config_info
+                                    .base_hash_algo
+                                    .contains(algorithms.base_hash_sel)
+                            {
                                 return spdm_result_err!(EINVAL);
                             }
                             self.common.negotiate_info.base_hash_sel = algorithms.base_hash_sel;
-                            if algorithms.base_asym_sel.bits() == 0 {
+                            if algorithms.base_asym_sel.bits().count_ones() != 1
+                                || !config_info
+                                    .base_asym_algo
+                                    .contains(algorithms.base_asym_sel)
+                            {
                                 return spdm_result_err!(EINVAL);
                             }
                             self.common.negotiate_info.base_asym_sel = algorithms.base_asym_sel;
@@ -114,17 +122,38 @@ impl<'a> RequesterContext<'a> {
                                 .iter()
                                 .take(algorithms.alg_struct_count as usize)
                             {
+                                // the responder must select at most one of what we offered
                                 match &alg.alg_supported {
                                     SpdmAlg::SpdmAlgoDhe(v) => {
+                                        if v.bits().count_ones() > 1
+                                            || !config_info.dhe_algo.contains(*v)
+                                        {
+                                            return spdm_result_err!(EINVAL);
+                                        }
                                         self.common.negotiate_info.dhe_sel = *v
                                     }
                                     SpdmAlg::SpdmAlgoAead(v) => {
+                                        if v.bits().count_ones() > 1
+                                            || !config_info.aead_algo.contains(*v)
+                                        {
+                                            return spdm_result_err!(EINVAL);
+                                        }
                                         self.common.negotiate_info.aead_sel = *v
                                     }
                                     SpdmAlg::SpdmAlgoReqAsym(v) => {
+                                        if v.bits().count_ones() > 1
+                                            || !config_info.req_asym_algo.contains(*v)
+                                        {
+                                            return spdm_result_err!(EINVAL);
+                                        }
                                         self.common.negotiate_info.req_asym_sel = *v
                                     }
                                     SpdmAlg::SpdmAlgoKeySchedule(v) => {
+                                        if v.bits().count_ones() > 1
+                                            || !config_info.key_schedule_algo.contains(*v)
+                                        {
+                                            return spdm_result_err!(EINVAL);
+                                        }
                                         self.common.negotiate_info.key_schedule_sel = *v
                                     }
                                     SpdmAlg::SpdmAlgoUnknown(_v) => {}
@@ -139,21 +168,10 @@ impl<'a> RequesterContext<'a> {
                                 .append_message(&receive_buffer[..used])
                                 .map_or_else(|| spdm_result_err!(ENOMEM), |_| Ok(()))?;
 
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                self.common.runtime_info.digest_context_m1m2 =
-                                    crypto::hash::hash_ctx_init(
-                                        self.common.negotiate_info.base_hash_sel,
-                                    );
-                                crypto::hash::hash_ctx_update(
-                                    self.common
-                                        .runtime_info
-                                        .digest_context_m1m2
-                                        .as_mut()
-                                        .unwrap(),
-                                    message_a.as_ref(),
-                                );
-                            }
+                            // M1/M2 restarts from the new message_a on its next message
+                            self.common.runtime_info.transcript_m1m2.reset();
+                            self.common.runtime_info.connection_state =
+                                SpdmConnectionState::Negotiated;
 
                             return Ok(());
                         }
@@ -177,7 +195,7 @@ impl<'a> RequesterContext<'a> {
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -212,6 +230,8 @@ mod tests_requester {
             rsp_provision_info,
         );
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::AfterCapabilities;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
@@ -225,4 +245,133 @@ mod tests_requester {
         let status = requester.send_receive_spdm_algorithm().is_ok();
         assert!(status);
     }
+
+    #[test]
+    fn test_case1_send_receive_spdm_algorithm_priority() {
+        let (mut rsp_config_info, rsp_provision_info) = create_info();
+        let (mut req_config_info, req_provision_info) = create_info();
+        for config_info in [&mut rsp_config_info, &mut req_config_info] {
+            config_info.base_hash_algo =
+                SpdmBaseHashAlgo::TPM_ALG_SHA_256 | SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+            config_info.dhe_algo = SpdmDheAlgo::SECP_256_R1 | SpdmDheAlgo::SECP_384_R1;
+        }
+        rsp_config_info.algo_priority = SpdmAlgoPriority {
+            base_hash_algo: &[SpdmBaseHashAlgo::TPM_ALG_SHA_256],
+            dhe_algo: &[SpdmDheAlgo::SECP_256_R1, SpdmDheAlgo::SECP_384_R1],
+            ..Default::default()
+        };
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_responder = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+
+        let mut responder = responder::ResponderContext::new(
+            &mut device_io_responder,
+            pcidoe_transport_encap,
+            rsp_config_info,
+            rsp_provision_info,
+        );
+
+        responder.common.runtime_info.connection_state = SpdmConnectionState::AfterCapabilities;
+
+        let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
+        let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap2,
+            req_config_info,
+            req_provision_info,
+        );
+
+        assert!(requester.send_receive_spdm_algorithm().is_ok());
+        assert_eq!(
+            requester.common.negotiate_info.base_hash_sel,
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256
+        );
+        assert_eq!(
+            requester.common.negotiate_info.dhe_sel,
+            SpdmDheAlgo::SECP_256_R1
+        );
+    }
+
+    #[test]
+    fn test_case2_handle_spdm_algorithm_response_selection() {
+        let (req_config_info, req_provision_info) = create_info();
+
+        let shared_buffer = SharedBuffer::new();
+        let mut device_io_requester = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+
+        let mut requester = RequesterContext::new(
+            &mut device_io_requester,
+            pcidoe_transport_encap,
+            req_config_info,
+            req_provision_info,
+        );
+        requester.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+
+        for (base_hash_sel, base_asym_sel, valid) in [
+            (
+                SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+                true,
+            ),
+            (
+                SpdmBaseHashAlgo::empty(),
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+                false,
+            ),
+            (
+                SpdmBaseHashAlgo::TPM_ALG_SHA_256 | SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+                false,
+            ),
+            (
+                SpdmBaseHashAlgo::TPM_ALG_SHA_512,
+                SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384,
+                false,
+            ),
+            (
+                SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+                SpdmBaseAsymAlgo::empty(),
+                false,
+            ),
+            (
+                SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+                SpdmBaseAsymAlgo::TPM_ALG_RSASSA_2048,
+                false,
+            ),
+        ] {
+            let response = SpdmMessage {
+                header: SpdmMessageHeader {
+                    version: SpdmVersion::SpdmVersion11,
+                    request_response_code: SpdmRequestResponseCode::SpdmResponseAlgorithms,
+                },
+                payload: SpdmMessagePayload::SpdmAlgorithmsResponse(
+                    SpdmAlgorithmsResponsePayload {
+                        measurement_specification_sel: SpdmMeasurementSpecification::DMTF,
+                        measurement_hash_algo: SpdmMeasurementHashAlgo::TPM_ALG_SHA_384,
+                        base_asym_sel,
+                        base_hash_sel,
+                        ..Default::default()
+                    },
+                ),
+            };
+            let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            let mut writer = Writer::init(&mut receive_buffer);
+            response.spdm_encode(&mut requester.common, &mut writer);
+            let used = writer.used();
+
+            requester.common.reset_runtime_info();
+            assert_eq!(
+                requester
+                    .handle_spdm_algorithm_response(0, &[], &receive_buffer[..used])
+                    .is_ok(),
+                valid
+            );
+        }
+    }
 }
@@ -0,0 +1,156 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use crate::common::SpdmNegotiateInfo;
+use crate::error::{spdm_err, SpdmResult};
+use crate::protocol::*;
+
+/// What the requester accepts from the responder, checked once VCA is done.
+///
+/// The default policy accepts everything the library supports.
+#[derive(Debug, Clone, Copy)]
+pub struct SpdmRequesterPolicy {
+    pub min_spdm_version: SpdmVersion,
+    /// Minimum digest size in bytes, for both the base hash and the measurement hash.
+    pub min_hash_size: u16,
+    pub allowed_base_asym_algo: SpdmBaseAsymAlgo,
+    pub allowed_req_asym_algo: SpdmReqAsymAlgo,
+    pub allowed_dhe_algo: SpdmDheAlgo,
+    pub allowed_aead_algo: SpdmAeadAlgo,
+    /// Capabilities the responder must report.
+    pub required_rsp_capabilities: SpdmResponseCapabilityFlags,
+}
+
+impl Default for SpdmRequesterPolicy {
+    fn default() -> Self {
+        SpdmRequesterPolicy {
+            min_spdm_version: SpdmVersion::SpdmVersion10,
+            min_hash_size: 0,
+            allowed_base_asym_algo: SpdmBaseAsymAlgo::all(),
+            allowed_req_asym_algo: SpdmReqAsymAlgo::all(),
+            allowed_dhe_algo: SpdmDheAlgo::all(),
+            allowed_aead_algo: SpdmAeadAlgo::all(),
+            required_rsp_capabilities: SpdmResponseCapabilityFlags::empty(),
+        }
+    }
+}
+
+impl SpdmRequesterPolicy {
+    /// Check the negotiated state, the error (EPOLICY) message names the violated rule.
+    ///
+    /// DHE, AEAD and requester asym are only checked when one was selected.
+    pub fn check(&self, negotiate_info: &SpdmNegotiateInfo) -> SpdmResult {
+        if negotiate_info.spdm_version_sel.get_u8() < self.min_spdm_version.get_u8() {
+            return Err(spdm_err!(EPOLICY, "SPDM version"));
+        }
+        if !negotiate_info
+            .rsp_capabilities_sel
+            .contains(self.required_rsp_capabilities)
+        {
+            return Err(spdm_err!(EPOLICY, "responder capabilities"));
+        }
+
+        let base_hash_sel = negotiate_info.base_hash_sel;
+        if base_hash_sel.bits().count_ones() != 1 || base_hash_sel.get_size() < self.min_hash_size {
+            return Err(spdm_err!(EPOLICY, "base hash algorithm"));
+        }
+        let measurement_hash_sel = negotiate_info.measurement_hash_sel;
+        if !measurement_hash_sel.is_empty()
+            && (measurement_hash_sel.bits().count_ones() != 1
+                || (measurement_hash_sel != SpdmMeasurementHashAlgo::RAW_BIT_STREAM
+                    && measurement_hash_sel.get_size() < self.min_hash_size))
+        {
+            return Err(spdm_err!(EPOLICY, "measurement hash algorithm"));
+        }
+
+        if !self
+            .allowed_base_asym_algo
+            .contains(negotiate_info.base_asym_sel)
+        {
+            return Err(spdm_err!(EPOLICY, "base asym algorithm"));
+        }
+        if !self
+            .allowed_req_asym_algo
+            .contains(negotiate_info.req_asym_sel)
+        {
+            return Err(spdm_err!(EPOLICY, "requester asym algorithm"));
+        }
+        if !self.allowed_dhe_algo.contains(negotiate_info.dhe_sel) {
+            return Err(spdm_err!(EPOLICY, "DHE algorithm"));
+        }
+        if !self.allowed_aead_algo.contains(negotiate_info.aead_sel) {
+            return Err(spdm_err!(EPOLICY, "AEAD algorithm"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test,))]
+mod tests {
+    use super::*;
+
+    fn negotiate_info() -> SpdmNegotiateInfo {
+        SpdmNegotiateInfo {
+            spdm_version_sel: SpdmVersion::SpdmVersion11,
+            rsp_capabilities_sel: SpdmResponseCapabilityFlags::CERT_CAP
+                | SpdmResponseCapabilityFlags::MEAS_CAP_SIG,
+            measurement_hash_sel: SpdmMeasurementHashAlgo::TPM_ALG_SHA_384,
+            base_hash_sel: SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            base_asym_sel: SpdmBaseAsymAlgo::TPM_ALG_RSASSA_2048,
+            dhe_sel: SpdmDheAlgo::SECP_384_R1,
+            aead_sel: SpdmAeadAlgo::AES_256_GCM,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_case0_default_policy() {
+        let policy = SpdmRequesterPolicy::default();
+        assert!(policy.check(&negotiate_info()).is_ok());
+
+        let mut info = negotiate_info();
+        info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_256 | SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        assert!(policy.check(&info).is_err());
+    }
+
+    #[test]
+    fn test_case1_policy_violation() {
+        let policy = SpdmRequesterPolicy {
+            min_spdm_version: SpdmVersion::SpdmVersion11,
+            min_hash_size: SHA384_DIGEST_SIZE as u16,
+            allowed_base_asym_algo: SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384
+                | SpdmBaseAsymAlgo::TPM_ALG_RSASSA_3072,
+            required_rsp_capabilities: SpdmResponseCapabilityFlags::MEAS_CAP_SIG,
+            ..Default::default()
+        };
+        let err = policy.check(&negotiate_info()).unwrap_err();
+        assert_eq!(err.num, crate::error::SpdmErrorNum::EPOLICY);
+        assert_eq!(err.msg, "base asym algorithm");
+
+        let mut info = negotiate_info();
+        info.base_asym_sel = SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+        assert!(policy.check(&info).is_ok());
+
+        info.spdm_version_sel = SpdmVersion::SpdmVersion10;
+        assert_eq!(policy.check(&info).unwrap_err().msg, "SPDM version");
+        info.spdm_version_sel = SpdmVersion::SpdmVersion12;
+
+        info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_256;
+        assert_eq!(policy.check(&info).unwrap_err().msg, "base hash algorithm");
+        info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_512;
+
+        info.measurement_hash_sel = SpdmMeasurementHashAlgo::TPM_ALG_SHA_256;
+        assert_eq!(
+            policy.check(&info).unwrap_err().msg,
+            "measurement hash algorithm"
+        );
+        info.measurement_hash_sel = SpdmMeasurementHashAlgo::RAW_BIT_STREAM;
+
+        info.rsp_capabilities_sel = SpdmResponseCapabilityFlags::MEAS_CAP_NO_SIG;
+        assert_eq!(
+            policy.check(&info).unwrap_err().msg,
+            "responder capabilities"
+        );
+    }
+}
@@ -13,11 +13,6 @@ use crate::requester::*;
 extern crate alloc;
 use alloc::boxed::Box;
 
-#[cfg(not(feature = "hashed-transcript-data"))]
-use crate::common::ManagedBuffer;
-
-const INITIAL_SESSION_ID: u16 = 0xFFFD;
-
 impl<'a> RequesterContext<'a> {
     pub fn send_receive_spdm_psk_exchange(
         &mut self,
@@ -26,16 +21,17 @@ impl<'a> RequesterContext<'a> {
         info!("send spdm psk exchange\n");
 
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let send_used =
+        let (req_session_id, send_used) =
             self.encode_spdm_psk_exchange(measurement_summary_hash_type, &mut send_buffer)?;
 
         self.send_message(&send_buffer[..send_used])?;
 
         // Receive
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let receive_used = self.receive_message(&mut receive_buffer, false)?;
+        let receive_used = self.receive_message(&mut receive_buffer)?;
         self.handle_spdm_psk_exchange_response(
             0,
+            req_session_id,
             measurement_summary_hash_type,
             &send_buffer[..send_used],
             &receive_buffer[..receive_used],
@@ -46,39 +42,21 @@ impl<'a> RequesterContext<'a> {
         &mut self,
         measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
         buf: &mut [u8],
-    ) -> SpdmResult<usize> {
+    ) -> SpdmResult<(u16, usize)> {
         let mut writer = Writer::init(buf);
 
-        let req_session_id = INITIAL_SESSION_ID;
+        let req_session_id = self
+            .common
+            .get_next_half_session_id(true)
+            .ok_or_else(|| spdm_err!(ENOMEM, "too many sessions"))?;
 
         let mut psk_context = [0u8; MAX_SPDM_PSK_CONTEXT_SIZE];
         crypto::rand::get_random(&mut psk_context)?;
 
-        let mut opaque;
-        if self
-            .common
-            .negotiate_info
-            .opaque_data_support
-            .contains(SpdmOpaqueSupport::OPAQUE_DATA_FMT1)
-        {
-            opaque = SpdmOpaqueStruct {
-                data_size: crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT1
-                    .len() as u16,
-                ..Default::default()
-            };
-            opaque.data[..(opaque.data_size as usize)].copy_from_slice(
-                crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT1.as_ref(),
-            );
-        } else {
-            opaque = SpdmOpaqueStruct {
-                data_size: crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT0
-                    .len() as u16,
-                ..Default::default()
-            };
-            opaque.data[..(opaque.data_size as usize)].copy_from_slice(
-                crate::common::opaque::REQ_DMTF_OPAQUE_DATA_SUPPORT_VERSION_LIST_FMT0.as_ref(),
-            );
-        }
+        let opaque = SpdmOpaqueStruct::req_set_dmtf_supported_secure_spdm_version_list(
+            &self.common,
+            self.common.config_info.secure_spdm_version,
+        );
 
         let request = SpdmMessage {
             header: SpdmMessageHeader {
@@ -97,12 +75,13 @@ impl<'a> RequesterContext<'a> {
             }),
         };
         request.spdm_encode(&mut self.common, &mut writer);
-        Ok(writer.used())
+        Ok((req_session_id, writer.used()))
     }
 
     pub fn handle_spdm_psk_exchange_response(
         &mut self,
         session_id: u32,
+        req_session_id: u16,
         measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
         send_buffer: &[u8],
         receive_buffer: &[u8],
@@ -137,48 +116,13 @@ impl<'a> RequesterContext<'a> {
                                 self.common.negotiate_info.base_hash_sel.get_size() as usize;
                             let temp_receive_used = receive_used - base_hash_size;
 
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let mut digest_context_th = crypto::hash::hash_ctx_init(
-                                self.common.negotiate_info.base_hash_sel,
-                            )
-                            .unwrap();
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    self.common.runtime_info.message_a.as_ref(),
-                                );
-                                crypto::hash::hash_ctx_update(&mut digest_context_th, send_buffer);
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    &receive_buffer[..temp_receive_used],
-                                );
-                            }
-
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let mut message_k = ManagedBuffer::default();
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                message_k
-                                    .append_message(send_buffer)
-                                    .ok_or(spdm_err!(ENOMEM))?;
-
-                                message_k
-                                    .append_message(&receive_buffer[..temp_receive_used])
-                                    .ok_or(spdm_err!(ENOMEM))?;
-                            }
+                            let mut transcript_th =
+                                self.common.create_req_transcript_th(INVALID_SLOT, true)?;
+                            transcript_th.append(send_buffer)?;
+                            transcript_th.append(&receive_buffer[..temp_receive_used])?;
 
                             // create session - generate the handshake secret (including finished_key)
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let th1 = self.common.calc_req_transcript_hash(
-                                INVALID_SLOT,
-                                true,
-                                &message_k,
-                                None,
-                            )?;
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let th1 =
-                                crypto::hash::hash_ctx_finalize(digest_context_th.clone()).unwrap();
+                            let th1 = transcript_th.hash()?;
                             debug!("!!! th1 : {:02x?}\n", th1.as_ref());
                             let base_hash_algo = self.common.negotiate_info.base_hash_sel;
                             let dhe_algo = self.common.negotiate_info.dhe_sel;
@@ -196,11 +140,30 @@ impl<'a> RequesterContext<'a> {
                             {
                                 secured_message_version.get_secure_spdm_version()
                             } else {
-                                0
+                                crate::common::opaque::DMTF_SECURE_SPDM_VERSION_10
                             };
+                            // the requester offered no other versions
+                            if !crate::common::opaque::is_supported_secure_spdm_version_up_to(
+                                secure_spdm_version_sel,
+                                self.common.config_info.secure_spdm_version,
+                            ) {
+                                error!(
+                                    "!!! unsupported secured message version {:02x} !!!\n",
+                                    secure_spdm_version_sel
+                                );
+                                return spdm_result_err!(EFAULT);
+                            }
 
-                            let session_id = ((INITIAL_SESSION_ID as u32) << 16)
+                            let session_id = ((req_session_id as u32) << 16)
                                 + psk_exchange_rsp.rsp_session_id as u32;
+                            if self
+                                .common
+                                .get_immutable_session_via_id(session_id)
+                                .is_some()
+                            {
+                                error!("!!! duplicate session ID : fail !!!\n");
+                                return spdm_result_err!(EINVAL);
+                            }
                             let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
                             let session = self
                                 .common
@@ -227,25 +190,13 @@ impl<'a> RequesterContext<'a> {
                             session.generate_handshake_secret(spdm_version_sel, &th1)?;
 
                             // verify HMAC with finished_key
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let transcript_data = self.common.calc_req_transcript_data(
-                                INVALID_SLOT,
-                                true,
-                                &message_k,
-                                None,
-                            )?;
                             let session = self
                                 .common
                                 .get_session_via_id(session_id)
                                 .ok_or(spdm_err!(EINVAL))?;
                             if session
                                 .verify_hmac_with_response_finished_key(
-                                    #[cfg(not(feature = "hashed-transcript-data"))]
-                                    transcript_data.as_ref(),
-                                    #[cfg(feature = "hashed-transcript-data")]
-                                    crypto::hash::hash_ctx_finalize(digest_context_th.clone())
-                                        .unwrap()
-                                        .as_ref(),
+                                    th1.as_ref(),
                                     &psk_exchange_rsp.verify_data,
                                 )
                                 .is_err()
@@ -256,21 +207,8 @@ impl<'a> RequesterContext<'a> {
                             } else {
                                 info!("verify_hmac_with_response_finished_key pass");
                             }
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                message_k
-                                    .append_message(psk_exchange_rsp.verify_data.as_ref())
-                                    .ok_or(spdm_err!(ENOMEM))?;
-                                session.runtime_info.message_k = message_k;
-                            }
-                            #[cfg(feature = "hashed-transcript-data")]
-                            {
-                                crypto::hash::hash_ctx_update(
-                                    &mut digest_context_th,
-                                    psk_exchange_rsp.verify_data.as_ref(),
-                                );
-                                session.runtime_info.digest_context_th = Some(digest_context_th);
-                            }
+                            transcript_th.append(psk_exchange_rsp.verify_data.as_ref())?;
+                            session.runtime_info.transcript_th = transcript_th;
 
                             session.set_session_state(
                                 crate::common::session::SpdmSessionState::SpdmSessionHandshaking,
@@ -298,12 +236,13 @@ impl<'a> RequesterContext<'a> {
                                 let used = rm.used;
                                 self.handle_spdm_psk_exchange_response(
                                     session_id,
+                                    req_session_id,
                                     measurement_summary_hash_type,
                                     send_buffer,
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -346,6 +285,8 @@ mod tests_requester {
         responder.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
         responder.common.negotiate_info.aead_sel = SpdmAeadAlgo::AES_128_GCM;
 
+        responder.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
         let pcidoe_transport_encap2 = &mut PciDoeTransportEncap {};
         let mut device_io_requester = FakeSpdmDeviceIo::new(&shared_buffer, &mut responder);
 
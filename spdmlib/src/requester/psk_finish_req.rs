@@ -2,33 +2,26 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use crate::message::*;
 use crate::protocol::*;
 use crate::requester::*;
 extern crate alloc;
-use crate::common::ManagedBuffer;
 use alloc::boxed::Box;
 
 impl<'a> RequesterContext<'a> {
     pub fn send_receive_spdm_psk_finish(&mut self, session_id: u32) -> SpdmResult {
         info!("send spdm psk_finish\n");
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let (send_used, message_f) = self.encode_spdm_psk_finish(session_id, &mut send_buffer)?;
+        let send_used = self.encode_spdm_psk_finish(session_id, &mut send_buffer)?;
         self.send_secured_message(session_id, &send_buffer[..send_used], false)?;
 
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let receive_used = self.receive_secured_message(session_id, &mut receive_buffer, false)?;
-        self.handle_spdm_psk_finish_response(session_id, message_f, &receive_buffer[..receive_used])
+        let receive_used = self.receive_secured_message(session_id, &mut receive_buffer)?;
+        self.handle_spdm_psk_finish_response(session_id, &receive_buffer[..receive_used])
     }
 
-    pub fn encode_spdm_psk_finish(
-        &mut self,
-        session_id: u32,
-        buf: &mut [u8],
-    ) -> SpdmResult<(usize, ManagedBuffer)> {
+    pub fn encode_spdm_psk_finish(&mut self, session_id: u32, buf: &mut [u8]) -> SpdmResult<usize> {
         let mut writer = Writer::init(buf);
 
         let request = SpdmMessage {
@@ -50,82 +43,27 @@ impl<'a> RequesterContext<'a> {
         let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
         let temp_used = send_used - base_hash_size;
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let mut message_f = ManagedBuffer::default();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        message_f
-            .append_message(&buf[..temp_used])
-            .ok_or(spdm_err!(ENOMEM))?;
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let session = if let Some(s) = self.common.get_immutable_session_via_id(session_id) {
-            s
-        } else {
-            return spdm_result_err!(EFAULT);
-        };
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let message_k = &session.runtime_info.message_k;
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = self.common.calc_req_transcript_data(
-            INVALID_SLOT,
-            true,
-            message_k,
-            Some(&message_f),
-        )?;
         let session = if let Some(s) = self.common.get_session_via_id(session_id) {
             s
         } else {
             return spdm_result_err!(EFAULT);
         };
-
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            session.runtime_info.digest_context_th.as_mut().unwrap(),
-            &buf[..temp_used],
-        );
-        #[cfg(feature = "hashed-transcript-data")]
-        let message_hash = crypto::hash::hash_ctx_finalize(
-            session
-                .runtime_info
-                .digest_context_th
-                .as_mut()
-                .cloned()
-                .unwrap(),
-        );
-        let hmac = session.generate_hmac_with_request_finished_key(
-            #[cfg(feature = "hashed-transcript-data")]
-            message_hash.unwrap().as_ref(),
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            transcript_data.as_ref(),
-        )?;
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        message_f
-            .append_message(hmac.as_ref())
-            .ok_or(spdm_err!(ENOMEM))?;
-
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                hmac.as_ref(),
-            );
-        }
+        session
+            .runtime_info
+            .transcript_th
+            .append(&buf[..temp_used])?;
+        let message_hash = session.runtime_info.transcript_th.hash()?;
+        let hmac = session.generate_hmac_with_request_finished_key(message_hash.as_ref())?;
+        session.runtime_info.transcript_th.append(hmac.as_ref())?;
 
         // patch the message before send
         buf[(send_used - base_hash_size)..send_used].copy_from_slice(hmac.as_ref());
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        return Ok((send_used, message_f));
-        #[cfg(feature = "hashed-transcript-data")]
-        Ok((send_used, ManagedBuffer::default()))
+        Ok(send_used)
     }
 
     pub fn handle_spdm_psk_finish_response(
         &mut self,
         session_id: u32,
-        #[cfg(not(feature = "hashed-transcript-data"))] mut message_f: ManagedBuffer,
-        #[cfg(feature = "hashed-transcript-data")] message_f: ManagedBuffer, // never use message_f for hashed-transcript-data, use session.runtime_info.message_f
         receive_buffer: &[u8],
     ) -> SpdmResult {
         let mut reader = Reader::init(receive_buffer);
@@ -142,71 +80,20 @@ impl<'a> RequesterContext<'a> {
                         if let Some(psk_finish_rsp) = psk_finish_rsp {
                             debug!("!!! psk_finish rsp : {:02x?}\n", psk_finish_rsp);
                             let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            {
-                                let session =
-                                    if let Some(s) = self.common.get_session_via_id(session_id) {
-                                        s
-                                    } else {
-                                        return spdm_result_err!(EFAULT);
-                                    };
-
-                                message_f
-                                    .append_message(&receive_buffer[..receive_used])
-                                    .ok_or(spdm_err!(ENOMEM))?;
-
-                                session.runtime_info.message_f = message_f;
-                            }
-
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let session = if let Some(s) =
-                                self.common.get_immutable_session_via_id(session_id)
-                            {
-                                s
-                            } else {
-                                return spdm_result_err!(EFAULT);
-                            };
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let message_k = &session.runtime_info.message_k; // generate the data secret
-                            #[cfg(not(feature = "hashed-transcript-data"))]
-                            let th2 = self.common.calc_req_transcript_hash(
-                                INVALID_SLOT,
-                                true,
-                                message_k,
-                                Some(&session.runtime_info.message_f),
-                            )?;
-
-                            #[cfg(feature = "hashed-transcript-data")]
                             let session =
                                 if let Some(s) = self.common.get_session_via_id(session_id) {
                                     s
                                 } else {
                                     return spdm_result_err!(EFAULT);
                                 };
-                            #[cfg(feature = "hashed-transcript-data")]
-                            crypto::hash::hash_ctx_update(
-                                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                                &receive_buffer[..receive_used],
-                            );
-
-                            #[cfg(feature = "hashed-transcript-data")]
-                            let th2 = crypto::hash::hash_ctx_finalize(
-                                session
-                                    .runtime_info
-                                    .digest_context_th
-                                    .as_mut()
-                                    .cloned()
-                                    .unwrap(),
-                            )
-                            .unwrap();
+                            session
+                                .runtime_info
+                                .transcript_th
+                                .append(&receive_buffer[..receive_used])?;
 
+                            // generate the data secret
+                            let th2 = session.runtime_info.transcript_th.hash()?;
                             debug!("!!! th2 : {:02x?}\n", th2.as_ref());
-                            let session =
-                                if let Some(s) = self.common.get_session_via_id(session_id) {
-                                    s
-                                } else {
-                                    return spdm_result_err!(EFAULT);
-                                };
                             session.generate_data_secret(spdm_version_sel, &th2)?;
                             session.set_session_state(
                                 crate::common::session::SpdmSessionState::SpdmSessionEstablished,
@@ -231,11 +118,10 @@ impl<'a> RequesterContext<'a> {
                                 let used = rm.used;
                                 self.handle_spdm_psk_finish_response(
                                     session_id,
-                                    message_f,
                                     &receive_buffer[..used],
                                 )
                             }
-                            _ => spdm_result_err!(EINVAL),
+                            Err(e) => Err(e),
                         }
                     }
                     _ => spdm_result_err!(EINVAL),
@@ -299,9 +185,8 @@ mod tests_requester {
         );
         responder.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
-        responder.common.session[0].runtime_info.digest_context_th = Some(
-            crypto::hash::hash_ctx_init(responder.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+        responder.common.session[0].runtime_info.transcript_th =
+            responder.common.new_transcript().unwrap();
 
         let dhe_secret = SpdmDheFinalKeyStruct {
             data_size: 48,
@@ -336,9 +221,8 @@ mod tests_requester {
         );
         requester.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
-        requester.common.session[0].runtime_info.digest_context_th = Some(
-            crypto::hash::hash_ctx_init(requester.common.negotiate_info.base_hash_sel).unwrap(),
-        );
+        requester.common.session[0].runtime_info.transcript_th =
+            requester.common.new_transcript().unwrap();
 
         let dhe_secret = SpdmDheFinalKeyStruct {
             data_size: 48,
@@ -2,14 +2,17 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::error::{spdm_result_err, SpdmResult};
+use crate::error::{spdm_err, spdm_result_err, SpdmErrorKind, SpdmResult};
 use crate::message::*;
 use crate::requester::*;
 
 impl<'a> RequesterContext<'a> {
+    /// Ask the responder for the response to the request it was not ready
+    /// for, in the session `session_id` if any.
     pub fn spdm_requester_respond_if_ready(
         &mut self,
-        expected_response_code: SpdmRequestResponseCode,
+        session_id: Option<u32>,
+        expected_response: SpdmExpectedResponse,
         extend_error_data: SpdmErrorResponseNotReadyExtData,
     ) -> SpdmResult<ReceivedMessage> {
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
@@ -27,20 +30,57 @@ impl<'a> RequesterContext<'a> {
         request.spdm_encode(&mut self.common, &mut writer);
 
         let used = writer.used();
-        self.send_message(&send_buffer[..used])?;
-
         let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
-        let used = self.receive_message(&mut receive_buffer, false)?;
+        let (used, app_type) = match session_id {
+            Some(session_id) => {
+                self.send_secured_request(session_id, &send_buffer[..used], false)?;
+                self.receive_with_retry(Some(session_id), &mut receive_buffer)?
+            }
+            None => {
+                self.send_message(&send_buffer[..used])?;
+                (self.receive_message(&mut receive_buffer)?, None)
+            }
+        };
+
+        let expected_response_code = match (expected_response, app_type) {
+            (SpdmExpectedResponse::App(expected_app_type), Some(app_type))
+                if app_type == expected_app_type =>
+            {
+                return Ok(ReceivedMessage {
+                    receive_buffer,
+                    used,
+                });
+            }
+            (SpdmExpectedResponse::Spdm(expected_response_code), None) => {
+                Some(expected_response_code)
+            }
+            // only an ERROR is expected in place of the application message
+            (SpdmExpectedResponse::App(_), None) => None,
+            (_, Some(_)) => return spdm_result_err!(EDEV),
+        };
 
         //Have a sanity check!
         let mut reader = Reader::init(&receive_buffer);
         match SpdmMessageHeader::read(&mut reader) {
             Some(message_header) => {
-                if message_header.request_response_code == expected_response_code {
+                if Some(message_header.request_response_code) == expected_response_code
+                    && message_header.version == self.common.negotiate_info.spdm_version_sel
+                {
                     Ok(ReceivedMessage {
                         receive_buffer,
                         used,
                     })
+                } else if message_header.request_response_code
+                    == SpdmRequestResponseCode::SpdmResponseError
+                {
+                    // the caller decides what to do if it is still not ready
+                    match SpdmErrorResponsePayload::spdm_read(&mut self.common, &mut reader) {
+                        Some(payload) => {
+                            Err(spdm_err!(EDEV, "peer error")
+                                .with_kind(SpdmErrorKind::Peer(payload)))
+                        }
+                        None => spdm_result_err!(EDEV),
+                    }
                 } else {
                     spdm_result_err!(EDEV)
                 }
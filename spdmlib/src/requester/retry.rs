@@ -0,0 +1,181 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use crate::common::ST1;
+use crate::config;
+use crate::message::{SpdmMessageHeader, SpdmRequestResponseCode};
+use codec::{Codec, Reader};
+
+/// How long the requester waits for a response, and how it retries a request.
+///
+/// The default waits as long as the timeout class of the request allows,
+/// and never retries.
+#[derive(Debug, Clone, Copy)]
+pub struct SpdmRetryPolicy {
+    /// Retries of one request, on Busy and, with `retransmit_on_timeout`, on timeout.
+    pub max_retries: u8,
+    /// Delay in us before the first retry on Busy, doubled for every further retry.
+    pub retry_delay: usize,
+    /// Send the request again when no response arrives in time, for lossy transports.
+    pub retransmit_on_timeout: bool,
+    /// Run VCA again when the responder answers RequestResynch.
+    pub reinit_on_resynch: bool,
+    /// Time in us from the first receive in which retries are made, 0 for no limit.
+    pub max_retry_time: usize,
+    /// Timeout in us of requests of the `St1` class.
+    pub st1: usize,
+    /// Transport round trip time in us, added to the CT of `Ct` class requests.
+    pub rtt: usize,
+}
+
+impl Default for SpdmRetryPolicy {
+    fn default() -> Self {
+        SpdmRetryPolicy {
+            max_retries: 0,
+            retry_delay: ST1,
+            retransmit_on_timeout: false,
+            reinit_on_resynch: false,
+            max_retry_time: 0,
+            st1: ST1,
+            rtt: 0,
+        }
+    }
+}
+
+/// How long the responder may take to answer a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpdmTimeoutClass {
+    /// No crypto operation on the responder, answered within ST1.
+    St1,
+    /// A crypto operation on the responder, answered within CT plus RTT.
+    Ct,
+}
+
+impl SpdmTimeoutClass {
+    /// The timeout class of the request `request_code`.
+    pub fn of(request_code: SpdmRequestResponseCode) -> Self {
+        use SpdmRequestResponseCode::*;
+        match request_code {
+            SpdmRequestGetVersion
+            | SpdmRequestGetCapabilities
+            | SpdmRequestNegotiateAlgorithms
+            | SpdmRequestGetDigests
+            | SpdmRequestGetCertificate
+            | SpdmRequestResponseIfReady
+            | SpdmRequestHeartbeat
+            | SpdmRequestKeyUpdate
+            | SpdmRequestEndSession
+            | SpdmRequestVendorDefinedRequest => SpdmTimeoutClass::St1,
+            SpdmRequestChallenge
+            | SpdmRequestGetMeasurements
+            | SpdmRequestKeyExchange
+            | SpdmRequestFinish
+            | SpdmRequestPskExchange
+            | SpdmRequestPskFinish => SpdmTimeoutClass::Ct,
+            _ => SpdmTimeoutClass::St1,
+        }
+    }
+}
+
+impl SpdmRetryPolicy {
+    /// Timeout in us of a request, `ct_exponent` is the CT exponent of the responder.
+    pub fn timeout(&self, class: SpdmTimeoutClass, ct_exponent: u8) -> usize {
+        match class {
+            SpdmTimeoutClass::St1 => self.st1,
+            SpdmTimeoutClass::Ct => 1usize
+                .checked_shl(ct_exponent as u32)
+                .unwrap_or(usize::MAX)
+                .saturating_add(self.rtt),
+        }
+    }
+
+    /// Delay in us before retry number `retry`, counted from 0.
+    pub fn retry_delay(&self, retry: u8) -> usize {
+        self.retry_delay
+            .saturating_mul(1usize.checked_shl(retry as u32).unwrap_or(usize::MAX))
+    }
+}
+
+/// The last request sent, kept to send it again.
+pub(crate) struct SpdmLastRequest {
+    /// Session ID and app message flag of a secured request.
+    pub session: Option<(u32, bool)>,
+    /// Transport message type of a secured application message.
+    pub app_type: Option<u8>,
+    pub spdm_buffer: [u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE],
+    pub spdm_used: usize,
+    pub transport_buffer: [u8; config::DATA_TRANSFER_SIZE],
+    pub transport_used: usize,
+}
+
+impl SpdmLastRequest {
+    /// The request code of the request, `Unknown(0)` for an application message.
+    pub fn request_code(&self) -> SpdmRequestResponseCode {
+        if self.app_type.is_some() {
+            return SpdmRequestResponseCode::Unknown(0);
+        }
+        let mut reader = Reader::init(&self.spdm_buffer[..self.spdm_used]);
+        match SpdmMessageHeader::read(&mut reader) {
+            Some(header) => header.request_response_code,
+            None => SpdmRequestResponseCode::Unknown(0),
+        }
+    }
+
+    /// The timeout class of the request, `St1` for an application message.
+    pub fn timeout_class(&self) -> SpdmTimeoutClass {
+        SpdmTimeoutClass::of(self.request_code())
+    }
+}
+
+impl Default for SpdmLastRequest {
+    fn default() -> Self {
+        SpdmLastRequest {
+            session: None,
+            app_type: None,
+            spdm_buffer: [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE],
+            spdm_used: 0,
+            transport_buffer: [0u8; config::DATA_TRANSFER_SIZE],
+            transport_used: 0,
+        }
+    }
+}
+
+#[cfg(all(test,))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case0_retry_policy() {
+        let policy = SpdmRetryPolicy {
+            retry_delay: 100,
+            rtt: 10,
+            ..Default::default()
+        };
+        assert_eq!(policy.timeout(SpdmTimeoutClass::St1, 20), ST1);
+        assert_eq!(policy.timeout(SpdmTimeoutClass::Ct, 20), (1 << 20) + 10);
+        assert_eq!(policy.timeout(SpdmTimeoutClass::Ct, 255), usize::MAX);
+        assert_eq!(policy.retry_delay(0), 100);
+        assert_eq!(policy.retry_delay(3), 800);
+        assert_eq!(policy.retry_delay(200), usize::MAX);
+    }
+
+    #[test]
+    fn test_case1_timeout_class() {
+        assert_eq!(
+            SpdmTimeoutClass::of(SpdmRequestResponseCode::SpdmRequestGetCertificate),
+            SpdmTimeoutClass::St1
+        );
+        assert_eq!(
+            SpdmTimeoutClass::of(SpdmRequestResponseCode::SpdmRequestKeyExchange),
+            SpdmTimeoutClass::Ct
+        );
+
+        let mut last_request = SpdmLastRequest::default();
+        last_request.spdm_buffer[..4].copy_from_slice(&[0x11, 0x83, 0, 0]);
+        last_request.spdm_used = 4;
+        assert_eq!(last_request.timeout_class(), SpdmTimeoutClass::Ct);
+        last_request.app_type = Some(0);
+        assert_eq!(last_request.timeout_class(), SpdmTimeoutClass::St1);
+    }
+}
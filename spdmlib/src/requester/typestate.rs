@@ -0,0 +1,271 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+//! Typestate API on top of `RequesterContext`.
+//!
+//! Each connection state only offers the requests the SPDM flow allows in it,
+//! and moving to the next state consumes the current one:
+//!
+//! `SpdmUnconnected` -> `SpdmNegotiated` -> `SpdmAuthenticated` -> `SpdmSecuredSession`
+//!
+//! A failed transition drops the state object, the `RequesterContext` is
+//! released and a new `SpdmUnconnected` starts over.
+
+use crate::common::INVALID_SLOT;
+use crate::error::SpdmResult;
+use crate::message::*;
+use crate::protocol::*;
+use crate::requester::RequesterContext;
+
+mod private {
+    use crate::requester::RequesterContext;
+
+    pub trait Sealed<'a> {
+        fn requester(&self) -> &RequesterContext<'a>;
+        fn requester_mut(&mut self) -> &mut RequesterContext<'a>;
+    }
+}
+
+/// A state in which the connection is negotiated, and a session can be
+/// started and ended again.
+pub trait SpdmConnected<'a>: private::Sealed<'a> {}
+
+/// No connection yet, VCA is the only thing to do.
+pub struct SpdmUnconnected<'r, 'a> {
+    requester: &'r mut RequesterContext<'a>,
+}
+
+impl<'r, 'a> SpdmUnconnected<'r, 'a> {
+    pub fn new(requester: &'r mut RequesterContext<'a>) -> Self {
+        SpdmUnconnected { requester }
+    }
+
+    /// Run GET_VERSION, GET_CAPABILITIES and NEGOTIATE_ALGORITHMS.
+    pub fn init_connection(self) -> SpdmResult<SpdmNegotiated<'r, 'a>> {
+        self.requester.init_connection()?;
+        Ok(SpdmNegotiated {
+            requester: self.requester,
+        })
+    }
+}
+
+/// Version, capabilities and algorithms are negotiated, the responder is not
+/// authenticated yet.
+pub struct SpdmNegotiated<'r, 'a> {
+    requester: &'r mut RequesterContext<'a>,
+}
+
+impl<'r, 'a> SpdmNegotiated<'r, 'a> {
+    pub fn context(&self) -> &RequesterContext<'a> {
+        self.requester
+    }
+
+    /// Get unsigned measurements, returns the total number of measurement blocks
+    /// for `SpdmMeasurementQueryTotalNumber`, else the number of blocks received.
+    pub fn get_measurements(
+        &mut self,
+        measurement_operation: SpdmMeasurementOperation,
+        spdm_measurement_record_structure: &mut SpdmMeasurementRecordStructure,
+    ) -> SpdmResult<u8> {
+        get_measurements(
+            self.requester,
+            None,
+            0,
+            SpdmMeasurementAttributes::empty(),
+            measurement_operation,
+            spdm_measurement_record_structure,
+        )
+    }
+
+    /// Get the digests and the cert chain of `slot_id`, then challenge the responder.
+    pub fn authenticate(
+        self,
+        slot_id: u8,
+        measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
+    ) -> SpdmResult<SpdmAuthenticated<'r, 'a>> {
+        self.requester.send_receive_spdm_digest(None)?;
+        self.requester
+            .send_receive_spdm_certificate(None, slot_id)?;
+        self.requester
+            .send_receive_spdm_challenge(slot_id, measurement_summary_hash_type)?;
+        Ok(SpdmAuthenticated {
+            requester: self.requester,
+            slot_id,
+        })
+    }
+
+    /// Start a session with PSK_EXCHANGE and PSK_FINISH.
+    pub fn start_psk_session(
+        self,
+        measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
+    ) -> SpdmResult<SpdmSecuredSession<Self>> {
+        start_session(self, true, INVALID_SLOT, measurement_summary_hash_type)
+    }
+}
+
+impl<'r, 'a> private::Sealed<'a> for SpdmNegotiated<'r, 'a> {
+    fn requester(&self) -> &RequesterContext<'a> {
+        self.requester
+    }
+
+    fn requester_mut(&mut self) -> &mut RequesterContext<'a> {
+        self.requester
+    }
+}
+
+impl<'r, 'a> SpdmConnected<'a> for SpdmNegotiated<'r, 'a> {}
+
+/// The responder proved it owns the cert chain of `slot_id`.
+pub struct SpdmAuthenticated<'r, 'a> {
+    requester: &'r mut RequesterContext<'a>,
+    slot_id: u8,
+}
+
+impl<'r, 'a> SpdmAuthenticated<'r, 'a> {
+    pub fn context(&self) -> &RequesterContext<'a> {
+        self.requester
+    }
+
+    pub fn slot_id(&self) -> u8 {
+        self.slot_id
+    }
+
+    /// Get measurements signed with the key of the authenticated slot, returns
+    /// the same number as `SpdmNegotiated::get_measurements`.
+    pub fn get_signed_measurements(
+        &mut self,
+        measurement_operation: SpdmMeasurementOperation,
+        spdm_measurement_record_structure: &mut SpdmMeasurementRecordStructure,
+    ) -> SpdmResult<u8> {
+        get_measurements(
+            self.requester,
+            None,
+            self.slot_id,
+            SpdmMeasurementAttributes::SIGNATURE_REQUESTED,
+            measurement_operation,
+            spdm_measurement_record_structure,
+        )
+    }
+
+    /// Start a session with KEY_EXCHANGE and FINISH, using the authenticated slot.
+    pub fn start_session(
+        self,
+        measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
+    ) -> SpdmResult<SpdmSecuredSession<Self>> {
+        let slot_id = self.slot_id;
+        start_session(self, false, slot_id, measurement_summary_hash_type)
+    }
+
+    /// Start a session with PSK_EXCHANGE and PSK_FINISH.
+    pub fn start_psk_session(
+        self,
+        measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
+    ) -> SpdmResult<SpdmSecuredSession<Self>> {
+        start_session(self, true, INVALID_SLOT, measurement_summary_hash_type)
+    }
+}
+
+impl<'r, 'a> private::Sealed<'a> for SpdmAuthenticated<'r, 'a> {
+    fn requester(&self) -> &RequesterContext<'a> {
+        self.requester
+    }
+
+    fn requester_mut(&mut self) -> &mut RequesterContext<'a> {
+        self.requester
+    }
+}
+
+impl<'r, 'a> SpdmConnected<'a> for SpdmAuthenticated<'r, 'a> {}
+
+/// An established session, ending it gives the connection state back.
+pub struct SpdmSecuredSession<S> {
+    connection: S,
+    session_id: u32,
+}
+
+impl<S> SpdmSecuredSession<S> {
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+}
+
+impl<'a, S: SpdmConnected<'a>> SpdmSecuredSession<S> {
+    pub fn context(&self) -> &RequesterContext<'a> {
+        private::Sealed::requester(&self.connection)
+    }
+
+    pub fn heartbeat(&mut self) -> SpdmResult {
+        let session_id = self.session_id;
+        private::Sealed::requester_mut(&mut self.connection).send_receive_spdm_heartbeat(session_id)
+    }
+
+    pub fn key_update(&mut self, key_update_operation: SpdmKeyUpdateOperation) -> SpdmResult {
+        let session_id = self.session_id;
+        private::Sealed::requester_mut(&mut self.connection)
+            .send_receive_spdm_key_update(session_id, key_update_operation)
+    }
+
+    /// Get measurements in the session, the session itself authenticates them.
+    pub fn get_measurements(
+        &mut self,
+        measurement_operation: SpdmMeasurementOperation,
+        spdm_measurement_record_structure: &mut SpdmMeasurementRecordStructure,
+    ) -> SpdmResult<u8> {
+        let session_id = self.session_id;
+        get_measurements(
+            private::Sealed::requester_mut(&mut self.connection),
+            Some(session_id),
+            0,
+            SpdmMeasurementAttributes::empty(),
+            measurement_operation,
+            spdm_measurement_record_structure,
+        )
+    }
+
+    /// End the session, the connection keeps its negotiated state.
+    pub fn end_session(mut self) -> SpdmResult<S> {
+        private::Sealed::requester_mut(&mut self.connection).end_session(
+            self.session_id,
+            SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE,
+        )?;
+        Ok(self.connection)
+    }
+}
+
+fn start_session<'a, S: SpdmConnected<'a>>(
+    mut connection: S,
+    use_psk: bool,
+    slot_id: u8,
+    measurement_summary_hash_type: SpdmMeasurementSummaryHashType,
+) -> SpdmResult<SpdmSecuredSession<S>> {
+    let session_id = private::Sealed::requester_mut(&mut connection).start_session(
+        use_psk,
+        slot_id,
+        measurement_summary_hash_type,
+    )?;
+    Ok(SpdmSecuredSession {
+        connection,
+        session_id,
+    })
+}
+
+fn get_measurements(
+    requester: &mut RequesterContext,
+    session_id: Option<u32>,
+    slot_id: u8,
+    measurement_attributes: SpdmMeasurementAttributes,
+    measurement_operation: SpdmMeasurementOperation,
+    spdm_measurement_record_structure: &mut SpdmMeasurementRecordStructure,
+) -> SpdmResult<u8> {
+    let mut total_number = 0;
+    requester.send_receive_spdm_measurement(
+        session_id,
+        slot_id,
+        measurement_attributes,
+        measurement_operation,
+        &mut total_number,
+        spdm_measurement_record_structure,
+    )?;
+    Ok(total_number)
+}
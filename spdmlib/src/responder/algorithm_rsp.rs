@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::common::SpdmCodec;
+use crate::common::{SpdmCodec, SpdmConnectionState};
 use crate::crypto;
 use crate::message::*;
 use crate::protocol::*;
@@ -69,26 +69,26 @@ impl<'a> ResponderContext<'a> {
             .prioritize(self.common.config_info.measurement_specification);
         self.common.negotiate_info.measurement_hash_sel =
             self.common.config_info.measurement_hash_algo;
-        self.common
-            .negotiate_info
-            .base_hash_sel
-            .prioritize(self.common.config_info.base_hash_algo);
-        self.common
-            .negotiate_info
-            .base_asym_sel
-            .prioritize(self.common.config_info.base_asym_algo);
-        self.common
-            .negotiate_info
-            .dhe_sel
-            .prioritize(self.common.config_info.dhe_algo);
-        self.common
-            .negotiate_info
-            .aead_sel
-            .prioritize(self.common.config_info.aead_algo);
-        self.common
-            .negotiate_info
-            .req_asym_sel
-            .prioritize(self.common.config_info.req_asym_algo);
+        self.common.negotiate_info.base_hash_sel.prioritize_with(
+            self.common.config_info.base_hash_algo,
+            self.common.config_info.algo_priority.base_hash_algo,
+        );
+        self.common.negotiate_info.base_asym_sel.prioritize_with(
+            self.common.config_info.base_asym_algo,
+            self.common.config_info.algo_priority.base_asym_algo,
+        );
+        self.common.negotiate_info.dhe_sel.prioritize_with(
+            self.common.config_info.dhe_algo,
+            self.common.config_info.algo_priority.dhe_algo,
+        );
+        self.common.negotiate_info.aead_sel.prioritize_with(
+            self.common.config_info.aead_algo,
+            self.common.config_info.algo_priority.aead_algo,
+        );
+        self.common.negotiate_info.req_asym_sel.prioritize_with(
+            self.common.config_info.req_asym_algo,
+            self.common.config_info.algo_priority.req_asym_algo,
+        );
         self.common
             .negotiate_info
             .key_schedule_sel
@@ -190,19 +190,9 @@ impl<'a> ResponderContext<'a> {
             .message_a
             .append_message(writer.used_slice());
 
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            self.common.runtime_info.digest_context_m1m2 =
-                crypto::hash::hash_ctx_init(self.common.negotiate_info.base_hash_sel);
-            crypto::hash::hash_ctx_update(
-                self.common
-                    .runtime_info
-                    .digest_context_m1m2
-                    .as_mut()
-                    .unwrap(),
-                self.common.runtime_info.message_a.as_ref(),
-            );
-        }
+        // M1/M2 restarts from the new message_a on its next message
+        self.common.runtime_info.transcript_m1m2.reset();
+        self.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
     }
 }
 
@@ -0,0 +1,24 @@
+// Copyright (c) 2022 Intel Corporation
+//
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use crate::error::SpdmResult;
+
+/// Handler of the application messages (PLDM, NVMe-MI, vendor protocols...)
+/// a requester sends in a secured session, registered by the integrator with
+/// `ResponderContext::set_app_message_handler`.
+pub trait SpdmAppMessageHandler {
+    /// Handle the plaintext `request` of the transport message type `app_type`
+    /// (the MCTP message type for MCTP), received in the session `session_id`.
+    ///
+    /// Write the response into `response` and return its size, it is sent back
+    /// in the session with the same `app_type`. Return an error to answer with
+    /// an SPDM ERROR message in the session instead.
+    fn dispatch_secured_app_message(
+        &mut self,
+        session_id: u32,
+        app_type: u8,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> SpdmResult<usize>;
+}
@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::common::SpdmCodec;
+use crate::common::{SpdmCodec, SpdmConnectionState};
 use crate::message::*;
 use crate::protocol::*;
 use crate::responder::*;
@@ -23,6 +23,13 @@ impl<'a> ResponderContext<'a> {
             request_response_code: _,
         }) = header
         {
+            if matches!(version, SpdmVersion::Unknown(_))
+                || !self.common.config_info.spdm_version.contains(&version)
+            {
+                error!("!!! get_capabilities : unsupported version !!!\n");
+                self.write_spdm_error(SpdmErrorCode::SpdmErrorVersionMismatch, 0, writer);
+                return;
+            }
             self.common.negotiate_info.spdm_version_sel = version;
         } else {
             error!("!!! get_capabilities : fail !!!\n");
@@ -89,6 +96,7 @@ impl<'a> ResponderContext<'a> {
             .runtime_info
             .message_a
             .append_message(writer.used_slice());
+        self.common.runtime_info.connection_state = SpdmConnectionState::AfterCapabilities;
     }
 }
 
@@ -3,8 +3,6 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
 use crate::common::SpdmCodec;
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::message::*;
 use crate::responder::*;
 
@@ -35,28 +33,15 @@ impl<'a> ResponderContext<'a> {
             return;
         }
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
         if self
             .common
-            .runtime_info
-            .message_b
-            .append_message(&bytes[..reader.used()])
-            .is_none()
+            .append_message_m1m2(&bytes[..reader.used()])
+            .is_err()
         {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return;
         }
 
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            self.common
-                .runtime_info
-                .digest_context_m1m2
-                .as_mut()
-                .unwrap(),
-            &bytes[..reader.used()],
-        );
-
         let get_certificate = get_certificate.unwrap();
         let slot_id = get_certificate.slot_id;
 
@@ -100,26 +85,14 @@ impl<'a> ResponderContext<'a> {
         };
         response.spdm_encode(&mut self.common, writer);
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        self.common
-            .runtime_info
-            .message_b
-            .append_message(writer.used_slice());
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            self.common
-                .runtime_info
-                .digest_context_m1m2
-                .as_mut()
-                .unwrap(),
-            writer.used_slice(),
-        );
+        let _ = self.common.append_message_m1m2(writer.used_slice());
     }
 }
 
 #[cfg(all(test,))]
 mod tests_responder {
     use super::*;
+    use crate::common::SpdmTranscriptMode;
     use crate::testlib::*;
     use crate::{crypto, responder};
     use codec::{Codec, Writer};
@@ -144,8 +117,7 @@ mod tests_responder {
             data: [0u8; config::MAX_SPDM_CERT_CHAIN_DATA_SIZE],
         });
 
-        context.common.runtime_info.digest_context_m1m2 =
-            Some(crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384).unwrap());
+        context.common.config_info.transcript_mode = SpdmTranscriptMode::Buffered;
 
         let spdm_message_header = &mut [0u8; 1024];
         let mut writer = Writer::init(spdm_message_header);
@@ -167,47 +139,43 @@ mod tests_responder {
         bytes[2..].copy_from_slice(&capabilities[0..1022]);
         context.handle_spdm_certificate(bytes, None);
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            let data = context.common.runtime_info.message_b.as_ref();
-            let u8_slice = &mut [0u8; 2048];
-            for (i, data) in data.iter().enumerate() {
-                u8_slice[i] = *data;
-            }
+        let data = context.common.runtime_info.transcript_m1m2.data().unwrap();
+        let u8_slice = &mut [0u8; 2048];
+        for (i, data) in data.iter().enumerate() {
+            u8_slice[i] = *data;
+        }
+
+        let mut message_header_slice = Reader::init(u8_slice);
+        let spdm_message_header = SpdmMessageHeader::read(&mut message_header_slice).unwrap();
+        assert_eq!(spdm_message_header.version, SpdmVersion::SpdmVersion10);
+        assert_eq!(
+            spdm_message_header.request_response_code,
+            SpdmRequestResponseCode::SpdmRequestGetCertificate
+        );
 
-            let mut message_header_slice = Reader::init(u8_slice);
-            let spdm_message_header = SpdmMessageHeader::read(&mut message_header_slice).unwrap();
-            assert_eq!(spdm_message_header.version, SpdmVersion::SpdmVersion10);
-            assert_eq!(
-                spdm_message_header.request_response_code,
-                SpdmRequestResponseCode::SpdmRequestGetCertificate
-            );
-
-            let spdm_struct_slice = &u8_slice[2..];
-            let mut reader = Reader::init(spdm_struct_slice);
-            let spdm_get_certificate_request_payload =
-                SpdmGetCertificateRequestPayload::spdm_read(&mut context.common, &mut reader)
-                    .unwrap();
-            assert_eq!(spdm_get_certificate_request_payload.slot_id, 100);
-            assert_eq!(spdm_get_certificate_request_payload.offset, 100);
-            assert_eq!(spdm_get_certificate_request_payload.length, 600);
-
-            let spdm_message_slice = &u8_slice[8..];
-            let mut reader = Reader::init(spdm_message_slice);
-            let spdm_message: SpdmMessage =
-                SpdmMessage::spdm_read(&mut context.common, &mut reader).unwrap();
-            assert_eq!(spdm_message.header.version, SpdmVersion::SpdmVersion11);
-            assert_eq!(
-                spdm_message.header.request_response_code,
-                SpdmRequestResponseCode::SpdmResponseCertificate
-            );
-            if let SpdmMessagePayload::SpdmCertificateResponse(payload) = &spdm_message.payload {
-                assert_eq!(payload.slot_id, 100);
-                assert_eq!(payload.portion_length, 412);
-                assert_eq!(payload.remainder_length, 0);
-                for i in 0..412 {
-                    assert_eq!(payload.cert_chain[i], 0u8);
-                }
+        let spdm_struct_slice = &u8_slice[2..];
+        let mut reader = Reader::init(spdm_struct_slice);
+        let spdm_get_certificate_request_payload =
+            SpdmGetCertificateRequestPayload::spdm_read(&mut context.common, &mut reader).unwrap();
+        assert_eq!(spdm_get_certificate_request_payload.slot_id, 100);
+        assert_eq!(spdm_get_certificate_request_payload.offset, 100);
+        assert_eq!(spdm_get_certificate_request_payload.length, 600);
+
+        let spdm_message_slice = &u8_slice[8..];
+        let mut reader = Reader::init(spdm_message_slice);
+        let spdm_message: SpdmMessage =
+            SpdmMessage::spdm_read(&mut context.common, &mut reader).unwrap();
+        assert_eq!(spdm_message.header.version, SpdmVersion::SpdmVersion11);
+        assert_eq!(
+            spdm_message.header.request_response_code,
+            SpdmRequestResponseCode::SpdmResponseCertificate
+        );
+        if let SpdmMessagePayload::SpdmCertificateResponse(payload) = &spdm_message.payload {
+            assert_eq!(payload.slot_id, 100);
+            assert_eq!(payload.portion_length, 412);
+            assert_eq!(payload.remainder_length, 0);
+            for i in 0..412 {
+                assert_eq!(payload.cert_chain[i], 0u8);
             }
         }
     }
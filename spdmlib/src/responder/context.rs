@@ -2,23 +2,19 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::common::{SpdmDeviceIo, SpdmTransportEncap};
+use super::SpdmAppMessageHandler;
+use crate::common::{SpdmConnectionState, SpdmDeviceIo, SpdmTransportEncap};
 use crate::config;
 use crate::error::SpdmResult;
 use crate::message::*;
-use codec::{Codec, Reader};
+use crate::protocol::{SpdmRequestCapabilityFlags, SpdmResponseCapabilityFlags, SpdmVersion};
+use codec::{Codec, Reader, Writer};
 
 pub struct ResponderContext<'a> {
     pub common: crate::common::SpdmContext<'a>,
+    pub app_message_handler: Option<&'a mut dyn SpdmAppMessageHandler>,
 }
 
-pub const M_SECURE_SESSION_RESPONSE: &[u8; 5] = &[
-    0x00u8, 0x00u8, //PLDM_MESSAGE_TYPE_CONTROL_DISCOVERY
-    0x02u8, //PLDM_CONTROL_DISCOVERY_COMMAND_GET_TID
-    0x00u8, //PLDM_BASE_CODE_SUCCESS
-    0x01u8, //TID
-];
-
 impl<'a> ResponderContext<'a> {
     pub fn new(
         device_io: &'a mut dyn SpdmDeviceIo,
@@ -33,9 +29,19 @@ impl<'a> ResponderContext<'a> {
                 config_info,
                 provision_info,
             ),
+            app_message_handler: None,
         }
     }
 
+    /// Use `app_message_handler` to answer the application messages received
+    /// in secured sessions.
+    pub fn set_app_message_handler(
+        &mut self,
+        app_message_handler: &'a mut dyn SpdmAppMessageHandler,
+    ) {
+        self.app_message_handler = Some(app_message_handler);
+    }
+
     pub fn send_message(&mut self, send_buffer: &[u8]) -> SpdmResult {
         let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
         let used = self.common.encap(send_buffer, &mut transport_buffer)?;
@@ -59,22 +65,93 @@ impl<'a> ResponderContext<'a> {
         self.common.device_io.send(&transport_buffer[..used])
     }
 
+    pub fn send_secured_app_message(
+        &mut self,
+        session_id: u32,
+        app_message: &[u8],
+        app_type: u8,
+    ) -> SpdmResult {
+        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+        let used = self.common.encode_secured_app_message(
+            session_id,
+            app_message,
+            &mut transport_buffer,
+            false,
+            app_type,
+        )?;
+        self.common.device_io.send(&transport_buffer[..used])
+    }
+
+    /// The heartbeat period of new sessions, 0 unless both sides support HEARTBEAT.
+    pub fn heartbeat_period(&self) -> u8 {
+        if self
+            .common
+            .negotiate_info
+            .req_capabilities_sel
+            .contains(SpdmRequestCapabilityFlags::HBEAT_CAP)
+            && self
+                .common
+                .negotiate_info
+                .rsp_capabilities_sel
+                .contains(SpdmResponseCapabilityFlags::HBEAT_CAP)
+        {
+            self.common.config_info.heartbeat_period
+        } else {
+            0
+        }
+    }
+
+    /// End the sessions without a secured message for twice their heartbeat
+    /// period, checked on every receive and by integrators that wait for
+    /// messages outside of `process_message`.
+    pub fn supervise_sessions(&mut self) {
+        let now = if let Some(now) = crate::time::now() {
+            now
+        } else {
+            return;
+        };
+        for session in self.common.session.iter_mut() {
+            let timeout = 2 * session.heartbeat_period as u64 * 1_000_000;
+            if let Some(idle_time) = session.heartbeat_idle_time(now) {
+                if idle_time >= timeout {
+                    let session_id = session.get_session_id();
+                    error!(
+                        "!!! heartbeat timeout, end session {:08x} !!!\n",
+                        session_id
+                    );
+                    let _ = session.teardown(session_id);
+                }
+            }
+        }
+    }
+
     pub fn process_message(
         &mut self,
         timeout: usize,
     ) -> Result<bool, (usize, [u8; config::DATA_TRANSFER_SIZE])> {
         let mut receive_buffer = [0u8; config::DATA_TRANSFER_SIZE];
-        match self.receive_message(&mut receive_buffer[..], timeout) {
+        let received = self.receive_message(&mut receive_buffer[..], timeout);
+        // also when nothing arrived in time, so that idle sessions end
+        self.supervise_sessions();
+        match received {
             Ok((used, secured_message)) => {
                 if secured_message {
                     let mut read = Reader::init(&receive_buffer[0..used]);
                     let session_id = u32::read(&mut read).ok_or((used, receive_buffer))?;
 
+                    // the requester updates the keys before it sends a request on
+                    // keys past the policy, the age is left out as clocks differ
+                    let key_update_policy = crate::common::session::SpdmKeyUpdatePolicy {
+                        max_key_age: 0,
+                        ..self.common.config_info.key_update_policy
+                    };
                     let spdm_session = self
                         .common
                         .get_session_via_id(session_id)
                         .ok_or((used, receive_buffer))?;
 
+                    let key_update_due = spdm_session.key_update_due(&key_update_policy, None);
+
                     let mut app_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
 
                     let decode_size = spdm_session.decode_spdm_secured_message(
@@ -91,17 +168,35 @@ impl<'a> ResponderContext<'a> {
                     let decap_result = self
                         .common
                         .transport_encap
-                        .decap_app(&app_buffer[0..decode_size], &mut spdm_buffer);
+                        .decap_app_message(&app_buffer[0..decode_size], &mut spdm_buffer);
                     match decap_result {
                         Err(_) => Err((used, receive_buffer)),
-                        Ok((decode_size, is_app_message)) => {
-                            if !is_app_message {
-                                Ok(self.dispatch_secured_message(
+                        Ok((decode_size, app_type)) => {
+                            if key_update_due
+                                && (app_type.is_some()
+                                    || spdm_buffer.get(1).copied()
+                                        != Some(
+                                            SpdmRequestResponseCode::SpdmRequestKeyUpdate.get_u8(),
+                                        ))
+                            {
+                                error!("!!! keys past the key update policy, end session !!!\n");
+                                if let Some(spdm_session) =
+                                    self.common.get_session_via_id(session_id)
+                                {
+                                    let _ = spdm_session.teardown(session_id);
+                                }
+                                return Err((used, receive_buffer));
+                            }
+                            match app_type {
+                                None => Ok(self.dispatch_secured_message(
                                     session_id,
                                     &spdm_buffer[0..decode_size],
-                                ))
-                            } else {
-                                Ok(self.dispatch_secured_app_message(session_id))
+                                )),
+                                Some(app_type) => Ok(self.dispatch_secured_app_message(
+                                    session_id,
+                                    app_type,
+                                    &spdm_buffer[0..decode_size],
+                                )),
                             }
                         }
                     }
@@ -141,163 +236,290 @@ impl<'a> ResponderContext<'a> {
     fn dispatch_secured_message(&mut self, session_id: u32, bytes: &[u8]) -> bool {
         let mut reader = Reader::init(bytes);
         match SpdmMessageHeader::read(&mut reader) {
-            Some(message_header) => match message_header.request_response_code {
-                SpdmRequestResponseCode::SpdmRequestResponseIfReady => {
-                    self.handle_spdm_respond_if_ready(bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetVersion => false,
-                SpdmRequestResponseCode::SpdmRequestGetCapabilities => false,
-                SpdmRequestResponseCode::SpdmRequestNegotiateAlgorithms => false,
-                SpdmRequestResponseCode::SpdmRequestGetDigests => {
-                    self.handle_spdm_digest(bytes, Some(session_id));
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetCertificate => {
-                    self.handle_spdm_certificate(bytes, Some(session_id));
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestChallenge => false,
-                SpdmRequestResponseCode::SpdmRequestGetMeasurements => {
-                    self.handle_spdm_measurement(Some(session_id), bytes);
-                    true
+            Some(message_header)
+                if message_header.version != self.common.negotiate_info.spdm_version_sel =>
+            {
+                let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+                let mut writer = Writer::init(&mut send_buffer);
+                self.write_spdm_error(SpdmErrorCode::SpdmErrorVersionMismatch, 0, &mut writer);
+                let _ = self.send_secured_message(session_id, writer.used_slice(), false);
+                true
+            }
+            Some(message_header) => {
+                let request_response_code = message_header.request_response_code;
+                if let Some(error_code) = self.check_request_allowed(request_response_code, true) {
+                    let error_data = if error_code == SpdmErrorCode::SpdmErrorUnsupportedRequest {
+                        request_response_code.get_u8()
+                    } else {
+                        0
+                    };
+                    let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+                    let mut writer = Writer::init(&mut send_buffer);
+                    self.write_spdm_error(error_code, error_data, &mut writer);
+                    let _ = self.send_secured_message(session_id, writer.used_slice(), false);
+                    return true;
                 }
+                match request_response_code {
+                    SpdmRequestResponseCode::SpdmRequestResponseIfReady => {
+                        self.handle_spdm_respond_if_ready(bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetDigests => {
+                        self.handle_spdm_digest(bytes, Some(session_id));
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetCertificate => {
+                        self.handle_spdm_certificate(bytes, Some(session_id));
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetMeasurements => {
+                        self.handle_spdm_measurement(Some(session_id), bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestKeyExchange => false,
-
-                SpdmRequestResponseCode::SpdmRequestFinish => {
-                    self.handle_spdm_finish(session_id, bytes);
-                    true
-                }
+                    SpdmRequestResponseCode::SpdmRequestFinish => {
+                        self.handle_spdm_finish(session_id, bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestPskExchange => false,
+                    SpdmRequestResponseCode::SpdmRequestPskFinish => {
+                        self.handle_spdm_psk_finish(session_id, bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestPskFinish => {
-                    self.handle_spdm_psk_finish(session_id, bytes);
-                    true
-                }
+                    SpdmRequestResponseCode::SpdmRequestHeartbeat => {
+                        self.handle_spdm_heartbeat(session_id, bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestHeartbeat => {
-                    self.handle_spdm_heartbeat(session_id, bytes);
-                    true
-                }
+                    SpdmRequestResponseCode::SpdmRequestKeyUpdate => {
+                        self.handle_spdm_key_update(session_id, bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestKeyUpdate => {
-                    self.handle_spdm_key_update(session_id, bytes);
-                    true
-                }
+                    SpdmRequestResponseCode::SpdmRequestEndSession => {
+                        let _ = self.handle_spdm_end_session(session_id, bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestVendorDefinedRequest => {
+                        self.handle_spdm_vendor_defined_request(session_id, bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestEndSession => {
-                    let _ = self.handle_spdm_end_session(session_id, bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestVendorDefinedRequest => {
-                    self.handle_spdm_vendor_defined_request(session_id, bytes);
-                    true
+                    // rejected by check_request_allowed
+                    _ => false,
                 }
-                SpdmRequestResponseCode::SpdmResponseDigests => false,
-                SpdmRequestResponseCode::SpdmResponseCertificate => false,
-                SpdmRequestResponseCode::SpdmResponseChallengeAuth => false,
-                SpdmRequestResponseCode::SpdmResponseVersion => false,
-                SpdmRequestResponseCode::SpdmResponseMeasurements => false,
-                SpdmRequestResponseCode::SpdmResponseCapabilities => false,
-                SpdmRequestResponseCode::SpdmResponseAlgorithms => false,
-                SpdmRequestResponseCode::SpdmResponseKeyExchangeRsp => false,
-                SpdmRequestResponseCode::SpdmResponseFinishRsp => false,
-                SpdmRequestResponseCode::SpdmResponsePskExchangeRsp => false,
-                SpdmRequestResponseCode::SpdmResponsePskFinishRsp => false,
-                SpdmRequestResponseCode::SpdmResponseHeartbeatAck => false,
-                SpdmRequestResponseCode::SpdmResponseKeyUpdateAck => false,
-                SpdmRequestResponseCode::SpdmResponseEndSessionAck => false,
-                SpdmRequestResponseCode::SpdmResponseError => false,
-                SpdmRequestResponseCode::SpdmResponseVendorDefinedResponse => false,
-                SpdmRequestResponseCode::Unknown(_) => false,
-            },
+            }
             None => false,
         }
     }
 
-    fn dispatch_secured_app_message(&mut self, session_id: u32) -> bool {
-        debug!("Send app secured message!(PLDM)\n");
-        let _ = self.send_secured_message(session_id, M_SECURE_SESSION_RESPONSE, true);
-        true
+    fn dispatch_secured_app_message(
+        &mut self,
+        session_id: u32,
+        app_type: u8,
+        bytes: &[u8],
+    ) -> bool {
+        let mut response = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let handled = match self.app_message_handler.as_mut() {
+            Some(app_message_handler) => app_message_handler
+                .dispatch_secured_app_message(session_id, app_type, bytes, &mut response)
+                .map_err(|_| {
+                    error!("!!! app message type {:02x} : fail !!!\n", app_type);
+                    SpdmErrorCode::SpdmErrorUnspecified
+                }),
+            None => {
+                error!("!!! no handler for app message type {:02x} !!!\n", app_type);
+                Err(SpdmErrorCode::SpdmErrorUnsupportedRequest)
+            }
+        };
+
+        // only a failure to send is not answered
+        let sent = match handled {
+            Ok(used) => {
+                debug!("Send app secured message!({:02x})\n", app_type);
+                self.send_secured_app_message(session_id, &response[..used], app_type)
+            }
+            Err(error_code) => {
+                let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+                let mut writer = Writer::init(&mut send_buffer);
+                self.write_spdm_error(error_code, 0, &mut writer);
+                self.send_secured_message(session_id, writer.used_slice(), false)
+            }
+        };
+        sent.is_ok()
     }
     pub fn dispatch_message(&mut self, bytes: &[u8]) -> bool {
         let mut reader = Reader::init(bytes);
         match SpdmMessageHeader::read(&mut reader) {
-            Some(message_header) => match message_header.request_response_code {
-                SpdmRequestResponseCode::SpdmRequestResponseIfReady => {
-                    self.handle_spdm_respond_if_ready(bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetVersion => {
-                    self.handle_spdm_version(bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetCapabilities => {
-                    self.handle_spdm_capability(bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestNegotiateAlgorithms => {
-                    self.handle_spdm_algorithm(bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetDigests => {
-                    self.handle_spdm_digest(bytes, None);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetCertificate => {
-                    self.handle_spdm_certificate(bytes, None);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestChallenge => {
-                    self.handle_spdm_challenge(bytes);
-                    true
-                }
-                SpdmRequestResponseCode::SpdmRequestGetMeasurements => {
-                    self.handle_spdm_measurement(None, bytes);
-                    true
+            // GET_VERSION is always 1.0, GET_CAPABILITIES selects the version
+            Some(message_header)
+                if message_header.version != self.common.negotiate_info.spdm_version_sel
+                    && !matches!(
+                        self.common.negotiate_info.spdm_version_sel,
+                        SpdmVersion::Unknown(_)
+                    )
+                    && !matches!(
+                        message_header.request_response_code,
+                        SpdmRequestResponseCode::SpdmRequestGetVersion
+                            | SpdmRequestResponseCode::SpdmRequestGetCapabilities
+                    ) =>
+            {
+                self.send_spdm_error(SpdmErrorCode::SpdmErrorVersionMismatch, 0);
+                true
+            }
+            Some(message_header) => {
+                let request_response_code = message_header.request_response_code;
+                if let Some(error_code) = self.check_request_allowed(request_response_code, false) {
+                    let error_data = if error_code == SpdmErrorCode::SpdmErrorUnsupportedRequest {
+                        request_response_code.get_u8()
+                    } else {
+                        0
+                    };
+                    self.send_spdm_error(error_code, error_data);
+                    return true;
                 }
+                match request_response_code {
+                    SpdmRequestResponseCode::SpdmRequestResponseIfReady => {
+                        self.handle_spdm_respond_if_ready(bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetVersion => {
+                        self.handle_spdm_version(bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetCapabilities => {
+                        self.handle_spdm_capability(bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestNegotiateAlgorithms => {
+                        self.handle_spdm_algorithm(bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetDigests => {
+                        self.handle_spdm_digest(bytes, None);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetCertificate => {
+                        self.handle_spdm_certificate(bytes, None);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestChallenge => {
+                        self.handle_spdm_challenge(bytes);
+                        true
+                    }
+                    SpdmRequestResponseCode::SpdmRequestGetMeasurements => {
+                        self.handle_spdm_measurement(None, bytes);
+                        true
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestKeyExchange => {
-                    matches!(self.handle_spdm_key_exchange(bytes), Ok(_))
-                }
+                    SpdmRequestResponseCode::SpdmRequestKeyExchange => {
+                        matches!(self.handle_spdm_key_exchange(bytes), Ok(_))
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestFinish => false,
+                    SpdmRequestResponseCode::SpdmRequestPskExchange => {
+                        matches!(self.handle_spdm_psk_exchange(bytes), Ok(_))
+                    }
 
-                SpdmRequestResponseCode::SpdmRequestPskExchange => {
-                    matches!(self.handle_spdm_psk_exchange(bytes), Ok(_))
+                    // rejected by check_request_allowed
+                    _ => false,
                 }
-
-                SpdmRequestResponseCode::SpdmRequestPskFinish => false,
-
-                SpdmRequestResponseCode::SpdmRequestHeartbeat => false,
-
-                SpdmRequestResponseCode::SpdmRequestKeyUpdate => false,
-
-                SpdmRequestResponseCode::SpdmRequestEndSession => false,
-                SpdmRequestResponseCode::SpdmRequestVendorDefinedRequest => false,
-                SpdmRequestResponseCode::SpdmResponseDigests => false,
-                SpdmRequestResponseCode::SpdmResponseCertificate => false,
-                SpdmRequestResponseCode::SpdmResponseChallengeAuth => false,
-                SpdmRequestResponseCode::SpdmResponseVersion => false,
-                SpdmRequestResponseCode::SpdmResponseMeasurements => false,
-                SpdmRequestResponseCode::SpdmResponseCapabilities => false,
-                SpdmRequestResponseCode::SpdmResponseAlgorithms => false,
-                SpdmRequestResponseCode::SpdmResponseKeyExchangeRsp => false,
-                SpdmRequestResponseCode::SpdmResponseFinishRsp => false,
-                SpdmRequestResponseCode::SpdmResponsePskExchangeRsp => false,
-                SpdmRequestResponseCode::SpdmResponsePskFinishRsp => false,
-                SpdmRequestResponseCode::SpdmResponseHeartbeatAck => false,
-                SpdmRequestResponseCode::SpdmResponseKeyUpdateAck => false,
-                SpdmRequestResponseCode::SpdmResponseEndSessionAck => false,
-                SpdmRequestResponseCode::SpdmResponseError => false,
-                SpdmRequestResponseCode::SpdmResponseVendorDefinedResponse => false,
-                SpdmRequestResponseCode::Unknown(_) => false,
-            },
+            }
             None => false,
         }
     }
+
+    /// The error to answer a request with, in a session if `in_session`, when
+    /// the connection state does not allow it or our capabilities do not
+    /// support it.
+    fn check_request_allowed(
+        &self,
+        request_response_code: SpdmRequestResponseCode,
+        in_session: bool,
+    ) -> Option<SpdmErrorCode> {
+        let connection_state = self.common.runtime_info.connection_state;
+        let required_capabilities = match request_response_code {
+            SpdmRequestResponseCode::SpdmRequestResponseIfReady => return None,
+            // only valid outside of a session
+            SpdmRequestResponseCode::SpdmRequestGetVersion
+            | SpdmRequestResponseCode::SpdmRequestGetCapabilities
+            | SpdmRequestResponseCode::SpdmRequestNegotiateAlgorithms
+            | SpdmRequestResponseCode::SpdmRequestChallenge
+            | SpdmRequestResponseCode::SpdmRequestKeyExchange
+            | SpdmRequestResponseCode::SpdmRequestPskExchange
+                if in_session =>
+            {
+                return Some(SpdmErrorCode::SpdmErrorUnexpectedRequest);
+            }
+            SpdmRequestResponseCode::SpdmRequestGetVersion => return None,
+            SpdmRequestResponseCode::SpdmRequestGetCapabilities => {
+                return if connection_state == SpdmConnectionState::AfterVersion {
+                    None
+                } else {
+                    Some(SpdmErrorCode::SpdmErrorUnexpectedRequest)
+                };
+            }
+            SpdmRequestResponseCode::SpdmRequestNegotiateAlgorithms => {
+                return if connection_state == SpdmConnectionState::AfterCapabilities {
+                    None
+                } else {
+                    Some(SpdmErrorCode::SpdmErrorUnexpectedRequest)
+                };
+            }
+            SpdmRequestResponseCode::SpdmRequestGetDigests
+            | SpdmRequestResponseCode::SpdmRequestGetCertificate => {
+                SpdmResponseCapabilityFlags::CERT_CAP
+            }
+            SpdmRequestResponseCode::SpdmRequestChallenge => SpdmResponseCapabilityFlags::CHAL_CAP,
+            SpdmRequestResponseCode::SpdmRequestGetMeasurements => {
+                SpdmResponseCapabilityFlags::MEAS_CAP_MASK
+            }
+            SpdmRequestResponseCode::SpdmRequestKeyExchange => {
+                SpdmResponseCapabilityFlags::KEY_EX_CAP
+            }
+            SpdmRequestResponseCode::SpdmRequestPskExchange => {
+                SpdmResponseCapabilityFlags::PSK_CAP_MASK
+            }
+            // only valid in a session
+            SpdmRequestResponseCode::SpdmRequestFinish
+            | SpdmRequestResponseCode::SpdmRequestPskFinish
+            | SpdmRequestResponseCode::SpdmRequestHeartbeat
+            | SpdmRequestResponseCode::SpdmRequestKeyUpdate
+            | SpdmRequestResponseCode::SpdmRequestEndSession
+                if !in_session =>
+            {
+                return Some(SpdmErrorCode::SpdmErrorUnexpectedRequest);
+            }
+            SpdmRequestResponseCode::SpdmRequestFinish => SpdmResponseCapabilityFlags::KEY_EX_CAP,
+            SpdmRequestResponseCode::SpdmRequestPskFinish => {
+                SpdmResponseCapabilityFlags::PSK_CAP_MASK
+            }
+            SpdmRequestResponseCode::SpdmRequestHeartbeat => SpdmResponseCapabilityFlags::HBEAT_CAP,
+            SpdmRequestResponseCode::SpdmRequestKeyUpdate => {
+                SpdmResponseCapabilityFlags::KEY_UPD_CAP
+            }
+            SpdmRequestResponseCode::SpdmRequestEndSession
+            | SpdmRequestResponseCode::SpdmRequestVendorDefinedRequest
+                if in_session =>
+            {
+                return None;
+            }
+            _ => return Some(SpdmErrorCode::SpdmErrorUnsupportedRequest),
+        };
+
+        if connection_state < SpdmConnectionState::Negotiated {
+            Some(SpdmErrorCode::SpdmErrorUnexpectedRequest)
+        } else if !self
+            .common
+            .config_info
+            .rsp_capabilities
+            .intersects(required_capabilities)
+        {
+            Some(SpdmErrorCode::SpdmErrorUnsupportedRequest)
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(all(test,))]
@@ -465,6 +687,52 @@ mod tests_responder {
         assert!(status);
     }
     #[test]
+    fn test_case1_process_message_undecodable_keeps_session() {
+        let (config_info, provision_info) = create_info();
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        let rsp_session_id = 0xFFFEu16;
+        let session_id = (0xffu32 << 16) + rsp_session_id as u32;
+        context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+        context.common.session[0].setup(session_id).unwrap();
+        context.common.session[0].set_crypto_param(
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmDheAlgo::SECP_384_R1,
+            SpdmAeadAlgo::AES_256_GCM,
+            SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+        );
+        context.common.session[0]
+            .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
+
+        // a secured message of the session that does not authenticate
+        let mut secured_message = [0x5au8; 64];
+        secured_message[..4].copy_from_slice(&session_id.to_le_bytes());
+        secured_message[4..6].copy_from_slice(&58u16.to_le_bytes());
+        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+        let used = context
+            .common
+            .transport_encap
+            .encap(&secured_message, &mut transport_buffer, true)
+            .unwrap();
+        shared_buffer.set_buffer(&transport_buffer[..used]);
+
+        assert!(context.process_message(ST1).is_err());
+        let session = context.common.get_session_via_id(session_id).unwrap();
+        assert_eq!(
+            session.get_session_state(),
+            crate::common::session::SpdmSessionState::SpdmSessionEstablished
+        );
+    }
+    #[test]
     #[should_panic(expected = "not implemented")]
     fn test_case0_dispatch_secured_message() {
         let (config_info, provision_info) = create_info();
@@ -481,6 +749,7 @@ mod tests_responder {
 
         crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
 
+        context.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion10;
         context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
         context.common.negotiate_info.base_asym_sel = SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
         context.common.negotiate_info.measurement_hash_sel =
@@ -502,6 +771,7 @@ mod tests_responder {
         });
         context.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionHandshaking);
+        context.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
 
         for i in 0..5 {
             let bytes = &mut [0u8; 4];
@@ -522,8 +792,9 @@ mod tests_responder {
                 request_response_code: dispatch_secured_data(i, false),
             };
             value.encode(&mut writer);
+            // answered, with an ERROR if not allowed in a session
             let status_secured = context.dispatch_secured_message(session_id, bytes);
-            assert!(!status_secured);
+            assert!(status_secured);
         }
         for i in 0..9 {
             let bytes = &mut [0u8; 4];
@@ -549,6 +820,446 @@ mod tests_responder {
         }
     }
 
+    #[test]
+    fn test_case1_dispatch_secured_message_version_mismatch() {
+        let (config_info, provision_info) = create_info();
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        context.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        let rsp_session_id = 0xffu16;
+        let session_id = (0xffu32 << 16) + rsp_session_id as u32;
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+        context.common.session[0].setup(session_id).unwrap();
+        context.common.session[0].set_crypto_param(
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmDheAlgo::SECP_384_R1,
+            SpdmAeadAlgo::AES_256_GCM,
+            SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+        );
+        context.common.session[0]
+            .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
+
+        let bytes = &mut [0u8; 4];
+        let mut writer = Writer::init(bytes);
+        let value = SpdmMessageHeader {
+            version: SpdmVersion::SpdmVersion10,
+            request_response_code: SpdmRequestResponseCode::SpdmRequestHeartbeat,
+        };
+        value.encode(&mut writer);
+        assert!(context.dispatch_secured_message(session_id, bytes));
+
+        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+        let used = shared_buffer.get_buffer(&mut transport_buffer);
+        // decode it as the peer would, from the sequence number it was sent with
+        context.common.session[0].set_response_direction_sequence_number(0);
+        let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let used = context
+            .common
+            .decode_secured_message(session_id, &transport_buffer[..used], &mut receive_buffer)
+            .unwrap();
+        assert_eq!(
+            &receive_buffer[..used],
+            &[
+                0x11,
+                SpdmRequestResponseCode::SpdmResponseError.get_u8(),
+                SpdmErrorCode::SpdmErrorVersionMismatch.get_u8(),
+                0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_case2_dispatch_secured_message_request_allowed() {
+        let (mut config_info, provision_info) = create_info();
+        config_info.rsp_capabilities -= SpdmResponseCapabilityFlags::HBEAT_CAP;
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        context.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        context.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+        let rsp_session_id = 0xffu16;
+        let session_id = (0xffu32 << 16) + rsp_session_id as u32;
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+        context.common.session[0].setup(session_id).unwrap();
+        context.common.session[0].set_crypto_param(
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmDheAlgo::SECP_384_R1,
+            SpdmAeadAlgo::AES_256_GCM,
+            SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+        );
+        context.common.session[0]
+            .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
+
+        let unexpected = SpdmErrorCode::SpdmErrorUnexpectedRequest.get_u8();
+        let unsupported = SpdmErrorCode::SpdmErrorUnsupportedRequest.get_u8();
+        let cases = [
+            (
+                SpdmRequestResponseCode::SpdmRequestGetVersion,
+                unexpected,
+                0,
+            ),
+            (SpdmRequestResponseCode::SpdmRequestChallenge, unexpected, 0),
+            (
+                SpdmRequestResponseCode::SpdmRequestKeyExchange,
+                unexpected,
+                0,
+            ),
+            (
+                SpdmRequestResponseCode::SpdmRequestPskExchange,
+                unexpected,
+                0,
+            ),
+            (
+                SpdmRequestResponseCode::SpdmResponseDigests,
+                unsupported,
+                SpdmRequestResponseCode::SpdmResponseDigests.get_u8(),
+            ),
+            (SpdmRequestResponseCode::Unknown(0), unsupported, 0),
+            (
+                SpdmRequestResponseCode::SpdmRequestHeartbeat,
+                unsupported,
+                SpdmRequestResponseCode::SpdmRequestHeartbeat.get_u8(),
+            ),
+        ];
+        for (sequence_number, (code, error_code, error_data)) in cases.iter().enumerate() {
+            let bytes = &mut [0u8; 4];
+            let mut writer = Writer::init(bytes);
+            let value = SpdmMessageHeader {
+                version: SpdmVersion::SpdmVersion11,
+                request_response_code: *code,
+            };
+            value.encode(&mut writer);
+            assert!(context.dispatch_secured_message(session_id, bytes));
+
+            let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+            let used = shared_buffer.get_buffer(&mut transport_buffer);
+            context.common.session[0]
+                .set_response_direction_sequence_number(sequence_number as u64);
+            let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            let used = context
+                .common
+                .decode_secured_message(session_id, &transport_buffer[..used], &mut receive_buffer)
+                .unwrap();
+            assert_eq!(
+                &receive_buffer[..used],
+                &[
+                    0x11,
+                    SpdmRequestResponseCode::SpdmResponseError.get_u8(),
+                    *error_code,
+                    *error_data
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_case0_supervise_sessions() {
+        let (config_info, provision_info) = create_info();
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        crate::time::register(TIME_IMPL.clone());
+        let now = crate::time::now().unwrap();
+
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+        for (i, session) in context.common.session.iter_mut().enumerate() {
+            session.setup(0xFFFE + i as u32).unwrap();
+            session.set_session_state(SpdmSessionState::SpdmSessionEstablished);
+            session.heartbeat_period = 1;
+            session.last_message_time = Some(now - 1_000_000);
+        }
+        // idle for twice the period
+        context.common.session[1].last_message_time = Some(now - 2_000_000);
+        // heartbeat off
+        context.common.session[2].heartbeat_period = 0;
+        context.common.session[2].last_message_time = Some(now - 10_000_000);
+        // not established yet
+        context.common.session[3].set_session_state(SpdmSessionState::SpdmSessionHandshaking);
+        context.common.session[3].last_message_time = Some(now - 10_000_000);
+
+        context.supervise_sessions();
+        assert_eq!(context.common.session[0].get_session_id(), 0xFFFE);
+        assert_eq!(context.common.session[1].get_session_id(), 0);
+        assert_eq!(context.common.session[2].get_session_id(), 0xFFFE + 2);
+        assert_eq!(context.common.session[3].get_session_id(), 0xFFFE + 3);
+    }
+
+    #[test]
+    fn test_case1_supervise_sessions_without_message() {
+        let (config_info, provision_info) = create_info();
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        crate::time::register(TIME_IMPL.clone());
+        let now = crate::time::now().unwrap();
+
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+        context.common.session[0].setup(0xFFFE).unwrap();
+        context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
+        context.common.session[0].heartbeat_period = 1;
+        context.common.session[0].last_message_time = Some(now - 2_000_000);
+
+        // nothing to receive
+        assert!(context.process_message(ST1).is_err());
+        assert_eq!(context.common.session[0].get_session_id(), 0);
+    }
+
+    struct EchoAppMessageHandler {
+        app_type: u8,
+        session_id: u32,
+    }
+
+    impl SpdmAppMessageHandler for EchoAppMessageHandler {
+        fn dispatch_secured_app_message(
+            &mut self,
+            session_id: u32,
+            app_type: u8,
+            request: &[u8],
+            response: &mut [u8],
+        ) -> SpdmResult<usize> {
+            if app_type != self.app_type {
+                return spdm_result_err!(EINVAL);
+            }
+            self.session_id = session_id;
+            response[..request.len()].copy_from_slice(request);
+            Ok(request.len())
+        }
+    }
+
+    #[test]
+    fn test_case0_dispatch_secured_app_message() {
+        let (config_info, provision_info) = create_info();
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        let mut app_message_handler = EchoAppMessageHandler {
+            app_type: 0x01,
+            session_id: 0,
+        };
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        let session_id = 0xFFFEFFFEu32;
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+        context.common.session[0].setup(session_id).unwrap();
+        context.common.session[0].set_crypto_param(
+            SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+            SpdmDheAlgo::SECP_384_R1,
+            SpdmAeadAlgo::AES_256_GCM,
+            SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+        );
+        context.common.session[0].set_session_state(SpdmSessionState::SpdmSessionEstablished);
+
+        context.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        let request = [0x80u8, 0x00, 0x02];
+
+        // decodes the secured SPDM message sent with `sequence_number`
+        let receive = |context: &mut ResponderContext, sequence_number: u64| {
+            let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+            let used = shared_buffer.get_buffer(&mut transport_buffer);
+            context.common.session[0].set_response_direction_sequence_number(sequence_number);
+            let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            let used = context
+                .common
+                .decode_secured_message(session_id, &transport_buffer[..used], &mut receive_buffer)
+                .unwrap();
+            receive_buffer[..used].to_vec()
+        };
+
+        // without a handler the request is unsupported
+        assert!(context.dispatch_secured_app_message(session_id, 0x01, &request));
+        assert_eq!(
+            receive(&mut context, 0),
+            [
+                0x11,
+                SpdmRequestResponseCode::SpdmResponseError.get_u8(),
+                SpdmErrorCode::SpdmErrorUnsupportedRequest.get_u8(),
+                0
+            ]
+        );
+
+        context.set_app_message_handler(&mut app_message_handler);
+        assert!(context.dispatch_secured_app_message(session_id, 0x01, &request));
+        let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+        assert!(shared_buffer.get_buffer(&mut transport_buffer) > request.len());
+
+        // the handler refuses other message types
+        assert!(context.dispatch_secured_app_message(session_id, 0x04, &request));
+        assert_eq!(
+            receive(&mut context, 2),
+            [
+                0x11,
+                SpdmRequestResponseCode::SpdmResponseError.get_u8(),
+                SpdmErrorCode::SpdmErrorUnspecified.get_u8(),
+                0
+            ]
+        );
+
+        drop(context);
+        assert_eq!(app_message_handler.session_id, session_id);
+    }
+
+    #[test]
+    fn test_case1_dispatch_message_connection_state() {
+        let (mut config_info, provision_info) = create_info();
+        config_info.rsp_capabilities -= SpdmResponseCapabilityFlags::CHAL_CAP;
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+
+        // returns (response code, param1, param2) for a bare request header
+        let dispatch = |context: &mut ResponderContext,
+                        version: SpdmVersion,
+                        request_response_code: SpdmRequestResponseCode| {
+            let bytes = &mut [0u8; 4];
+            let mut writer = Writer::init(bytes);
+            SpdmMessageHeader {
+                version,
+                request_response_code,
+            }
+            .encode(&mut writer);
+            assert!(context.dispatch_message(bytes));
+
+            let mut transport_buffer = [0u8; config::DATA_TRANSFER_SIZE];
+            let used = shared_buffer.get_buffer(&mut transport_buffer);
+            let mut receive_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+            context
+                .common
+                .decap(&transport_buffer[..used], &mut receive_buffer)
+                .unwrap();
+            (receive_buffer[1], receive_buffer[2], receive_buffer[3])
+        };
+        let error = SpdmRequestResponseCode::SpdmResponseError.get_u8();
+        let unexpected = SpdmErrorCode::SpdmErrorUnexpectedRequest.get_u8();
+        let unsupported = SpdmErrorCode::SpdmErrorUnsupportedRequest.get_u8();
+
+        let response = dispatch(
+            &mut context,
+            SpdmVersion::SpdmVersion11,
+            SpdmRequestResponseCode::SpdmRequestGetCapabilities,
+        );
+        assert_eq!(response, (error, unexpected, 0));
+
+        let response = dispatch(
+            &mut context,
+            SpdmVersion::SpdmVersion10,
+            SpdmRequestResponseCode::SpdmRequestGetVersion,
+        );
+        assert_eq!(
+            response.0,
+            SpdmRequestResponseCode::SpdmResponseVersion.get_u8()
+        );
+        assert_eq!(
+            context.common.runtime_info.connection_state,
+            SpdmConnectionState::AfterVersion
+        );
+
+        for request_response_code in [
+            SpdmRequestResponseCode::SpdmRequestNegotiateAlgorithms,
+            SpdmRequestResponseCode::SpdmRequestGetDigests,
+            SpdmRequestResponseCode::SpdmRequestKeyExchange,
+        ] {
+            let response = dispatch(
+                &mut context,
+                SpdmVersion::SpdmVersion11,
+                request_response_code,
+            );
+            assert_eq!(response, (error, unexpected, 0));
+        }
+
+        let response = dispatch(
+            &mut context,
+            SpdmVersion::SpdmVersion11,
+            SpdmRequestResponseCode::SpdmResponseDigests,
+        );
+        assert_eq!(
+            response,
+            (
+                error,
+                unsupported,
+                SpdmRequestResponseCode::SpdmResponseDigests.get_u8()
+            )
+        );
+
+        context.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        context.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+
+        let response = dispatch(
+            &mut context,
+            SpdmVersion::SpdmVersion11,
+            SpdmRequestResponseCode::SpdmRequestChallenge,
+        );
+        assert_eq!(
+            response,
+            (
+                error,
+                unsupported,
+                SpdmRequestResponseCode::SpdmRequestChallenge.get_u8()
+            )
+        );
+
+        for request_response_code in [
+            SpdmRequestResponseCode::SpdmRequestGetCapabilities,
+            SpdmRequestResponseCode::SpdmRequestFinish,
+            SpdmRequestResponseCode::SpdmRequestEndSession,
+        ] {
+            let response = dispatch(
+                &mut context,
+                SpdmVersion::SpdmVersion11,
+                request_response_code,
+            );
+            assert_eq!(response, (error, unexpected, 0));
+        }
+    }
+
     fn dispatch_secured_data(num: usize, status: bool) -> SpdmRequestResponseCode {
         let response_flase = [
             SpdmRequestResponseCode::SpdmRequestGetVersion,
@@ -37,28 +37,15 @@ impl<'a> ResponderContext<'a> {
             return;
         }
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
         if self
             .common
-            .runtime_info
-            .message_b
-            .append_message(&bytes[..reader.used()])
-            .is_none()
+            .append_message_m1m2(&bytes[..reader.used()])
+            .is_err()
         {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return;
         }
 
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            self.common
-                .runtime_info
-                .digest_context_m1m2
-                .as_mut()
-                .unwrap(),
-            &bytes[..reader.used()],
-        );
-
         let digest_size = self.common.negotiate_info.base_hash_sel.get_size();
 
         info!("send spdm digest\n");
@@ -93,21 +80,7 @@ impl<'a> ResponderContext<'a> {
         writer.mut_used_slice()[(used - cert_chain_hash.data_size as usize)..used]
             .copy_from_slice(cert_chain_hash.as_ref());
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        self.common
-            .runtime_info
-            .message_b
-            .append_message(writer.used_slice());
-
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            self.common
-                .runtime_info
-                .digest_context_m1m2
-                .as_mut()
-                .unwrap(),
-            writer.used_slice(),
-        );
+        let _ = self.common.append_message_m1m2(writer.used_slice());
     }
 }
 
@@ -139,8 +112,6 @@ mod tests_responder {
             data: [0u8; config::MAX_SPDM_CERT_CHAIN_DATA_SIZE],
         });
         context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
-        context.common.runtime_info.digest_context_m1m2 =
-            Some(crypto::hash::hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384).unwrap());
 
         let spdm_message_header = &mut [0u8; 1024];
         let mut writer = Writer::init(spdm_message_header);
@@ -5,36 +5,58 @@
 use crate::common::SpdmCodec;
 use crate::error::{spdm_err, SpdmResult};
 use crate::message::*;
+use crate::protocol::SpdmResponseCapabilityFlags;
 use crate::responder::*;
 
 impl<'a> ResponderContext<'a> {
     pub fn handle_spdm_end_session(&mut self, session_id: u32, bytes: &[u8]) -> SpdmResult {
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let mut writer = Writer::init(&mut send_buffer);
-        if self.write_spdm_end_session_response(bytes, &mut writer) {
+        if let Some(end_session_request_attributes) =
+            self.write_spdm_end_session_response(bytes, &mut writer)
+        {
             self.send_secured_message(session_id, writer.used_slice(), false)?;
             let session = self
                 .common
                 .get_session_via_id(session_id)
                 .ok_or(spdm_err!(EINVAL))?;
-            session.teardown(session_id)
+            session.teardown(session_id)?;
+
+            // only a CACHE_CAP responder keeps the negotiated state on request
+            if self
+                .common
+                .config_info
+                .rsp_capabilities
+                .contains(SpdmResponseCapabilityFlags::CACHE_CAP)
+                && !end_session_request_attributes
+                    .contains(SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE)
+            {
+                self.common.reset_negotiated_state();
+            }
+            Ok(())
         } else {
             self.send_message(writer.used_slice())
         }
     }
 
-    pub fn write_spdm_end_session_response(&mut self, bytes: &[u8], writer: &mut Writer) -> bool {
+    /// The request attributes if END_SESSION_ACK is written.
+    pub fn write_spdm_end_session_response(
+        &mut self,
+        bytes: &[u8],
+        writer: &mut Writer,
+    ) -> Option<SpdmEndSessionRequestAttributes> {
         let mut reader = Reader::init(bytes);
         SpdmMessageHeader::read(&mut reader);
 
         let end_session_req =
             SpdmEndSessionRequestPayload::spdm_read(&mut self.common, &mut reader);
-        if let Some(end_session_req) = end_session_req {
+        let end_session_request_attributes = if let Some(end_session_req) = end_session_req {
             debug!("!!! end_session req : {:02x?}\n", end_session_req);
+            end_session_req.end_session_request_attributes
         } else {
             error!("!!! end_session req : fail !!!\n");
-            return false;
-        }
+            return None;
+        };
 
         info!("send spdm end_session rsp\n");
 
@@ -46,7 +68,7 @@ impl<'a> ResponderContext<'a> {
             payload: SpdmMessagePayload::SpdmEndSessionResponse(SpdmEndSessionResponsePayload {}),
         };
         response.spdm_encode(&mut self.common, writer);
-        true
+        Some(end_session_request_attributes)
     }
 }
 
@@ -54,6 +76,7 @@ impl<'a> ResponderContext<'a> {
 mod tests_responder {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmConnectionState;
     use crate::message::SpdmMessageHeader;
     use crate::protocol::gen_array_clone;
     use crate::testlib::*;
@@ -109,4 +132,72 @@ mod tests_responder {
         bytes[2..].copy_from_slice(&session_request[0..1022]);
         let _ = context.handle_spdm_end_session(session_id, bytes);
     }
+
+    #[test]
+    fn test_case1_handle_spdm_end_session() {
+        let (mut config_info, provision_info) = create_info();
+        config_info.rsp_capabilities |= SpdmResponseCapabilityFlags::CACHE_CAP;
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+        crypto::asym_sign::register(ASYM_SIGN_IMPL.clone());
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+        context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        context.common.runtime_info.connection_state = SpdmConnectionState::Negotiated;
+        context
+            .common
+            .runtime_info
+            .message_a
+            .append_message(&[0x10]);
+        context.common.session = gen_array_clone(SpdmSession::new(), 4);
+
+        for (session_id, end_session_request_attributes, connection_state) in [
+            (
+                0xFFFF_FFFFu32,
+                SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE,
+                SpdmConnectionState::Negotiated,
+            ),
+            (
+                0xFFFE_FFFEu32,
+                SpdmEndSessionRequestAttributes::empty(),
+                SpdmConnectionState::NotStarted,
+            ),
+        ] {
+            context.common.session[0].setup(session_id).unwrap();
+            context.common.session[0].set_crypto_param(
+                SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+                SpdmDheAlgo::SECP_384_R1,
+                SpdmAeadAlgo::AES_256_GCM,
+                SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+            );
+            context.common.session[0].set_session_state(
+                crate::common::session::SpdmSessionState::SpdmSessionEstablished,
+            );
+
+            let bytes = &mut [0u8; 4];
+            let mut writer = Writer::init(bytes);
+            SpdmMessageHeader {
+                version: SpdmVersion::SpdmVersion11,
+                request_response_code: SpdmRequestResponseCode::SpdmRequestEndSession,
+            }
+            .encode(&mut writer);
+            SpdmEndSessionRequestPayload {
+                end_session_request_attributes,
+            }
+            .spdm_encode(&mut context.common, &mut writer);
+
+            assert!(context.handle_spdm_end_session(session_id, bytes).is_ok());
+            assert!(context.common.get_session_via_id(session_id).is_none());
+            assert_eq!(
+                context.common.runtime_info.connection_state,
+                connection_state
+            );
+        }
+        assert!(context.common.runtime_info.message_a.as_ref().is_empty());
+    }
 }
@@ -4,6 +4,7 @@
 
 use crate::common::SpdmCodec;
 use crate::message::*;
+use crate::protocol::SpdmVersion;
 use crate::responder::*;
 
 impl<'a> ResponderContext<'a> {
@@ -15,7 +16,11 @@ impl<'a> ResponderContext<'a> {
     ) {
         let error = SpdmMessage {
             header: SpdmMessageHeader {
-                version: self.common.negotiate_info.spdm_version_sel,
+                // errors before version negotiation are SPDM 1.0 messages
+                version: match self.common.negotiate_info.spdm_version_sel {
+                    SpdmVersion::Unknown(_) => SpdmVersion::SpdmVersion10,
+                    spdm_version_sel => spdm_version_sel,
+                },
                 request_response_code: SpdmRequestResponseCode::SpdmResponseError,
             },
             payload: SpdmMessagePayload::SpdmErrorResponse(SpdmErrorResponsePayload {
@@ -3,13 +3,9 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
 use crate::common::SpdmCodec;
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::protocol::*;
 use crate::responder::*;
 
-#[cfg(not(feature = "hashed-transcript-data"))]
-use crate::common::ManagedBuffer;
 use crate::message::*;
 extern crate alloc;
 use alloc::boxed::Box;
@@ -55,82 +51,44 @@ impl<'a> ResponderContext<'a> {
         let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
         let temp_used = read_used - base_hash_size;
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let mut message_f = ManagedBuffer::default();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if message_f.append_message(&bytes[..temp_used]).is_none() {
-            panic!("message_f add the message error");
+        let session = self.common.get_session_via_id(session_id).unwrap();
+        if session
+            .runtime_info
+            .transcript_th
+            .append(&bytes[..temp_used])
+            .is_err()
+        {
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return false;
         }
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let session = self
-            .common
-            .get_immutable_session_via_id(session_id)
-            .unwrap();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let message_k = &session.runtime_info.message_k.clone();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data =
-            &self
-                .common
-                .calc_rsp_transcript_data(false, message_k, Some(&message_f));
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if transcript_data.is_err() {
+        let message_hash = session.runtime_info.transcript_th.hash();
+        if message_hash.is_err() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return false;
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = transcript_data.as_ref().unwrap();
 
+        if session
+            .verify_hmac_with_request_finished_key(
+                message_hash.unwrap().as_ref(),
+                &finish_req.verify_data,
+            )
+            .is_err()
         {
-            let session = self.common.get_session_via_id(session_id).unwrap();
-
-            #[cfg(feature = "hashed-transcript-data")]
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                &bytes[..temp_used],
-            );
-
-            #[cfg(feature = "hashed-transcript-data")]
-            let message_hash = crypto::hash::hash_ctx_finalize(
-                session
-                    .runtime_info
-                    .digest_context_th
-                    .as_mut()
-                    .cloned()
-                    .unwrap(),
-            );
-
-            if session
-                .verify_hmac_with_request_finished_key(
-                    #[cfg(not(feature = "hashed-transcript-data"))]
-                    transcript_data.as_ref(),
-                    #[cfg(feature = "hashed-transcript-data")]
-                    message_hash.unwrap().as_ref(),
-                    &finish_req.verify_data,
-                )
-                .is_err()
-            {
-                error!("verify_hmac_with_request_finished_key fail");
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return false;
-            } else {
-                info!("verify_hmac_with_request_finished_key pass");
-            }
-
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            if message_f
-                .append_message(finish_req.verify_data.as_ref())
-                .is_none()
-            {
-                panic!("message_f add the message error");
-            }
+            error!("verify_hmac_with_request_finished_key fail");
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return false;
+        } else {
+            info!("verify_hmac_with_request_finished_key pass");
+        }
 
-            #[cfg(feature = "hashed-transcript-data")]
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                finish_req.verify_data.as_ref(),
-            );
+        if session
+            .runtime_info
+            .transcript_th
+            .append(finish_req.verify_data.as_ref())
+            .is_err()
+        {
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return false;
         }
 
         let in_clear_text = self
@@ -166,126 +124,47 @@ impl<'a> ResponderContext<'a> {
         response.spdm_encode(&mut self.common, writer);
         let used = writer.used();
 
-        #[cfg(feature = "hashed-transcript-data")]
         let session = self.common.get_session_via_id(session_id).unwrap();
-
         if in_clear_text {
             // generate HMAC with finished_key
             let temp_used = used - base_hash_size;
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            if message_f
-                .append_message(&writer.used_slice()[..temp_used])
-                .is_none()
-            {
-                panic!("message_f add the message error");
-            }
-
-            #[cfg(feature = "hashed-transcript-data")]
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                &writer.used_slice()[..temp_used],
-            );
-
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            let transcript_data =
-                self.common
-                    .calc_rsp_transcript_data(false, message_k, Some(&message_f));
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            if transcript_data.is_err() {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                let session = self.common.get_session_via_id(session_id).unwrap();
-                let _ = session.teardown(session_id);
-                return false;
-            }
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            let transcript_data = transcript_data.unwrap();
-
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            let session = self.common.get_session_via_id(session_id).unwrap();
-
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            let hmac = session.generate_hmac_with_response_finished_key(transcript_data.as_ref());
-
-            #[cfg(feature = "hashed-transcript-data")]
-            let message_hash = crypto::hash::hash_ctx_finalize(
-                session
-                    .runtime_info
-                    .digest_context_th
-                    .as_mut()
-                    .cloned()
-                    .unwrap(),
-            );
-
-            #[cfg(feature = "hashed-transcript-data")]
-            let hmac =
-                session.generate_hmac_with_response_finished_key(message_hash.unwrap().as_ref());
+            let hmac = session
+                .runtime_info
+                .transcript_th
+                .append(&writer.used_slice()[..temp_used])
+                .and_then(|_| session.runtime_info.transcript_th.hash())
+                .and_then(|message_hash| {
+                    session.generate_hmac_with_response_finished_key(message_hash.as_ref())
+                })
+                .and_then(|hmac| {
+                    session.runtime_info.transcript_th.append(hmac.as_ref())?;
+                    Ok(hmac)
+                });
             if hmac.is_err() {
                 let _ = session.teardown(session_id);
                 self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
                 return false;
             }
             let hmac = hmac.unwrap();
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            if message_f.append_message(hmac.as_ref()).is_none() {
-                let _ = session.teardown(session_id);
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return false;
-            } else {
-                session.runtime_info.message_f = message_f.clone();
-            }
-
-            #[cfg(feature = "hashed-transcript-data")]
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                hmac.as_ref(),
-            );
 
             // patch the message before send
             writer.mut_used_slice()[(used - base_hash_size)..used].copy_from_slice(hmac.as_ref());
-        } else {
-            #[cfg(not(feature = "hashed-transcript-data"))]
-            if message_f.append_message(writer.used_slice()).is_none() {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                let session = self.common.get_session_via_id(session_id).unwrap();
-                let _ = session.teardown(session_id);
-                return false;
-            } else {
-                let session = self.common.get_session_via_id(session_id).unwrap();
-                session.runtime_info.message_f = message_f.clone();
-            }
-            #[cfg(feature = "hashed-transcript-data")]
-            crypto::hash::hash_ctx_update(
-                session.runtime_info.digest_context_th.as_mut().unwrap(),
-                writer.used_slice(),
-            );
-        }
-
-        // generate the data secret
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let th2 = self
-            .common
-            .calc_rsp_transcript_hash(false, message_k, Some(&message_f));
-        #[cfg(feature = "hashed-transcript-data")]
-        let th2 = crypto::hash::hash_ctx_finalize(
-            session
-                .runtime_info
-                .digest_context_th
-                .as_mut()
-                .cloned()
-                .unwrap(),
-        );
-        #[cfg(feature = "hashed-transcript-data")]
-        if th2.is_none() {
-            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-            let session = self.common.get_session_via_id(session_id).unwrap();
+        } else if session
+            .runtime_info
+            .transcript_th
+            .append(writer.used_slice())
+            .is_err()
+        {
             let _ = session.teardown(session_id);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return false;
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
+
+        // generate the data secret
+        let th2 = session.runtime_info.transcript_th.hash();
         if th2.is_err() {
-            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-            let session = self.common.get_session_via_id(session_id).unwrap();
             let _ = session.teardown(session_id);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return false;
         }
         let th2 = th2.unwrap();
@@ -304,6 +183,7 @@ impl<'a> ResponderContext<'a> {
 mod tests_responder {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmTranscriptMode;
     use crate::message::SpdmMessageHeader;
     use crate::protocol::gen_array_clone;
     use crate::testlib::*;
@@ -311,7 +191,6 @@ mod tests_responder {
     use codec::{Codec, Writer};
 
     #[test]
-    #[cfg(not(feature = "hashed-transcript-data"))]
     fn test_case0_handle_spdm_finish() {
         let (config_info, provision_info) = create_info();
         let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
@@ -343,6 +222,9 @@ mod tests_responder {
             SpdmResponseCapabilityFlags::HANDSHAKE_IN_THE_CLEAR_CAP;
         context.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
+        context.common.config_info.transcript_mode = SpdmTranscriptMode::Buffered;
+        context.common.session[0].runtime_info.transcript_th =
+            context.common.new_transcript().unwrap();
         let spdm_message_header = &mut [0u8; 1024];
         let mut writer = Writer::init(spdm_message_header);
         let value = SpdmMessageHeader {
@@ -414,8 +296,8 @@ mod tests_responder {
         );
         context.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
-        context.common.session[0].runtime_info.digest_context_th =
-            Some(crypto::hash::hash_ctx_init(context.common.negotiate_info.base_hash_sel).unwrap());
+        context.common.session[0].runtime_info.transcript_th =
+            context.common.new_transcript().unwrap();
 
         let spdm_message_header = &mut [0u8; 1024];
         let mut writer = Writer::init(spdm_message_header);
@@ -6,13 +6,11 @@ use crate::error::{spdm_err, spdm_result_err, SpdmResult};
 use crate::responder::*;
 
 use crate::common::SpdmCodec;
-use crate::common::{ManagedBuffer, SpdmOpaqueSupport};
+use crate::common::SpdmTranscript;
 use crate::crypto;
 use crate::protocol::*;
 extern crate alloc;
-use crate::common::opaque::SpdmOpaqueStruct;
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto::HashCtx;
+use crate::common::opaque::{SpdmOpaqueStruct, DMTF_SECURE_SPDM_VERSION_10};
 use crate::message::*;
 use alloc::boxed::Box;
 
@@ -20,10 +18,17 @@ impl<'a> ResponderContext<'a> {
     pub fn handle_spdm_key_exchange(&mut self, bytes: &[u8]) -> SpdmResult {
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let mut writer = Writer::init(&mut send_buffer);
-        self.write_spdm_key_exchange_response(bytes, &mut writer)?;
+        if let Err(err) = self.write_spdm_key_exchange_response(bytes, &mut writer) {
+            // a failure answered with an ERROR leaves it in the writer
+            if writer.used() != 0 {
+                self.send_message(writer.used_slice())?;
+            }
+            return Err(err);
+        }
         self.send_message(writer.used_slice())
     }
 
+    /// On error, `writer` holds the ERROR response to send, if one is due.
     pub fn write_spdm_key_exchange_response(
         &mut self,
         bytes: &[u8],
@@ -36,6 +41,8 @@ impl<'a> ResponderContext<'a> {
             SpdmKeyExchangeRequestPayload::spdm_read(&mut self.common, &mut reader);
 
         let mut return_opaque = SpdmOpaqueStruct::default();
+        // Secured Messages 1.0 unless the requester lists versions to select
+        let mut secure_spdm_version_sel = DMTF_SECURE_SPDM_VERSION_10;
 
         if let Some(key_exchange_req) = &key_exchange_req {
             debug!("!!! key_exchange req : {:02x?}\n", key_exchange_req);
@@ -66,38 +73,24 @@ impl<'a> ResponderContext<'a> {
                 if secured_message_version_list.version_count
                     > crate::common::opaque::MAX_SECURE_SPDM_VERSION_COUNT as u8
                 {
+                    self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
                     return spdm_result_err!(EINVAL);
                 }
-                for index in 0..secured_message_version_list.version_count as usize {
-                    if secured_message_version_list.versions_list[index].get_secure_spdm_version()
-                        == self.common.config_info.secure_spdm_version
-                    {
-                        if self
-                            .common
-                            .negotiate_info
-                            .opaque_data_support
-                            .contains(SpdmOpaqueSupport::OPAQUE_DATA_FMT1)
-                        {
-                            return_opaque.data_size =
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT1
-                                    .len() as u16;
-                            return_opaque.data[..(return_opaque.data_size as usize)]
-                                .copy_from_slice(
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT1
-                                    .as_ref(),
-                            );
-                        } else {
-                            return_opaque.data_size =
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT0
-                                    .len() as u16;
-                            return_opaque.data[..(return_opaque.data_size as usize)]
-                                .copy_from_slice(
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT0
-                                    .as_ref(),
-                            );
-                        }
+                let version = match secured_message_version_list
+                    .select_secure_spdm_version(self.common.config_info.secure_spdm_version)
+                {
+                    Some(version) => version,
+                    None => {
+                        error!("!!! no secured message version in common !!!\n");
+                        self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+                        return spdm_result_err!(EINVAL);
                     }
-                }
+                };
+                return_opaque = SpdmOpaqueStruct::rsp_set_dmtf_secure_spdm_version_selection(
+                    &self.common,
+                    version,
+                );
+                secure_spdm_version_sel = version.get_secure_spdm_version();
             }
         } else {
             error!("!!! key_exchange req : fail !!!\n");
@@ -105,6 +98,15 @@ impl<'a> ResponderContext<'a> {
             return spdm_result_err!(EINVAL);
         }
 
+        let rsp_session_id =
+            if let Some(rsp_session_id) = self.common.get_next_half_session_id(false) {
+                rsp_session_id
+            } else {
+                error!("!!! too many sessions : fail !!!\n");
+                self.write_spdm_error(SpdmErrorCode::SpdmErrorSessionLimitExceeded, 0, writer);
+                return spdm_result_err!(EBUSY);
+            };
+
         info!("send spdm key_exchange rsp\n");
 
         let (exchange, key_exchange_context) =
@@ -121,6 +123,7 @@ impl<'a> ResponderContext<'a> {
             key_exchange_context.compute_final_key(&key_exchange_req.as_ref().unwrap().exchange);
 
         if final_key.is_none() {
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(ESEC);
         }
         let final_key = final_key.unwrap();
@@ -129,15 +132,13 @@ impl<'a> ResponderContext<'a> {
         let mut random = [0u8; SPDM_RANDOM_SIZE];
         let _ = crypto::rand::get_random(&mut random);
 
-        let rsp_session_id = 0xFFFE;
-
         let response = SpdmMessage {
             header: SpdmMessageHeader {
                 version: self.common.negotiate_info.spdm_version_sel,
                 request_response_code: SpdmRequestResponseCode::SpdmResponseKeyExchangeRsp,
             },
             payload: SpdmMessagePayload::SpdmKeyExchangeResponse(SpdmKeyExchangeResponsePayload {
-                heartbeat_period: self.common.config_info.heartbeat_period,
+                heartbeat_period: self.heartbeat_period(),
                 rsp_session_id,
                 mut_auth_req: SpdmKeyExchangeMutAuthAttributes::empty(),
                 req_slot_id: 0x0,
@@ -147,7 +148,7 @@ impl<'a> ResponderContext<'a> {
                     data_size: self.common.negotiate_info.base_hash_sel.get_size(),
                     data: Box::new([0xaa; SPDM_MAX_HASH_SIZE]),
                 },
-                opaque: return_opaque.clone(),
+                opaque: return_opaque,
                 signature: SpdmSignatureStruct {
                     data_size: self.common.negotiate_info.base_asym_sel.get_size(),
                     data: [0xbb; SPDM_MAX_ASYM_KEY_SIZE],
@@ -159,81 +160,46 @@ impl<'a> ResponderContext<'a> {
             }),
         };
 
-        response.spdm_encode(&mut self.common, writer);
-        let used = writer.used();
+        // errors are written to the empty writer, the response joins it at the end
+        let mut rsp_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let mut rsp_writer = Writer::init(&mut rsp_buffer);
+        response.spdm_encode(&mut self.common, &mut rsp_writer);
+        let used = rsp_writer.used();
 
         // generate signature
         let base_asym_size = self.common.negotiate_info.base_asym_sel.get_size() as usize;
         let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
         let temp_used = used - base_asym_size - base_hash_size;
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let mut message_k = ManagedBuffer::default();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            if message_k.append_message(&bytes[..reader.used()]).is_none() {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return spdm_result_err!(EFAULT);
-            }
-            if message_k
-                .append_message(&writer.used_slice()[..temp_used])
-                .is_none()
-            {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return spdm_result_err!(EFAULT);
-            }
-        }
-
-        #[cfg(feature = "hashed-transcript-data")]
-        let cert_chain_hash;
-        #[cfg(feature = "hashed-transcript-data")]
-        if let Some(hash) = self.common.get_certchain_hash_rsp(false) {
-            cert_chain_hash = hash;
-        } else {
-            return spdm_result_err!(EINVAL);
+        let transcript_th = self.common.create_rsp_transcript_th(false);
+        if transcript_th.is_err() {
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return spdm_result_err!(EFAULT);
         }
-
-        #[cfg(feature = "hashed-transcript-data")]
-        let mut digest_context_th =
-            crypto::hash::hash_ctx_init(self.common.negotiate_info.base_hash_sel).unwrap();
-        #[cfg(feature = "hashed-transcript-data")]
+        let mut transcript_th = transcript_th.unwrap();
+        if transcript_th.append(&bytes[..reader.used()]).is_err()
+            || transcript_th
+                .append(&rsp_writer.used_slice()[..temp_used])
+                .is_err()
         {
-            crypto::hash::hash_ctx_update(
-                &mut digest_context_th,
-                self.common.runtime_info.message_a.as_ref(),
-            );
-            crypto::hash::hash_ctx_update(&mut digest_context_th, cert_chain_hash.as_ref());
-            crypto::hash::hash_ctx_update(&mut digest_context_th, &bytes[..reader.used()]);
-            crypto::hash::hash_ctx_update(
-                &mut digest_context_th,
-                &writer.used_slice()[..temp_used],
-            );
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return spdm_result_err!(EFAULT);
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let signature = self.generate_key_exchange_rsp_signature(&message_k);
-        #[cfg(feature = "hashed-transcript-data")]
-        let signature = self.generate_key_exchange_rsp_signature(digest_context_th.clone());
+
+        let slot_id = key_exchange_req.as_ref().unwrap().slot_id;
+        let signature = self.generate_key_exchange_rsp_signature(slot_id, &transcript_th);
         if signature.is_err() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
         let signature = signature.unwrap();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if message_k.append_message(signature.as_ref()).is_none() {
+        if transcript_th.append(signature.as_ref()).is_err() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(&mut digest_context_th, signature.as_ref());
 
         // create session - generate the handshake secret (including finished_key)
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let th1 = self
-            .common
-            .calc_rsp_transcript_hash(false, &message_k, None);
-        #[cfg(feature = "hashed-transcript-data")]
-        let th1 = crypto::hash::hash_ctx_finalize(digest_context_th.clone());
-        #[cfg(not(feature = "hashed-transcript-data"))]
+        let th1 = transcript_th.hash();
         if th1.is_err() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
@@ -251,8 +217,8 @@ impl<'a> ResponderContext<'a> {
         let session = self.common.get_next_avaiable_session();
         if session.is_none() {
             error!("!!! too many sessions : fail !!!\n");
-            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-            return spdm_result_err!(EFAULT);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorSessionLimitExceeded, 0, writer);
+            return spdm_result_err!(EBUSY);
         }
 
         let session = session.unwrap();
@@ -262,142 +228,71 @@ impl<'a> ResponderContext<'a> {
         session.set_use_psk(false);
         session.set_crypto_param(hash_algo, dhe_algo, aead_algo, key_schedule_algo);
         session.set_transport_param(sequence_number_count, max_random_count);
-        session.set_dhe_secret(spdm_version_sel, final_key)?;
-        session
-            .generate_handshake_secret(spdm_version_sel, &th1)
-            .unwrap();
-
-        // generate HMAC with finished_key
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = self
-            .common
-            .calc_rsp_transcript_data(false, &message_k, None);
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if transcript_data.is_err() {
+        if session.set_dhe_secret(spdm_version_sel, final_key).is_err()
+            || session
+                .generate_handshake_secret(spdm_version_sel, &th1)
+                .is_err()
+        {
+            let _ = session.teardown(session_id);
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = transcript_data.unwrap();
 
+        // generate HMAC with finished_key
         let session = self.common.get_session_via_id(session_id).unwrap();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let hmac = session.generate_hmac_with_response_finished_key(transcript_data.as_ref());
-        #[cfg(feature = "hashed-transcript-data")]
-        let hmac = session.generate_hmac_with_response_finished_key(
-            crypto::hash::hash_ctx_finalize(digest_context_th.clone())
-                .unwrap()
-                .as_ref(),
-        );
+        let hmac = session.generate_hmac_with_response_finished_key(th1.as_ref());
         if hmac.is_err() {
             let _ = session.teardown(session_id);
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
         let hmac = hmac.unwrap();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            if message_k.append_message(hmac.as_ref()).is_none() {
-                let _ = session.teardown(session_id);
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return spdm_result_err!(EFAULT);
-            }
-
-            session.runtime_info.message_k = message_k;
-        }
-
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            crypto::hash::hash_ctx_update(&mut digest_context_th, hmac.as_ref());
-
-            session.runtime_info.digest_context_th = Some(digest_context_th);
+        if transcript_th.append(hmac.as_ref()).is_err() {
+            let _ = session.teardown(session_id);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return spdm_result_err!(EFAULT);
         }
+        session.runtime_info.transcript_th = transcript_th;
 
         // patch the message before send
-        writer.mut_used_slice()[(used - base_hash_size - base_asym_size)..(used - base_hash_size)]
+        rsp_writer.mut_used_slice()
+            [(used - base_hash_size - base_asym_size)..(used - base_hash_size)]
             .copy_from_slice(signature.as_ref());
-        writer.mut_used_slice()[(used - base_hash_size)..used].copy_from_slice(hmac.as_ref()); // impl AsRef<[u8]> for SpdmDigestStruct
+        rsp_writer.mut_used_slice()[(used - base_hash_size)..used].copy_from_slice(hmac.as_ref()); // impl AsRef<[u8]> for SpdmDigestStruct
 
-        let heartbeat_period = self.common.config_info.heartbeat_period;
-        let secure_spdm_version_sel = self.common.config_info.secure_spdm_version;
+        if writer.extend_from_slice(rsp_writer.used_slice()).is_none() {
+            let session = self.common.get_session_via_id(session_id).unwrap();
+            let _ = session.teardown(session_id);
+            return spdm_result_err!(ENOMEM);
+        }
+
+        let heartbeat_period = self.heartbeat_period();
         let session = self.common.get_session_via_id(session_id).unwrap();
         session.set_session_state(crate::common::session::SpdmSessionState::SpdmSessionHandshaking);
 
         session.heartbeat_period = heartbeat_period;
-        if return_opaque.data_size != 0 {
-            session.secure_spdm_version_sel = secure_spdm_version_sel;
-        }
+        session.secure_spdm_version_sel = secure_spdm_version_sel;
 
         Ok(())
     }
 
-    #[cfg(feature = "hashed-transcript-data")]
     pub fn generate_key_exchange_rsp_signature(
         &mut self,
-        message_k: HashCtx,
+        slot_id: u8,
+        transcript_th: &SpdmTranscript,
     ) -> SpdmResult<SpdmSignatureStruct> {
-        let message_hash = crypto::hash::hash_ctx_finalize(message_k).unwrap();
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
-        let mut message = ManagedBuffer::default();
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_2)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
-
-        crypto::asym_sign::sign(
-            self.common.negotiate_info.base_hash_sel,
-            self.common.negotiate_info.base_asym_sel,
-            message.as_ref(),
-        )
-        .ok_or_else(|| spdm_err!(EFAULT))
-    }
-
-    #[cfg(not(feature = "hashed-transcript-data"))]
-    pub fn generate_key_exchange_rsp_signature(
-        &mut self,
-        message_k: &ManagedBuffer,
-    ) -> SpdmResult<SpdmSignatureStruct> {
-        let mut message = self
-            .common
-            .calc_rsp_transcript_data(false, message_k, None)?;
-        // we dont need create message hash for verify
-        // we just print message hash for debug purpose
-        let message_hash =
-            crypto::hash::hash_all(self.common.negotiate_info.base_hash_sel, message.as_ref())
-                .ok_or_else(|| spdm_err!(EFAULT))?;
-        debug!("message_hash - {:02x?}", message_hash.as_ref());
-
-        if self.common.negotiate_info.spdm_version_sel == SpdmVersion::SpdmVersion12 {
-            message.reset_message();
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_PREFIX_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_2)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(&SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT)
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-            message
-                .append_message(message_hash.as_ref())
-                .ok_or_else(|| spdm_err!(ENOMEM))?;
-        }
+        let message = self.common.get_signing_data(
+            transcript_th,
+            &SPDM_VERSION_1_2_SIGNING_CONTEXT_ZEROPAD_2,
+            &SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT,
+        )?;
 
         crypto::asym_sign::sign(
+            slot_id,
+            self.common.negotiate_info.spdm_version_sel,
             self.common.negotiate_info.base_hash_sel,
             self.common.negotiate_info.base_asym_sel,
+            &SPDM_KEY_EXCHANGE_RESPONSE_SIGN_CONTEXT,
             message.as_ref(),
         )
         .ok_or_else(|| spdm_err!(EFAULT))
@@ -407,6 +302,7 @@ impl<'a> ResponderContext<'a> {
 #[cfg(all(test,))]
 mod tests_responder {
     use super::*;
+    use crate::common::opaque::SpdmOpaqueSupport;
     use crate::message::SpdmMessageHeader;
     use crate::testlib::*;
     use crate::{crypto, responder};
@@ -414,7 +310,6 @@ mod tests_responder {
     use codec::{Codec, Writer};
 
     #[test]
-    #[cfg(not(feature = "hashed-transcript-data"))]
     fn test_case0_handle_spdm_key_exchange() {
         let (config_info, provision_info) = create_info();
         let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
@@ -482,4 +377,93 @@ mod tests_responder {
 
         let _ = context.handle_spdm_key_exchange(bytes);
     }
+
+    #[test]
+    fn test_case1_key_exchange_no_secured_version_in_common() {
+        let (mut config_info, provision_info) = create_info();
+        config_info.secure_spdm_version = crate::common::opaque::DMTF_SECURE_SPDM_VERSION_10;
+        let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+        let shared_buffer = SharedBuffer::new();
+        let mut socket_io_transport = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+
+        let mut context = responder::ResponderContext::new(
+            &mut socket_io_transport,
+            pcidoe_transport_encap,
+            config_info,
+            provision_info,
+        );
+        context.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion11;
+        context.common.negotiate_info.opaque_data_support = SpdmOpaqueSupport::OPAQUE_DATA_FMT1;
+        context.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+        context.common.negotiate_info.base_asym_sel = SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+        context.common.negotiate_info.dhe_sel = SpdmDheAlgo::SECP_384_R1;
+
+        // a requester that offers the versions up to 1.0 lists only 1.0
+        let opaque = SpdmOpaqueStruct::req_set_dmtf_supported_secure_spdm_version_list(
+            &context.common,
+            crate::common::opaque::DMTF_SECURE_SPDM_VERSION_10,
+        );
+        let version_list = opaque
+            .rsp_get_dmtf_supported_secure_spdm_version_list(&mut context.common)
+            .unwrap();
+        assert_eq!(version_list.version_count, 1);
+        assert_eq!(
+            version_list.versions_list[0].get_secure_spdm_version(),
+            crate::common::opaque::DMTF_SECURE_SPDM_VERSION_10
+        );
+
+        // one that offers only 1.1, the count and the first version of the FMT1 list
+        let mut opaque =
+            SpdmOpaqueStruct::req_set_dmtf_supported_secure_spdm_version_list(&context.common, 0);
+        opaque.data[10] = 1;
+        opaque.data[12] = crate::common::opaque::DMTF_SECURE_SPDM_VERSION_11;
+        let version_list = opaque
+            .rsp_get_dmtf_supported_secure_spdm_version_list(&mut context.common)
+            .unwrap();
+        assert_eq!(version_list.version_count, 1);
+        assert_eq!(
+            version_list.versions_list[0].get_secure_spdm_version(),
+            crate::common::opaque::DMTF_SECURE_SPDM_VERSION_11
+        );
+        let request = SpdmMessage {
+            header: SpdmMessageHeader {
+                version: SpdmVersion::SpdmVersion11,
+                request_response_code: SpdmRequestResponseCode::SpdmRequestKeyExchange,
+            },
+            payload: SpdmMessagePayload::SpdmKeyExchangeRequest(SpdmKeyExchangeRequestPayload {
+                measurement_summary_hash_type:
+                    SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+                slot_id: 0,
+                req_session_id: 0xffu16,
+                session_policy: 0,
+                random: SpdmRandomStruct {
+                    data: [100u8; SPDM_RANDOM_SIZE],
+                },
+                exchange: SpdmDheExchangeStruct {
+                    data_size: 96,
+                    data: [0x5au8; SPDM_MAX_DHE_KEY_SIZE],
+                },
+                opaque,
+            }),
+        };
+        let mut request_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let mut writer = Writer::init(&mut request_buffer);
+        request.spdm_encode(&mut context.common, &mut writer);
+        let used = writer.used();
+
+        let mut response_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let mut writer = Writer::init(&mut response_buffer);
+        assert!(context
+            .write_spdm_key_exchange_response(&request_buffer[..used], &mut writer)
+            .is_err());
+        assert_eq!(
+            writer.used_slice(),
+            [
+                0x11,
+                SpdmRequestResponseCode::SpdmResponseError.get_u8(),
+                SpdmErrorCode::SpdmErrorInvalidRequest.get_u8(),
+                0
+            ]
+        );
+    }
 }
@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+mod app_message_handler;
 mod context;
 
 mod algorithm_rsp;
@@ -23,6 +24,7 @@ mod version_rsp;
 mod error_rsp;
 mod vendor_rsp;
 
+pub use app_message_handler::SpdmAppMessageHandler;
 pub use context::ResponderContext;
 
 use crate::config;
@@ -2,9 +2,8 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::common::opaque::SpdmOpaqueStruct;
+use crate::common::opaque::{SpdmOpaqueStruct, DMTF_SECURE_SPDM_VERSION_10};
 use crate::common::SpdmCodec;
-use crate::common::SpdmOpaqueSupport;
 use crate::crypto;
 use crate::error::{spdm_result_err, SpdmResult};
 use crate::message::*;
@@ -14,17 +13,21 @@ use config::MAX_SPDM_PSK_CONTEXT_SIZE;
 extern crate alloc;
 use alloc::boxed::Box;
 
-#[cfg(not(feature = "hashed-transcript-data"))]
-use crate::common::ManagedBuffer;
-
 impl<'a> ResponderContext<'a> {
     pub fn handle_spdm_psk_exchange(&mut self, bytes: &[u8]) -> SpdmResult {
         let mut send_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
         let mut writer = Writer::init(&mut send_buffer);
-        self.write_spdm_psk_exchange_response(bytes, &mut writer)?;
+        if let Err(err) = self.write_spdm_psk_exchange_response(bytes, &mut writer) {
+            // a failure answered with an ERROR leaves it in the writer
+            if writer.used() != 0 {
+                self.send_message(writer.used_slice())?;
+            }
+            return Err(err);
+        }
         self.send_message(writer.used_slice())
     }
 
+    /// On error, `writer` holds the ERROR response to send, if one is due.
     pub fn write_spdm_psk_exchange_response(
         &mut self,
         bytes: &[u8],
@@ -37,6 +40,8 @@ impl<'a> ResponderContext<'a> {
             SpdmPskExchangeRequestPayload::spdm_read(&mut self.common, &mut reader);
 
         let mut return_opaque = SpdmOpaqueStruct::default();
+        // Secured Messages 1.0 unless the requester lists versions to select
+        let mut secure_spdm_version_sel = DMTF_SECURE_SPDM_VERSION_10;
 
         if let Some(psk_exchange_req) = &psk_exchange_req {
             debug!("!!! psk_exchange req : {:02x?}\n", psk_exchange_req);
@@ -58,38 +63,24 @@ impl<'a> ResponderContext<'a> {
                 if secured_message_version_list.version_count
                     > crate::common::opaque::MAX_SECURE_SPDM_VERSION_COUNT as u8
                 {
+                    self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
                     return spdm_result_err!(EINVAL);
                 }
-                for index in 0..secured_message_version_list.version_count as usize {
-                    if secured_message_version_list.versions_list[index].get_secure_spdm_version()
-                        == self.common.config_info.secure_spdm_version
-                    {
-                        if self
-                            .common
-                            .negotiate_info
-                            .opaque_data_support
-                            .contains(SpdmOpaqueSupport::OPAQUE_DATA_FMT1)
-                        {
-                            return_opaque.data_size =
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT1
-                                    .len() as u16;
-                            return_opaque.data[..(return_opaque.data_size as usize)]
-                                .copy_from_slice(
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT1
-                                    .as_ref(),
-                            );
-                        } else {
-                            return_opaque.data_size =
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT0
-                                    .len() as u16;
-                            return_opaque.data[..(return_opaque.data_size as usize)]
-                                .copy_from_slice(
-                                crate::common::opaque::RSP_DMTF_OPAQUE_DATA_VERSION_SELECTION_FMT0
-                                    .as_ref(),
-                            );
-                        }
+                let version = match secured_message_version_list
+                    .select_secure_spdm_version(self.common.config_info.secure_spdm_version)
+                {
+                    Some(version) => version,
+                    None => {
+                        error!("!!! no secured message version in common !!!\n");
+                        self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+                        return spdm_result_err!(EINVAL);
                     }
-                }
+                };
+                return_opaque = SpdmOpaqueStruct::rsp_set_dmtf_secure_spdm_version_selection(
+                    &self.common,
+                    version,
+                );
+                secure_spdm_version_sel = version.get_secure_spdm_version();
             }
         } else {
             error!("!!! psk_exchange req : fail !!!\n");
@@ -97,29 +88,34 @@ impl<'a> ResponderContext<'a> {
             return spdm_result_err!(EFAULT);
         }
 
-        #[cfg(feature = "hashed-transcript-data")]
-        let mut digest_context_th =
-            crypto::hash::hash_ctx_init(self.common.negotiate_info.base_hash_sel).unwrap();
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            &mut digest_context_th,
-            self.common.runtime_info.message_a.as_ref(),
-        );
+        let rsp_session_id =
+            if let Some(rsp_session_id) = self.common.get_next_half_session_id(false) {
+                rsp_session_id
+            } else {
+                error!("!!! too many sessions : fail !!!\n");
+                self.write_spdm_error(SpdmErrorCode::SpdmErrorSessionLimitExceeded, 0, writer);
+                return spdm_result_err!(EBUSY);
+            };
+
+        let transcript_th = self.common.create_rsp_transcript_th(true);
+        if transcript_th.is_err() {
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return spdm_result_err!(EFAULT);
+        }
+        let mut transcript_th = transcript_th.unwrap();
 
         info!("send spdm psk_exchange rsp\n");
 
         let mut psk_context = [0u8; MAX_SPDM_PSK_CONTEXT_SIZE];
         let _ = crypto::rand::get_random(&mut psk_context);
 
-        let rsp_session_id = 0xFFFD;
-
         let response = SpdmMessage {
             header: SpdmMessageHeader {
                 version: self.common.negotiate_info.spdm_version_sel,
                 request_response_code: SpdmRequestResponseCode::SpdmResponsePskExchangeRsp,
             },
             payload: SpdmMessagePayload::SpdmPskExchangeResponse(SpdmPskExchangeResponsePayload {
-                heartbeat_period: self.common.config_info.heartbeat_period,
+                heartbeat_period: self.heartbeat_period(),
                 rsp_session_id,
                 measurement_summary_hash: SpdmDigestStruct {
                     data_size: self.common.negotiate_info.base_hash_sel.get_size(),
@@ -129,7 +125,7 @@ impl<'a> ResponderContext<'a> {
                     data_size: self.common.negotiate_info.base_hash_sel.get_size(),
                     data: psk_context,
                 },
-                opaque: return_opaque.clone(),
+                opaque: return_opaque,
                 verify_data: SpdmDigestStruct {
                     data_size: self.common.negotiate_info.base_hash_sel.get_size(),
                     data: Box::new([0xcc; SPDM_MAX_HASH_SIZE]),
@@ -137,49 +133,26 @@ impl<'a> ResponderContext<'a> {
             }),
         };
 
-        response.spdm_encode(&mut self.common, writer);
-        let used = writer.used();
+        // errors are written to the empty writer, the response joins it at the end
+        let mut rsp_buffer = [0u8; config::MAX_SPDM_MESSAGE_BUFFER_SIZE];
+        let mut rsp_writer = Writer::init(&mut rsp_buffer);
+        response.spdm_encode(&mut self.common, &mut rsp_writer);
+        let used = rsp_writer.used();
 
         let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
         let temp_used = used - base_hash_size;
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let mut message_k = ManagedBuffer::default();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            if message_k.append_message(&bytes[..reader.used()]).is_none() {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return spdm_result_err!(EFAULT);
-            }
-            if message_k
-                .append_message(&writer.used_slice()[..temp_used])
-                .is_none()
-            {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return spdm_result_err!(EFAULT);
-            }
-        }
-
-        #[cfg(feature = "hashed-transcript-data")]
+        if transcript_th.append(&bytes[..reader.used()]).is_err()
+            || transcript_th
+                .append(&rsp_writer.used_slice()[..temp_used])
+                .is_err()
         {
-            crypto::hash::hash_ctx_update(&mut digest_context_th, &bytes[..reader.used()]);
-            crypto::hash::hash_ctx_update(
-                &mut digest_context_th,
-                &writer.used_slice()[..temp_used],
-            );
-        }
-
-        // create session - generate the handshake secret (including finished_key)
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let th1 = self.common.calc_rsp_transcript_hash(true, &message_k, None);
-        #[cfg(feature = "hashed-transcript-data")]
-        let th1 = crypto::hash::hash_ctx_finalize(digest_context_th.clone());
-        #[cfg(feature = "hashed-transcript-data")]
-        if th1.is_none() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
+
+        // create session - generate the handshake secret (including finished_key)
+        let th1 = transcript_th.hash();
         if th1.is_err() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
@@ -197,8 +170,8 @@ impl<'a> ResponderContext<'a> {
         let session = self.common.get_next_avaiable_session();
         if session.is_none() {
             error!("!!! too many sessions : fail !!!\n");
-            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-            return spdm_result_err!(EFAULT);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorSessionLimitExceeded, 0, writer);
+            return spdm_result_err!(EBUSY);
         }
 
         let session = session.unwrap();
@@ -213,63 +186,46 @@ impl<'a> ResponderContext<'a> {
         psk_key.data[0..(psk_key.data_size as usize)].copy_from_slice(b"TestPskData\0");
         session.set_crypto_param(hash_algo, dhe_algo, aead_algo, key_schedule_algo);
         session.set_transport_param(sequence_number_count, max_random_count);
-        session.set_dhe_secret(spdm_version_sel, psk_key)?; // transfer the ownership out
-        session
-            .generate_handshake_secret(spdm_version_sel, &th1)
-            .unwrap();
-
-        // generate HMAC with finished_key
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = self.common.calc_rsp_transcript_data(true, &message_k, None);
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if transcript_data.is_err() {
+        if session.set_dhe_secret(spdm_version_sel, psk_key).is_err()
+            || session
+                .generate_handshake_secret(spdm_version_sel, &th1)
+                .is_err()
+        {
+            let _ = session.teardown(session_id);
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = transcript_data.unwrap();
 
+        // generate HMAC with finished_key
         let session = self.common.get_session_via_id(session_id).unwrap();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let hmac = session.generate_hmac_with_response_finished_key(transcript_data.as_ref());
-        #[cfg(feature = "hashed-transcript-data")]
-        let hmac = session.generate_hmac_with_response_finished_key(
-            crypto::hash::hash_ctx_finalize(digest_context_th.clone())
-                .unwrap()
-                .as_ref(),
-        );
+        let hmac = session.generate_hmac_with_response_finished_key(th1.as_ref());
         if hmac.is_err() {
             let _ = session.teardown(session_id);
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return spdm_result_err!(EFAULT);
         }
         let hmac = hmac.unwrap();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            if message_k.append_message(hmac.as_ref()).is_none() {
-                let _ = session.teardown(session_id);
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                return spdm_result_err!(EFAULT);
-            }
-            session.runtime_info.message_k = message_k;
-        }
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            crypto::hash::hash_ctx_update(&mut digest_context_th, hmac.as_ref());
-            session.runtime_info.digest_context_th = Some(digest_context_th);
+        if transcript_th.append(hmac.as_ref()).is_err() {
+            let _ = session.teardown(session_id);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return spdm_result_err!(EFAULT);
         }
+        session.runtime_info.transcript_th = transcript_th;
 
         // patch the message before send
-        writer.mut_used_slice()[(used - base_hash_size)..used].copy_from_slice(hmac.as_ref());
-        let heartbeat_period = self.common.config_info.heartbeat_period;
-        let secure_spdm_version_sel = self.common.config_info.secure_spdm_version;
+        rsp_writer.mut_used_slice()[(used - base_hash_size)..used].copy_from_slice(hmac.as_ref());
+        if writer.extend_from_slice(rsp_writer.used_slice()).is_none() {
+            let session = self.common.get_session_via_id(session_id).unwrap();
+            let _ = session.teardown(session_id);
+            return spdm_result_err!(ENOMEM);
+        }
+
+        let heartbeat_period = self.heartbeat_period();
         let session = self.common.get_session_via_id(session_id).unwrap();
         session.set_session_state(crate::common::session::SpdmSessionState::SpdmSessionHandshaking);
 
         session.heartbeat_period = heartbeat_period;
-        if return_opaque.data_size != 0 {
-            session.secure_spdm_version_sel = secure_spdm_version_sel;
-        }
+        session.secure_spdm_version_sel = secure_spdm_version_sel;
 
         Ok(())
     }
@@ -2,11 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-#[cfg(not(feature = "hashed-transcript-data"))]
-use crate::common::ManagedBuffer;
 use crate::common::SpdmCodec;
-#[cfg(feature = "hashed-transcript-data")]
-use crate::crypto;
 use crate::responder::*;
 
 use crate::message::*;
@@ -53,55 +49,24 @@ impl<'a> ResponderContext<'a> {
         let base_hash_size = self.common.negotiate_info.base_hash_sel.get_size() as usize;
         let temp_used = read_used - base_hash_size;
 
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let mut message_f = ManagedBuffer::default();
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if message_f.append_message(&bytes[..temp_used]).is_none() {
-            panic!("message_f add the message error");
-        }
-
         let session = self.common.get_session_via_id(session_id).unwrap();
-
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            session.runtime_info.digest_context_th.as_mut().unwrap(),
-            &bytes[..temp_used],
-        );
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let message_k = &session.runtime_info.message_k.clone();
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data =
-            self.common
-                .calc_rsp_transcript_data(true, message_k, Some(&message_f));
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if transcript_data.is_err() {
+        if session
+            .runtime_info
+            .transcript_th
+            .append(&bytes[..temp_used])
+            .is_err()
+        {
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return false;
+        }
+        let message_hash = session.runtime_info.transcript_th.hash();
+        if message_hash.is_err() {
             self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
             return false;
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        let transcript_data = transcript_data.unwrap();
-
-        #[cfg(feature = "hashed-transcript-data")]
-        let message_hash = crypto::hash::hash_ctx_finalize(
-            session
-                .runtime_info
-                .digest_context_th
-                .as_mut()
-                .cloned()
-                .unwrap(),
-        );
 
-        let session = self
-            .common
-            .get_immutable_session_via_id(session_id)
-            .unwrap();
         if session
             .verify_hmac_with_request_finished_key(
-                #[cfg(not(feature = "hashed-transcript-data"))]
-                transcript_data.as_ref(),
-                #[cfg(feature = "hashed-transcript-data")]
                 message_hash.unwrap().as_ref(),
                 &psk_finish_req.verify_data,
             )
@@ -113,23 +78,17 @@ impl<'a> ResponderContext<'a> {
         } else {
             info!("verify_hmac_with_request_finished_key pass");
         }
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if message_f
-            .append_message(psk_finish_req.verify_data.as_ref())
-            .is_none()
+
+        if session
+            .runtime_info
+            .transcript_th
+            .append(psk_finish_req.verify_data.as_ref())
+            .is_err()
         {
-            panic!("message_f add the message error");
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return false;
         }
 
-        #[cfg(feature = "hashed-transcript-data")]
-        let session = self.common.get_session_via_id(session_id).unwrap();
-
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            session.runtime_info.digest_context_th.as_mut().unwrap(),
-            psk_finish_req.verify_data.as_ref(),
-        );
-
         info!("send spdm psk_finish rsp\n");
 
         let response = SpdmMessage {
@@ -142,48 +101,18 @@ impl<'a> ResponderContext<'a> {
 
         response.spdm_encode(&mut self.common, writer);
 
-        #[cfg(feature = "hashed-transcript-data")]
-        let session = self.common.get_session_via_id(session_id).unwrap();
-
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        if message_f.append_message(writer.used_slice()).is_none() {
-            panic!("message_f add the message error");
-        }
-
-        #[cfg(feature = "hashed-transcript-data")]
-        crypto::hash::hash_ctx_update(
-            session.runtime_info.digest_context_th.as_mut().unwrap(),
-            writer.used_slice(),
-        );
-
         let session = self.common.get_session_via_id(session_id).unwrap();
         // generate the data secret
-        let th2;
-        #[cfg(not(feature = "hashed-transcript-data"))]
-        {
-            session.runtime_info.message_f = message_f.clone();
-            th2 = self
-                .common
-                .calc_rsp_transcript_hash(true, message_k, Some(&message_f));
-            if th2.is_err() {
-                self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
-                let session = self.common.get_session_via_id(session_id).unwrap();
-                let _ = session.teardown(session_id);
-                return false;
-            }
-        }
-        #[cfg(feature = "hashed-transcript-data")]
-        {
-            th2 = crypto::hash::hash_ctx_finalize(
-                session
-                    .runtime_info
-                    .digest_context_th
-                    .as_mut()
-                    .cloned()
-                    .unwrap(),
-            );
+        let th2 = session
+            .runtime_info
+            .transcript_th
+            .append(writer.used_slice())
+            .and_then(|_| session.runtime_info.transcript_th.hash());
+        if th2.is_err() {
+            let _ = session.teardown(session_id);
+            self.write_spdm_error(SpdmErrorCode::SpdmErrorInvalidRequest, 0, writer);
+            return false;
         }
-
         let th2 = th2.unwrap();
         debug!("!!! th2 : {:02x?}\n", th2.as_ref());
         let spdm_version_sel = self.common.negotiate_info.spdm_version_sel;
@@ -196,6 +125,7 @@ impl<'a> ResponderContext<'a> {
 mod tests_responder {
     use super::*;
     use crate::common::session::SpdmSession;
+    use crate::common::SpdmTranscriptMode;
     use crate::message::SpdmMessageHeader;
     use crate::protocol::gen_array_clone;
     use crate::testlib::*;
@@ -203,7 +133,6 @@ mod tests_responder {
     use codec::{Codec, Writer};
 
     #[test]
-    #[cfg(not(feature = "hashed-transcript-data"))]
     fn test_case0_handle_spdm_psk_finish() {
         let (config_info, provision_info) = create_info();
         let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
@@ -232,6 +161,9 @@ mod tests_responder {
         );
         context.common.session[0]
             .set_session_state(crate::common::session::SpdmSessionState::SpdmSessionEstablished);
+        context.common.config_info.transcript_mode = SpdmTranscriptMode::Buffered;
+        context.common.session[0].runtime_info.transcript_th =
+            context.common.new_transcript().unwrap();
 
         let spdm_message_header = &mut [0u8; 1024];
         let mut writer = Writer::init(spdm_message_header);
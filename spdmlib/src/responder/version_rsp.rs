@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use crate::common::SpdmCodec;
+use crate::common::{SpdmCodec, SpdmConnectionState};
 use crate::message::*;
 use crate::protocol::*;
 use crate::responder::*;
@@ -28,8 +28,9 @@ impl<'a> ResponderContext<'a> {
             return;
         }
 
-        // clear cache data
-        self.common.reset_runtime_info();
+        // GET_VERSION restarts the connection: clear cache data, the
+        // negotiated state, the peer info and the sessions
+        self.common.reset_context();
 
         if self
             .common
@@ -72,6 +73,7 @@ impl<'a> ResponderContext<'a> {
             .runtime_info
             .message_a
             .append_message(writer.used_slice());
+        self.common.runtime_info.connection_state = SpdmConnectionState::AfterVersion;
     }
 }
 
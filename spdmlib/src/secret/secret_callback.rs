@@ -32,15 +32,6 @@ type SpdmRequesterDataSignCbType = fn(
     message: &[u8],
     message_size: u8,
 ) -> Option<SpdmSignatureStruct>;
-type SpdmResponderDataSignCbType = fn(
-    spdm_version: SpdmVersion,
-    op_code: u8,
-    req_base_asym_alg: SpdmReqAsymAlgo,
-    base_hash_algo: SpdmBaseHashAlgo,
-    is_data_hash: bool,
-    message: &[u8],
-    message_size: u8,
-) -> Option<SpdmSignatureStruct>;
 type SpdmPskHandshakeSecretHkdfExpandCbType = fn(
     spdm_version: SpdmVersion,
     base_hash_algo: SpdmBaseHashAlgo,
@@ -66,8 +57,6 @@ pub struct SpdmSecret {
 
     pub spdm_requester_data_sign_cb: SpdmRequesterDataSignCbType,
 
-    pub spdm_responder_data_sign_cb: SpdmResponderDataSignCbType,
-
     pub spdm_psk_handshake_secret_hkdf_expand_cb: SpdmPskHandshakeSecretHkdfExpandCbType,
 
     pub spdm_psk_master_secret_hkdf_expand_cb: SpdmPskMasterSecretHkdfExpandCbType,
@@ -12,8 +12,35 @@ static TIME_INSTANCE: OnceCell<SpdmTime> = OnceCell::uninit();
 
 static DEFAULT: SpdmTime = SpdmTime {
     sleep_cb: |_: usize| unimplemented!(),
+    get_unix_time_cb: default_unix_time,
+    now_cb: default_now,
 };
 
+#[cfg(feature = "std")]
+fn default_unix_time() -> Option<u64> {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|ds| ds.as_secs())
+}
+
+#[cfg(not(feature = "std"))]
+fn default_unix_time() -> Option<u64> {
+    None
+}
+
+#[cfg(feature = "std")]
+fn default_now() -> Option<u64> {
+    static START: OnceCell<std::time::Instant> = OnceCell::uninit();
+    let start = START.try_get_or_init(std::time::Instant::now).ok()?;
+    Some(start.elapsed().as_micros() as u64)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_now() -> Option<u64> {
+    None
+}
+
 pub fn register(context: SpdmTime) -> bool {
     TIME_INSTANCE.try_init_once(|| context).is_ok()
 }
@@ -25,3 +52,69 @@ pub fn sleep(us: usize) {
         .unwrap()
         .sleep_cb)(us)
 }
+
+/// Unlike `sleep`, reading the time does not fix the default, certificate checks read it
+/// and `register` must still work after that.
+pub fn get_unix_time() -> Option<u64> {
+    (TIME_INSTANCE.try_get().unwrap_or(&DEFAULT).get_unix_time_cb)()
+}
+
+/// Monotonic time in us, None if the platform has no clock.
+pub fn now() -> Option<u64> {
+    (TIME_INSTANCE.try_get().unwrap_or(&DEFAULT).now_cb)()
+}
+
+/// A point in monotonic time, for a timeout that spans several waits.
+///
+/// Without a clock a deadline never expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpdmDeadline {
+    expiry: Option<u64>,
+}
+
+impl SpdmDeadline {
+    /// The deadline `us` from now.
+    pub fn after(us: usize) -> Self {
+        SpdmDeadline {
+            expiry: now().map(|now| now.saturating_add(us as u64)),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match (self.expiry, now()) {
+            (Some(expiry), Some(now)) => now >= expiry,
+            _ => false,
+        }
+    }
+
+    /// Time left in us, None without a clock.
+    pub fn remaining(&self) -> Option<usize> {
+        let remaining = self.expiry?.saturating_sub(now()?);
+        Some(core::convert::TryFrom::try_from(remaining).unwrap_or(usize::MAX))
+    }
+}
+
+#[cfg(all(test,))]
+mod tests {
+    use super::*;
+    use crate::testlib::{advance_time, TIME_IMPL};
+
+    #[test]
+    fn test_case0_deadline() {
+        register(TIME_IMPL.clone());
+
+        let deadline = SpdmDeadline::after(1000);
+        assert!(!deadline.is_expired());
+        assert_eq!(deadline.remaining(), Some(1000));
+
+        sleep(600);
+        assert_eq!(deadline.remaining(), Some(400));
+        advance_time(400);
+        assert!(deadline.is_expired());
+        assert_eq!(deadline.remaining(), Some(0));
+
+        let deadline = SpdmDeadline { expiry: None };
+        assert!(!deadline.is_expired());
+        assert_eq!(deadline.remaining(), None);
+    }
+}
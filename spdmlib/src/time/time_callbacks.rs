@@ -5,4 +5,8 @@
 #[derive(Clone)]
 pub struct SpdmTime {
     pub sleep_cb: fn(us: usize),
+    /// Wall clock time in seconds since the UNIX epoch, None if not available.
+    pub get_unix_time_cb: fn() -> Option<u64>,
+    /// Monotonic time in us since an arbitrary start, None if not available.
+    pub now_cb: fn() -> Option<u64>,
 }
@@ -7,7 +7,7 @@
 use spdmlib::crypto::SpdmAsymSign;
 
 use spdmlib::protocol::{
-    SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmSignatureStruct, RSAPSS_2048_KEY_SIZE,
+    SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmSignatureStruct, SpdmVersion, RSAPSS_2048_KEY_SIZE,
     RSAPSS_3072_KEY_SIZE, RSAPSS_4096_KEY_SIZE, RSASSA_2048_KEY_SIZE, RSASSA_3072_KEY_SIZE,
     RSASSA_4096_KEY_SIZE, SPDM_MAX_ASYM_KEY_SIZE,
 };
@@ -17,8 +17,11 @@ use super::utils::get_test_key_directory;
 pub static ASYM_SIGN_IMPL: SpdmAsymSign = SpdmAsymSign { sign_cb: asym_sign };
 
 fn asym_sign(
+    _slot_id: u8,
+    _spdm_version: SpdmVersion,
     base_hash_algo: SpdmBaseHashAlgo,
     base_asym_algo: SpdmBaseAsymAlgo,
+    _sign_context: &[u8],
     data: &[u8],
 ) -> Option<SpdmSignatureStruct> {
     match (base_hash_algo, base_asym_algo) {
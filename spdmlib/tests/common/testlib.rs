@@ -105,8 +105,10 @@ pub fn create_info() -> (common::SpdmConfigInfo, common::SpdmProvisionInfo) {
     let provision_info = common::SpdmProvisionInfo {
         my_cert_chain_data: Some(my_cert_chain_data.clone()),
         my_cert_chain: None,
+        peer_root_cert_data: Default::default(),
+        peer_cert_chain_root_hash: Default::default(),
+        peer_root_crl_data: Default::default(),
         peer_cert_chain_data: Some(my_cert_chain_data),
-        peer_cert_chain_root_hash: None,
         default_version: SpdmVersion::SpdmVersion11,
     };
 
@@ -280,8 +282,11 @@ impl SpdmTransportEncap for PciDoeTransportEncap {
 pub static ASYM_SIGN_IMPL: SpdmAsymSign = SpdmAsymSign { sign_cb: asym_sign };
 
 fn asym_sign(
+    _slot_id: u8,
+    _spdm_version: SpdmVersion,
     base_hash_algo: SpdmBaseHashAlgo,
     base_asym_algo: SpdmBaseAsymAlgo,
+    _sign_context: &[u8],
     data: &[u8],
 ) -> Option<SpdmSignatureStruct> {
     match (base_hash_algo, base_asym_algo) {
@@ -490,9 +495,9 @@ fn get_random(data: &mut [u8]) -> SpdmResult<usize> {
 }
 
 pub const REQ_CERT_CHAIN_DATA: SpdmCertChainData = SpdmCertChainData {
-    data_size: 1545,
+    data_size: 1609,
     data: [
-        0x09, 0x06, 0x00, 0x00, 0x4e, 0x75, 0x0a, 0x31, 0x8a, 0x1c, 0x58, 0x20, 0x15, 0xa2, 0x8c,
+        0x49, 0x06, 0x00, 0x00, 0x4e, 0x75, 0x0a, 0x31, 0x8a, 0x1c, 0x58, 0x20, 0x15, 0xa2, 0x8c,
         0x03, 0x4d, 0xb2, 0x96, 0x25, 0x7d, 0x8f, 0xef, 0x31, 0x47, 0x45, 0x3e, 0x40, 0x76, 0xfc,
         0x45, 0x92, 0x12, 0x66, 0xa8, 0x6e, 0x27, 0xfc, 0x41, 0x31, 0x7e, 0x72, 0x32, 0x53, 0x54,
         0x15, 0x3a, 0x92, 0x54, 0xff, 0xbd, 0xcd, 0x30, 0x82, 0x01, 0xD0, 0x30, 0x82, 0x01, 0x56,
@@ -558,7 +563,7 @@ pub const REQ_CERT_CHAIN_DATA: SpdmCertChainData = SpdmCertChainData {
         0x02, 0x30, 0x1C, 0x08, 0xDE, 0x61, 0x7F, 0x5B, 0xA7, 0xC8, 0x6B, 0xAF, 0x8D, 0x9D, 0xF5,
         0x3A, 0xC1, 0x54, 0xE5, 0x5F, 0x21, 0xC7, 0x69, 0x57, 0xDB, 0x63, 0xC4, 0x45, 0x09, 0x66,
         0xAB, 0x70, 0xAB, 0xC0, 0xBF, 0xB2, 0xC0, 0x6A, 0x7F, 0x51, 0xDA, 0xE8, 0xAE, 0x93, 0xC4,
-        0x43, 0x33, 0x4A, 0x07, 0xA4, 0x30, 0x82, 0x02, 0x22, 0x30, 0x82, 0x01, 0xA8, 0xA0, 0x03,
+        0x43, 0x33, 0x4A, 0x07, 0xA4, 0x30, 0x82, 0x02, 0x62, 0x30, 0x82, 0x01, 0xE9, 0xA0, 0x03,
         0x02, 0x01, 0x02, 0x02, 0x01, 0x03, 0x30, 0x0A, 0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE, 0x3D,
         0x04, 0x03, 0x03, 0x30, 0x2E, 0x31, 0x2C, 0x30, 0x2A, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0C,
         0x23, 0x69, 0x6E, 0x74, 0x65, 0x6C, 0x20, 0x74, 0x65, 0x73, 0x74, 0x20, 0x45, 0x43, 0x50,
@@ -576,7 +581,7 @@ pub const REQ_CERT_CHAIN_DATA: SpdmCertChainData = SpdmCertChainData {
         0x89, 0x16, 0x49, 0xCA, 0x40, 0x6A, 0x91, 0x18, 0x09, 0x7F, 0x27, 0xEF, 0xE4, 0xA6, 0x26,
         0x1A, 0xFD, 0xD4, 0xD1, 0x57, 0xDB, 0x5B, 0x1B, 0x75, 0x05, 0xF9, 0x15, 0x9D, 0x33, 0x34,
         0xBE, 0x90, 0xB7, 0x5E, 0xD7, 0x05, 0xB9, 0x73, 0x85, 0x1E, 0x4A, 0xD3, 0x00, 0x5B, 0x7D,
-        0x10, 0x68, 0xBF, 0x4F, 0xCA, 0xE6, 0x38, 0x4C, 0xA3, 0x81, 0x9C, 0x30, 0x81, 0x99, 0x30,
+        0x10, 0x68, 0xBF, 0x4F, 0xCA, 0xE6, 0x38, 0x4C, 0xA3, 0x81, 0xDD, 0x30, 0x81, 0xDA, 0x30,
         0x0C, 0x06, 0x03, 0x55, 0x1D, 0x13, 0x01, 0x01, 0xFF, 0x04, 0x02, 0x30, 0x00, 0x30, 0x0B,
         0x06, 0x03, 0x55, 0x1D, 0x0F, 0x04, 0x04, 0x03, 0x02, 0x05, 0xE0, 0x30, 0x1D, 0x06, 0x03,
         0x55, 0x1D, 0x0E, 0x04, 0x16, 0x04, 0x14, 0x1D, 0xEE, 0x4B, 0x09, 0x37, 0x82, 0xED, 0x77,
@@ -587,19 +592,19 @@ pub const REQ_CERT_CHAIN_DATA: SpdmCertChainData = SpdmCertChainData {
         0x38, 0x39, 0x30, 0x30, 0x2A, 0x06, 0x03, 0x55, 0x1D, 0x25, 0x01, 0x01, 0xFF, 0x04, 0x20,
         0x30, 0x1E, 0x06, 0x08, 0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01, 0x06, 0x08, 0x2B,
         0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02, 0x06, 0x08, 0x2B, 0x06, 0x01, 0x05, 0x05, 0x07,
-        0x03, 0x09, 0x30, 0x0A, 0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x03, 0x03,
-        0x68, 0x00, 0x30, 0x65, 0x02, 0x30, 0x09, 0x0D, 0x98, 0x7A, 0xD6, 0xD1, 0x9A, 0x43, 0x45,
-        0x1E, 0xBB, 0xC1, 0x0B, 0x23, 0x2E, 0xA4, 0x8A, 0x10, 0x32, 0xDB, 0xE1, 0x89, 0xBE, 0xDA,
-        0x6A, 0x51, 0x36, 0x24, 0x48, 0x1F, 0x2F, 0x66, 0xA2, 0x6B, 0xDB, 0xAB, 0x78, 0x5E, 0x4A,
-        0x5C, 0x57, 0x36, 0x9E, 0xAE, 0x72, 0x80, 0x6E, 0x2D, 0x02, 0x31, 0x00, 0x86, 0xD5, 0xD5,
-        0x6C, 0x2B, 0x90, 0xFB, 0x3D, 0xB6, 0x84, 0x68, 0x93, 0xD3, 0xBD, 0xD8, 0xFC, 0x0D, 0x64,
-        0x77, 0x36, 0x90, 0x8A, 0xE4, 0xBB, 0xD9, 0x60, 0x3B, 0x78, 0x9B, 0x58, 0x9A, 0x31, 0x71,
-        0xCB, 0x83, 0xB8, 0xFE, 0x54, 0x6A, 0xAB, 0xF8, 0x3B, 0x07, 0x8B, 0xE0, 0xCE, 0x26, 0x6F,
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x03, 0x09, 0x30, 0x1E, 0x06, 0x0A, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x1C, 0x82, 0x12,
+        0x06, 0x04, 0x10, 0x30, 0x0E, 0x30, 0x0C, 0x06, 0x0A, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x83,
+        0x1C, 0x82, 0x12, 0x02, 0x30, 0x1F, 0x06, 0x03, 0x55, 0x1D, 0x23, 0x04, 0x18, 0x30, 0x16,
+        0x80, 0x14, 0x42, 0xBC, 0xED, 0xBA, 0xD1, 0x5A, 0x68, 0xDF, 0x71, 0x41, 0xF8, 0xA5, 0x0E,
+        0x36, 0xCA, 0xF5, 0xDF, 0x52, 0x50, 0x09, 0x30, 0x0A, 0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE,
+        0x3D, 0x04, 0x03, 0x03, 0x03, 0x67, 0x00, 0x30, 0x64, 0x02, 0x30, 0x65, 0x97, 0xF3, 0x83,
+        0x12, 0xAE, 0x66, 0xDF, 0x57, 0x76, 0xBA, 0x14, 0xDA, 0x60, 0xD6, 0x67, 0x92, 0xE0, 0x4D,
+        0xC7, 0x03, 0x3F, 0x98, 0xE4, 0xCF, 0xC7, 0x8F, 0xF1, 0x83, 0xD8, 0x0E, 0x95, 0xA3, 0x01,
+        0x13, 0x5B, 0x2D, 0x91, 0xDD, 0x82, 0xB2, 0x27, 0x65, 0xB3, 0xF4, 0x70, 0xC2, 0x32, 0x02,
+        0x30, 0x57, 0x61, 0xE4, 0x87, 0xD5, 0x31, 0xC1, 0x6E, 0x8D, 0xF9, 0xAB, 0x9E, 0x42, 0x03,
+        0xC0, 0x5F, 0x8A, 0xC9, 0x39, 0xFE, 0xF3, 0x00, 0x28, 0x0C, 0x0C, 0xF5, 0xE5, 0x81, 0x64,
+        0x31, 0xB7, 0xFB, 0xA0, 0xF0, 0xF4, 0x85, 0xBC, 0xCE, 0xDF, 0x38, 0xF9, 0x20, 0x2A, 0x88,
+        0x5E, 0x8D, 0xC5, 0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
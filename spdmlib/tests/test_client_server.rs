@@ -7,6 +7,7 @@ use common::fake_device_io::{FakeSpdmDeviceIo, FakeSpdmDeviceIoReceve};
 use common::shared_buffer::SharedBuffer;
 
 use pcidoe_transport::PciDoeTransportEncap;
+use spdmlib::message::SpdmKeyUpdateOperation;
 use spdmlib::protocol::SpdmMeasurementSummaryHashType;
 use spdmlib::requester;
 use spdmlib::responder;
@@ -84,3 +85,61 @@ fn intergration_client_server() {
         log::info!("\nSession session_id not got ????? \n");
     }
 }
+
+#[test]
+fn intergration_client_server_typestate() {
+    spdmlib::crypto::asym_sign::register(common::crypto_callbacks::ASYM_SIGN_IMPL.clone());
+
+    let shared_buffer = SharedBuffer::new();
+    let device_io_responder = &mut FakeSpdmDeviceIoReceve::new(&shared_buffer);
+    let transport_encap_responder = &mut PciDoeTransportEncap {};
+
+    let (config_info, provision_info) = common::utils::rsp_create_info();
+    let mut responder_context = responder::ResponderContext::new(
+        device_io_responder,
+        transport_encap_responder,
+        config_info,
+        provision_info,
+    );
+
+    let device_io_requester = &mut FakeSpdmDeviceIo::new(&shared_buffer, &mut responder_context);
+    let transport_encap_requester = &mut PciDoeTransportEncap {};
+
+    let (config_info, provision_info) = common::utils::req_create_info();
+    let mut requester_context = requester::RequesterContext::new(
+        device_io_requester,
+        transport_encap_requester,
+        config_info,
+        provision_info,
+    );
+
+    let negotiated = requester::SpdmUnconnected::new(&mut requester_context)
+        .init_connection()
+        .expect("negotiation failed");
+    let authenticated = negotiated
+        .authenticate(
+            0,
+            SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+        )
+        .expect("authentication failed");
+
+    let mut session = authenticated
+        .start_session(SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone)
+        .expect("start session failed");
+    assert!(session.heartbeat().is_ok());
+    assert!(session
+        .key_update(SpdmKeyUpdateOperation::SpdmUpdateAllKeys)
+        .is_ok());
+    let authenticated = session.end_session().expect("end session failed");
+    assert_eq!(authenticated.slot_id(), 0);
+
+    let session = authenticated
+        .start_psk_session(SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone)
+        .expect("start psk session failed");
+    assert!(session
+        .context()
+        .common
+        .get_immutable_session_via_id(session.session_id())
+        .is_some());
+    assert!(session.end_session().is_ok());
+}
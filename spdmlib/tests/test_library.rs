@@ -3,10 +3,14 @@ mod common;
 use codec::{u24, Codec};
 use codec::{Reader, Writer};
 use spdmlib::common::opaque::*;
+use spdmlib::common::session::{SpdmSession, SpdmSessionState};
 use spdmlib::common::SpdmCodec;
 use spdmlib::config::{
     MAX_SPDM_CERT_CHAIN_DATA_SIZE, MAX_SPDM_MEASUREMENT_VALUE_LEN, MAX_SPDM_OPAQUE_SIZE,
 };
+use spdmlib::crypto::SpdmAsymSign;
+use spdmlib::message::*;
+use spdmlib::protocol::{SpdmAeadAlgo, SpdmDheFinalKeyStruct, SpdmKeyScheduleAlgo, SpdmVersion};
 use spdmlib::protocol::{
     SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmCertChain, SpdmCertChainData, SpdmDheAlgo,
     SpdmDheExchangeStruct, SpdmDigestStruct, SpdmDmtfMeasurementStructure, SpdmDmtfMeasurementType,
@@ -14,6 +18,11 @@ use spdmlib::protocol::{
     SPDM_MAX_ASYM_KEY_SIZE, SPDM_MAX_DHE_KEY_SIZE, SPDM_MAX_HASH_SIZE,
 };
 use spdmlib::protocol::{SpdmDmtfMeasurementRepresentation, SpdmMeasurementBlockStructure};
+use spdmlib::protocol::{
+    SpdmMeasurementSummaryHashType, SpdmNonceStruct, SPDM_CHALLENGE_AUTH_SIGN_CONTEXT,
+};
+use spdmlib::responder;
+use std::sync::Mutex;
 
 use common::testlib::*;
 
@@ -310,3 +319,200 @@ fn test_case0_spdm_measurement_block_structure() {
     }
     assert_eq!(9, reader.left());
 }
+
+fn new_secured_message_session(secure_spdm_version_sel: u8) -> SpdmSession {
+    let mut session = SpdmSession::new();
+    session.setup(4294901758u32).unwrap();
+    session.set_crypto_param(
+        SpdmBaseHashAlgo::TPM_ALG_SHA_384,
+        SpdmDheAlgo::SECP_384_R1,
+        SpdmAeadAlgo::AES_256_GCM,
+        SpdmKeyScheduleAlgo::SPDM_KEY_SCHEDULE,
+    );
+    session
+        .set_dhe_secret(
+            SpdmVersion::SpdmVersion11,
+            SpdmDheFinalKeyStruct {
+                data_size: 48,
+                data: Box::new([0; SPDM_MAX_DHE_KEY_SIZE]),
+            },
+        )
+        .unwrap();
+    let th = SpdmDigestStruct {
+        data_size: 48,
+        data: Box::new([0; SPDM_MAX_HASH_SIZE]),
+    };
+    session
+        .generate_handshake_secret(SpdmVersion::SpdmVersion11, &th)
+        .unwrap();
+    session
+        .generate_data_secret(SpdmVersion::SpdmVersion11, &th)
+        .unwrap();
+    session.set_session_state(SpdmSessionState::SpdmSessionEstablished);
+    session.set_transport_param(2, 32);
+    session.secure_spdm_version_sel = secure_spdm_version_sel;
+    session
+}
+
+#[test]
+fn test_case0_secured_message_random_padding() {
+    let mut sender = new_secured_message_session(0x11);
+    let mut receiver = new_secured_message_session(0x11);
+    let send_buffer = [100u8; 16];
+    let mut max_size = 0;
+    let mut min_size = usize::MAX;
+    for _ in 0..16 {
+        let mut message = [0u8; 128];
+        let size = sender
+            .encode_spdm_secured_message(&send_buffer, &mut message, true)
+            .unwrap();
+        assert!(size <= 4 + 2 + 2 + 2 + 16 + 32 + 16);
+        max_size = max_size.max(size);
+        min_size = min_size.min(size);
+
+        let mut app_buffer = [0u8; 128];
+        let used = receiver
+            .decode_spdm_secured_message(&message[..size], &mut app_buffer, true)
+            .unwrap();
+        assert_eq!(&app_buffer[..used], &send_buffer[..]);
+    }
+    assert!(max_size > min_size);
+
+    // no random data with 1.0
+    let mut sender = new_secured_message_session(0x10);
+    let mut message = [0u8; 128];
+    let size = sender
+        .encode_spdm_secured_message(&send_buffer, &mut message, true)
+        .unwrap();
+    assert_eq!(size, 4 + 2 + 2 + 2 + 16 + 16);
+}
+
+#[test]
+fn test_case0_export_keying_material() {
+    let mut session = new_secured_message_session(0x11);
+    let export = |session: &SpdmSession, label: &[u8], context: &[u8], len: usize| {
+        let mut keying_material = vec![0u8; len];
+        session
+            .export_keying_material(
+                SpdmVersion::SpdmVersion11,
+                label,
+                context,
+                &mut keying_material,
+            )
+            .map(|_| keying_material)
+    };
+    let keying_material = export(&session, b"ide key", &[], 32).unwrap();
+    assert_eq!(
+        export(&session, b"ide key", &[], 32).unwrap(),
+        keying_material
+    );
+    assert_ne!(
+        export(&session, b"ide key", &[0x01], 32).unwrap(),
+        keying_material
+    );
+    assert_ne!(
+        export(&session, b"storage key", &[], 32).unwrap(),
+        keying_material
+    );
+    assert_ne!(
+        export(&session, b"ide key", &[], 48).unwrap()[..32],
+        keying_material[..]
+    );
+    assert!(export(&session, b"ide key", &[], 0).is_err());
+
+    // more than one hash, up to 255 of them
+    let long_keying_material = export(&session, b"ide key", &[], 49).unwrap();
+    assert_ne!(long_keying_material[48..], [0u8; 1]);
+    assert_eq!(
+        export(&session, b"ide key", &[], 49).unwrap(),
+        long_keying_material
+    );
+    assert!(export(&session, b"ide key", &[], 255 * 48).is_ok());
+    assert!(export(&session, b"ide key", &[], 255 * 48 + 1).is_err());
+
+    // independent of the keys of the session
+    session
+        .create_data_secret_update(SpdmVersion::SpdmVersion11, true, true)
+        .unwrap();
+    session
+        .activate_data_secret_update(SpdmVersion::SpdmVersion11, true, true, true)
+        .unwrap();
+    assert_eq!(
+        export(&session, b"ide key", &[], 32).unwrap(),
+        keying_material
+    );
+
+    session.set_session_state(SpdmSessionState::SpdmSessionHandshaking);
+    assert!(export(&session, b"ide key", &[], 32).is_err());
+}
+
+static SIGN_REQUEST: Mutex<Option<(u8, Vec<u8>)>> = Mutex::new(None);
+
+fn recording_asym_sign(
+    slot_id: u8,
+    spdm_version: SpdmVersion,
+    base_hash_algo: SpdmBaseHashAlgo,
+    base_asym_algo: SpdmBaseAsymAlgo,
+    sign_context: &[u8],
+    data: &[u8],
+) -> Option<SpdmSignatureStruct> {
+    *SIGN_REQUEST.lock().unwrap() = Some((slot_id, sign_context.to_vec()));
+    (ASYM_SIGN_IMPL.sign_cb)(
+        slot_id,
+        spdm_version,
+        base_hash_algo,
+        base_asym_algo,
+        sign_context,
+        data,
+    )
+}
+
+#[test]
+fn test_case0_asym_sign_slot_id_and_context() {
+    spdmlib::crypto::asym_sign::register(SpdmAsymSign {
+        sign_cb: recording_asym_sign,
+    });
+
+    let (config_info, provision_info) = create_info();
+    let pcidoe_transport_encap = &mut PciDoeTransportEncap {};
+    let shared_buffer = SharedBuffer::new();
+    let mut device_io = FakeSpdmDeviceIoReceve::new(&shared_buffer);
+    let mut responder = responder::ResponderContext::new(
+        &mut device_io,
+        pcidoe_transport_encap,
+        config_info,
+        provision_info,
+    );
+    responder.common.provision_info.my_cert_chain = Some(SpdmCertChainData {
+        data_size: 512u16,
+        data: [0u8; MAX_SPDM_CERT_CHAIN_DATA_SIZE],
+    });
+    responder.common.negotiate_info.spdm_version_sel = SpdmVersion::SpdmVersion12;
+    responder.common.negotiate_info.base_hash_sel = SpdmBaseHashAlgo::TPM_ALG_SHA_384;
+    responder.common.negotiate_info.base_asym_sel = SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P384;
+    responder.common.reset_runtime_info();
+
+    // the provisioned public key
+    let request = &mut [0u8; 1024];
+    let mut writer = Writer::init(request);
+    SpdmMessage {
+        header: SpdmMessageHeader {
+            version: SpdmVersion::SpdmVersion12,
+            request_response_code: SpdmRequestResponseCode::SpdmRequestChallenge,
+        },
+        payload: SpdmMessagePayload::SpdmChallengeRequest(SpdmChallengeRequestPayload {
+            slot_id: 0xFF,
+            measurement_summary_hash_type:
+                SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
+            nonce: SpdmNonceStruct { data: [100u8; 32] },
+        }),
+    }
+    .spdm_encode(&mut responder.common, &mut writer);
+    let used = writer.used();
+    responder.handle_spdm_challenge(&request[..used]);
+
+    assert_eq!(
+        SIGN_REQUEST.lock().unwrap().take(),
+        Some((0xFF, SPDM_CHALLENGE_AUTH_SIGN_CONTEXT.to_vec()))
+    );
+}
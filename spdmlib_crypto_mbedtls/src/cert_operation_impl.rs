@@ -10,6 +10,7 @@ use spdmlib::error::{spdm_err, spdm_result_err, SpdmResult};
 pub static DEFAULT: SpdmCertOperation = SpdmCertOperation {
     get_cert_from_cert_chain_cb: get_cert_from_cert_chain,
     verify_cert_chain_cb: verify_cert_chain,
+    get_cert_info_cb: spdmlib::crypto::x509v3::get_cert_info,
 };
 
 use crate::ffi::spdm_verify_cert_chain;
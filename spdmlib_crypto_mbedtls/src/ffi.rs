@@ -4,6 +4,28 @@
 
 use core::ffi::{c_int, c_uchar, c_void};
 
+/// `mbedtls_sha256_context`, for the storage of a streaming hash.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct mbedtls_sha256_context {
+    pub total: [u32; 2],
+    pub state: [u32; 8],
+    pub buffer: [c_uchar; 64],
+    pub is224: c_int,
+}
+
+/// `mbedtls_sha512_context`, for the storage of a streaming hash.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct mbedtls_sha512_context {
+    pub total: [u64; 2],
+    pub state: [u64; 8],
+    pub buffer: [c_uchar; 128],
+    pub is384: c_int,
+}
+
 extern "C" {
     pub fn spdm_aead_aes_gcm_encrypt(
         key: *const c_uchar,
@@ -140,6 +162,22 @@ extern "C" {
         is384: c_int,
     ) -> c_int;
 
+    pub fn mbedtls_sha256_init(ctx: *mut c_void);
+    pub fn mbedtls_sha256_free(ctx: *mut c_void);
+    pub fn mbedtls_sha256_clone(dst: *mut c_void, src: *const c_void);
+    pub fn mbedtls_sha256_starts_ret(ctx: *mut c_void, is224: c_int) -> c_int;
+    pub fn mbedtls_sha256_update_ret(ctx: *mut c_void, input: *const c_uchar, ilen: usize)
+        -> c_int;
+    pub fn mbedtls_sha256_finish_ret(ctx: *mut c_void, output: *mut c_uchar) -> c_int;
+
+    pub fn mbedtls_sha512_init(ctx: *mut c_void);
+    pub fn mbedtls_sha512_free(ctx: *mut c_void);
+    pub fn mbedtls_sha512_clone(dst: *mut c_void, src: *const c_void);
+    pub fn mbedtls_sha512_starts_ret(ctx: *mut c_void, is384: c_int) -> c_int;
+    pub fn mbedtls_sha512_update_ret(ctx: *mut c_void, input: *const c_uchar, ilen: usize)
+        -> c_int;
+    pub fn mbedtls_sha512_finish_ret(ctx: *mut c_void, output: *mut c_uchar) -> c_int;
+
     pub fn mbedtls_md_info_from_type(md_type: c_int) -> *mut c_void;
 
     pub fn mbedtls_hkdf_expand(
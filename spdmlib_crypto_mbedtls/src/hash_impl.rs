@@ -2,15 +2,25 @@
 //
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use spdmlib::crypto::SpdmHash;
+extern crate alloc;
+use alloc::boxed::Box;
+
+use spdmlib::crypto::{SpdmHash, SpdmHashCtx};
+use spdmlib::error::{spdm_err, spdm_result_err, SpdmResult};
 use spdmlib::protocol::{SpdmBaseHashAlgo, SpdmDigestStruct};
 
 pub static DEFAULT: SpdmHash = SpdmHash {
     hash_all_cb: hash_all,
+    hash_ctx_init_cb: hash_ctx_init,
 };
 
-use super::ffi::{mbedtls_sha256, mbedtls_sha512};
-use core::ffi::c_uchar;
+use super::ffi::{
+    mbedtls_sha256, mbedtls_sha256_clone, mbedtls_sha256_context, mbedtls_sha256_finish_ret,
+    mbedtls_sha256_free, mbedtls_sha256_init, mbedtls_sha256_starts_ret, mbedtls_sha256_update_ret,
+    mbedtls_sha512, mbedtls_sha512_clone, mbedtls_sha512_context, mbedtls_sha512_finish_ret,
+    mbedtls_sha512_free, mbedtls_sha512_init, mbedtls_sha512_starts_ret, mbedtls_sha512_update_ret,
+};
+use core::ffi::{c_uchar, c_void};
 
 fn hash_all(base_hash_algo: SpdmBaseHashAlgo, data: &[u8]) -> Option<SpdmDigestStruct> {
     let d = data.as_ptr() as *const c_uchar;
@@ -45,6 +55,113 @@ fn hash_all(base_hash_algo: SpdmBaseHashAlgo, data: &[u8]) -> Option<SpdmDigestS
     Some(spdm_digest)
 }
 
+// sized and aligned for either context
+#[repr(C)]
+union HashCtxStorage {
+    sha256: mbedtls_sha256_context,
+    sha512: mbedtls_sha512_context,
+}
+
+struct SpdmHashCtxMbedtls {
+    base_hash_algo: SpdmBaseHashAlgo,
+    state: Box<HashCtxStorage>,
+}
+
+impl SpdmHashCtxMbedtls {
+    fn new(base_hash_algo: SpdmBaseHashAlgo) -> Self {
+        let mut ctx = SpdmHashCtxMbedtls {
+            base_hash_algo,
+            // all zero is a valid value of both contexts
+            state: Box::new(unsafe { core::mem::zeroed() }),
+        };
+        match base_hash_algo {
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256 => unsafe { mbedtls_sha256_init(ctx.as_mut_ptr()) },
+            _ => unsafe { mbedtls_sha512_init(ctx.as_mut_ptr()) },
+        }
+        ctx
+    }
+
+    fn as_ptr(&self) -> *const c_void {
+        &*self.state as *const HashCtxStorage as *const c_void
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut c_void {
+        &mut *self.state as *mut HashCtxStorage as *mut c_void
+    }
+}
+
+fn hash_ctx_init(base_hash_algo: SpdmBaseHashAlgo) -> Option<Box<dyn SpdmHashCtx>> {
+    let (is_sha512, variant) = match base_hash_algo {
+        SpdmBaseHashAlgo::TPM_ALG_SHA_256 => (false, 0),
+        SpdmBaseHashAlgo::TPM_ALG_SHA_384 => (true, 1),
+        SpdmBaseHashAlgo::TPM_ALG_SHA_512 => (true, 0),
+        _ => return None,
+    };
+    let mut ctx = SpdmHashCtxMbedtls::new(base_hash_algo);
+    let res = if is_sha512 {
+        unsafe { mbedtls_sha512_starts_ret(ctx.as_mut_ptr(), variant) }
+    } else {
+        unsafe { mbedtls_sha256_starts_ret(ctx.as_mut_ptr(), variant) }
+    };
+    if res != 0 {
+        return None;
+    }
+    Some(Box::new(ctx))
+}
+
+impl SpdmHashCtx for SpdmHashCtxMbedtls {
+    fn update(&mut self, data: &[u8]) -> SpdmResult {
+        let d = data.as_ptr() as *const c_uchar;
+        let res = match self.base_hash_algo {
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256 => unsafe {
+                mbedtls_sha256_update_ret(self.as_mut_ptr(), d, data.len())
+            },
+            _ => unsafe { mbedtls_sha512_update_ret(self.as_mut_ptr(), d, data.len()) },
+        };
+        if res != 0 {
+            return spdm_result_err!(EFAULT);
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>) -> Option<SpdmDigestStruct> {
+        let mut spdm_digest = SpdmDigestStruct::default();
+        let res = match self.base_hash_algo {
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256 => unsafe {
+                mbedtls_sha256_finish_ret(self.as_mut_ptr(), spdm_digest.data.as_mut_ptr())
+            },
+            _ => unsafe {
+                mbedtls_sha512_finish_ret(self.as_mut_ptr(), spdm_digest.data.as_mut_ptr())
+            },
+        };
+        if res != 0 {
+            return None;
+        }
+        spdm_digest.data_size = self.base_hash_algo.get_size();
+        Some(spdm_digest)
+    }
+
+    fn box_clone(&self) -> Box<dyn SpdmHashCtx> {
+        let mut ctx = SpdmHashCtxMbedtls::new(self.base_hash_algo);
+        match self.base_hash_algo {
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256 => unsafe {
+                mbedtls_sha256_clone(ctx.as_mut_ptr(), self.as_ptr())
+            },
+            _ => unsafe { mbedtls_sha512_clone(ctx.as_mut_ptr(), self.as_ptr()) },
+        }
+        Box::new(ctx)
+    }
+}
+
+impl Drop for SpdmHashCtxMbedtls {
+    fn drop(&mut self) {
+        match self.base_hash_algo {
+            SpdmBaseHashAlgo::TPM_ALG_SHA_256 => unsafe { mbedtls_sha256_free(self.as_mut_ptr()) },
+            _ => unsafe { mbedtls_sha512_free(self.as_mut_ptr()) },
+        }
+    }
+}
+
 #[cfg(all(test,))]
 mod tests {
     use super::*;
@@ -85,4 +202,17 @@ mod tests {
         let hash_all = hash_all(base_hash_algo, data);
         assert_eq!(hash_all.is_none(), true);
     }
+    #[test]
+    fn test_case0_hash_ctx() {
+        let helloworld = hash_all(SpdmBaseHashAlgo::TPM_ALG_SHA_384, b"hello, world").unwrap();
+        let hellobuddy = hash_all(SpdmBaseHashAlgo::TPM_ALG_SHA_384, b"hello, buddy").unwrap();
+        let mut ctx = hash_ctx_init(SpdmBaseHashAlgo::TPM_ALG_SHA_384).unwrap();
+        assert!(ctx.update(b"hello").is_ok());
+        assert!(ctx.update(b", ").is_ok());
+        let mut ctx_d = ctx.clone();
+        assert!(ctx_d.update(b"buddy").is_ok());
+        assert!(ctx.update(b"world").is_ok());
+        assert_eq!(ctx.finalize().unwrap().as_ref(), helloworld.as_ref());
+        assert_eq!(ctx_d.finalize().unwrap().as_ref(), hellobuddy.as_ref());
+    }
 }
@@ -7,7 +7,7 @@ use std::path::PathBuf;
 use spdmlib::crypto::SpdmAsymSign;
 
 use spdmlib::protocol::{
-    SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmSignatureStruct, RSAPSS_2048_KEY_SIZE,
+    SpdmBaseAsymAlgo, SpdmBaseHashAlgo, SpdmSignatureStruct, SpdmVersion, RSAPSS_2048_KEY_SIZE,
     RSAPSS_3072_KEY_SIZE, RSAPSS_4096_KEY_SIZE, RSASSA_2048_KEY_SIZE, RSASSA_3072_KEY_SIZE,
     RSASSA_4096_KEY_SIZE, SPDM_MAX_ASYM_KEY_SIZE,
 };
@@ -15,10 +15,18 @@ use spdmlib::protocol::{
 pub static ASYM_SIGN_IMPL: SpdmAsymSign = SpdmAsymSign { sign_cb: asym_sign };
 
 fn asym_sign(
+    slot_id: u8,
+    _spdm_version: SpdmVersion,
     base_hash_algo: SpdmBaseHashAlgo,
     base_asym_algo: SpdmBaseAsymAlgo,
+    _sign_context: &[u8],
     data: &[u8],
 ) -> Option<SpdmSignatureStruct> {
+    // the emulator only provisions a certificate chain in slot 0, its leaf
+    // key is also the provisioned public key (slot 0xFF)
+    if slot_id != 0 && slot_id != 0xFF {
+        return None;
+    }
     match (base_hash_algo, base_asym_algo) {
         (SpdmBaseHashAlgo::TPM_ALG_SHA_256, SpdmBaseAsymAlgo::TPM_ALG_ECDSA_ECC_NIST_P256) => {
             sign_ecdsa_asym_algo(&ring::signature::ECDSA_P256_SHA256_FIXED_SIGNING, data)
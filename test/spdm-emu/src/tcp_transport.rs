@@ -29,7 +29,7 @@ impl SpdmDeviceIo for TcpTransport<'_> {
         if res.is_ok() {
             Ok(())
         } else {
-            spdm_result_err!(EIO)
+            spdm_result_err!(ETRANSPORT)
         }
     }
 
@@ -112,52 +112,29 @@ fn test_spdm(
         ..Default::default()
     };
 
-    let mut peer_cert_chain_data = SpdmCertChainData {
-        ..Default::default()
-    };
-
+    // trust any responder whose cert chain is rooted in the test CA
     let ca_file_path = if USE_ECDSA {
         "test_key/EcP384/ca.cert.der"
     } else {
         "test_key/Rsa3072/ca.cert.der"
     };
     let ca_cert = std::fs::read(ca_file_path).expect("unable to read ca cert!");
-    let inter_file_path = if USE_ECDSA {
-        "test_key/EcP384/inter.cert.der"
-    } else {
-        "test_key/Rsa3072/inter.cert.der"
-    };
-    let inter_cert = std::fs::read(inter_file_path).expect("unable to read inter cert!");
-    let leaf_file_path = if USE_ECDSA {
-        "test_key/EcP384/end_responder.cert.der"
-    } else {
-        "test_key/Rsa3072/end_responder.cert.der"
+    let mut peer_root_cert_data = SpdmCertChainData {
+        data_size: ca_cert.len() as u16,
+        ..Default::default()
     };
-    let leaf_cert = std::fs::read(leaf_file_path).expect("unable to read leaf cert!");
-
-    let ca_len = ca_cert.len();
-    let inter_len = inter_cert.len();
-    let leaf_len = leaf_cert.len();
-    println!(
-        "total cert size - {:?} = {:?} + {:?} + {:?}",
-        ca_len + inter_len + leaf_len,
-        ca_len,
-        inter_len,
-        leaf_len
-    );
-    peer_cert_chain_data.data_size = (ca_len + inter_len + leaf_len) as u16;
-    peer_cert_chain_data.data[0..ca_len].copy_from_slice(ca_cert.as_ref());
-    peer_cert_chain_data.data[ca_len..(ca_len + inter_len)].copy_from_slice(inter_cert.as_ref());
-    peer_cert_chain_data.data[(ca_len + inter_len)..(ca_len + inter_len + leaf_len)]
-        .copy_from_slice(leaf_cert.as_ref());
+    peer_root_cert_data.data[..ca_cert.len()].copy_from_slice(ca_cert.as_ref());
 
-    let provision_info = common::SpdmProvisionInfo {
+    let mut provision_info = common::SpdmProvisionInfo {
         my_cert_chain_data: None,
         my_cert_chain: None,
-        peer_cert_chain_data: Some(peer_cert_chain_data),
-        peer_cert_chain_root_hash: None,
+        peer_root_cert_data: Default::default(),
+        peer_cert_chain_root_hash: Default::default(),
+        peer_root_crl_data: Default::default(),
+        peer_cert_chain_data: None,
         default_version: SpdmVersion::SpdmVersion12,
     };
+    provision_info.peer_root_cert_data[0] = Some(peer_root_cert_data);
 
     let mut context = requester::RequesterContext::new(
         socket_io_transport,
@@ -268,7 +245,13 @@ fn test_spdm(
             panic!("send_receive_spdm_certificate failed");
         }
 
-        if context.end_session(session_id).is_err() {
+        if context
+            .end_session(
+                session_id,
+                SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE,
+            )
+            .is_err()
+        {
             panic!("end_session failed");
         }
     } else {
@@ -281,7 +264,13 @@ fn test_spdm(
         SpdmMeasurementSummaryHashType::SpdmMeasurementSummaryHashTypeNone,
     );
     if let Ok(session_id) = result {
-        if context.end_session(session_id).is_err() {
+        if context
+            .end_session(
+                session_id,
+                SpdmEndSessionRequestAttributes::PRESERVE_NEGOTIATED_STATE,
+            )
+            .is_err()
+        {
             panic!("\nSession session_id is err\n");
         }
     } else {
@@ -22,8 +22,10 @@ use spdm_emu::crypto_callback::ASYM_SIGN_IMPL;
 use spdm_emu::secret_impl_sample::*;
 use spdm_emu::socket_io_transport::SocketIoTransport;
 use spdm_emu::spdm_emu::*;
+use spdmlib::error::SpdmResult;
 use spdmlib::secret::*;
 use spdmlib::{common, config, protocol::*, responder};
+use spdmlib::{spdm_err, spdm_result_err};
 
 fn process_socket_message(
     stream: &mut TcpStream,
@@ -132,12 +134,41 @@ fn main() {
     }
 }
 
+const MCTP_MESSAGE_TYPE_PLDM: u8 = 0x01;
+
+const PLDM_GET_TID_RESPONSE: &[u8; 5] = &[
+    0x00u8, 0x00u8, //PLDM_MESSAGE_TYPE_CONTROL_DISCOVERY
+    0x02u8, //PLDM_CONTROL_DISCOVERY_COMMAND_GET_TID
+    0x00u8, //PLDM_BASE_CODE_SUCCESS
+    0x01u8, //TID
+];
+
+/// Answers PLDM requests in a session with the GetTID response.
+struct PldmAppMessageHandler {}
+
+impl responder::SpdmAppMessageHandler for PldmAppMessageHandler {
+    fn dispatch_secured_app_message(
+        &mut self,
+        _session_id: u32,
+        app_type: u8,
+        _request: &[u8],
+        response: &mut [u8],
+    ) -> SpdmResult<usize> {
+        if app_type != MCTP_MESSAGE_TYPE_PLDM {
+            return spdm_result_err!(EINVAL);
+        }
+        response[..PLDM_GET_TID_RESPONSE.len()].copy_from_slice(PLDM_GET_TID_RESPONSE);
+        Ok(PLDM_GET_TID_RESPONSE.len())
+    }
+}
+
 fn handle_message(
     stream: &mut TcpStream,
     transport_encap: &mut dyn SpdmTransportEncap,
 ) -> Result<bool, (usize, [u8; config::DATA_TRANSFER_SIZE])> {
     println!("handle_message!");
     let mut socket_io_transport = SocketIoTransport::new(stream);
+    let mut app_message_handler = PldmAppMessageHandler {};
 
     let config_info = common::SpdmConfigInfo {
         spdm_version: [
@@ -225,8 +256,10 @@ fn handle_message(
     let provision_info = common::SpdmProvisionInfo {
         my_cert_chain_data: Some(my_cert_chain_data),
         my_cert_chain: None,
+        peer_root_cert_data: Default::default(),
+        peer_cert_chain_root_hash: Default::default(),
+        peer_root_crl_data: Default::default(),
         peer_cert_chain_data: None,
-        peer_cert_chain_root_hash: None,
         default_version: SpdmVersion::SpdmVersion12,
     };
 
@@ -237,7 +270,10 @@ fn handle_message(
         config_info,
         provision_info,
     );
+    context.set_app_message_handler(&mut app_message_handler);
     loop {
+        // end the sessions that went idle while the last message was handled
+        context.supervise_sessions();
         // if failed, receieved message can't be processed. then the message will need caller to deal.
         // now caller need to deal with message in context.
         let res = context.process_message(ST1);